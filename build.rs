@@ -4,7 +4,7 @@ extern crate bindgen;
 
 use cmake::Config;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     // CRITICAL: Export variables IMMEDIATELY at the very start
@@ -17,6 +17,16 @@ fn main() {
         }
     };
     
+    // Set below when the ggml libraries carry the patches of patches/
+    println!("cargo:rustc-check-cfg=cfg(ggml_patched)");
+
+    // Without native only the pure-Rust GGUF code is compiled: no ggml
+    // build, no bindings, nothing to link
+    if env::var_os("CARGO_FEATURE_NATIVE").is_none() {
+        println!("[BUILD] native feature disabled - skipping the ggml build and bindings");
+        return;
+    }
+
     // Export test variable
     println!("cargo:TEST_VAR=test_value");
     eprintln!("cargo:warning=[ggml-rs] TEST: Exported cargo:TEST_VAR (should be DEP_GGML_RS_TEST_VAR)");
@@ -56,13 +66,46 @@ fn main() {
     println!("[BUILD] Metal feature enabled: {}", cfg!(feature = "metal"));
     println!("[BUILD] Vulkan feature enabled: {}", cfg!(feature = "vulkan"));
     println!("[BUILD] OpenBLAS feature enabled: {}", cfg!(feature = "openblas"));
+    println!("[BUILD] MKL feature enabled: {}", cfg!(feature = "mkl"));
+    println!("[BUILD] BLIS feature enabled: {}", cfg!(feature = "blis"));
+    let blas_vendors = [cfg!(feature = "openblas"), cfg!(feature = "mkl"), cfg!(feature = "blis")];
+    if blas_vendors.iter().filter(|&&enabled| enabled).count() > 1 {
+        panic!("the openblas, mkl and blis features select ggml's BLAS library; enable only one");
+    }
     println!("[BUILD] HIPBLAS feature enabled: {}", cfg!(feature = "hipblas"));
     println!("[BUILD] Intel-SYCL feature enabled: {}", cfg!(feature = "intel-sycl"));
+    println!("[BUILD] RPC feature enabled: {}", cfg!(feature = "rpc"));
+    println!("[BUILD] CANN feature enabled: {}", cfg!(feature = "cann"));
+    println!("[BUILD] Backend-DL feature enabled: {}", cfg!(feature = "backend-dl"));
+    println!("[BUILD] CPU-all-variants feature enabled: {}", cfg!(feature = "cpu-all-variants"));
+    println!("[BUILD] AVX512 feature enabled: {}", cfg!(feature = "avx512"));
+    println!("[BUILD] AMX feature enabled: {}", cfg!(feature = "amx"));
+    println!("[BUILD] Disable-native feature enabled: {}", cfg!(feature = "disable-native"));
+    println!("[BUILD] Dotprod feature enabled: {}", cfg!(feature = "dotprod"));
+    println!("[BUILD] I8MM feature enabled: {}", cfg!(feature = "i8mm"));
+    println!("[BUILD] SVE feature enabled: {}", cfg!(feature = "sve"));
     
     println!("[BUILD] Building BOTH variants (llama and whisper) unconditionally");
     println!("[BUILD] This ensures both sets of libraries are available regardless of which dependent crate builds first");
     
     let target = env::var("TARGET").unwrap();
+    let wasm = target.starts_with("wasm32");
+    if wasm {
+        check_wasm_target(&target);
+    }
+    let ios = target.contains("apple-ios");
+    if ios {
+        check_ios_target(&target);
+    }
+    check_cpu_isa_features(&target);
+    // An installed ggml replaces every variant; libraries from a companion
+    // crate or a release download skip bindgen and CMake
+    let system = if wasm { None } else { find_system_ggml() };
+    let prebuilt = if wasm || system.is_some() {
+        None
+    } else {
+        find_prebuilt().or_else(|| download_prebuilt(&out_dir))
+    };
     
     // Link C++ standard library
     if let Some(cpp_stdlib) = get_cpp_link_stdlib(&target) {
@@ -95,6 +138,16 @@ fn main() {
         }
     }
 
+    if cfg!(feature = "mkl") {
+        println!("cargo:rustc-link-search={}", mkl_lib_dir(&mkl_root()).display());
+        println!("cargo:rustc-link-lib=mkl_rt");
+    }
+
+    if cfg!(feature = "blis") {
+        println!("cargo:rustc-link-search={}", blis_root().join("lib").display());
+        println!("cargo:rustc-link-lib=blis");
+    }
+
     #[cfg(feature = "cuda")]
     {
         println!("cargo:rustc-link-lib=cublas");
@@ -105,37 +158,48 @@ fn main() {
     // Get the manifest directory and locate ggml source
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("Failed to get CARGO_MANIFEST_DIR");
     let manifest_path = PathBuf::from(&manifest_dir);
-    let ggml_root = manifest_path.join("ggml");
-
-    if !ggml_root.exists() {
-        panic!("GGML source directory not found at: {}", ggml_root.display());
+    if !manifest_path.join("ggml").exists() {
+        panic!("GGML source directory not found at: {}", manifest_path.join("ggml").display());
     }
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
-    // Generate bindings
-    let bindings = bindgen::Builder::default()
-        .header("wrapper.h")
-        .clang_arg(format!("-I{}", manifest_path.display()))
-        .allowlist_function("ggml_.*")
-        .allowlist_type("ggml_.*")
-        .allowlist_function("gguf_.*")
-        .allowlist_type("gguf_.*")
-        .allowlist_var("GGML_.*")
-        .allowlist_var("GGUF_.*")
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-        .generate()
-        .expect("Unable to generate bindings");
+    // Builds from source use a copy of ggml/ and wrapper.h with the patches
+    // of patches/series applied (see patches/README.md)
+    let source_root = if system.is_none() && prebuilt.is_none() {
+        patched_sources(&manifest_path, &out_dir)
+    } else {
+        manifest_path.clone()
+    };
+    let ggml_root = source_root.join("ggml");
+    // Prebuilt libraries are packaged from a build from source, so only a
+    // system ggml lacks the patches; the Rust APIs that need them are left
+    // out with it
+    if system.is_none() {
+        println!("cargo:rustc-cfg=ggml_patched");
+    }
 
     let out_path = out_dir.join("bindings.rs");
-    bindings
-        .write_to_file(&out_path)
-        .expect("Couldn't write bindings!");
+    if let Some(root) = &prebuilt {
+        // The companion crate ships the bindings generated with its libraries
+        std::fs::copy(root.join("bindings.rs"), &out_path)
+            .expect("Couldn't copy prebuilt bindings!");
+    } else if let Some(system) = &system {
+        // Bind the installed headers, which may differ from the vendored ones
+        let wrapper = out_dir.join("system_wrapper.h");
+        let content = std::fs::read_to_string(manifest_path.join("wrapper.h"))
+            .expect("Couldn't read wrapper.h")
+            .replace("#include \"ggml/include/", "#include \"");
+        std::fs::write(&wrapper, content).expect("Couldn't write system_wrapper.h");
+        generate_bindings(&wrapper, &system.include_dir, &target, &out_path);
+    } else {
+        generate_bindings(&source_root.join("wrapper.h"), &source_root, &target, &out_path);
+    }
 
     // Export variables even on docs.rs so dependent crates can find them
     // (We still need to export INCLUDE even if we don't build the library)
     // Exporting INCLUDE creates DEP_GGML_RS_INCLUDE for dependent crates
-    let ggml_include = ggml_root.join("include");
+    let ggml_include = system.as_ref().map_or_else(|| ggml_root.join("include"), |system| system.include_dir.clone());
     println!("cargo:INCLUDE={}", ggml_include.display());
     
     // Stop if we're on docs.rs (don't build the library, but export placeholder variables)
@@ -149,16 +213,24 @@ fn main() {
         println!("cargo:GGML_WHISPER_LIB_DIR={}", out_dir.join("whisper").join("lib").display());
         println!("cargo:GGML_WHISPER_BIN_DIR={}", out_dir.join("whisper").join("bin").display());
         println!("cargo:GGML_WHISPER_BASENAME=ggml_whisper");
+        write_build_info(&out_dir, &[], false, system.is_some());
         return;
     }
 
     // Export common include directory (same for both variants) - ALWAYS export this
-    println!("cargo:INCLUDE={}", ggml_root.join("include").display());
+    println!("cargo:INCLUDE={}", ggml_include.display());
     println!("[BUILD] Exported cargo:INCLUDE (becomes DEP_GGML_RS_INCLUDE)");
     
-    // Build BOTH variants unconditionally (llama and whisper)
-    // This ensures both sets of libraries are available regardless of which dependent crate builds first
-    println!("[BUILD] Building both GGML variants (llama and whisper)...");
+    // Build the variants enabled by variant-llama / variant-whisper (both by
+    // default, and both when neither is enabled). A skipped variant still
+    // gets its DEP_ placeholders below
+    let mut variant_llama = env::var_os("CARGO_FEATURE_VARIANT_LLAMA").is_some();
+    let mut variant_whisper = env::var_os("CARGO_FEATURE_VARIANT_WHISPER").is_some();
+    if !variant_llama && !variant_whisper {
+        variant_llama = true;
+        variant_whisper = true;
+    }
+    println!("[BUILD] Building GGML variants: llama={} whisper={}", variant_llama, variant_whisper);
     
     // Pre-allocate paths based on OUT_DIR so we can export them even if build fails
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -167,8 +239,43 @@ fn main() {
     let whisper_lib_dir_fallback = out_dir.join("whisper").join("lib");
     let whisper_bin_dir_fallback = out_dir.join("whisper").join("bin");
     
-    let llama_result = build_ggml_variant(&ggml_root, "ggml_llama", "llama");
-    let whisper_result = build_ggml_variant(&ggml_root, "ggml_whisper", "whisper");
+    // In wasm the libraries are static, so both variants would define the
+    // same symbols: build only the one the namespace feature selects
+    let build_llama = variant_llama && (!wasm || !cfg!(feature = "namespace-whisper"));
+    let build_whisper = variant_whisper && (!wasm || cfg!(feature = "namespace-whisper"));
+    // wasm, iOS, capi and python link the namespace's variant into this crate
+    if wasm || ios || cfg!(feature = "capi") || cfg!(feature = "python") {
+        let (built, variant) = if cfg!(feature = "namespace-whisper") {
+            (build_whisper, "whisper")
+        } else {
+            (build_llama, "llama")
+        };
+        if !built {
+            panic!("The {} variant is linked into ggml-rs for this build; enable the variant-{} feature", variant, variant);
+        }
+    }
+    let llama_result = if !variant_llama {
+        Err("skipped, variant-llama is disabled".into())
+    } else if let Some(system) = &system {
+        Ok((system.lib_dir.clone(), system.bin_dir.clone()))
+    } else if let Some(root) = &prebuilt {
+        prebuilt_variant(root, "ggml_llama", "llama")
+    } else if build_llama {
+        build_ggml_variant(&ggml_root, "ggml_llama", "llama")
+    } else {
+        Err("not built for this wasm target (namespace-whisper)".into())
+    };
+    let whisper_result = if !variant_whisper {
+        Err("skipped, variant-whisper is disabled".into())
+    } else if let Some(system) = &system {
+        Ok((system.lib_dir.clone(), system.bin_dir.clone()))
+    } else if let Some(root) = &prebuilt {
+        prebuilt_variant(root, "ggml_whisper", "whisper")
+    } else if build_whisper {
+        build_ggml_variant(&ggml_root, "ggml_whisper", "whisper")
+    } else {
+        Err("not built for this wasm target (namespace-llama)".into())
+    };
     
     // Export environment variables for both variants so consumers can find them
     // Consumers will link to their own variant using these variables
@@ -198,6 +305,10 @@ fn main() {
         }
     };
     
+    // A system ggml is not namespaced: every variant links its libraries
+    let llama_basename = system.as_ref().map_or("ggml_llama", |system| system.basename.as_str());
+    let whisper_basename = system.as_ref().map_or("ggml_whisper", |system| system.basename.as_str());
+
     // ALWAYS export variables again with final paths (overwrites initial exports)
     eprintln!("cargo:warning=[ggml-rs] Exporting FINAL llama variant variables:");
     eprintln!("cargo:warning=[ggml-rs]   GGML_LLAMA_LIB_DIR={}", llama_lib_dir.display());
@@ -206,7 +317,7 @@ fn main() {
     // Export using cargo: prefix - Cargo will make these available as DEP_GGML_RS_*
     println!("cargo:GGML_LLAMA_LIB_DIR={}", llama_lib_dir.display());
     println!("cargo:GGML_LLAMA_BIN_DIR={}", llama_bin_dir.display());
-    println!("cargo:GGML_LLAMA_BASENAME=ggml_llama");
+    println!("cargo:GGML_LLAMA_BASENAME={}", llama_basename);
     
     eprintln!("cargo:warning=[ggml-rs] Exporting FINAL whisper variant variables:");
     eprintln!("cargo:warning=[ggml-rs]   GGML_WHISPER_LIB_DIR={}", whisper_lib_dir.display());
@@ -214,26 +325,621 @@ fn main() {
     
     println!("cargo:GGML_WHISPER_LIB_DIR={}", whisper_lib_dir.display());
     println!("cargo:GGML_WHISPER_BIN_DIR={}", whisper_bin_dir.display());
-    println!("cargo:GGML_WHISPER_BASENAME=ggml_whisper");
+    println!("cargo:GGML_WHISPER_BASENAME={}", whisper_basename);
     
     eprintln!("cargo:warning=[ggml-rs] ========================================");
     eprintln!("cargo:warning=[ggml-rs] Build script COMPLETED successfully");
     eprintln!("cargo:warning=[ggml-rs] All variables exported:");
     eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_LLAMA_LIB_DIR={}", llama_lib_dir.display());
     eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_LLAMA_BIN_DIR={}", llama_bin_dir.display());
-    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_LLAMA_BASENAME=ggml_llama");
+    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_LLAMA_BASENAME={}", llama_basename);
     eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_WHISPER_LIB_DIR={}", whisper_lib_dir.display());
     eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_WHISPER_BIN_DIR={}", whisper_bin_dir.display());
-    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_WHISPER_BASENAME=ggml_whisper");
-    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_INCLUDE={}", ggml_root.join("include").display());
+    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_GGML_WHISPER_BASENAME={}", whisper_basename);
+    eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_INCLUDE={}", ggml_include.display());
     eprintln!("cargo:warning=[ggml-rs] ========================================");
     
+    // Namespaces beyond llama/whisper requested by consumers, e.g. ggml_sd
+    // for stable-diffusion.cpp: built and patched the same way, exported as
+    // DEP_GGML_RS_<NAMESPACE>_{LIB_DIR,BIN_DIR,BASENAME}
+    println!("cargo:rerun-if-env-changed=GGML_RS_NAMESPACES");
+    for namespace in extra_namespaces() {
+        let key = namespace.to_uppercase();
+        let result = if let Some(system) = &system {
+            Ok((system.lib_dir.clone(), system.bin_dir.clone()))
+        } else if wasm {
+            // the variants share their symbols, and only one can be linked
+            Err("extra namespaces are not built for wasm targets".into())
+        } else {
+            build_ggml_variant(&ggml_root, &namespace, &namespace)
+        };
+        let (lib_dir, bin_dir) = match result {
+            Ok(dirs) => {
+                println!("[BUILD] ✓ {} variant built successfully", namespace);
+                dirs
+            }
+            Err(e) => {
+                eprintln!("cargo:warning=Failed to build {} variant: {}", namespace, e);
+                (out_dir.join(&namespace).join("lib"), out_dir.join(&namespace).join("bin"))
+            }
+        };
+        println!("cargo:{}_LIB_DIR={}", key, lib_dir.display());
+        println!("cargo:{}_BIN_DIR={}", key, bin_dir.display());
+        println!("cargo:{}_BASENAME={}", key, system.as_ref().map_or(namespace.as_str(), |system| system.basename.as_str()));
+        eprintln!("cargo:warning=[ggml-rs]   DEP_GGML_RS_{}_LIB_DIR={}", key, lib_dir.display());
+    }
+
+    // Recorded for ggml_rs::build_info(); both variants share their options
+    let cmake_flags = read_cmake_flags(&llama_lib_dir)
+        .or_else(|| read_cmake_flags(&whisper_lib_dir))
+        .unwrap_or_default();
+    write_build_info(&out_dir, &cmake_flags, prebuilt.is_some(), system.is_some());
+
     // IMPORTANT: Do NOT emit cargo:rustc-link-lib here
     // Each consumer crate (llama-cpp-rs, whisper-rs) will link to its own variant
+
+    // In wasm the one variant built is linked here, statically, since
+    // there is no shared library for consumers to load
+    if wasm {
+        let (lib_dir, basename) = if build_whisper {
+            (&whisper_lib_dir, "ggml_whisper")
+        } else {
+            (&llama_lib_dir, "ggml_llama")
+        };
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        for suffix in ["", "-base", "-cpu"] {
+            println!("cargo:rustc-link-lib=static={}{}", basename, suffix);
+        }
+        if wasm_threads() {
+            println!("cargo:rustc-link-arg=-pthread");
+        }
+    }
+
+    // Likewise on iOS, where apps ship static libraries. Both variants are
+    // built (for build-xcframework.sh) but only the namespace's is linked;
+    // besides -base and -cpu it has -blas (Accelerate) and -metal
+    if ios {
+        let (lib_dir, basename) = if cfg!(feature = "namespace-whisper") {
+            (&whisper_lib_dir, whisper_basename)
+        } else {
+            (&llama_lib_dir, llama_basename)
+        };
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        for lib in static_libraries(lib_dir, basename) {
+            println!("cargo:rustc-link-lib=static={}", lib);
+        }
+    }
+
+    // Except for the C library and the Python extension module: with capi or
+    // python this crate is the consumer, and links the variant its namespace
+    // feature selects. The rpath finds the variant next to the library, where
+    // cargo-c installs it and the wheel bundles it (see pyproject.toml)
+    if cfg!(feature = "capi") || cfg!(feature = "python") {
+        let (lib_dir, basename) = if cfg!(feature = "namespace-whisper") {
+            (&whisper_lib_dir, whisper_basename)
+        } else {
+            (&llama_lib_dir, llama_basename)
+        };
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        println!("cargo:rustc-link-lib=dylib={}", basename);
+        println!("cargo:rustc-link-lib=dylib={}-base", basename);
+        let target_os = target_os();
+        if target_os == "linux" {
+            println!("cargo:rustc-cdylib-link-arg=-Wl,-rpath,$ORIGIN");
+        } else if target_os == "macos" {
+            println!("cargo:rustc-cdylib-link-arg=-Wl,-rpath,@loader_path");
+        }
+        let consumer = if cfg!(feature = "capi") { "capi" } else { "python" };
+        eprintln!("cargo:warning=[ggml-rs] {}: linking {} from {}", consumer, basename, lib_dir.display());
+    }
+
+    // This crate's own binaries (gguf-diff, ...) and test binaries call ggml
+    // directly, so they link the namespace's variant too. rustc-link-arg
+    // only reaches the targets of this package, not dependent crates
+    if !wasm && !ios {
+        let (lib_dir, basename) = if cfg!(feature = "namespace-whisper") || !variant_llama {
+            (&whisper_lib_dir, whisper_basename)
+        } else {
+            (&llama_lib_dir, llama_basename)
+        };
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        let libs = shared_libraries(lib_dir, basename);
+        // RpcBackend and ggml-rpc-server call ggml-rpc directly, which a
+        // backend-dl build only has as a module
+        if cfg!(feature = "rpc") && !libs.iter().any(|lib| lib.ends_with("-rpc")) {
+            eprintln!("cargo:warning=[ggml-rs] rpc: no {}-rpc library in {}, ggml-rpc-server will not link", basename, lib_dir.display());
+        }
+        for lib in libs {
+            if target.contains("msvc") {
+                println!("cargo:rustc-link-arg={}.lib", lib);
+            } else {
+                println!("cargo:rustc-link-arg=-l{}", lib);
+            }
+        }
+        // The binaries find the libraries copied next to them (see
+        // copy_runtime_libraries), as Windows does by default
+        let target_os = target_os();
+        if target_os == "linux" {
+            println!("cargo:rustc-link-arg-bins=-Wl,-rpath,$ORIGIN");
+        } else if target_os == "macos" {
+            println!("cargo:rustc-link-arg-bins=-Wl,-rpath,@executable_path");
+        }
+    }
+}
+
+/// Generate bindings. Backend headers are only bound when their library
+/// is built, see wrapper.h
+fn generate_bindings(wrapper: &Path, include_dir: &Path, target: &str, out_path: &Path) {
+    let mut builder = bindgen::Builder::default();
+    if cfg!(feature = "cuda") {
+        builder = builder.clang_arg("-DGGML_RS_CUDA");
+    }
+    if cfg!(feature = "metal") {
+        builder = builder.clang_arg("-DGGML_RS_METAL");
+    }
+    if cfg!(feature = "vulkan") {
+        builder = builder.clang_arg("-DGGML_RS_VULKAN");
+    }
+    if cfg!(feature = "rpc") {
+        builder = builder.clang_arg("-DGGML_RS_RPC");
+    }
+    if cfg!(feature = "cann") {
+        builder = builder.clang_arg("-DGGML_RS_CANN");
+    }
+    if target.contains("apple-ios") {
+        if let Some(sdk) = ios_sdk_path(target) {
+            builder = builder.clang_arg(format!("-isysroot{}", sdk.display()));
+        }
+    }
+    if target.starts_with("wasm32") {
+        builder = builder.clang_arg(format!("--target={}", target));
+        if let Some(sysroot) = emscripten_sysroot() {
+            builder = builder.clang_arg(format!("--sysroot={}", sysroot.display()));
+        }
+    }
+    let bindings = builder
+        .header(wrapper.to_string_lossy())
+        .clang_arg(format!("-I{}", include_dir.display()))
+        .allowlist_function("ggml_.*")
+        .allowlist_type("ggml_.*")
+        .allowlist_function("gguf_.*")
+        .allowlist_type("gguf_.*")
+        .allowlist_var("GGML_.*")
+        .allowlist_var("GGUF_.*")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .generate()
+        .expect("Unable to generate bindings");
+
+    bindings
+        .write_to_file(out_path)
+        .expect("Couldn't write bindings!");
+}
+
+/// Copy ggml/ and wrapper.h to OUT_DIR/vendor and apply the patches listed
+/// in patches/series there, leaving the crate's own sources untouched: they
+/// may be read-only, as in a vendor directory or the Nix store. Files whose
+/// content is unchanged are not rewritten, so CMake does not rebuild them
+fn patched_sources(manifest_dir: &Path, out_dir: &Path) -> PathBuf {
+    let patches_dir = manifest_dir.join("patches");
+    println!("cargo:rerun-if-changed={}", patches_dir.display());
+    println!("cargo:rerun-if-changed={}", manifest_dir.join("ggml").display());
+    println!("cargo:rerun-if-changed={}", manifest_dir.join("wrapper.h").display());
+
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path.display(), e))
+    };
+    let series = read(&patches_dir.join("series"));
+    let mut patched: std::collections::HashMap<PathBuf, String> = std::collections::HashMap::new();
+    for name in series.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        for (file, hunks) in parse_patch(&read(&patches_dir.join(name)), name) {
+            let content = match patched.remove(&file) {
+                Some(content) => content,
+                None => read(&manifest_dir.join(&file)),
+            };
+            let content = apply_hunks(&content, &hunks)
+                .unwrap_or_else(|line| panic!("patches/{}: hunk at {}:{} does not apply to the vendored ggml", name, file.display(), line));
+            patched.insert(file, content);
+        }
+        println!("[BUILD] Applied patches/{}", name);
+    }
+
+    let root = out_dir.join("vendor");
+    copy_patched(manifest_dir, &root, Path::new("ggml"), &patched);
+    copy_patched(manifest_dir, &root, Path::new("wrapper.h"), &patched);
+    root
+}
+
+/// A hunk of a unified diff: its line in the original file, the lines it
+/// replaces and the lines replacing them
+struct Hunk {
+    line: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+}
+
+/// The files a unified diff (as written by `git diff`) changes, relative to
+/// the crate root, and their hunks
+fn parse_patch(diff: &str, name: &str) -> Vec<(PathBuf, Vec<Hunk>)> {
+    let mut files: Vec<(PathBuf, Vec<Hunk>)> = Vec::new();
+    let mut lines = diff.lines();
+    while let Some(line) = lines.next() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            let path = path.split('\t').next().unwrap_or(path);
+            files.push((PathBuf::from(path.strip_prefix("b/").unwrap_or(path)), Vec::new()));
+        } else if let Some(range) = line.strip_prefix("@@ -") {
+            // @@ -start,count +start,count @@
+            let count = |range: &str| -> (usize, usize) {
+                let mut parts = range.splitn(2, ',');
+                let start = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                let count = parts.next().map_or(Some(1), |n| n.parse().ok()).unwrap_or(0);
+                (start, count)
+            };
+            let mut ranges = range.split(' ');
+            let (start, mut old_left) = count(ranges.next().unwrap_or(""));
+            let (_, mut new_left) = count(ranges.next().unwrap_or("").trim_start_matches('+'));
+            let mut hunk = Hunk { line: start, old: Vec::new(), new: Vec::new() };
+            while old_left > 0 || new_left > 0 {
+                let line = lines.next().unwrap_or_else(|| panic!("patches/{}: truncated hunk", name));
+                if line.starts_with('\\') {
+                    continue; // \ No newline at end of file
+                }
+                let (kind, text) = line.split_at(line.len().min(1));
+                if kind != "+" {
+                    hunk.old.push(text.to_string());
+                    old_left = old_left.saturating_sub(1);
+                }
+                if kind != "-" {
+                    hunk.new.push(text.to_string());
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            files
+                .last_mut()
+                .unwrap_or_else(|| panic!("patches/{}: hunk before a +++ line", name))
+                .1
+                .push(hunk);
+        }
+    }
+    files
+}
+
+/// `content` with `hunks` applied, each where its old lines are nearest to
+/// its line number; the line number of the first hunk that matches nowhere
+fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<String, usize> {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    // how far lines have moved from the original numbering
+    let mut offset: isize = 0;
+    for hunk in hunks {
+        let expected = (hunk.line.saturating_sub(1) as isize + offset).max(0) as usize;
+        let matches = |at: usize| at + hunk.old.len() <= lines.len() && lines[at..at + hunk.old.len()] == hunk.old[..];
+        let at = (0..=lines.len())
+            .flat_map(|distance| [expected.checked_sub(distance), Some(expected + distance)])
+            .flatten()
+            .find(|&at| matches(at))
+            .ok_or(hunk.line)?;
+        lines.splice(at..at + hunk.old.len(), hunk.new.iter().cloned());
+        offset = at as isize - hunk.line.saturating_sub(1) as isize + hunk.new.len() as isize - hunk.old.len() as isize;
+    }
+    let mut patched = lines.join("\n");
+    if content.ends_with('\n') {
+        patched.push('\n');
+    }
+    Ok(patched)
+}
+
+/// Copy `path` (a file or directory relative to `from`) to `to`, taking
+/// the content of patched files from `patched`
+fn copy_patched(from: &Path, to: &Path, path: &Path, patched: &std::collections::HashMap<PathBuf, String>) {
+    let source = from.join(path);
+    let destination = to.join(path);
+    if source.is_dir() {
+        std::fs::create_dir_all(&destination).unwrap_or_else(|e| panic!("Couldn't create {}: {}", destination.display(), e));
+        for entry in std::fs::read_dir(&source).unwrap_or_else(|e| panic!("Couldn't read {}: {}", source.display(), e)).flatten() {
+            copy_patched(from, to, &path.join(entry.file_name()), patched);
+        }
+        return;
+    }
+    let content = match patched.get(path) {
+        Some(content) => content.clone().into_bytes(),
+        None => std::fs::read(&source).unwrap_or_else(|e| panic!("Couldn't read {}: {}", source.display(), e)),
+    };
+    if std::fs::read(&destination).ok().as_deref() != Some(&content[..]) {
+        std::fs::write(&destination, &content).unwrap_or_else(|e| panic!("Couldn't write {}: {}", destination.display(), e));
+    }
+}
+
+/// Cargo features that change what the ggml libraries are built with, and
+/// so must match between this build and a prebuilt companion crate
+const PREBUILT_FEATURES: &[&str] = &[
+    "cuda", "metal", "vulkan", "openblas", "mkl", "blis", "openmp", "hipblas", "intel-sycl", "cann", "rpc",
+    "backend-dl", "avx512", "amx", "disable-native", "dotprod", "i8mm", "sve",
+    "cpu-all-variants",
+];
+
+/// With the prebuilt feature, the root of the ggml-rs-prebuilt-* crate for
+/// this target. Falls back to building from source (None) when there is no
+/// companion crate for the target or it was packaged with other backends
+fn find_prebuilt() -> Option<PathBuf> {
+    env::var_os("CARGO_FEATURE_PREBUILT")?;
+    // Each companion crate sets links = "ggml_rs_prebuilt_<target>" and
+    // exports cargo:ROOT, which reaches us as DEP_GGML_RS_PREBUILT_<TARGET>_ROOT
+    let root = env::vars()
+        .find(|(key, _)| key.starts_with("DEP_GGML_RS_PREBUILT_") && key.ends_with("_ROOT"))
+        .map(|(_, value)| PathBuf::from(value));
+    let root = match root {
+        Some(root) => root,
+        None => {
+            println!("cargo:warning=[ggml-rs] prebuilt: no ggml-rs-prebuilt crate for {}, building ggml from source", env::var("TARGET").unwrap());
+            return None;
+        }
+    };
+
+    if !prebuilt_matches(&root, "prebuilt") {
+        return None;
+    }
+    println!("[BUILD] Using prebuilt ggml libraries from {}", root.display());
+    Some(root)
+}
+
+/// The enabled PREBUILT_FEATURES, sorted
+fn enabled_backend_features() -> Vec<&'static str> {
+    let mut enabled: Vec<&str> = PREBUILT_FEATURES
+        .iter()
+        .copied()
+        .filter(|feature| env::var_os(format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"))).is_some())
+        .collect();
+    enabled.sort_unstable();
+    enabled
+}
+
+/// Whether the libraries packaged at root were built with exactly the
+/// enabled backend features, per its features.txt
+fn prebuilt_matches(root: &Path, source: &str) -> bool {
+    let packaged = std::fs::read_to_string(root.join("features.txt")).unwrap_or_default();
+    let mut packaged: Vec<&str> = packaged.split_whitespace().collect();
+    packaged.sort_unstable();
+    let enabled = enabled_backend_features();
+    if packaged != enabled {
+        println!(
+            "cargo:warning=[ggml-rs] {}: {} was packaged with features {:?} but {:?} are enabled, building ggml from source",
+            source, root.display(), packaged, enabled
+        );
+        return false;
+    }
+    true
+}
+
+/// With download-binaries, fetch the release archive of prebuilt libraries
+/// for this target and backend features, check it against the checksum
+/// pinned in binaries.sha256 and unpack it in OUT_DIR. Any failure, including
+/// no network, falls back to building from source (None)
+#[cfg(feature = "download-binaries")]
+fn download_prebuilt(out_dir: &Path) -> Option<PathBuf> {
+    use sha2::{Digest, Sha256};
+    use std::fs;
+    use std::process::Command;
+
+    let fallback = |reason: String| {
+        println!("cargo:warning=[ggml-rs] download-binaries: {}, building ggml from source", reason);
+        None
+    };
+
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+    let mut name = format!("ggml-rs-{}-{}", version, env::var("TARGET").unwrap());
+    for feature in enabled_backend_features() {
+        name.push('-');
+        name.push_str(feature);
+    }
+    name.push_str(".tar.gz");
+
+    // sha256sum format: "<hex>  <file>"
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed={}", manifest_dir.join("binaries.sha256").display());
+    let checksums = fs::read_to_string(manifest_dir.join("binaries.sha256")).unwrap_or_default();
+    let pinned: Vec<&str> = checksums.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')).collect();
+    if pinned.is_empty() {
+        return fallback(format!("ggml-rs {} publishes no binaries yet (binaries.sha256 is empty)", version));
+    }
+    let expected = pinned
+        .iter()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim_start_matches([' ', '*']) == name)
+        .map(|(sum, _)| sum.to_ascii_lowercase());
+    let expected = match expected {
+        Some(sum) => sum,
+        None => return fallback(format!("no published binaries named {}", name)),
+    };
+
+    // Unpacked by an earlier run of this build script
+    let root = out_dir.join("download");
+    if fs::read_to_string(root.join(".sha256")).ok().as_deref() == Some(expected.as_str()) {
+        return Some(root);
+    }
+
+    println!("cargo:rerun-if-env-changed=CARGO_NET_OFFLINE");
+    if env::var("CARGO_NET_OFFLINE").as_deref() == Ok("true") {
+        return fallback("CARGO_NET_OFFLINE is set".to_string());
+    }
+    println!("cargo:rerun-if-env-changed=GGML_RS_BINARIES_URL");
+    let base = env::var("GGML_RS_BINARIES_URL")
+        .unwrap_or_else(|_| format!("https://github.com/joshatdia/ggml-rs/releases/download/v{}", version));
+    let url = format!("{}/{}", base.trim_end_matches('/'), name);
+    let archive = out_dir.join(&name);
+    println!("[BUILD] Downloading {}", url);
+    let status = Command::new("curl")
+        .args(["--fail", "--location", "--silent", "--show-error", "--retry", "2", "--output"])
+        .arg(&archive)
+        .arg(&url)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => return fallback(format!("downloading {} failed ({})", url, status)),
+        Err(e) => return fallback(format!("could not run curl: {}", e)),
+    }
+
+    let data = match fs::read(&archive) {
+        Ok(data) => data,
+        Err(e) => return fallback(format!("could not read {}: {}", archive.display(), e)),
+    };
+    let actual: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        let _ = fs::remove_file(&archive);
+        return fallback(format!("checksum mismatch for {} (expected {}, got {})", url, expected, actual));
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    if let Err(e) = fs::create_dir_all(&root) {
+        return fallback(format!("could not create {}: {}", root.display(), e));
+    }
+    let status = Command::new("tar").arg("-xzf").arg(&archive).arg("-C").arg(&root).status();
+    let _ = fs::remove_file(&archive);
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => return fallback(format!("unpacking {} failed ({})", name, status)),
+        Err(e) => return fallback(format!("could not run tar: {}", e)),
+    }
+    let _ = fs::write(root.join(".sha256"), &expected);
+
+    if !prebuilt_matches(&root, "download-binaries") {
+        return None;
+    }
+    println!("[BUILD] Using downloaded ggml libraries from {}", root.display());
+    Some(root)
+}
+
+#[cfg(not(feature = "download-binaries"))]
+fn download_prebuilt(_out_dir: &Path) -> Option<PathBuf> {
+    None
+}
+
+/// Use one variant from a prebuilt companion crate or downloaded archive in
+/// place of build_ggml_variant: same layout, <root>/<tag>/{lib,bin}
+fn prebuilt_variant(root: &Path, namespace: &str, tag: &str) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+    let prefix = root.join(tag);
+    let lib_dir = prefix.join("lib");
+    let bin_dir = prefix.join("bin");
+    if !lib_dir.exists() {
+        return Err(format!("prebuilt {} variant missing at {}", tag, lib_dir.display()).into());
+    }
+    copy_runtime_libraries(&prefix, &lib_dir, namespace);
+    Ok((lib_dir, bin_dir))
+}
+
+/// A ggml installed outside this crate, linked instead of the vendored one
+struct SystemGgml {
+    lib_dir: PathBuf,
+    bin_dir: PathBuf,
+    include_dir: PathBuf,
+    /// Library name without prefix or suffix, "ggml" unless renamed
+    basename: String,
+}
+
+/// The installed ggml to use, from GGML_SYS_LIB_DIR (and optionally
+/// GGML_SYS_INCLUDE_DIR, GGML_SYS_BIN_DIR, GGML_SYS_BASENAME) or, with the
+/// system feature, from pkg-config. Panics rather than fall back to the
+/// vendored sources, since packagers asking for it must not bundle ggml
+fn find_system_ggml() -> Option<SystemGgml> {
+    for var in ["GGML_SYS_LIB_DIR", "GGML_SYS_INCLUDE_DIR", "GGML_SYS_BIN_DIR", "GGML_SYS_BASENAME"] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+    let basename = env::var("GGML_SYS_BASENAME").unwrap_or_else(|_| "ggml".to_string());
+    let system = if let Some(lib_dir) = env::var_os("GGML_SYS_LIB_DIR") {
+        let lib_dir = PathBuf::from(lib_dir);
+        let include_dir = env::var_os("GGML_SYS_INCLUDE_DIR")
+            .map(PathBuf::from)
+            .or_else(|| lib_dir.parent().map(|prefix| prefix.join("include")))
+            .expect("GGML_SYS_LIB_DIR has no parent; set GGML_SYS_INCLUDE_DIR");
+        let bin_dir = env::var_os("GGML_SYS_BIN_DIR").map_or_else(|| lib_dir.clone(), PathBuf::from);
+        SystemGgml { lib_dir, bin_dir, include_dir, basename }
+    } else if env::var_os("CARGO_FEATURE_SYSTEM").is_some() {
+        probe_pkg_config(basename)
+    } else {
+        return None;
+    };
+
+    if !system.include_dir.join("ggml.h").exists() {
+        panic!("System ggml: ggml.h not found in {}; set GGML_SYS_INCLUDE_DIR", system.include_dir.display());
+    }
+    println!("[BUILD] Using system ggml {} from {}", system.basename, system.lib_dir.display());
+    Some(system)
+}
+
+#[cfg(feature = "system")]
+fn probe_pkg_config(basename: String) -> SystemGgml {
+    // ggml's own ggml.pc; the vendored version is the oldest the safe
+    // wrappers are written against
+    let library = pkg_config::Config::new()
+        .atleast_version(&vendored_ggml_version())
+        .cargo_metadata(false)
+        .probe("ggml")
+        .unwrap_or_else(|e| panic!("System ggml: {}\nInstall ggml or set GGML_SYS_LIB_DIR", e));
+    let lib_dir = library.link_paths.first().cloned().unwrap_or_else(|| PathBuf::from("/usr/lib"));
+    let include_dir = library.include_paths.first().cloned().unwrap_or_else(|| PathBuf::from("/usr/include"));
+    SystemGgml { bin_dir: lib_dir.clone(), lib_dir, include_dir, basename }
+}
+
+#[cfg(not(feature = "system"))]
+fn probe_pkg_config(_basename: String) -> SystemGgml {
+    unreachable!("CARGO_FEATURE_SYSTEM is only set with the system feature")
+}
+
+/// GGML_VERSION_{MAJOR,MINOR,PATCH} of ggml/CMakeLists.txt, as "0.9.4"
+#[cfg(feature = "system")]
+fn vendored_ggml_version() -> String {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let cmake = std::fs::read_to_string(manifest_dir.join("ggml").join("CMakeLists.txt")).unwrap_or_default();
+    let part = |name: &str| {
+        let prefix = format!("set(GGML_VERSION_{} ", name);
+        cmake
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(&prefix)?.strip_suffix(')').map(str::to_string))
+            .unwrap_or_else(|| "0".to_string())
+    };
+    format!("{}.{}.{}", part("MAJOR"), part("MINOR"), part("PATCH"))
+}
+
+/// Extra variant namespaces from GGML_RS_NAMESPACES, a comma or space
+/// separated list of library base names such as "ggml_sd". Set it for the
+/// whole build with an [env] entry in .cargo/config.toml
+fn extra_namespaces() -> Vec<String> {
+    let list = env::var("GGML_RS_NAMESPACES").unwrap_or_default();
+    let mut namespaces: Vec<String> = Vec::new();
+    for name in list.split(|c: char| c == ',' || c.is_whitespace()).filter(|n| !n.is_empty()) {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            panic!("GGML_RS_NAMESPACES: invalid namespace {:?}, use lowercase letters, digits and '_'", name);
+        }
+        // the built-in variants, and their install directories under OUT_DIR
+        if matches!(name, "ggml" | "ggml_llama" | "ggml_whisper" | "llama" | "whisper" | "build") {
+            panic!("GGML_RS_NAMESPACES: {:?} is reserved", name);
+        }
+        if !namespaces.iter().any(|n| n == name) {
+            namespaces.push(name.to_string());
+        }
+    }
+    // patch_ggml_config_cmake swaps names textually, so none may be a
+    // prefix of another
+    let all: Vec<&str> = ["ggml_llama", "ggml_whisper"].into_iter().chain(namespaces.iter().map(String::as_str)).collect();
+    for a in &all {
+        if let Some(b) = all.iter().find(|b| a != *b && b.starts_with(a)) {
+            panic!("GGML_RS_NAMESPACES: {:?} is a prefix of {:?}", a, b);
+        }
+    }
+    namespaces
+}
+
+/// Every namespace this build produces except `namespace`
+fn other_namespaces(namespace: &str) -> Vec<String> {
+    ["ggml_llama".to_string(), "ggml_whisper".to_string()]
+        .into_iter()
+        .chain(extra_namespaces())
+        .filter(|n| n != namespace)
+        .collect()
 }
 
 /// Build a single GGML variant with the specified namespace
-fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+fn build_ggml_variant(ggml_root: &Path, namespace: &str, tag: &str) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
     println!("[BUILD] Building {} variant with namespace: {}", tag, namespace);
 
     // Build ggml as shared library using CMake
@@ -257,17 +963,70 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
         .very_verbose(true)
         .pic(true);
     
+    if env::var("TARGET").unwrap().starts_with("wasm32") {
+        // emscripten only links static libraries into the module; ggml-cpu
+        // adds -msimd128 itself
+        config.define("BUILD_SHARED_LIBS", "OFF");
+        config.define("GGML_NATIVE", "OFF");
+        if let Some(root) = emscripten_root() {
+            let toolchain = root.join("cmake").join("Modules").join("Platform").join("Emscripten.cmake");
+            config.define("CMAKE_TOOLCHAIN_FILE", toolchain.to_string_lossy().as_ref());
+        }
+        if wasm_threads() {
+            // workers become web workers over a SharedArrayBuffer
+            config.cflag("-pthread");
+            config.cxxflag("-pthread");
+        }
+    }
+
     // Always set namespace for this variant
     config.define("GGML_NAME", namespace);
     println!("[BUILD] Setting GGML_NAME={} for {} variant", namespace, tag);
     println!("[BUILD] Using install prefix: {}", variant_install_prefix.display());
 
-    if cfg!(target_os = "windows") {
+    if target_os() == "windows" {
         config.cxxflag("/utf-8");
     }
     
     let target = env::var("TARGET").unwrap();
 
+    if target.contains("apple-ios") {
+        // Static libraries for the device or simulator SDK; with metal the
+        // shaders are embedded (GGML_METAL_EMBED_LIBRARY below), as apps
+        // cannot load a loose default.metallib
+        config.define("BUILD_SHARED_LIBS", "OFF");
+        config.define("GGML_NATIVE", "OFF");
+        config.define("CMAKE_SYSTEM_NAME", "iOS");
+        config.define("CMAKE_OSX_SYSROOT", ios_sdk(&target));
+        config.define("CMAKE_OSX_ARCHITECTURES", if target.starts_with("aarch64") { "arm64" } else { "x86_64" });
+        config.define("CMAKE_OSX_DEPLOYMENT_TARGET", ios_deployment_target());
+    }
+
+    // Portable builds: ggml's baseline instruction sets (AVX2, FMA, F16C
+    // and BMI2 on x86-64) instead of -march=native, which would also
+    // override the explicit AVX512/AMX options (both imply this feature)
+    if cfg!(feature = "disable-native") {
+        config.define("GGML_NATIVE", "OFF");
+    }
+    if cfg!(feature = "avx512") {
+        for option in ["GGML_AVX512", "GGML_AVX512_VBMI", "GGML_AVX512_VNNI", "GGML_AVX512_BF16"] {
+            config.define(option, "ON");
+        }
+    }
+    if cfg!(feature = "amx") {
+        for option in ["GGML_AMX_TILE", "GGML_AMX_INT8", "GGML_AMX_BF16"] {
+            config.define(option, "ON");
+        }
+    }
+    if target.starts_with("aarch64") {
+        // ggml only uses GGML_CPU_ARM_ARCH (-march) in non-native builds
+        if let Some(arch) = arm_arch() {
+            println!("[BUILD] GGML_CPU_ARM_ARCH={}", arch);
+            config.define("GGML_NATIVE", "OFF");
+            config.define("GGML_CPU_ARM_ARCH", arch);
+        }
+    }
+
     if cfg!(feature = "cuda") {
         println!("[BUILD] Configuring CUDA support");
         config.define("GGML_CUDA", "ON");
@@ -327,6 +1086,24 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
         println!("cargo:rerun-if-env-changed=BLAS_INCLUDE_DIRS");
     }
 
+    if cfg!(feature = "mkl") {
+        // mkl_rt: the single dynamic library selecting threading at runtime
+        let root = mkl_root();
+        config.define("GGML_BLAS", "ON");
+        config.define("GGML_BLAS_VENDOR", "Intel10_64_dyn");
+        config.define("BLAS_INCLUDE_DIRS", root.join("include"));
+        // FindBLAS looks for MKL under $MKLROOT
+        config.env("MKLROOT", &root);
+    }
+
+    if cfg!(feature = "blis") {
+        let root = blis_root();
+        config.define("GGML_BLAS", "ON");
+        config.define("GGML_BLAS_VENDOR", "FLAME");
+        config.define("BLAS_INCLUDE_DIRS", root.join("include").join("blis"));
+        config.define("CMAKE_PREFIX_PATH", &root);
+    }
+
     if cfg!(feature = "metal") {
         config.define("GGML_METAL", "ON");
         config.define("GGML_METAL_NDEBUG", "ON");
@@ -342,6 +1119,21 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
         config.define("GGML_OPENMP", "OFF");
     }
 
+    if cfg!(feature = "rpc") {
+        config.define("GGML_RPC", "ON");
+    }
+
+    if cfg!(feature = "backend-dl") {
+        // backends become modules loaded at runtime (BackendRegistry::load_all)
+        config.define("GGML_BACKEND_DL", "ON");
+    }
+
+    if cfg!(feature = "cpu-all-variants") {
+        // one CPU module per instruction set level (x64, sandybridge,
+        // haswell, ...); the registry loads the best one the CPU supports
+        config.define("GGML_CPU_ALL_VARIANTS", "ON");
+    }
+
     if cfg!(feature = "intel-sycl") {
         config.define("GGML_SYCL", "ON");
         config.define("GGML_SYCL_TARGET", "INTEL");
@@ -349,9 +1141,22 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
         config.define("CMAKE_CXX_COMPILER", "icpx");
     }
 
+    if cfg!(feature = "cann") {
+        let toolkit = cann_toolkit_home();
+        config.define("GGML_CANN", "ON");
+        config.define("CANN_INSTALL_DIR", &toolkit);
+        println!("cargo:rerun-if-env-changed=SOC_VERSION");
+        if let Ok(soc) = env::var("SOC_VERSION") {
+            config.define("SOC_VERSION", soc);
+        }
+        println!("cargo:rustc-link-search={}", toolkit.join("lib64").display());
+        println!("[BUILD] CANN toolkit: {}", toolkit.display());
+    }
+
     // Allow passing any GGML or CMAKE compile flags
     for (key, value) in env::vars() {
-        let is_ggml_flag = key.starts_with("GGML_");
+        // GGML_CPU_ARM_ARCH is combined with the ARM features by arm_arch()
+        let is_ggml_flag = key.starts_with("GGML_") && !key.starts_with("GGML_RS_") && key != "GGML_CPU_ARM_ARCH";
         let is_cmake_flag = key.starts_with("CMAKE_");
         if is_ggml_flag || is_cmake_flag {
             config.define(&key, &value);
@@ -361,6 +1166,10 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
     println!("[BUILD] Starting CMake build...");
     let destination = config.build();
     println!("[BUILD] CMake build completed. Output directory: {}", destination.display());
+    record_cmake_flags(
+        &destination.join("build").join("CMakeCache.txt"),
+        &variant_install_prefix.join("cmake_flags.txt"),
+    );
 
     // Explicitly run CMake install to ensure libraries are installed
     // The build() function should run install automatically, but we'll verify
@@ -453,8 +1262,71 @@ fn build_ggml_variant(ggml_root: &PathBuf, namespace: &str, tag: &str) -> Result
     Ok((lib_dir, bin_dir))
 }
 
+/// CMake cache entries worth reporting: every ggml option, as configured
+/// or defaulted, and the build type. Compiler paths are left out
+fn record_cmake_flags(cache: &Path, dest: &Path) {
+    let content = match std::fs::read_to_string(cache) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("cargo:warning=Failed to read {}: {}", cache.display(), e);
+            return;
+        }
+    };
+    let mut flags = Vec::new();
+    for line in content.lines() {
+        // KEY:TYPE=VALUE
+        let (key, value) = match line.split_once('=') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let (key, ty) = key.split_once(':').unwrap_or((key, ""));
+        if ty == "INTERNAL" || ty == "STATIC" {
+            continue;
+        }
+        let wanted = key.starts_with("GGML_")
+            || matches!(key, "BUILD_SHARED_LIBS" | "CMAKE_BUILD_TYPE" | "CMAKE_CUDA_ARCHITECTURES");
+        if wanted {
+            flags.push(format!("{}={}", key, value));
+        }
+    }
+    flags.sort();
+    if let Err(e) = std::fs::write(dest, flags.join("\n")) {
+        eprintln!("cargo:warning=Failed to write {}: {}", dest.display(), e);
+    }
+}
+
+/// Flags recorded by record_cmake_flags next to a variant's lib directory
+fn read_cmake_flags(lib_dir: &Path) -> Option<Vec<String>> {
+    let content = std::fs::read_to_string(lib_dir.parent()?.join("cmake_flags.txt")).ok()?;
+    Some(content.lines().map(str::to_string).collect())
+}
+
+/// Write OUT_DIR/build_info.rs, included by src/build_info.rs
+fn write_build_info(out_dir: &Path, cmake_flags: &[String], prebuilt: bool, system: bool) {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+
+    let code = format!(
+        "pub(crate) const FEATURES: &[&str] = &{:?};\n\
+         pub(crate) const CMAKE_FLAGS: &[&str] = &{:?};\n\
+         pub(crate) const TARGET: &str = {:?};\n\
+         pub(crate) const PROFILE: &str = {:?};\n\
+         pub(crate) const PREBUILT: bool = {};\n\
+         pub(crate) const SYSTEM: bool = {};\n",
+        features,
+        cmake_flags,
+        env::var("TARGET").unwrap(),
+        env::var("PROFILE").unwrap_or_default(),
+        prebuilt,
+        system,
+    );
+    std::fs::write(out_dir.join("build_info.rs"), code).expect("Couldn't write build_info.rs!");
+}
+
 /// Patch ggml-config.cmake to use namespaced library names
-fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf, namespace: &str) {
+fn patch_ggml_config_cmake(cmake_build_dir: &Path, install_prefix: &Path, namespace: &str) {
     use std::fs;
     use std::io::Write;
     
@@ -565,7 +1437,7 @@ fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf,
         
         // Replace backend library patterns specifically
         // Pattern: find_library(... ggml-cpu ...) -> find_library(... {namespace}-cpu ...)
-        let backend_libs = vec!["cpu", "cuda", "metal", "vulkan", "hip", "blas", "sycl"];
+        let backend_libs = vec!["cpu", "cuda", "metal", "vulkan", "hip", "blas", "sycl", "cann", "rpc"];
         for backend in &backend_libs {
             // Replace in find_library calls
             patched = patched.replace(
@@ -601,18 +1473,19 @@ fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf,
         );
         
         // IMPORTANT: Also check if the file already contains the wrong namespace and fix it
-        let wrong_namespace = if namespace == "ggml_llama" { "ggml_whisper" } else { "ggml_llama" };
-        if patched.contains(wrong_namespace) {
-            eprintln!("cargo:warning=[PATCH] ⚠ Found wrong namespace '{}' in config file, fixing...", wrong_namespace);
-            // Replace wrong namespace with correct one
-            patched = patched.replace(&wrong_namespace, namespace);
+        for wrong_namespace in other_namespaces(namespace) {
+            if patched.contains(&wrong_namespace) {
+                eprintln!("cargo:warning=[PATCH] ⚠ Found wrong namespace '{}' in config file, fixing...", wrong_namespace);
+                // Replace wrong namespace with correct one
+                patched = patched.replace(&wrong_namespace, namespace);
+            }
         }
         
         // Restore "ggml::"
         patched = patched.replace(protected_marker, "ggml::");
         
         // Build the list of all ggml imported targets we may need to guard/dedup
-        let backend_libs = vec!["cpu", "cuda", "metal", "vulkan", "hip", "blas", "sycl"];
+        let backend_libs = vec!["cpu", "cuda", "metal", "vulkan", "hip", "blas", "sycl", "cann", "rpc"];
         let mut all_targets: Vec<String> = Vec::new();
         all_targets.push(format!("ggml::{}", namespace));
         all_targets.push(format!("ggml::{}-base", namespace));
@@ -777,17 +1650,11 @@ fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf,
 
         // FINAL ENFORCEMENT: Ensure the correct namespace is used everywhere.
         // If this whisper file still references ggml_llama (or vice versa), force-replace.
-        if namespace == "ggml_whisper" {
-            if patched.contains("ggml_llama-") || patched.contains(" ggml_llama") {
-                eprintln!("cargo:warning=[PATCH] ⚠ Enforcing whisper namespace: replacing ggml_llama* → ggml_whisper*");
-                patched = patched.replace("ggml_llama-", "ggml_whisper-");
-                patched = patched.replace(" ggml_llama", " ggml_whisper");
-            }
-        } else if namespace == "ggml_llama" {
-            if patched.contains("ggml_whisper-") || patched.contains(" ggml_whisper") {
-                eprintln!("cargo:warning=[PATCH] ⚠ Enforcing llama namespace: replacing ggml_whisper* → ggml_llama*");
-                patched = patched.replace("ggml_whisper-", "ggml_llama-");
-                patched = patched.replace(" ggml_whisper", " ggml_llama");
+        for other in other_namespaces(namespace) {
+            if patched.contains(&format!("{}-", other)) || patched.contains(&format!(" {}", other)) {
+                eprintln!("cargo:warning=[PATCH] ⚠ Enforcing {} namespace: replacing {}* → {}*", namespace, other, namespace);
+                patched = patched.replace(&format!("{}-", other), &format!("{}-", namespace));
+                patched = patched.replace(&format!(" {}", other), &format!(" {}", namespace));
             }
         }
         
@@ -800,10 +1667,11 @@ fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf,
                 eprintln!("cargo:warning=[PATCH] ⚠ WARNING: patched content does NOT contain namespace '{}'", namespace);
             }
             
-            // Check for wrong namespace (the other variant's namespace)
-            let wrong_namespace = if namespace == "ggml_llama" { "ggml_whisper" } else { "ggml_llama" };
-            if patched.contains(wrong_namespace) {
-                eprintln!("cargo:warning=[PATCH] ⚠ ERROR: patched content contains WRONG namespace '{}'!", wrong_namespace);
+            // Check for wrong namespace (another variant's namespace)
+            for wrong_namespace in other_namespaces(namespace) {
+                if patched.contains(&wrong_namespace) {
+                    eprintln!("cargo:warning=[PATCH] ⚠ ERROR: patched content contains WRONG namespace '{}'!", wrong_namespace);
+                }
             }
             
             // Write the patched content back
@@ -824,9 +1692,14 @@ fn patch_ggml_config_cmake(cmake_build_dir: &PathBuf, install_prefix: &PathBuf,
     }
 }
 
-fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &str) {
+fn copy_runtime_libraries(destination: &Path, lib_dir: &Path, namespace: &str) {
     use std::fs;
     
+    // wasm and iOS builds are static libraries, linked into this crate
+    if matches!(target_os().as_str(), "emscripten" | "ios") {
+        return;
+    }
+
     println!("[COPY] Starting DLL copy process for {} variant...", namespace);
     println!("[COPY] Destination: {}", destination.display());
     println!("[COPY] Library directory: {}", lib_dir.display());
@@ -854,10 +1727,11 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
         return;
     }
     
-    // Determine library extension based on platform
-    let lib_ext = if cfg!(target_os = "windows") {
+    // Determine library extension based on the target platform
+    let target_os = target_os();
+    let lib_ext = if target_os == "windows" {
         "dll"
-    } else if cfg!(target_os = "macos") {
+    } else if target_os == "macos" {
         "dylib"
     } else {
         "so"
@@ -886,20 +1760,30 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
     if cfg!(feature = "metal") {
         libraries.push(format!("{}-metal", lib_base_name));
     }
-    if cfg!(feature = "openblas") || cfg!(target_os = "macos") {
+    if cfg!(feature = "openblas")
+        || cfg!(feature = "mkl")
+        || cfg!(feature = "blis")
+        || target_os == "macos"
+    {
         libraries.push(format!("{}-blas", lib_base_name));
     }
     if cfg!(feature = "intel-sycl") {
         libraries.push(format!("{}-sycl", lib_base_name));
     }
+    if cfg!(feature = "cann") {
+        libraries.push(format!("{}-cann", lib_base_name));
+    }
+    if cfg!(feature = "rpc") {
+        libraries.push(format!("{}-rpc", lib_base_name));
+    }
     
     // Copy libraries from install directory
     println!("[COPY] Libraries to copy: {:?}", libraries);
     for lib_name in libraries.iter() {
         println!("[COPY] Checking for library: {}", lib_name);
-        let lib_file = if cfg!(target_os = "windows") {
+        let lib_file = if target_os == "windows" {
             lib_dir.join(format!("{}.{}", lib_name, lib_ext))
-        } else if cfg!(target_os = "macos") {
+        } else if target_os == "macos" {
             lib_dir.join(format!("lib{}.{}", lib_name, lib_ext))
         } else {
             lib_dir.join(format!("lib{}.{}", lib_name, lib_ext))
@@ -916,9 +1800,9 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
         } else {
             println!("[COPY]   Not found in install directory, checking build directory...");
             // Also check build directory (library might be built but not installed)
-            let build_lib_file = if cfg!(target_os = "windows") {
+            let build_lib_file = if target_os == "windows" {
                 destination.join("build").join("src").join("Release").join(format!("{}.{}", lib_name, lib_ext))
-            } else if cfg!(target_os = "macos") {
+            } else if target_os == "macos" {
                 destination.join("build").join("src").join(format!("lib{}.{}", lib_name, lib_ext))
             } else {
                 destination.join("build").join("src").join(format!("lib{}.{}", lib_name, lib_ext))
@@ -938,8 +1822,34 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
         }
     }
     
+    // Dynamic backends are modules installed in bin rather than lib, and
+    // named .so on macOS too; copy every one, including CPU variants
+    if cfg!(feature = "backend-dl") {
+        let module_dir = lib_dir.parent().unwrap().join("bin");
+        let (module_prefix, module_ext) = if target_os == "windows" {
+            (format!("{}-", lib_base_name), ".dll")
+        } else {
+            (format!("lib{}-", lib_base_name), ".so")
+        };
+        println!("[COPY] Checking for backend modules in: {}", module_dir.display());
+        if let Ok(entries) = fs::read_dir(&module_dir) {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if !file_name.starts_with(&module_prefix) || !file_name.ends_with(module_ext) {
+                    continue;
+                }
+                let target_file = target_dir.join(&file_name);
+                if let Err(e) = fs::copy(entry.path(), &target_file) {
+                    eprintln!("cargo:warning=Failed to copy {} to {}: {}", entry.path().display(), target_file.display(), e);
+                } else {
+                    println!("[COPY] ✓ Copied {} to {}", entry.path().display(), target_file.display());
+                }
+            }
+        }
+    }
+
     // Also check bin directory on Windows (DLLs might be installed there)
-    if cfg!(target_os = "windows") {
+    if target_os == "windows" {
         let bin_dir = destination.join("bin");
         println!("[COPY] Checking bin directory: {}", bin_dir.display());
         if bin_dir.exists() {
@@ -994,7 +1904,7 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
         }
         
         // Also check build/bin/Release directory (Windows Release build output)
-        if cfg!(target_os = "windows") {
+        if target_os == "windows" {
             let build_bin_release_dir = destination.join("build").join("bin").join("Release");
             println!("[COPY] Checking build/bin/Release directory: {}", build_bin_release_dir.display());
             if build_bin_release_dir.exists() {
@@ -1023,8 +1933,318 @@ fn copy_runtime_libraries(destination: &PathBuf, lib_dir: &PathBuf, namespace: &
 }
 
 // From https://github.com/alexcrichton/cc-rs/blob/fba7feded71ee4f63cfe885673ead6d7b4f2f454/src/lib.rs#L2462
+/// Browser builds go through emscripten, the only toolchain with a C++
+/// standard library for wasm; backends other than the CPU have no wasm port.
+fn check_wasm_target(target: &str) {
+    if !target.contains("emscripten") {
+        panic!(
+            "ggml-rs builds for wasm with the wasm32-unknown-emscripten target (emsdk), not {}",
+            target
+        );
+    }
+    let unsupported = [
+        ("cuda", cfg!(feature = "cuda")),
+        ("metal", cfg!(feature = "metal")),
+        ("vulkan", cfg!(feature = "vulkan")),
+        ("hipblas", cfg!(feature = "hipblas")),
+        ("intel-sycl", cfg!(feature = "intel-sycl")),
+        ("cann", cfg!(feature = "cann")),
+        ("openblas", cfg!(feature = "openblas")),
+        ("mkl", cfg!(feature = "mkl")),
+        ("blis", cfg!(feature = "blis")),
+        ("openmp", cfg!(feature = "openmp")),
+        ("rpc", cfg!(feature = "rpc")),
+        ("backend-dl", cfg!(feature = "backend-dl")),
+        ("capi", cfg!(feature = "capi")),
+        ("python", cfg!(feature = "python")),
+    ];
+    for (feature, enabled) in unsupported {
+        if enabled {
+            panic!("the {} feature is not available for {}", feature, target);
+        }
+    }
+    for var in ["EMSDK", "EMSCRIPTEN", "EM_CACHE", "PATH"] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+    let root = emscripten_root().unwrap_or_else(|| {
+        panic!(
+            "emscripten not found for {}: activate the emsdk (EMSDK), set EMSCRIPTEN to the directory of emcc, or put emcc on PATH",
+            target
+        )
+    });
+    println!(
+        "[BUILD] wasm target {} with emscripten at {}: CPU backend with SIMD128, {}",
+        target,
+        root.display(),
+        if wasm_threads() { "wasm threads" } else { "single-threaded" }
+    );
+}
+
+/// The AVX512 and AMX features are x86-64 instruction sets, and MSVC has no
+/// AMX options; dotprod, i8mm and SVE are AArch64 extensions. The CPU
+/// variants each pick their own instruction sets.
+fn check_cpu_isa_features(target: &str) {
+    if cfg!(feature = "cpu-all-variants") {
+        let fixed = [
+            ("avx512", cfg!(feature = "avx512")),
+            ("dotprod", cfg!(feature = "dotprod")),
+            ("i8mm", cfg!(feature = "i8mm")),
+            ("sve", cfg!(feature = "sve")),
+        ];
+        for (feature, enabled) in fixed {
+            if enabled {
+                panic!("the {} feature cannot be combined with cpu-all-variants", feature);
+            }
+        }
+        if env::var_os("GGML_CPU_ARM_ARCH").is_some() {
+            panic!("GGML_CPU_ARM_ARCH cannot be combined with cpu-all-variants");
+        }
+    }
+    if !target.starts_with("aarch64") {
+        let arm = [
+            ("dotprod", cfg!(feature = "dotprod")),
+            ("i8mm", cfg!(feature = "i8mm")),
+            ("sve", cfg!(feature = "sve")),
+        ];
+        for (feature, enabled) in arm {
+            if enabled {
+                panic!("the {} feature is only available for aarch64 targets, not {}", feature, target);
+            }
+        }
+    }
+    if !target.starts_with("x86_64") {
+        for (feature, enabled) in [("avx512", cfg!(feature = "avx512")), ("amx", cfg!(feature = "amx"))] {
+            if enabled {
+                panic!("the {} feature is only available for x86_64 targets, not {}", feature, target);
+            }
+        }
+    }
+    if cfg!(feature = "amx") && target.contains("msvc") {
+        panic!("the amx feature is not available for {}: MSVC does not support AMX", target);
+    }
+}
+
+fn check_ios_target(target: &str) {
+    let unsupported = [
+        ("cuda", cfg!(feature = "cuda")),
+        ("vulkan", cfg!(feature = "vulkan")),
+        ("hipblas", cfg!(feature = "hipblas")),
+        ("intel-sycl", cfg!(feature = "intel-sycl")),
+        ("cann", cfg!(feature = "cann")),
+        ("openblas", cfg!(feature = "openblas")),
+        ("mkl", cfg!(feature = "mkl")),
+        ("blis", cfg!(feature = "blis")),
+        ("openmp", cfg!(feature = "openmp")),
+        ("backend-dl", cfg!(feature = "backend-dl")),
+        ("capi", cfg!(feature = "capi")),
+        ("python", cfg!(feature = "python")),
+    ];
+    for (feature, enabled) in unsupported {
+        if enabled {
+            panic!("the {} feature is not available for {}", feature, target);
+        }
+    }
+    println!(
+        "[BUILD] iOS target {}: {} SDK, iOS {}, static libraries",
+        target,
+        ios_sdk(target),
+        ios_deployment_target()
+    );
+}
+
+/// SDK name CMake and xcrun accept for an iOS target.
+fn ios_sdk(target: &str) -> &'static str {
+    if target.ends_with("-sim") || target.starts_with("x86_64") {
+        "iphonesimulator"
+    } else {
+        "iphoneos"
+    }
+}
+
+/// Minimum iOS version, from `IPHONEOS_DEPLOYMENT_TARGET` like rustc. 14.0
+/// by default, the oldest with the Metal features ggml uses.
+fn ios_deployment_target() -> String {
+    println!("cargo:rerun-if-env-changed=IPHONEOS_DEPLOYMENT_TARGET");
+    env::var("IPHONEOS_DEPLOYMENT_TARGET").unwrap_or_else(|_| "14.0".to_string())
+}
+
+/// The SDK root for bindgen, from `xcrun --show-sdk-path`.
+fn ios_sdk_path(target: &str) -> Option<PathBuf> {
+    let output = std::process::Command::new("xcrun")
+        .args(["--sdk", ios_sdk(target), "--show-sdk-path"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Names of the static libraries of a variant in lib_dir, as rustc-link-lib
+/// takes them: lib<basename>*.a without prefix and extension.
+fn static_libraries(lib_dir: &Path, basename: &str) -> Vec<String> {
+    variant_libraries(lib_dir, basename, "lib", ".a")
+}
+
+/// Names of the shared libraries of a variant in lib_dir, likewise: the
+/// import libraries <basename>*.lib on Windows, lib<basename>*.so or .dylib
+/// elsewhere. Backend modules (backend-dl) are not among them.
+fn shared_libraries(lib_dir: &Path, basename: &str) -> Vec<String> {
+    let (prefix, ext) = match target_os().as_str() {
+        "windows" => ("", ".lib"),
+        "macos" => ("lib", ".dylib"),
+        _ => ("lib", ".so"),
+    };
+    variant_libraries(lib_dir, basename, prefix, ext)
+}
+
+fn variant_libraries(lib_dir: &Path, basename: &str, prefix: &str, ext: &str) -> Vec<String> {
+    let mut libs: Vec<String> = std::fs::read_dir(lib_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stem = name.strip_prefix(prefix)?.strip_suffix(ext)?;
+            let rest = stem.strip_prefix(basename)?;
+            (rest.is_empty() || rest.starts_with('-')).then(|| stem.to_string())
+        })
+        .collect();
+    libs.sort();
+    libs
+}
+
+/// The emscripten directory, holding emcc and the CMake toolchain file:
+/// `EMSCRIPTEN`, the one of the emsdk named by `EMSDK`, or where the `emcc`
+/// on `PATH` resolves to (distribution packages install no emsdk).
+fn emscripten_root() -> Option<PathBuf> {
+    let from_env = [
+        env::var_os("EMSCRIPTEN").map(PathBuf::from),
+        env::var_os("EMSDK").map(|emsdk| PathBuf::from(emsdk).join("upstream").join("emscripten")),
+    ];
+    let from_path = env::var_os("PATH").and_then(|path| {
+        env::split_paths(&path)
+            .map(|dir| dir.join("emcc"))
+            .find(|emcc| emcc.exists())
+            .and_then(|emcc| std::fs::canonicalize(emcc).ok()?.parent().map(PathBuf::from))
+    });
+    from_env.into_iter().flatten().chain(from_path).find(|root| {
+        root.join("cmake").join("Modules").join("Platform").join("Emscripten.cmake").exists()
+    })
+}
+
+/// The sysroot emscripten populates in its cache (`EM_CACHE` if set).
+fn emscripten_sysroot() -> Option<PathBuf> {
+    let cache = env::var_os("EM_CACHE")
+        .map(PathBuf::from)
+        .or_else(|| emscripten_root().map(|root| root.join("cache")))?;
+    let sysroot = cache.join("sysroot");
+    sysroot.exists().then_some(sysroot)
+}
+
+/// Whether the wasm target has threads: built with
+/// `-C target-feature=+atomics,+bulk-memory`, for pages served
+/// cross-origin isolated.
+fn wasm_threads() -> bool {
+    env::var("CARGO_CFG_TARGET_FEATURE").is_ok_and(|f| f.split(',').any(|f| f == "atomics"))
+}
+
+/// `-march` for AArch64 builds: `GGML_CPU_ARM_ARCH` if set, otherwise the
+/// first architecture version with every requested extension, plus
+/// `+dotprod`, `+i8mm` and `+sve` for the enabled features. `None` keeps
+/// ggml's default.
+fn arm_arch() -> Option<String> {
+    println!("cargo:rerun-if-env-changed=GGML_CPU_ARM_ARCH");
+    let extensions: Vec<&str> = [
+        ("dotprod", cfg!(feature = "dotprod")),
+        ("i8mm", cfg!(feature = "i8mm")),
+        ("sve", cfg!(feature = "sve")),
+    ]
+    .into_iter()
+    .filter_map(|(extension, enabled)| enabled.then_some(extension))
+    .collect();
+    let base = env::var("GGML_CPU_ARM_ARCH").ok().filter(|arch| !arch.is_empty());
+    if base.is_none() && extensions.is_empty() {
+        return None;
+    }
+    let mut arch = base.unwrap_or_else(|| {
+        if cfg!(feature = "i8mm") { "armv8.6-a" } else { "armv8.2-a" }.to_string()
+    });
+    for extension in extensions {
+        if !arch.split('+').any(|tag| tag == extension) {
+            arch.push('+');
+            arch.push_str(extension);
+        }
+    }
+    Some(arch)
+}
+
+/// The oneAPI MKL installation: `MKLROOT` (set by `setvars`) or the
+/// default oneAPI location.
+fn mkl_root() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=MKLROOT");
+    let root = env::var_os("MKLROOT").map(PathBuf::from).unwrap_or_else(|| {
+        if cfg!(windows) {
+            PathBuf::from(r"C:\Program Files (x86)\Intel\oneAPI\mkl\latest")
+        } else {
+            PathBuf::from("/opt/intel/oneapi/mkl/latest")
+        }
+    });
+    if !root.join("include").join("mkl.h").exists() {
+        panic!(
+            "MKL not found at {}: install oneAPI MKL and run setvars or set MKLROOT",
+            root.display()
+        );
+    }
+    root
+}
+
+/// `lib/intel64` in older MKL layouts, `lib` since oneAPI 2024.
+fn mkl_lib_dir(root: &Path) -> PathBuf {
+    let intel64 = root.join("lib").join("intel64");
+    if intel64.exists() {
+        intel64
+    } else {
+        root.join("lib")
+    }
+}
+
+/// The prefix BLIS is installed under (`include/blis/blis.h`, `lib`):
+/// `BLIS_PATH` or the first standard prefix that has it.
+fn blis_root() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=BLIS_PATH");
+    if let Some(root) = env::var_os("BLIS_PATH") {
+        return PathBuf::from(root);
+    }
+    ["/usr/local", "/usr", "/opt/homebrew"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|root| root.join("include").join("blis").join("blis.h").exists())
+        .unwrap_or_else(|| panic!("BLIS not found in /usr/local, /usr or /opt/homebrew: set BLIS_PATH to its install prefix"))
+}
+
+/// The Ascend CANN toolkit: `ASCEND_TOOLKIT_HOME` (set by the toolkit's
+/// `set_env.sh`) or the default install location.
+fn cann_toolkit_home() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=ASCEND_TOOLKIT_HOME");
+    let toolkit = env::var_os("ASCEND_TOOLKIT_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/usr/local/Ascend/ascend-toolkit/latest"));
+    if !toolkit.join("include").join("acl").exists() {
+        panic!(
+            "CANN toolkit not found at {}: install it and source set_env.sh or set ASCEND_TOOLKIT_HOME",
+            toolkit.display()
+        );
+    }
+    toolkit
+}
+
+/// The OS being built for. `cfg!(target_os)` in a build script is the host's,
+/// which differs when cross compiling.
+fn target_os() -> String {
+    env::var("CARGO_CFG_TARGET_OS").unwrap()
+}
+
 fn get_cpp_link_stdlib(target: &str) -> Option<&'static str> {
-    if target.contains("msvc") {
+    if target.contains("msvc") || target.contains("emscripten") {
         None
     } else if target.contains("apple") || target.contains("freebsd") || target.contains("openbsd") {
         Some("c++")
@@ -0,0 +1,87 @@
+#ifndef GGML_RS_H
+#define GGML_RS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// An open GGUF file.
+typedef struct GgmlRsGguf GgmlRsGguf;
+
+// Location, type and shape of one tensor of a GGUF file. `name` lives as
+// long as the file; `type` is -1 for types this ggml does not know.
+typedef struct GgmlRsTensorInfo {
+  const char *name;
+  int32_t type;
+  uint32_t n_dims;
+  int64_t ne[4];
+  size_t offset;
+  size_t size;
+} GgmlRsTensorInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last failure on this thread, or `NULL`. Valid until
+// the next failing call on the thread.
+const char *ggml_rs_last_error(void);
+
+// Base name of the ggml library this build links, `"ggml_llama"` or
+// `"ggml_whisper"`.
+const char *ggml_rs_variant(void);
+
+// Free a string returned by this library.
+void ggml_rs_string_free(char *s);
+
+// The type id named `name` (e.g. `"q4_K"`), or -1.
+int32_t ggml_rs_type_from_name(const char *name);
+
+// Bytes of one row of `n_per_row` values of type `ty`, or 0.
+size_t ggml_rs_row_size(int32_t ty, size_t n_per_row);
+
+// Quantize `n` values of `src`, rows of `n_per_row`, to `ty` into `dst`.
+// `imatrix`, `n_per_row` column weights, may be `NULL` unless the type
+// requires it. Returns the bytes written.
+size_t ggml_rs_quantize(int32_t ty, const float *src, size_t n, size_t n_per_row, const float *imatrix, uint8_t *dst, size_t dst_size);
+
+// Convert `src_size` bytes of whole `ty` blocks to f32 values in `dst`,
+// which holds `dst_len`. Returns the values written.
+size_t ggml_rs_dequantize(int32_t ty, const uint8_t *src, size_t src_size, float *dst, size_t dst_len);
+
+// Rewrite the GGUF file `src` to `dst` with its tensors converted to `ty`
+// where [`QuantPlan`] can convert them (1D norms and biases keep their
+// type).
+int ggml_rs_requantize(const char *src, const char *dst, int32_t ty);
+
+// Open the GGUF file at `path`, or `NULL`. Free it with
+// [`ggml_rs_gguf_free`].
+GgmlRsGguf *ggml_rs_gguf_open(const char *path);
+
+// Close a file opened with [`ggml_rs_gguf_open`]; `NULL` is ignored.
+void ggml_rs_gguf_free(GgmlRsGguf *f);
+
+// Number of tensors in the file.
+size_t ggml_rs_gguf_n_tensors(const GgmlRsGguf *f);
+
+// Fill `out` with tensor `i`.
+int ggml_rs_gguf_tensor_info(const GgmlRsGguf *f, size_t i, GgmlRsTensorInfo *out);
+
+// Read the data of tensor `name` into `dst`, which holds its `size`
+// bytes.
+int ggml_rs_gguf_read_tensor(const GgmlRsGguf *f, const char *name, uint8_t *dst, size_t dst_size);
+
+// The string value of `key`, or `NULL` if it is missing or not a string.
+// Free it with [`ggml_rs_string_free`].
+char *ggml_rs_gguf_get_string(const GgmlRsGguf *f, const char *key);
+
+// The value of `key`, any integer type or bool, into `out`.
+int ggml_rs_gguf_get_int(const GgmlRsGguf *f, const char *key, int64_t *out);
+
+// The value of `key`, any float or integer type, into `out`.
+int ggml_rs_gguf_get_float(const GgmlRsGguf *f, const char *key, double *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* GGML_RS_H */
@@ -0,0 +1,3 @@
+//! ggml built for `aarch64-apple-darwin` (Metal backend), packaged for the `prebuilt`
+//! feature of ggml-rs. The crate has no Rust API: its build script hands
+//! the library directory to ggml-rs, which links it instead of running CMake.
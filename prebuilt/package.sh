@@ -0,0 +1,57 @@
+#!/bin/sh
+# Fill a companion crate with a release build of both ggml variants.
+#
+# usage: prebuilt/package.sh <crate dir> [cargo features]
+#   prebuilt/package.sh prebuilt/x86_64-linux
+#   prebuilt/package.sh prebuilt/aarch64-apple metal
+#   prebuilt/package.sh prebuilt/win64-cuda cuda
+#
+# Run it on the crate's target; the features are recorded in features.txt
+# and ggml-rs only uses the crate when its enabled backends match. The same
+# files are archived in prebuilt/dist for the download-binaries feature.
+set -eu
+
+crate=$(cd "$1" && pwd)
+features=${2:-}
+repo=$(cd "$(dirname "$0")/.." && pwd)
+
+out_dir=$(cd "$repo" && cargo build --release --lib --features "$features" \
+    --message-format=json-render-diagnostics \
+    | grep '"reason":"build-script-executed"' \
+    | grep '[#/]ggml-rs[@#]' \
+    | sed 's/.*"out_dir":"\([^"]*\)".*/\1/; s/\\\\/\\/g' \
+    | tail -n 1)
+if [ -z "$out_dir" ]; then
+    echo "package.sh: could not find the ggml-rs build output" >&2
+    exit 1
+fi
+
+rm -rf "$crate/llama" "$crate/whisper"
+for variant in llama whisper; do
+    mkdir -p "$crate/$variant"
+    for dir in lib bin include; do
+        if [ -d "$out_dir/$variant/$dir" ]; then
+            cp -R "$out_dir/$variant/$dir" "$crate/$variant/"
+        fi
+    done
+    cp "$out_dir/$variant/cmake_flags.txt" "$crate/$variant/" 2>/dev/null || true
+done
+cp "$out_dir/bindings.rs" "$crate/bindings.rs"
+echo "$features" | tr ',' ' ' > "$crate/features.txt"
+
+echo "packaged $out_dir into $crate"
+
+# The same files as a release archive for the download-binaries feature
+version=$(sed -n 's/^version = "\(.*\)"/\1/p' "$repo/Cargo.toml" | head -n 1)
+target=$(rustc -vV | sed -n 's/^host: //p')
+suffix=$(echo "$features" | tr ', ' '\n\n' | sed '/^$/d' | sort | sed 's/^/-/' | tr -d '\n')
+archive="ggml-rs-$version-$target$suffix.tar.gz"
+mkdir -p "$repo/prebuilt/dist"
+tar -czf "$repo/prebuilt/dist/$archive" -C "$crate" bindings.rs features.txt llama whisper
+if command -v sha256sum >/dev/null; then
+    sum=$(sha256sum "$repo/prebuilt/dist/$archive" | cut -d' ' -f1)
+else
+    sum=$(shasum -a 256 "$repo/prebuilt/dist/$archive" | cut -d' ' -f1)
+fi
+echo "archived prebuilt/dist/$archive; add to binaries.sha256:"
+echo "$sum  $archive"
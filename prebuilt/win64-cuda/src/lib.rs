@@ -0,0 +1,3 @@
+//! ggml built for `x86_64-pc-windows-msvc` (CUDA backend), packaged for the `prebuilt`
+//! feature of ggml-rs. The crate has no Rust API: its build script hands
+//! the library directory to ggml-rs, which links it instead of running CMake.
@@ -0,0 +1,15 @@
+//! Exports this crate's directory to ggml-rs as DEP_<LINKS>_ROOT.
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    if !root.join("bindings.rs").exists() {
+        panic!(
+            "{} holds no ggml build; fill it with prebuilt/package.sh before publishing",
+            root.display()
+        );
+    }
+    println!("cargo:ROOT={}", root.display());
+}
@@ -0,0 +1,3 @@
+//! ggml built for `x86_64-unknown-linux-gnu` (CPU backend), packaged for the `prebuilt`
+//! feature of ggml-rs. The crate has no Rust API: its build script hands
+//! the library directory to ggml-rs, which links it instead of running CMake.
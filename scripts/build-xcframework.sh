@@ -0,0 +1,54 @@
+#!/bin/sh
+# Build the static ggml variants for iOS devices and simulators and assemble
+# ggml_llama.xcframework and ggml_whisper.xcframework for Xcode projects.
+#
+# usage: scripts/build-xcframework.sh [cargo features] [output dir]
+#   scripts/build-xcframework.sh metal target/xcframework
+#
+# Needs Xcode and the aarch64-apple-ios, aarch64-apple-ios-sim and
+# x86_64-apple-ios rust targets. IPHONEOS_DEPLOYMENT_TARGET is honoured.
+set -eu
+
+features=${1:-metal}
+repo=$(cd "$(dirname "$0")/.." && pwd)
+out=${2:-$repo/target/xcframework}
+mkdir -p "$out"
+out=$(cd "$out" && pwd)
+work="$out/work"
+
+# OUT_DIR of the ggml-rs build script for a target
+build() {
+    (cd "$repo" && cargo build --release --lib --target "$1" --features "$features" \
+        --message-format=json-render-diagnostics \
+        | grep '"reason":"build-script-executed"' \
+        | grep '[#/]ggml-rs[@#]' \
+        | sed 's/.*"out_dir":"\([^"]*\)".*/\1/' \
+        | tail -n 1)
+}
+
+device=$(build aarch64-apple-ios)
+sim_arm64=$(build aarch64-apple-ios-sim)
+sim_x86_64=$(build x86_64-apple-ios)
+
+rm -rf "$work"
+for variant in llama whisper; do
+    name=ggml_$variant
+    # one archive per slice: ggml, -base, -cpu, -blas and -metal merged
+    for slice in ios sim-arm64 sim-x86_64 sim; do
+        mkdir -p "$work/$slice"
+    done
+    libtool -static -o "$work/ios/lib$name.a" "$device/$variant/lib/lib$name"*.a
+    libtool -static -o "$work/sim-arm64/lib$name.a" "$sim_arm64/$variant/lib/lib$name"*.a
+    libtool -static -o "$work/sim-x86_64/lib$name.a" "$sim_x86_64/$variant/lib/lib$name"*.a
+    lipo -create "$work/sim-arm64/lib$name.a" "$work/sim-x86_64/lib$name.a" \
+        -output "$work/sim/lib$name.a"
+
+    rm -rf "$out/$name.xcframework"
+    xcodebuild -create-xcframework \
+        -library "$work/ios/lib$name.a" -headers "$repo/ggml/include" \
+        -library "$work/sim/lib$name.a" -headers "$repo/ggml/include" \
+        -output "$out/$name.xcframework"
+done
+rm -rf "$work"
+
+echo "wrote $out/ggml_llama.xcframework and $out/ggml_whisper.xcframework"
@@ -0,0 +1,187 @@
+//! Conversions between Apache Arrow arrays and 1D/2D tensors, for feeding
+//! columns read from Arrow or Parquet datasets to a graph.
+//!
+//! A primitive array of `n` values maps to a tensor `[n]`; a
+//! `FixedSizeList` array of `n` lists of `dim` values (an embedding
+//! column) maps to a tensor `[dim, n]`, one row per list. Values are copied
+//! straight from the Arrow buffer into tensor memory and back, with no
+//! staging `Vec` in between.
+//!
+//! ```ignore
+//! let batch = parquet_reader.next().unwrap()?;
+//! let emb = ctx.new_tensor_from_arrow(batch.column_by_name("embedding").unwrap())?;
+//! let scores = ctx.mul_mat(emb, query)?;
+//! // ... compute ...
+//! let column = scores.to_arrow()?; // Float32Array
+//! ```
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+};
+use arrow_array::{Array, ArrayRef, ArrowPrimitiveType, FixedSizeListArray, PrimitiveArray};
+use arrow_buffer::ScalarBuffer;
+use arrow_schema::{DataType, Field};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// The ggml type holding values of `dt` without conversion.
+fn arrow_to_type(dt: &DataType) -> Result<Type> {
+    Ok(match dt {
+        DataType::Float32 => Type::F32,
+        DataType::Float64 => Type::F64,
+        DataType::Float16 => Type::F16,
+        DataType::Int8 => Type::I8,
+        DataType::Int16 => Type::I16,
+        DataType::Int32 => Type::I32,
+        DataType::Int64 => Type::I64,
+        dt => {
+            return Err(Error::TypeMismatch {
+                expected: "an Arrow float or integer array".to_string(),
+                found: dt.to_string(),
+            })
+        }
+    })
+}
+
+fn primitive_bytes<T: ArrowPrimitiveType>(a: &dyn Array) -> &[u8] {
+    a.as_primitive::<T>().values().inner().as_slice()
+}
+
+/// The type, `[ne0, ne1]` and value bytes of an array, rejecting nulls.
+fn arrow_layout(array: &dyn Array) -> Result<(Type, [i64; 2], &[u8])> {
+    let (values, ne) = match array.as_fixed_size_list_opt() {
+        Some(list) => (
+            list.values().as_ref(),
+            [list.value_length() as i64, list.len() as i64],
+        ),
+        None => (array, [array.len() as i64, 1]),
+    };
+    if array.null_count() > 0 || values.null_count() > 0 {
+        return Err(Error::InvalidArgument(
+            "Arrow array with nulls has no tensor equivalent".to_string(),
+        ));
+    }
+    let ty = arrow_to_type(values.data_type())?;
+    let bytes = match ty {
+        Type::F32 => primitive_bytes::<Float32Type>(values),
+        Type::F64 => primitive_bytes::<Float64Type>(values),
+        Type::F16 => primitive_bytes::<Float16Type>(values),
+        Type::I8 => primitive_bytes::<Int8Type>(values),
+        Type::I16 => primitive_bytes::<Int16Type>(values),
+        Type::I32 => primitive_bytes::<Int32Type>(values),
+        _ => primitive_bytes::<Int64Type>(values),
+    };
+    Ok((ty, ne, bytes))
+}
+
+/// The values of a contiguous host tensor as a primitive array.
+fn read_primitive<T: ArrowPrimitiveType>(t: &Tensor<'_>) -> ArrayRef {
+    let mut values = vec![T::Native::default(); t.nelements() as usize];
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            t.data() as *const u8,
+            values.as_mut_ptr() as *mut u8,
+            t.nbytes(),
+        )
+    };
+    Arc::new(PrimitiveArray::<T>::new(ScalarBuffer::from(values), None))
+}
+
+fn read_f32(t: &Tensor<'_>) -> Result<ArrayRef> {
+    Ok(Arc::new(PrimitiveArray::<Float32Type>::from(
+        t.to_vec_f32()?,
+    )))
+}
+
+impl Context {
+    /// A new tensor holding a copy of `array`: `[n]` for a primitive array,
+    /// `[dim, n]` for a `FixedSizeList` of `dim` values. F32, F64, F16 and
+    /// integer values keep their type.
+    pub fn new_tensor_from_arrow(&self, array: &dyn Array) -> Result<Tensor<'_>> {
+        let (ty, ne, _) = arrow_layout(array)?;
+        let rows = if array.as_fixed_size_list_opt().is_some() {
+            2
+        } else {
+            1
+        };
+        let t = self.new_tensor(ty, &ne[..rows])?;
+        t.write_arrow(array)?;
+        Ok(t)
+    }
+}
+
+impl Tensor<'_> {
+    /// Overwrite the tensor with the values of `array`, e.g. the next batch
+    /// of a column. Shape and type must match as for
+    /// [`Context::new_tensor_from_arrow`].
+    pub fn write_arrow(&self, array: &dyn Array) -> Result<()> {
+        let (ty, [ne0, ne1], bytes) = arrow_layout(array)?;
+        if ty != self.ty() {
+            return Err(Error::TypeMismatch {
+                expected: self.ty().to_string(),
+                found: array.data_type().to_string(),
+            });
+        }
+        if self.ne() != [ne0, ne1, 1, 1] {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot write an Arrow array of [{}, {}] to ne {:?}",
+                ne0,
+                ne1,
+                self.ne()
+            )));
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.data() as *mut u8, bytes.len())
+        };
+        Ok(())
+    }
+
+    /// Copy a 1D or 2D tensor into a new Arrow array: a primitive array for
+    /// `[n]`, a `FixedSizeList` of `ne[0]` values per row for `[ne0, n]`.
+    ///
+    /// Contiguous F32, F64, F16 and integer tensors keep their type; others
+    /// (quantized, BF16, or with rows not packed one after the other) are
+    /// read with [`Tensor::to_vec_f32`] and become `Float32`.
+    pub fn to_arrow(&self) -> Result<ArrayRef> {
+        let ne = self.ne();
+        if ne[2] != 1 || ne[3] != 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "an Arrow array needs a 1D or 2D tensor, not {:?}",
+                ne
+            )));
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        let values = match self.ty() {
+            _ if !self.is_contiguous() => read_f32(self)?,
+            Type::F32 => read_primitive::<Float32Type>(self),
+            Type::F64 => read_primitive::<Float64Type>(self),
+            Type::F16 => read_primitive::<Float16Type>(self),
+            Type::I8 => read_primitive::<Int8Type>(self),
+            Type::I16 => read_primitive::<Int16Type>(self),
+            Type::I32 => read_primitive::<Int32Type>(self),
+            Type::I64 => read_primitive::<Int64Type>(self),
+            _ => read_f32(self)?,
+        };
+        if self.n_dims() == 1 {
+            return Ok(values);
+        }
+        let field = Arc::new(Field::new("item", values.data_type().clone(), false));
+        let list = FixedSizeListArray::try_new(field, ne[0] as i32, values, None)
+            .map_err(|e| Error::InvalidArgument(format!("cannot build Arrow list: {}", e)))?;
+        Ok(Arc::new(list))
+    }
+}
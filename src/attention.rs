@@ -0,0 +1,170 @@
+//! Fused attention (`ggml_flash_attn_ext`) and the accumulation precision of
+//! attention and matrix-product nodes.
+//!
+//! Shapes below list `ne[0]` (innermost) first.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Accumulation precision of a `MUL_MAT` or `FLASH_ATTN_EXT` node,
+/// mirroring `enum ggml_prec`.
+///
+/// With [`Default`](Precision::Default), backends may accumulate in F16 when
+/// the inputs are F16, which is faster but overflows on models with large
+/// activations; the symptom is `inf`/NaN logits on the GPU while the CPU is
+/// fine. [`F32`](Precision::F32) accumulates in F32 on every backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    #[default]
+    Default,
+    F32,
+}
+
+impl Precision {
+    fn as_raw(self) -> crate::ggml_prec {
+        match self {
+            Precision::Default => crate::ggml_prec_GGML_PREC_DEFAULT,
+            Precision::F32 => crate::ggml_prec_GGML_PREC_F32,
+        }
+    }
+
+    fn from_raw(raw: crate::ggml_prec) -> Self {
+        match raw {
+            crate::ggml_prec_GGML_PREC_F32 => Precision::F32,
+            _ => Precision::Default,
+        }
+    }
+}
+
+impl Context {
+    /// Fused `softmax(q k^T * scale + mask * slope) v`, without
+    /// materializing the scores.
+    ///
+    /// - `q`: `[d_k, n_tokens, n_head, n_seq]`
+    /// - `k`: `[d_k, n_kv, n_head_kv, n_seq]`, with `n_head` a multiple of
+    ///   `n_head_kv` (grouped-query attention)
+    /// - `v`: `[d_v, n_kv, n_head_kv, n_seq]`
+    /// - `mask`: F16 `[n_kv, n_rows, ne2, ne3]`, contiguous, with `n_rows`
+    ///   at least `n_tokens` padded to `GGML_KQ_MASK_PAD`; dims 2 and 3
+    ///   broadcast over heads and sequences
+    ///
+    /// The result is F32 `[d_v, n_head, n_tokens, n_seq]`, heads and tokens
+    /// swapped relative to `q`. `max_bias > 0` applies ALiBi and requires a
+    /// mask; `logit_softcap > 0` caps the scores with `tanh`. Set the
+    /// precision with [`Tensor::set_precision`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn flash_attn_ext<'a>(
+        &'a self,
+        q: Tensor<'a>,
+        k: Tensor<'a>,
+        v: Tensor<'a>,
+        mask: Option<Tensor<'a>>,
+        scale: f32,
+        max_bias: f32,
+        logit_softcap: f32,
+    ) -> Result<Tensor<'a>> {
+        let (qn, kn, vn) = (q.ne(), k.ne(), v.ne());
+        // ggml asserts the first two; backends index out of bounds otherwise
+        if kn[0] != qn[0]
+            || qn[2] % kn[2].max(1) != 0
+            || qn[3] != kn[3]
+            || qn[3] != vn[3]
+            || vn[1] != kn[1]
+            || vn[2] != kn[2]
+        {
+            return Err(Error::ShapeMismatch(format!(
+                "flash_attn_ext of q {:?}, k {:?} and v {:?}",
+                qn, kn, vn
+            )));
+        }
+        if let Some(mask) = mask {
+            if mask.ty() != Type::F16 {
+                return Err(Error::TypeMismatch {
+                    expected: format!("flash_attn_ext mask {}", Type::F16),
+                    found: mask.ty().to_string(),
+                });
+            }
+            if !mask.is_contiguous() {
+                return Err(Error::NotContiguous);
+            }
+            let mn = mask.ne();
+            let pad = crate::GGML_KQ_MASK_PAD as i64;
+            let rows = (qn[1] + pad - 1) / pad * pad;
+            if mn[2] == 0
+                || mn[3] == 0
+                || mn[0] != kn[1]
+                || mn[1] < rows
+                || qn[2] % mn[2] != 0
+                || qn[3] % mn[3] != 0
+            {
+                return Err(Error::ShapeMismatch(format!(
+                    "flash_attn_ext mask {:?} does not fit {} queries over {} keys \
+                     (rows are padded to {})",
+                    mn, qn[1], kn[1], pad
+                )));
+            }
+        } else if max_bias > 0.0 {
+            return Err(Error::InvalidArgument(
+                "flash_attn_ext: ALiBi (max_bias > 0) needs a mask".to_string(),
+            ));
+        }
+        self.ensure_capacity(Type::F32.row_size(vn[0]) * (qn[1] * qn[2] * qn[3]) as usize)?;
+        let mask = mask.map_or(std::ptr::null_mut(), |m| m.as_ptr());
+        self.op_result("ggml_flash_attn_ext", unsafe {
+            crate::ggml_flash_attn_ext(
+                self.as_ptr(),
+                q.as_ptr(),
+                k.as_ptr(),
+                v.as_ptr(),
+                mask,
+                scale,
+                max_bias,
+                logit_softcap,
+            )
+        })
+    }
+}
+
+impl<'a> Tensor<'a> {
+    /// Choose how a [`Context::mul_mat`] or [`Context::flash_attn_ext`]
+    /// result is accumulated, e.g. to fix overflowing attention scores:
+    ///
+    /// ```ignore
+    /// let kq = ctx.mul_mat(k, q)?.set_precision(Precision::F32)?;
+    /// ```
+    pub fn set_precision(self, prec: Precision) -> Result<Tensor<'a>> {
+        // ggml asserts the op
+        match self.op() {
+            crate::ggml_op_GGML_OP_MUL_MAT => unsafe {
+                crate::ggml_mul_mat_set_prec(self.as_ptr(), prec.as_raw())
+            },
+            crate::ggml_op_GGML_OP_FLASH_ATTN_EXT => unsafe {
+                crate::ggml_flash_attn_ext_set_prec(self.as_ptr(), prec.as_raw())
+            },
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "precision applies to MUL_MAT and FLASH_ATTN_EXT nodes, not {}",
+                    self.op_desc()
+                )))
+            }
+        }
+        Ok(self)
+    }
+
+    /// The accumulation precision of a `MUL_MAT` or `FLASH_ATTN_EXT` node.
+    pub fn precision(&self) -> Option<Precision> {
+        match self.op() {
+            crate::ggml_op_GGML_OP_MUL_MAT => {
+                // ggml_mul_mat_set_prec stores it in op_params[0]
+                let raw = unsafe { (*self.as_ptr()).op_params[0] };
+                Some(Precision::from_raw(raw as crate::ggml_prec))
+            }
+            crate::ggml_op_GGML_OP_FLASH_ATTN_EXT => Some(Precision::from_raw(unsafe {
+                crate::ggml_flash_attn_ext_get_prec(self.as_ptr())
+            })),
+            _ => None,
+        }
+    }
+}
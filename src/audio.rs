@@ -0,0 +1,448 @@
+//! Log-mel spectrograms of f32 PCM, as Whisper-style speech encoders take
+//! them.
+//!
+//! [`MelParams::whisper`] reproduces OpenAI Whisper's `log_mel_spectrogram`
+//! (and so whisper.cpp's): 16 kHz audio, a 400-point periodic Hann window
+//! with a hop of 160, Slaney mel filters, `log10` clamped to 8 below the
+//! maximum and rescaled by `(x + 4) / 4`. The result has one row per mel
+//! band, so its tensor is F32 `[n_frames, n_mels]`, the layout the
+//! encoder's first convolution reads.
+//!
+//! ```ignore
+//! let mel = MelSpectrogram::compute(&pcm_16khz, &MelParams::whisper(80))?;
+//! let input = ctx.new_tensor_from_mel(&mel)?; // [3000, 80]
+//! ```
+
+use std::f64::consts::PI;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Whisper's sample rate.
+pub const WHISPER_SAMPLE_RATE: usize = 16_000;
+
+/// Whisper's window: 30 seconds at 16 kHz.
+pub const WHISPER_N_SAMPLES: usize = 30 * WHISPER_SAMPLE_RATE;
+
+/// Parameters of a log-mel spectrogram. Built by value from
+/// [`MelParams::whisper`] or [`MelParams::new`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MelParams {
+    sample_rate: usize,
+    n_fft: usize,
+    hop_length: usize,
+    n_mels: usize,
+    fmin: f32,
+    fmax: f32,
+    pad_to: Option<usize>,
+}
+
+impl MelParams {
+    /// A spectrogram of `n_mels` bands between 0 Hz and the Nyquist
+    /// frequency, without padding.
+    pub fn new(sample_rate: usize, n_fft: usize, hop_length: usize, n_mels: usize) -> Self {
+        MelParams {
+            sample_rate,
+            n_fft,
+            hop_length,
+            n_mels,
+            fmin: 0.0,
+            fmax: sample_rate as f32 / 2.0,
+            pad_to: None,
+        }
+    }
+
+    /// Whisper's parameters, with the audio padded or trimmed to 30
+    /// seconds (3000 frames). `n_mels` is 80, or 128 for large-v3.
+    pub fn whisper(n_mels: usize) -> Self {
+        Self::new(WHISPER_SAMPLE_RATE, 400, 160, n_mels).pad_to(Some(WHISPER_N_SAMPLES))
+    }
+
+    /// Limit the filters to `fmin..fmax` Hz.
+    pub fn freq_range(mut self, fmin: f32, fmax: f32) -> Self {
+        self.fmin = fmin;
+        self.fmax = fmax;
+        self
+    }
+
+    /// Pad the audio with silence, or trim it, to exactly this many samples
+    /// before the transform.
+    pub fn pad_to(mut self, n_samples: Option<usize>) -> Self {
+        self.pad_to = n_samples;
+        self
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    pub fn n_fft(&self) -> usize {
+        self.n_fft
+    }
+
+    pub fn hop_length(&self) -> usize {
+        self.hop_length
+    }
+
+    pub fn n_mels(&self) -> usize {
+        self.n_mels
+    }
+
+    /// Frequency bins of each frame's spectrum, `n_fft / 2 + 1`.
+    pub fn n_bins(&self) -> usize {
+        self.n_fft / 2 + 1
+    }
+
+    /// Frames computed from `n_samples` of audio (after padding).
+    pub fn n_frames(&self, n_samples: usize) -> usize {
+        self.pad_to.unwrap_or(n_samples) / self.hop_length.max(1)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.n_fft < 2 || self.hop_length == 0 || self.n_mels == 0 || self.sample_rate == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "mel spectrogram needs n_fft >= 2 and a nonzero hop, band count and sample \
+                 rate, got {:?}",
+                self
+            )));
+        }
+        if !(0.0 <= self.fmin && self.fmin < self.fmax) {
+            return Err(Error::InvalidArgument(format!(
+                "mel frequency range {}..{} Hz is empty",
+                self.fmin, self.fmax
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Hz to mel on the Slaney scale: linear below 1 kHz, logarithmic above.
+fn hz_to_mel(hz: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if hz >= 1000.0 {
+        15.0 + (hz / 1000.0).ln() / log_step
+    } else {
+        hz * 3.0 / 200.0
+    }
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    let log_step = 6.4f64.ln() / 27.0;
+    if mel >= 15.0 {
+        1000.0 * ((mel - 15.0) * log_step).exp()
+    } else {
+        mel * 200.0 / 3.0
+    }
+}
+
+/// A mel filterbank: `n_mels` rows of `n_bins` weights.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MelFilters {
+    n_mels: usize,
+    n_bins: usize,
+    weights: Vec<f32>,
+}
+
+impl MelFilters {
+    /// Slaney-normalized triangular filters, as `librosa.filters.mel` (and
+    /// so Whisper's `mel_filters.npz`) computes them.
+    pub fn new(params: &MelParams) -> Result<Self> {
+        params.check()?;
+        let (n_mels, n_bins) = (params.n_mels, params.n_bins());
+        let (mel_min, mel_max) = (hz_to_mel(params.fmin as f64), hz_to_mel(params.fmax as f64));
+        let edges: Vec<f64> = (0..n_mels + 2)
+            .map(|i| mel_to_hz(mel_min + (mel_max - mel_min) * i as f64 / (n_mels + 1) as f64))
+            .collect();
+        let bin_hz = params.sample_rate as f64 / params.n_fft as f64;
+        let mut weights = vec![0f32; n_mels * n_bins];
+        for (m, row) in weights.chunks_exact_mut(n_bins).enumerate() {
+            let (lo, mid, hi) = (edges[m], edges[m + 1], edges[m + 2]);
+            let norm = 2.0 / (hi - lo);
+            for (b, w) in row.iter_mut().enumerate() {
+                let hz = b as f64 * bin_hz;
+                let rising = (hz - lo) / (mid - lo);
+                let falling = (hi - hz) / (hi - mid);
+                *w = (rising.min(falling).max(0.0) * norm) as f32;
+            }
+        }
+        Ok(MelFilters {
+            n_mels,
+            n_bins,
+            weights,
+        })
+    }
+
+    /// Filters stored elsewhere, e.g. the ones a whisper.cpp model file
+    /// carries: `n_mels` rows of `n_bins` weights.
+    pub fn from_raw(n_mels: usize, n_bins: usize, weights: Vec<f32>) -> Result<Self> {
+        if weights.len() != n_mels * n_bins {
+            return Err(Error::ShapeMismatch(format!(
+                "{} filter weights for {} mels of {} bins",
+                weights.len(),
+                n_mels,
+                n_bins
+            )));
+        }
+        Ok(MelFilters {
+            n_mels,
+            n_bins,
+            weights,
+        })
+    }
+
+    pub fn n_mels(&self) -> usize {
+        self.n_mels
+    }
+
+    pub fn n_bins(&self) -> usize {
+        self.n_bins
+    }
+
+    /// The weights, row `m` holding band `m`.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+}
+
+/// A mixed-radix FFT of one size: radix 2 while the length is even, a
+/// direct DFT on the odd remainder (25 for Whisper's 400).
+struct Fft {
+    n: usize,
+    cos: Vec<f32>,
+    sin: Vec<f32>,
+}
+
+impl Fft {
+    fn new(n: usize) -> Self {
+        let angle = |i: usize| 2.0 * PI * i as f64 / n as f64;
+        Fft {
+            n,
+            cos: (0..n).map(|i| angle(i).cos() as f32).collect(),
+            sin: (0..n).map(|i| angle(i).sin() as f32).collect(),
+        }
+    }
+
+    /// `x * exp(-2 pi i k / m)` with `k / m` given as `idx / n`.
+    fn rotate(&self, x: [f32; 2], idx: usize) -> [f32; 2] {
+        let (c, s) = (self.cos[idx], self.sin[idx]);
+        [x[0] * c + x[1] * s, x[1] * c - x[0] * s]
+    }
+
+    fn transform(&self, x: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        let m = x.len();
+        let step = self.n / m;
+        if m % 2 == 1 {
+            return (0..m)
+                .map(|k| {
+                    x.iter().enumerate().fold([0.0; 2], |acc, (j, &v)| {
+                        let t = self.rotate(v, (k * j % m) * step);
+                        [acc[0] + t[0], acc[1] + t[1]]
+                    })
+                })
+                .collect();
+        }
+        let even: Vec<_> = x.iter().step_by(2).copied().collect();
+        let odd: Vec<_> = x.iter().skip(1).step_by(2).copied().collect();
+        let (even, odd) = (self.transform(&even), self.transform(&odd));
+        let mut out = vec![[0.0; 2]; m];
+        for k in 0..m / 2 {
+            let (e, t) = (even[k], self.rotate(odd[k], k * step));
+            out[k] = [e[0] + t[0], e[1] + t[1]];
+            out[k + m / 2] = [e[0] - t[0], e[1] - t[1]];
+        }
+        out
+    }
+
+    /// `|X[k]|^2` for `k` in `0..=n/2` of the real signal `x`.
+    fn power(&self, x: &[f32], out: &mut [f32]) {
+        let x: Vec<[f32; 2]> = x.iter().map(|&v| [v, 0.0]).collect();
+        for (p, c) in out.iter_mut().zip(self.transform(&x)) {
+            *p = c[0] * c[0] + c[1] * c[1];
+        }
+    }
+}
+
+/// A log-mel spectrogram: `n_mels` rows of `n_frames` values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MelSpectrogram {
+    n_mels: usize,
+    n_frames: usize,
+    data: Vec<f32>,
+}
+
+impl MelSpectrogram {
+    /// The spectrogram of mono `pcm` sampled at `params.sample_rate()`,
+    /// with filters from [`MelFilters::new`].
+    pub fn compute(pcm: &[f32], params: &MelParams) -> Result<Self> {
+        Self::compute_with(pcm, params, &MelFilters::new(params)?)
+    }
+
+    /// Like [`compute`](Self::compute), with given filters.
+    pub fn compute_with(pcm: &[f32], params: &MelParams, filters: &MelFilters) -> Result<Self> {
+        params.check()?;
+        if filters.n_mels != params.n_mels || filters.n_bins != params.n_bins() {
+            return Err(Error::ShapeMismatch(format!(
+                "filters of {} mels by {} bins for a spectrogram of {} mels by {} bins",
+                filters.n_mels,
+                filters.n_bins,
+                params.n_mels,
+                params.n_bins()
+            )));
+        }
+        let mut samples = pcm.to_vec();
+        if let Some(n) = params.pad_to {
+            samples.resize(n, 0.0);
+        }
+        let (n_fft, hop, n_bins) = (params.n_fft, params.hop_length, params.n_bins());
+        let n_frames = samples.len() / hop;
+        if n_frames == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "{} samples make no frames with a hop of {}",
+                samples.len(),
+                hop
+            )));
+        }
+
+        // frames are centered on multiples of the hop, the signal mirrored
+        // (without repeating the edge) past both ends, as torch.stft does
+        let len = samples.len() as isize;
+        let at = |i: isize| {
+            let i = if i < 0 { -i } else { i };
+            let i = if i >= len { 2 * (len - 1) - i } else { i };
+            if (0..len).contains(&i) {
+                samples[i as usize]
+            } else {
+                0.0
+            }
+        };
+        let window: Vec<f32> = (0..n_fft)
+            .map(|i| (0.5 * (1.0 - (2.0 * PI * i as f64 / n_fft as f64).cos())) as f32)
+            .collect();
+        let fft = Fft::new(n_fft);
+        let mut frame = vec![0f32; n_fft];
+        let mut power = vec![0f32; n_bins];
+        let mut data = vec![0f32; params.n_mels * n_frames];
+        for f in 0..n_frames {
+            let start = (f * hop) as isize - (n_fft / 2) as isize;
+            for (i, v) in frame.iter_mut().enumerate() {
+                *v = at(start + i as isize) * window[i];
+            }
+            fft.power(&frame, &mut power);
+            for (m, row) in filters.weights.chunks_exact(n_bins).enumerate() {
+                let sum: f64 = row.iter().zip(&power).map(|(&w, &p)| (w * p) as f64).sum();
+                data[m * n_frames + f] = sum.max(1e-10).log10() as f32;
+            }
+        }
+
+        let max = data.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        for v in &mut data {
+            *v = (v.max(max - 8.0) + 4.0) / 4.0;
+        }
+        Ok(MelSpectrogram {
+            n_mels: params.n_mels,
+            n_frames,
+            data,
+        })
+    }
+
+    pub fn n_mels(&self) -> usize {
+        self.n_mels
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.n_frames
+    }
+
+    /// The values, row `m` holding band `m` over time.
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+}
+
+impl Context {
+    /// A new F32 tensor `[n_frames, n_mels]` holding `mel`.
+    pub fn new_tensor_from_mel(&self, mel: &MelSpectrogram) -> Result<Tensor<'_>> {
+        let t = self.new_tensor_2d(Type::F32, mel.n_frames as i64, mel.n_mels as i64)?;
+        t.write(&mel.data)?;
+        Ok(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_matches_naive_dft() {
+        let n = 400;
+        // a few tones and some LCG noise
+        let mut state = 12345u32;
+        let x: Vec<f32> = (0..n)
+            .map(|i| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let t = i as f64 / n as f64;
+                let noise = (state >> 8) as f64 / (1u32 << 24) as f64 - 0.5;
+                ((2.0 * PI * 7.0 * t).sin() + 0.3 * (2.0 * PI * 61.0 * t).cos() + noise) as f32
+            })
+            .collect();
+        let mut power = vec![0f32; n / 2 + 1];
+        Fft::new(n).power(&x, &mut power);
+
+        let naive: Vec<f64> = (0..=n / 2)
+            .map(|k| {
+                let (re, im) = x.iter().enumerate().fold((0.0, 0.0), |(re, im), (j, &v)| {
+                    let a = 2.0 * PI * (k * j % n) as f64 / n as f64;
+                    (re + v as f64 * a.cos(), im - v as f64 * a.sin())
+                });
+                re * re + im * im
+            })
+            .collect();
+        let scale = naive.iter().copied().fold(0.0, f64::max);
+        for (k, (&p, &q)) in power.iter().zip(&naive).enumerate() {
+            assert!(
+                (p as f64 - q).abs() <= 1e-5 * scale,
+                "bin {}: fft {} naive {}",
+                k,
+                p,
+                q
+            );
+        }
+    }
+
+    #[test]
+    fn whisper_frames() {
+        let pcm: Vec<f32> = (0..WHISPER_SAMPLE_RATE)
+            .map(|i| (2.0 * PI * 440.0 * i as f64 / WHISPER_SAMPLE_RATE as f64).sin() as f32)
+            .collect();
+        let params = MelParams::whisper(80);
+        assert_eq!(params.n_frames(pcm.len()), 3000);
+        let mel = MelSpectrogram::compute(&pcm, &params).unwrap();
+        assert_eq!((mel.n_mels(), mel.n_frames()), (80, 3000));
+        assert_eq!(mel.data().len(), 80 * 3000);
+        // the clamp leaves at most 8 (2 after rescaling) below the maximum
+        let max = mel.data().iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        assert!(mel.data().iter().all(|&v| v >= max - 2.0 && v.is_finite()));
+    }
+
+    #[test]
+    fn whisper_filters_match_librosa() {
+        // librosa.filters.mel(sr=16000, n_fft=400, n_mels=80), the filters
+        // in Whisper's mel_filters.npz
+        let filters = MelFilters::new(&MelParams::whisper(80)).unwrap();
+        assert_eq!((filters.n_mels(), filters.n_bins()), (80, 201));
+        let row = |m: usize| &filters.weights()[m * 201..(m + 1) * 201];
+        assert!((row(0)[1] - 0.024_862_594).abs() < 1e-8);
+        let expected_sums = [
+            (0, 0.024_862_594),
+            (10, 0.024_862_594),
+            (40, 0.026_664_86),
+            (79, 0.024_925_34),
+        ];
+        for (m, sum) in expected_sums {
+            let got: f32 = row(m).iter().sum();
+            assert!((got - sum).abs() < 1e-6, "row {}: {} != {}", m, got, sum);
+        }
+    }
+}
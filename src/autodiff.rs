@@ -0,0 +1,127 @@
+//! Reverse-mode automatic differentiation.
+//!
+//! Mark trainable tensors with [`Tensor::set_param`] and a scalar F32 loss
+//! with [`Tensor::set_loss`], build the forward graph in a graph created with
+//! `grads = true`, then call [`Graph::build_backward_expand`]:
+//!
+//! ```ignore
+//! let w = ctx.new_tensor_2d(Type::F32, 4, 4)?.set_param()?;
+//! let loss = ctx.sum(ctx.sqr(ctx.mul_mat(w, x)?)?)?.set_loss()?;
+//! let mut graph = ctx.new_graph_custom(256, true)?;
+//! graph.build_forward_expand(loss);
+//! graph.build_backward_expand(&ctx)?;
+//! graph.reset()?;
+//! graph.compute(&ctx, 4)?;
+//! let dw = graph.grad(w).unwrap().to_vec_f32()?;
+//! ```
+//!
+//! Not every op has a backward pass; ggml aborts when it meets one that
+//! does not.
+
+use std::ptr;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::graph::Graph;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+impl Tensor<'_> {
+    fn has_flag(&self, flag: crate::ggml_tensor_flag) -> bool {
+        unsafe { (*self.as_ptr()).flags as crate::ggml_tensor_flag & flag != 0 }
+    }
+
+    /// Mark as a trainable parameter. Only leaf tensors (not op results) can
+    /// be parameters.
+    pub fn set_param(self) -> Result<Self> {
+        if self.op() != crate::ggml_op_GGML_OP_NONE {
+            return Err(Error::InvalidArgument(format!(
+                "`{}` is the result of {} and cannot be a parameter",
+                self.name(),
+                self.op_desc()
+            )));
+        }
+        unsafe { crate::ggml_set_param(self.as_ptr()) };
+        Ok(self)
+    }
+
+    /// Mark as a loss to minimize. Must be an F32 scalar; several losses
+    /// add up.
+    pub fn set_loss(self) -> Result<Self> {
+        if self.ty() != Type::F32 || self.nelements() != 1 {
+            return Err(Error::InvalidArgument(format!(
+                "a loss must be an F32 scalar, got {} {:?}",
+                self.ty(),
+                self.ne()
+            )));
+        }
+        unsafe { crate::ggml_set_loss(self.as_ptr()) };
+        Ok(self)
+    }
+
+    pub fn is_param(&self) -> bool {
+        self.has_flag(crate::ggml_tensor_flag_GGML_TENSOR_FLAG_PARAM)
+    }
+
+    pub fn is_loss(&self) -> bool {
+        self.has_flag(crate::ggml_tensor_flag_GGML_TENSOR_FLAG_LOSS)
+    }
+}
+
+impl<'ctx> Graph<'ctx> {
+    fn require_grads(&self) -> Result<()> {
+        if self.has_grads() {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(
+                "graph has no gradient storage; create it with new_graph_custom(size, true)"
+                    .to_string(),
+            ))
+        }
+    }
+
+    /// Append the backward pass for the forward graph built so far.
+    ///
+    /// Gradient tensors are allocated in `ctx`, which must have room for
+    /// roughly one tensor per node.
+    pub fn build_backward_expand(&mut self, ctx: &Context) -> Result<()> {
+        self.require_grads()?;
+        if self.n_nodes() == 0 {
+            return Err(Error::InvalidArgument(
+                "build the forward graph before the backward pass".to_string(),
+            ));
+        }
+        if !self.nodes().any(|n| n.is_param()) {
+            return Err(Error::InvalidArgument(
+                "graph has no parameters; mark them with Tensor::set_param".to_string(),
+            ));
+        }
+        if !self.nodes().any(|n| n.is_loss()) {
+            return Err(Error::InvalidArgument(
+                "graph has no loss; mark it with Tensor::set_loss".to_string(),
+            ));
+        }
+        unsafe { crate::ggml_build_backward_expand(ctx.as_ptr(), self.as_ptr(), ptr::null_mut()) };
+        Ok(())
+    }
+
+    /// Gradient of the loss with respect to `t`, once the backward pass is
+    /// built. `None` if `t` does not influence the loss.
+    pub fn grad(&self, t: Tensor<'_>) -> Option<Tensor<'ctx>> {
+        unsafe { Tensor::from_raw(crate::ggml_graph_get_grad(self.as_ptr(), t.as_ptr())) }
+    }
+
+    /// Gradient accumulator of `t`, which differs from [`Graph::grad`] when
+    /// gradients are accumulated over several batches.
+    pub fn grad_acc(&self, t: Tensor<'_>) -> Option<Tensor<'ctx>> {
+        unsafe { Tensor::from_raw(crate::ggml_graph_get_grad_acc(self.as_ptr(), t.as_ptr())) }
+    }
+
+    /// Zero all gradients and set the loss gradient to 1; call before each
+    /// backward compute.
+    pub fn reset(&mut self) -> Result<()> {
+        self.require_grads()?;
+        unsafe { crate::ggml_graph_reset(self.as_ptr()) };
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+//! Backend buffers (`ggml_backend_buffer`): memory tensors live in.
+
+use std::any::Any;
+use std::ffi::{c_void, CStr};
+use std::fmt;
+use std::ptr::NonNull;
+
+use super::registry::Device;
+use crate::context::Context;
+use crate::error::{check_status, Error, Result};
+use crate::tensor::Tensor;
+
+/// An owned backend buffer, freed on drop, or with the [`Context`] it was
+/// handed to with [`Context::alloc_in`].
+///
+/// A buffer over memory the application owns, such as a memory-mapped
+/// file, holds on to that memory's owner and drops it after the buffer:
+///
+/// ```ignore
+/// let map = unsafe { memmap2::MmapOptions::new().map_copy(&file)? };
+/// let weights = BackendBuffer::cpu_from_owner(map)?;
+/// ctx.alloc_in(weights)?; // the map now lives as long as ctx
+/// ```
+pub struct BackendBuffer {
+    ptr: NonNull<crate::ggml_backend_buffer>,
+    // dropped after the buffer is freed
+    owner: Option<Box<dyn Any + Send>>,
+}
+
+unsafe impl Send for BackendBuffer {}
+
+impl BackendBuffer {
+    /// Take ownership of a buffer; it is freed on drop.
+    ///
+    /// # Safety
+    /// `ptr` must be a buffer that nothing else frees.
+    pub unsafe fn from_raw(ptr: crate::ggml_backend_buffer_t) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| BackendBuffer { ptr, owner: None })
+    }
+
+    /// A CPU buffer over `size` bytes at `ptr`, without copying them.
+    ///
+    /// # Safety
+    /// The memory must be readable and writable, and stay valid and in place
+    /// until the buffer, and every tensor placed in it, is gone.
+    pub unsafe fn cpu_from_ptr(ptr: *mut c_void, size: usize) -> Result<Self> {
+        let align = BufferType::cpu().alignment();
+        // ggml asserts this
+        if ptr.is_null() || !(ptr as usize).is_multiple_of(align) {
+            return Err(Error::InvalidArgument(format!(
+                "buffer memory must be {}-byte aligned",
+                align
+            )));
+        }
+        Self::from_raw(crate::ggml_backend_cpu_buffer_from_ptr(ptr, size))
+            .ok_or(Error::NullPointer("ggml_backend_cpu_buffer_from_ptr"))
+    }
+
+    /// A CPU buffer over the bytes of `owner`, e.g. a `Vec<u8>` or a
+    /// writable memory map, which the buffer keeps until it is freed.
+    /// The bytes must start on a [`BufferType::cpu`] alignment boundary.
+    pub fn cpu_from_owner<T: AsMut<[u8]> + Send + 'static>(owner: T) -> Result<Self> {
+        // boxed first, so the bytes of inline owners such as arrays stay put
+        let mut owner = Box::new(owner);
+        let bytes = (*owner).as_mut();
+        let mut buffer =
+            unsafe { Self::cpu_from_ptr(bytes.as_mut_ptr() as *mut c_void, bytes.len())? };
+        buffer.owner = Some(owner);
+        Ok(buffer)
+    }
+
+    /// Like [`cpu_from_owner`](Self::cpu_from_owner) for owners that only
+    /// hand out shared bytes, such as a read-only memory map or an Arrow
+    /// buffer.
+    ///
+    /// # Safety
+    /// Nothing may write to tensors placed in the buffer, nor clear it:
+    /// the memory may be read-only or shared with other readers.
+    pub unsafe fn cpu_from_ref<T: AsRef<[u8]> + Send + 'static>(owner: T) -> Result<Self> {
+        let owner = Box::new(owner);
+        let bytes = (*owner).as_ref();
+        let mut buffer = Self::cpu_from_ptr(bytes.as_ptr() as *mut c_void, bytes.len())?;
+        buffer.owner = Some(owner);
+        Ok(buffer)
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_buffer_t {
+        self.ptr.as_ptr()
+    }
+
+    /// The owner of the memory the buffer borrows, if any.
+    pub(super) fn owner(&self) -> Option<&(dyn Any + Send)> {
+        self.owner.as_deref()
+    }
+
+    /// Give up ownership without freeing the buffer. The owner of memory
+    /// the buffer borrows is leaked, so the memory stays valid.
+    pub fn into_raw(self) -> crate::ggml_backend_buffer_t {
+        let ptr = self.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
+    pub fn name(&self) -> &str {
+        let ptr = unsafe { crate::ggml_backend_buffer_name(self.as_ptr()) };
+        if ptr.is_null() {
+            return "";
+        }
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("")
+    }
+
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        unsafe { crate::ggml_backend_buffer_get_size(self.as_ptr()) }
+    }
+
+    /// Alignment of tensors placed in the buffer.
+    pub fn alignment(&self) -> usize {
+        unsafe { crate::ggml_backend_buffer_get_alignment(self.as_ptr()) }
+    }
+
+    /// Start of the buffer; only dereferenceable if [`is_host`](Self::is_host).
+    pub fn base(&self) -> *mut c_void {
+        unsafe { crate::ggml_backend_buffer_get_base(self.as_ptr()) }
+    }
+
+    /// Whether the memory is directly accessible from the host.
+    pub fn is_host(&self) -> bool {
+        unsafe { crate::ggml_backend_buffer_is_host(self.as_ptr()) }
+    }
+
+    pub fn buffer_type(&self) -> Option<BufferType> {
+        unsafe { BufferType::from_raw(crate::ggml_backend_buffer_get_type(self.as_ptr())) }
+    }
+
+    /// Set every byte of the buffer to `value`.
+    pub fn clear(&self, value: u8) {
+        unsafe { crate::ggml_backend_buffer_clear(self.as_ptr(), value) }
+    }
+}
+
+impl Drop for BackendBuffer {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_backend_buffer_free(self.as_ptr()) }
+    }
+}
+
+impl fmt::Debug for BackendBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackendBuffer")
+            .field("name", &self.name())
+            .field("size", &self.size())
+            .field("is_host", &self.is_host())
+            .field("borrowed", &self.owner.is_some())
+            .finish()
+    }
+}
+
+/// A kind of backend memory (`ggml_backend_buffer_type`), such as a
+/// device's own memory or pinned host memory it can reach quickly. Buffer
+/// types are owned by ggml, so this is a plain `Copy` handle.
+///
+/// Tensors in pinned host memory copy to and from the device faster than
+/// tensors in ordinary host memory, which makes them good staging areas:
+///
+/// ```ignore
+/// let pinned = gpu.device().unwrap().host_buffer_type().unwrap();
+/// staging_ctx.alloc_in(pinned.alloc(staging_ctx_size)?)?;
+/// staging.write_f32(&batch)?;
+/// staging.copy_to(input)?; // `input` lives on the GPU
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct BufferType {
+    ptr: NonNull<crate::ggml_backend_buffer_type>,
+}
+
+unsafe impl Send for BufferType {}
+unsafe impl Sync for BufferType {}
+
+impl BufferType {
+    /// # Safety
+    /// `ptr` must point to a buffer type that lives for the rest of the
+    /// process, as the ones ggml hands out do.
+    pub unsafe fn from_raw(ptr: crate::ggml_backend_buffer_type_t) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| BufferType { ptr })
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_buffer_type_t {
+        self.ptr.as_ptr()
+    }
+
+    /// Ordinary host memory, used by the CPU backend.
+    pub fn cpu() -> Self {
+        unsafe { Self::from_raw(crate::ggml_backend_cpu_buffer_type()) }
+            .expect("ggml_backend_cpu_buffer_type returned a null pointer")
+    }
+
+    pub fn name(&self) -> &str {
+        let ptr = unsafe { crate::ggml_backend_buft_name(self.as_ptr()) };
+        if ptr.is_null() {
+            return "";
+        }
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("")
+    }
+
+    /// Alignment of tensors in buffers of this type.
+    pub fn alignment(&self) -> usize {
+        unsafe { crate::ggml_backend_buft_get_alignment(self.as_ptr()) }
+    }
+
+    /// Largest buffer that can be allocated, `usize::MAX` if unlimited.
+    pub fn max_size(&self) -> usize {
+        unsafe { crate::ggml_backend_buft_get_max_size(self.as_ptr()) }
+    }
+
+    /// Bytes `tensor` takes in a buffer of this type, which may include
+    /// padding beyond [`Tensor::nbytes`].
+    pub fn alloc_size(&self, tensor: Tensor<'_>) -> usize {
+        unsafe { crate::ggml_backend_buft_get_alloc_size(self.as_ptr(), tensor.as_ptr()) }
+    }
+
+    /// Whether buffers of this type are directly accessible from the host.
+    pub fn is_host(&self) -> bool {
+        unsafe { crate::ggml_backend_buft_is_host(self.as_ptr()) }
+    }
+
+    /// The device the memory belongs to, if any.
+    pub fn device(&self) -> Option<Device> {
+        unsafe { Device::from_raw(crate::ggml_backend_buft_get_device(self.as_ptr())) }
+    }
+
+    /// Allocate a buffer of `size` bytes.
+    pub fn alloc(&self, size: usize) -> Result<BackendBuffer> {
+        if size > self.max_size() {
+            return Err(Error::OutOfMemory {
+                needed: size,
+                available: self.max_size(),
+            });
+        }
+        unsafe {
+            BackendBuffer::from_raw(crate::ggml_backend_buft_alloc_buffer(self.as_ptr(), size))
+        }
+        .ok_or(Error::NullPointer("ggml_backend_buft_alloc_buffer"))
+    }
+}
+
+impl fmt::Debug for BufferType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferType")
+            .field("name", &self.name())
+            .field("is_host", &self.is_host())
+            .finish()
+    }
+}
+
+impl Context {
+    /// Place every tensor of this `no_alloc` context that has no data yet
+    /// in `buffer`, one after the other, and keep the buffer alive as long
+    /// as the context.
+    pub fn alloc_in(&self, buffer: BackendBuffer) -> Result<()> {
+        if !self.no_alloc() {
+            return Err(Error::InvalidArgument(
+                "backend allocation needs a no_alloc context".to_string(),
+            ));
+        }
+        let buf = buffer.as_ptr();
+        let align = buffer.alignment().max(1);
+        let base = buffer.base() as usize;
+        let start = (align - base % align) % align;
+        let is_view = |t: &Tensor<'_>| unsafe { !(*t.as_ptr()).view_src.is_null() };
+        let pending: Vec<Tensor<'_>> = self.tensors().filter(|t| t.data().is_null()).collect();
+
+        // ggml aborts when a tensor does not fit, so check first
+        let needed = pending
+            .iter()
+            .filter(|t| !is_view(t))
+            .map(|t| unsafe { crate::ggml_backend_buffer_get_alloc_size(buf, t.as_ptr()) })
+            .fold(start, |end, size| end + size.next_multiple_of(align));
+        if needed > buffer.size() {
+            return Err(Error::OutOfMemory {
+                needed,
+                available: buffer.size(),
+            });
+        }
+
+        let mut offset = start;
+        for t in pending {
+            // views get their data from their source
+            let status = if is_view(&t) {
+                unsafe { crate::ggml_backend_view_init(t.as_ptr()) }
+            } else {
+                let size = unsafe { crate::ggml_backend_buffer_get_alloc_size(buf, t.as_ptr()) };
+                let addr = unsafe { (buffer.base() as *mut u8).add(offset) };
+                offset += size.next_multiple_of(align);
+                unsafe { crate::ggml_backend_tensor_alloc(buf, t.as_ptr(), addr as *mut c_void) }
+            };
+            check_status(status)?;
+        }
+        self.keep_buffer(buffer);
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Allocate data for every tensor of this `no_alloc` context that has
+    /// none yet in one new buffer of type `buft`. The buffer lives as long
+    /// as the context.
+    pub fn alloc_with(&self, buft: BufferType) -> Result<()> {
+        if !self.no_alloc() {
+            return Err(Error::InvalidArgument(
+                "backend allocation needs a no_alloc context".to_string(),
+            ));
+        }
+        if self.tensors().all(|t| !t.data().is_null()) {
+            return Ok(());
+        }
+        let buffer = unsafe {
+            BackendBuffer::from_raw(crate::ggml_backend_alloc_ctx_tensors_from_buft(
+                self.as_ptr(),
+                buft.as_ptr(),
+            ))
+        }
+        .ok_or(Error::NullPointer(
+            "ggml_backend_alloc_ctx_tensors_from_buft",
+        ))?;
+        self.keep_buffer(buffer);
+        Ok(())
+    }
+}
+
+impl Tensor<'_> {
+    fn check_backend_range(&self, offset: usize, len: usize) -> Result<()> {
+        if self.data().is_null() || self.raw_buffer().is_null() {
+            return Err(Error::NoData);
+        }
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.nbytes())
+        {
+            return Err(Error::ShapeMismatch(format!(
+                "{} bytes at offset {} of a tensor of {} bytes",
+                len,
+                offset,
+                self.nbytes()
+            )));
+        }
+        Ok(())
+    }
+
+    fn raw_buffer(&self) -> crate::ggml_backend_buffer_t {
+        let t = unsafe { &*self.as_ptr() };
+        if t.view_src.is_null() {
+            t.buffer
+        } else {
+            unsafe { (*t.view_src).buffer }
+        }
+    }
+
+    /// Copy `data` into the tensor's memory at byte `offset`, wherever it
+    /// lives; for device memory this is a transfer to the device.
+    pub fn write_bytes(&self, offset: usize, data: &[u8]) -> Result<()> {
+        self.check_backend_range(offset, data.len())?;
+        unsafe {
+            crate::ggml_backend_tensor_set(
+                self.as_ptr(),
+                data.as_ptr() as *const c_void,
+                offset,
+                data.len(),
+            )
+        };
+        Ok(())
+    }
+
+    /// Copy `out.len()` bytes from byte `offset` of the tensor's memory,
+    /// wherever it lives.
+    pub fn read_bytes(&self, offset: usize, out: &mut [u8]) -> Result<()> {
+        self.check_backend_range(offset, out.len())?;
+        unsafe {
+            crate::ggml_backend_tensor_get(
+                self.as_ptr(),
+                out.as_mut_ptr() as *mut c_void,
+                offset,
+                out.len(),
+            )
+        };
+        Ok(())
+    }
+
+    /// Copy the data of this tensor into `dst`, which must have the same
+    /// type and layout, between any two buffers.
+    pub fn copy_to(&self, dst: Tensor<'_>) -> Result<()> {
+        if self.ty() != dst.ty() || self.ne() != dst.ne() || self.nb() != dst.nb() {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot copy {} {:?} to {} {:?} with a different layout",
+                self.ty(),
+                self.ne(),
+                dst.ty(),
+                dst.ne()
+            )));
+        }
+        self.check_backend_range(0, 0)?;
+        dst.check_backend_range(0, 0)?;
+        unsafe { crate::ggml_backend_tensor_copy(self.as_ptr(), dst.as_ptr()) };
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! The CPU backend, with control over its threads and an abort callback.
+
+use std::ffi::c_void;
+use std::ops::Deref;
+
+use super::instance::Backend;
+use crate::error::{Error, Result};
+use crate::threadpool::{Threadpool, ThreadpoolParams};
+
+pub(super) type AbortFn = Box<dyn FnMut() -> bool + Send>;
+
+pub(super) unsafe extern "C" fn abort_trampoline(data: *mut c_void) -> bool {
+    let f = &mut *(data as *mut AbortFn);
+    f()
+}
+
+/// The CPU backend (`ggml_backend_cpu_init`). Derefs to [`Backend`], so it
+/// computes graphs and works with everything that takes a backend.
+///
+/// ```ignore
+/// let mut cpu = CpuBackend::new(8)?;
+/// let deadline = Instant::now() + Duration::from_secs(5);
+/// cpu.set_abort_callback(move || Instant::now() > deadline);
+/// cpu.compute(&mut graph)?; // Err(Error::Aborted) once the deadline passes
+/// ```
+pub struct CpuBackend {
+    // freed before the threadpool and callback it refers to
+    backend: Backend,
+    n_threads: usize,
+    threadpool: Option<Threadpool>,
+    abort: Option<Box<AbortFn>>,
+}
+
+impl CpuBackend {
+    /// A CPU backend computing with `n_threads` threads, at least one.
+    pub fn new(n_threads: usize) -> Result<Self> {
+        let backend = unsafe { Backend::from_raw(crate::ggml_backend_cpu_init()) }
+            .ok_or(Error::NullPointer("ggml_backend_cpu_init"))?;
+        let mut cpu = CpuBackend {
+            backend,
+            n_threads: 0,
+            threadpool: None,
+            abort: None,
+        };
+        cpu.set_n_threads(n_threads);
+        Ok(cpu)
+    }
+
+    /// A CPU backend computing on a new threadpool of its own, configured
+    /// by `params`: worker count, CPU mask, strict pinning, priority and
+    /// polling.
+    pub fn with_threadpool_params(params: &ThreadpoolParams) -> Result<Self> {
+        let mut cpu = Self::new(params.n_threads())?;
+        cpu.set_threadpool(Some(Threadpool::new(params)?));
+        Ok(cpu)
+    }
+
+    pub fn n_threads(&self) -> usize {
+        self.n_threads
+    }
+
+    /// Number of threads to compute with. With a threadpool attached, at
+    /// most the pool's threads are used.
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        self.n_threads = n_threads.clamp(1, crate::threadpool::max_threads());
+        unsafe { crate::ggml_backend_cpu_set_n_threads(self.as_ptr(), self.n_threads as i32) }
+    }
+
+    /// Compute on the workers of `threadpool` instead of threads started
+    /// for each graph, and use all of them. `None` detaches the pool; a pool
+    /// that is replaced is paused and returned.
+    ///
+    /// The backend owns the pool: ggml cannot run two computes on one pool
+    /// at once, so it is not shared with other backends.
+    pub fn set_threadpool(&mut self, threadpool: Option<Threadpool>) -> Option<Threadpool> {
+        let ptr = threadpool
+            .as_ref()
+            .map_or(std::ptr::null_mut(), |tp| tp.as_ptr());
+        unsafe { crate::ggml_backend_cpu_set_threadpool(self.as_ptr(), ptr) };
+        if let Some(tp) = &threadpool {
+            self.set_n_threads(tp.n_threads());
+        }
+        std::mem::replace(&mut self.threadpool, threadpool)
+    }
+
+    pub fn threadpool(&self) -> Option<&Threadpool> {
+        self.threadpool.as_ref()
+    }
+
+    /// Call `f` between graph nodes during compute; when it returns `true`
+    /// the compute stops and returns [`Error::Aborted`]. Replaces any
+    /// previous callback.
+    pub fn set_abort_callback(&mut self, f: impl FnMut() -> bool + Send + 'static) {
+        let mut abort: Box<AbortFn> = Box::new(Box::new(f));
+        unsafe {
+            crate::ggml_backend_cpu_set_abort_callback(
+                self.as_ptr(),
+                Some(abort_trampoline),
+                &mut *abort as *mut AbortFn as *mut c_void,
+            )
+        };
+        self.abort = Some(abort);
+    }
+
+    pub fn clear_abort_callback(&mut self) {
+        unsafe {
+            crate::ggml_backend_cpu_set_abort_callback(self.as_ptr(), None, std::ptr::null_mut())
+        };
+        self.abort = None;
+    }
+}
+
+impl Deref for CpuBackend {
+    type Target = Backend;
+
+    fn deref(&self) -> &Backend {
+        &self.backend
+    }
+}
+
+impl std::fmt::Debug for CpuBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuBackend")
+            .field("n_threads", &self.n_threads)
+            .field("threadpool", &self.threadpool.is_some())
+            .field("abort_callback", &self.abort.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,244 @@
+//! The CUDA backend: device selection, VRAM queries and stream interop.
+
+#[cfg(ggml_patched)]
+use std::ffi::c_void;
+use std::ffi::{c_char, c_int, CStr};
+use std::ops::Deref;
+
+#[cfg(ggml_patched)]
+use super::buffer::{BackendBuffer, BufferType};
+use super::instance::Backend;
+use super::registry::BackendRegistry;
+use crate::error::{Error, Result};
+
+// The compute capability is not part of ggml's API; ask the CUDA runtime,
+// which the `cuda` feature links.
+extern "C" {
+    fn cudaDeviceGetAttribute(value: *mut c_int, attr: c_int, device: c_int) -> c_int;
+}
+
+const CUDA_DEV_ATTR_COMPUTE_CAPABILITY_MAJOR: c_int = 75;
+const CUDA_DEV_ATTR_COMPUTE_CAPABILITY_MINOR: c_int = 76;
+
+fn device_attribute(device: usize, attr: c_int) -> Result<u32> {
+    let mut value = 0;
+    let err = unsafe { cudaDeviceGetAttribute(&mut value, attr, device as c_int) };
+    if err != 0 {
+        return Err(Error::InvalidArgument(format!(
+            "cudaDeviceGetAttribute failed for device {} with error {}",
+            device, err
+        )));
+    }
+    Ok(value as u32)
+}
+
+fn check_device(device: usize) -> Result<()> {
+    let count = CudaBackend::device_count();
+    if device >= count {
+        return Err(Error::InvalidArgument(format!(
+            "CUDA device {} out of range ({} devices)",
+            device, count
+        )));
+    }
+    Ok(())
+}
+
+/// A CUDA device as reported by [`CudaBackend::devices`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CudaDevice {
+    pub index: usize,
+    /// The device name, e.g. `"NVIDIA GeForce RTX 4090"`.
+    pub description: String,
+    /// Free and total memory in bytes at the time of the query.
+    pub memory_free: usize,
+    pub memory_total: usize,
+    /// Major and minor compute capability, e.g. `(8, 9)`.
+    pub compute_capability: (u32, u32),
+}
+
+/// The CUDA backend on one device. Derefs to [`Backend`].
+///
+/// ```ignore
+/// for dev in CudaBackend::devices()? {
+///     println!("{}: {} sm_{}{}", dev.index, dev.description,
+///              dev.compute_capability.0, dev.compute_capability.1);
+/// }
+/// let cuda = CudaBackend::new(0)?;
+/// let (free, total) = cuda.memory();
+/// ```
+pub struct CudaBackend {
+    backend: Backend,
+    device: usize,
+}
+
+impl CudaBackend {
+    /// Initialize the backend on device `device`, counting from 0.
+    pub fn new(device: usize) -> Result<Self> {
+        check_device(device)?;
+        let backend = unsafe { Backend::from_raw(crate::ggml_backend_cuda_init(device as c_int)) }
+            .ok_or(Error::NullPointer("ggml_backend_cuda_init"))?;
+        Ok(CudaBackend { backend, device })
+    }
+
+    /// Initialize the backend on device `device`, queueing its work on the
+    /// application's `stream` instead of a stream of its own, so ggml's
+    /// compute and copies are ordered with the application's kernels:
+    ///
+    /// ```ignore
+    /// let stream = cuda_ctx.new_stream()?; // cudarc
+    /// let cuda = unsafe { CudaBackend::with_stream(0, stream.cu_stream() as *mut c_void)? };
+    /// launch_preprocess(&stream, ...);
+    /// cuda.compute_async(&mut graph)?; // runs after the preprocessing kernel
+    /// ```
+    ///
+    /// Not available with a system ggml: this relies on a patch to the
+    /// vendored sources (patches/0003-cuda-external-stream.patch).
+    ///
+    /// # Safety
+    /// `stream` must be a `cudaStream_t` created on device `device` that
+    /// stays valid until the backend is dropped; the backend does not
+    /// destroy it.
+    #[cfg(ggml_patched)]
+    pub unsafe fn with_stream(device: usize, stream: *mut c_void) -> Result<Self> {
+        check_device(device)?;
+        if stream.is_null() {
+            return Err(Error::InvalidArgument(
+                "the default (null) stream cannot be used".to_string(),
+            ));
+        }
+        let backend = Backend::from_raw(crate::ggml_backend_cuda_init_with_stream(
+            device as c_int,
+            stream,
+        ))
+        .ok_or(Error::NullPointer("ggml_backend_cuda_init_with_stream"))?;
+        Ok(CudaBackend { backend, device })
+    }
+
+    /// The `cudaStream_t` the backend queues its work on, to order the
+    /// application's own work after it. Not available with a system ggml,
+    /// like [`with_stream`](Self::with_stream).
+    #[cfg(ggml_patched)]
+    pub fn stream(&self) -> *mut c_void {
+        unsafe { crate::ggml_backend_cuda_get_stream(self.as_ptr()) }
+    }
+
+    /// Use `size` bytes of device memory at `dev_ptr` on device `device`,
+    /// allocated by another framework, as a buffer of this backend without
+    /// copying it. Place tensors in it with
+    /// [`Context::alloc_in`](crate::Context::alloc_in) to feed another
+    /// framework's outputs to a graph, or read its results in place.
+    ///
+    /// ```ignore
+    /// let out = torch_out.data_ptr() as *mut c_void; // on cuda:0
+    /// let buf = unsafe { CudaBackend::buffer_from_device_ptr(0, out, torch_out.nbytes())? };
+    /// inputs_ctx.alloc_in(buf)?;
+    /// ```
+    ///
+    /// Not available with a system ggml: this relies on a patch to the
+    /// vendored sources (patches/0004-cuda-buffer-from-ptr.patch).
+    ///
+    /// # Safety
+    /// `dev_ptr` must point to at least `size` bytes allocated on device
+    /// `device` that stay valid until the buffer, and every tensor placed
+    /// in it, is gone. The buffer does not free the memory. Work that other
+    /// frameworks queue on it must be ordered with ggml's, e.g. with
+    /// [`with_stream`](Self::with_stream).
+    #[cfg(ggml_patched)]
+    pub unsafe fn buffer_from_device_ptr(
+        device: usize,
+        dev_ptr: *mut c_void,
+        size: usize,
+    ) -> Result<BackendBuffer> {
+        check_device(device)?;
+        let align = BufferType::from_raw(crate::ggml_backend_cuda_buffer_type(device as c_int))
+            .map_or(1, |buft| buft.alignment());
+        if dev_ptr.is_null() || !(dev_ptr as usize).is_multiple_of(align) {
+            return Err(Error::InvalidArgument(format!(
+                "device memory must be {}-byte aligned",
+                align
+            )));
+        }
+        BackendBuffer::from_raw(crate::ggml_backend_cuda_buffer_from_ptr(
+            device as c_int,
+            dev_ptr,
+            size,
+        ))
+        .ok_or(Error::NullPointer("ggml_backend_cuda_buffer_from_ptr"))
+    }
+
+    /// Number of CUDA devices visible to the process.
+    pub fn device_count() -> usize {
+        unsafe { crate::ggml_backend_cuda_get_device_count() }.max(0) as usize
+    }
+
+    /// Describe every visible CUDA device.
+    pub fn devices() -> Result<Vec<CudaDevice>> {
+        (0..Self::device_count()).map(Self::device_info).collect()
+    }
+
+    /// Describe device `device`.
+    pub fn device_info(device: usize) -> Result<CudaDevice> {
+        check_device(device)?;
+        let mut buf = [0 as c_char; 256];
+        unsafe {
+            crate::ggml_backend_cuda_get_device_description(
+                device as c_int,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        let description = unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        let (memory_free, memory_total) = device_memory(device);
+        Ok(CudaDevice {
+            index: device,
+            description,
+            memory_free,
+            memory_total,
+            compute_capability: (
+                device_attribute(device, CUDA_DEV_ATTR_COMPUTE_CAPABILITY_MAJOR)?,
+                device_attribute(device, CUDA_DEV_ATTR_COMPUTE_CAPABILITY_MINOR)?,
+            ),
+        })
+    }
+
+    /// Allocate device buffers created from now on as unified memory; see
+    /// [`BackendRegistry::set_unified_memory`].
+    pub fn set_unified_memory(enable: bool) -> Result<()> {
+        unsafe { BackendRegistry::from_raw(crate::ggml_backend_cuda_reg()) }
+            .ok_or(Error::NullPointer("ggml_backend_cuda_reg"))?
+            .set_unified_memory(enable)
+    }
+
+    pub fn device_index(&self) -> usize {
+        self.device
+    }
+
+    /// Free and total memory of the backend's device in bytes.
+    pub fn memory(&self) -> (usize, usize) {
+        device_memory(self.device)
+    }
+}
+
+fn device_memory(device: usize) -> (usize, usize) {
+    let (mut free, mut total) = (0, 0);
+    unsafe { crate::ggml_backend_cuda_get_device_memory(device as c_int, &mut free, &mut total) };
+    (free, total)
+}
+
+impl Deref for CudaBackend {
+    type Target = Backend;
+
+    fn deref(&self) -> &Backend {
+        &self.backend
+    }
+}
+
+impl std::fmt::Debug for CudaBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CudaBackend")
+            .field("device", &self.device)
+            .finish()
+    }
+}
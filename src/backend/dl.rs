@@ -0,0 +1,83 @@
+//! Loading backends built as modules (`GGML_BACKEND_DL`) at runtime.
+
+use std::ffi::CString;
+use std::path::Path;
+
+use super::registry::BackendRegistry;
+use crate::error::{Error, Result};
+
+fn path_cstr(path: &Path) -> Result<CString> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    #[cfg(not(unix))]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!("path {} is not valid UTF-8", path.display()))
+        })?
+        .as_bytes()
+        .to_vec();
+    CString::new(bytes)
+        .map_err(|_| Error::InvalidArgument(format!("path {} contains a NUL byte", path.display())))
+}
+
+/// Run `load`, then return the registries it added.
+fn newly_registered(load: impl FnOnce()) -> Vec<BackendRegistry> {
+    let before: Vec<BackendRegistry> = BackendRegistry::all().collect();
+    load();
+    BackendRegistry::all()
+        .filter(|reg| !before.contains(reg))
+        .collect()
+}
+
+impl BackendRegistry {
+    /// Load the backend module at `path`, e.g. `libggml_llama-cuda.so`, and
+    /// register it. Fails if the file cannot be loaded, is not a backend,
+    /// or the backend does not support this machine; ggml logs the reason.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let c_path = path_cstr(path)?;
+        unsafe { Self::from_raw(crate::ggml_backend_load(c_path.as_ptr())) }.ok_or_else(|| {
+            Error::InvalidArgument(format!("could not load a backend from {}", path.display()))
+        })
+    }
+
+    /// Load the best build of every backend found next to the executable
+    /// and in the working directory, plus the module named by the
+    /// `GGML_BACKEND_PATH` environment variable, and return the backends
+    /// that became available.
+    ///
+    /// ```ignore
+    /// for reg in BackendRegistry::load_all() {
+    ///     println!("loaded {} with {} devices", reg.name(), reg.device_count());
+    /// }
+    /// let backend = Backend::best()?;
+    /// ```
+    ///
+    /// Backends are registered again each time they are loaded, so call
+    /// this once at startup.
+    pub fn load_all() -> Vec<Self> {
+        newly_registered(|| unsafe { crate::ggml_backend_load_all() })
+    }
+
+    /// Like [`load_all`](Self::load_all), searching only `dir`.
+    pub fn load_all_from(dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let c_dir = path_cstr(dir.as_ref())?;
+        Ok(newly_registered(|| unsafe {
+            crate::ggml_backend_load_all_from_path(c_dir.as_ptr())
+        }))
+    }
+
+    /// Unregister a loaded backend and unload its module.
+    ///
+    /// # Safety
+    /// Every [`Backend`](super::Backend), buffer and event of the backend's
+    /// devices must be gone, and no copy of the registry or its
+    /// [`Device`](super::Device)s may be used afterwards.
+    pub unsafe fn unload(self) {
+        crate::ggml_backend_unload(self.as_ptr())
+    }
+}
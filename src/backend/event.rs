@@ -0,0 +1,180 @@
+//! Asynchronous compute and backend events (`ggml_backend_event`).
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use super::instance::Backend;
+use super::registry::Device;
+use crate::error::{check_status, Error, Result};
+use crate::graph::Graph;
+
+/// A point in a backend's queue of work: recorded on one backend, it can
+/// be waited for by the host or by another backend of the same kind.
+///
+/// ```ignore
+/// let done = Event::new(gpu0.device().unwrap())?;
+/// let pending = gpu0.compute_async(&mut graph)?;
+/// done.record(&gpu0)?;
+/// gpu1.wait_event(&done)?; // gpu1's later work starts after graph
+/// ```
+///
+/// Only some devices have events; see [`Device::supports_events`].
+pub struct Event {
+    ptr: NonNull<crate::ggml_backend_event>,
+    device: Device,
+}
+
+unsafe impl Send for Event {}
+
+impl Event {
+    pub fn new(device: Device) -> Result<Self> {
+        if !device.supports_events() {
+            return Err(Error::InvalidArgument(format!(
+                "{} does not support events",
+                device.name()
+            )));
+        }
+        let ptr = unsafe { crate::ggml_backend_event_new(device.as_ptr()) };
+        NonNull::new(ptr)
+            .map(|ptr| Event { ptr, device })
+            .ok_or(Error::NullPointer("ggml_backend_event_new"))
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_event_t {
+        self.ptr.as_ptr()
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+
+    /// Mark the end of the work queued on `backend` so far, which must run
+    /// on the event's device.
+    pub fn record(&self, backend: &Backend) -> Result<()> {
+        if backend.device() != Some(self.device) {
+            return Err(Error::InvalidArgument(format!(
+                "cannot record an event of {} on {}",
+                self.device.name(),
+                backend.name()
+            )));
+        }
+        unsafe { crate::ggml_backend_event_record(self.as_ptr(), backend.as_ptr()) };
+        Ok(())
+    }
+
+    /// Block until the work before the last [`record`](Self::record) is done.
+    pub fn synchronize(&self) {
+        unsafe { crate::ggml_backend_event_synchronize(self.as_ptr()) }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_backend_event_free(self.as_ptr()) }
+    }
+}
+
+impl std::fmt::Debug for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Event")
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+/// A graph being computed by [`Backend::compute_async`]. The graph and
+/// its context stay borrowed until the compute is done: [`wait`](Self::wait)
+/// for it, or drop the handle, which waits too.
+#[must_use = "dropping a PendingCompute waits for it"]
+pub struct PendingCompute<'a> {
+    backend: &'a Backend,
+    _graph: PhantomData<&'a mut ()>,
+}
+
+impl<'a> PendingCompute<'a> {
+    pub fn backend(&self) -> &'a Backend {
+        self.backend
+    }
+
+    /// Block until the graph is computed.
+    pub fn wait(self) {}
+
+    /// Wait for the graph without blocking the executor: the wait runs on
+    /// tokio's blocking pool. Backends without events, such as the CPU,
+    /// finish computing before [`Backend::compute_async`] returns, so there
+    /// is nothing to wait for.
+    ///
+    /// The future borrows the backend and is not `Send`; await it in the
+    /// task that owns the backend.
+    #[cfg(feature = "tokio")]
+    pub async fn wait_async(self) -> Result<()> {
+        let Some(event) = self
+            .backend
+            .device()
+            .filter(|d| d.supports_events())
+            .and_then(|d| Event::new(d).ok())
+        else {
+            return Ok(());
+        };
+        event.record(self.backend)?;
+        tokio::task::spawn_blocking(move || event.synchronize())
+            .await
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        // the queue is empty, so dropping self returns at once
+        Ok(())
+    }
+}
+
+impl Drop for PendingCompute<'_> {
+    fn drop(&mut self) {
+        self.backend.synchronize()
+    }
+}
+
+impl std::fmt::Debug for PendingCompute<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingCompute")
+            .field("backend", &self.backend)
+            .finish()
+    }
+}
+
+impl Backend {
+    /// Queue `graph` for computing and return without waiting for it, so
+    /// the host can prepare the next batch meanwhile. The CPU backend
+    /// computes before returning.
+    ///
+    /// ```ignore
+    /// let pending = backend.compute_async(&mut graph)?;
+    /// let next = tokenize(&input); // overlaps with the compute
+    /// pending.wait();
+    /// ```
+    pub fn compute_async<'a>(&'a self, graph: &'a mut Graph<'_>) -> Result<PendingCompute<'a>> {
+        check_status(unsafe {
+            crate::ggml_backend_graph_compute_async(self.as_ptr(), graph.as_ptr())
+        })?;
+        Ok(PendingCompute {
+            backend: self,
+            _graph: PhantomData,
+        })
+    }
+
+    /// Make work queued on this backend after this call wait for `event`,
+    /// without blocking the host. Both must belong to the same kind of
+    /// device, e.g. two CUDA GPUs.
+    pub fn wait_event(&self, event: &Event) -> Result<()> {
+        let device = self.device();
+        // ggml aborts on backends without events or of another kind
+        let compatible = device
+            .is_some_and(|d| d.supports_events() && d.registry() == event.device().registry());
+        if !compatible {
+            return Err(Error::InvalidArgument(format!(
+                "{} cannot wait for an event of {}",
+                self.name(),
+                event.device().name()
+            )));
+        }
+        unsafe { crate::ggml_backend_event_wait(self.as_ptr(), event.as_ptr()) };
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! The graph allocator (`ggml_gallocr`): reusable compute buffers.
+
+use std::collections::HashSet;
+use std::ptr::{self, NonNull};
+
+use super::buffer::BufferType;
+use super::instance::Backend;
+use crate::context::live_contexts;
+use crate::error::{Error, Result};
+use crate::graph::Graph;
+
+/// Tensors an allocator placed in its buffers. Before the allocator reuses
+/// or frees them, they are detached (`data` and `buffer` cleared), so the
+/// handles still around fail with [`Error::NoData`] instead of reading
+/// freed memory. Tensors whose context is gone are left alone.
+#[derive(Default)]
+pub(super) struct Placements {
+    // tensor, id of its context, buffer it was placed in
+    tensors: Vec<(*mut crate::ggml_tensor, u64, crate::ggml_backend_buffer_t)>,
+}
+
+// the pointers are only followed under the live context lock
+unsafe impl Send for Placements {}
+
+impl Placements {
+    /// The tensors allocating `graph` may place: those without memory in
+    /// the graph's context, and among the nodes and their sources.
+    pub(super) fn unplaced(graph: &Graph<'_>) -> Vec<*mut crate::ggml_tensor> {
+        let mut seen = HashSet::new();
+        if let Some((_, ctx)) = live_contexts().owner(graph.as_ptr()) {
+            let mut t = unsafe { crate::ggml_get_first_tensor(ctx) };
+            while !t.is_null() {
+                seen.insert(t);
+                t = unsafe { crate::ggml_get_next_tensor(ctx, t) };
+            }
+        }
+        for node in graph.nodes() {
+            let raw = unsafe { &*node.as_ptr() };
+            seen.insert(node.as_ptr());
+            seen.extend(raw.src.iter().copied().filter(|src| !src.is_null()));
+            if !raw.view_src.is_null() {
+                seen.insert(raw.view_src);
+            }
+        }
+        seen.into_iter()
+            .filter(|&t| unsafe { (*t).data.is_null() })
+            .collect()
+    }
+
+    /// Record those of `unplaced` that were given memory.
+    pub(super) fn record(&mut self, unplaced: Vec<*mut crate::ggml_tensor>) {
+        let live = live_contexts();
+        for t in unplaced {
+            let raw = unsafe { &*t };
+            if raw.data.is_null() {
+                continue;
+            }
+            if let Some((id, _)) = live.owner(t) {
+                self.tensors.push((t, id, raw.buffer));
+            }
+        }
+    }
+
+    /// Detach every recorded tensor that is still alive and in the buffer
+    /// it was placed in.
+    pub(super) fn detach(&mut self) {
+        let live = live_contexts();
+        for (t, id, buffer) in self.tensors.drain(..) {
+            if live.owner(t).map(|(owner, _)| owner) != Some(id) {
+                continue;
+            }
+            let raw = unsafe { &mut *t };
+            if raw.buffer == buffer {
+                raw.data = ptr::null_mut();
+                raw.buffer = ptr::null_mut();
+            }
+        }
+    }
+}
+
+/// Allocates the tensors of graphs in a compute buffer it owns, reusing
+/// memory between tensors whose lifetimes do not overlap, and keeps the
+/// buffer from one graph to the next. Use one allocator per backend; for
+/// graphs spanning several backends, use a [`Scheduler`](super::Scheduler).
+///
+/// Reserving with the largest graph up front sizes the buffer once:
+///
+/// ```ignore
+/// let mut galloc = GraphAllocator::new(&backend)?;
+/// galloc.reserve(&build_graph(&ctx, max_batch)?)?;
+/// for batch in batches {
+///     let mut graph = build_graph(&ctx, batch.len())?;
+///     galloc.alloc_graph(&mut graph)?;
+///     backend.compute(&mut graph)?;
+/// }
+/// ```
+///
+/// Mark graph inputs with [`Tensor::set_input`](crate::Tensor::set_input)
+/// and outputs with [`Tensor::set_output`](crate::Tensor::set_output) so
+/// they are not overwritten. Allocated tensors live in the allocator's
+/// buffer: read the outputs before the next allocation or dropping it.
+/// Both detach the tensors of the previous graph, which then fail with
+/// [`Error::NoData`].
+pub struct GraphAllocator {
+    ptr: NonNull<crate::ggml_gallocr>,
+    placed: Placements,
+}
+
+unsafe impl Send for GraphAllocator {}
+
+impl GraphAllocator {
+    /// An allocator with a compute buffer of `backend`'s default buffer type.
+    pub fn new(backend: &Backend) -> Result<Self> {
+        let buft = backend
+            .default_buffer_type()
+            .ok_or(Error::NullPointer("ggml_backend_get_default_buffer_type"))?;
+        Self::with_buffer_type(buft)
+    }
+
+    /// An allocator with a compute buffer of type `buft`.
+    pub fn with_buffer_type(buft: BufferType) -> Result<Self> {
+        let ptr = unsafe { crate::ggml_gallocr_new(buft.as_ptr()) };
+        NonNull::new(ptr)
+            .map(|ptr| GraphAllocator {
+                ptr,
+                placed: Placements::default(),
+            })
+            .ok_or(Error::NullPointer("ggml_gallocr_new"))
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_gallocr_t {
+        self.ptr.as_ptr()
+    }
+
+    /// Size the buffer for `graph` without allocating it; call with the
+    /// worst case graph to avoid reallocating later.
+    pub fn reserve(&mut self, graph: &Graph<'_>) -> Result<()> {
+        // the buffer may be reallocated
+        self.placed.detach();
+        if unsafe { crate::ggml_gallocr_reserve(self.as_ptr(), graph.as_ptr()) } {
+            Ok(())
+        } else {
+            Err(Error::AllocFailed)
+        }
+    }
+
+    /// Allocate every tensor of `graph` without data, growing the buffer
+    /// if the graph does not fit.
+    pub fn alloc_graph(&mut self, graph: &mut Graph<'_>) -> Result<()> {
+        self.placed.detach();
+        let unplaced = Placements::unplaced(graph);
+        let ok = unsafe { crate::ggml_gallocr_alloc_graph(self.as_ptr(), graph.as_ptr()) };
+        self.placed.record(unplaced);
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::AllocFailed)
+        }
+    }
+
+    /// Size of the compute buffer in bytes, or 0 before it is allocated.
+    pub fn buffer_size(&self) -> usize {
+        unsafe { crate::ggml_gallocr_get_buffer_size(self.as_ptr(), 0) }
+    }
+}
+
+impl Drop for GraphAllocator {
+    fn drop(&mut self) {
+        self.placed.detach();
+        unsafe { crate::ggml_gallocr_free(self.as_ptr()) }
+    }
+}
+
+impl std::fmt::Debug for GraphAllocator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphAllocator")
+            .field("buffer_size", &self.buffer_size())
+            .finish()
+    }
+}
@@ -0,0 +1,196 @@
+//! Host buffers backed by huge pages.
+//!
+//! The weights of a large model span hundreds of thousands of 4 KiB pages,
+//! more than the TLB can cover, so CPU matrix multiplications stall on page
+//! walks. Backing them with 2 MiB pages cuts the walks by a factor of 512.
+
+use std::ffi::c_void;
+
+use super::buffer::{BackendBuffer, BufferType};
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+/// Which pages back a buffer from [`BackendBuffer::cpu_huge_pages`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePages {
+    /// Pages reserved by the administrator (`vm.nr_hugepages`), always huge.
+    Explicit,
+    /// Transparent huge pages: the kernel uses huge pages where it can
+    /// find them, ordinary ones elsewhere.
+    Transparent,
+    /// Ordinary pages; huge pages are unavailable here.
+    Normal,
+}
+
+/// Anonymous memory from [`map_huge`], unmapped on drop.
+#[cfg(unix)]
+struct HugeMemory {
+    ptr: *mut u8,
+    len: usize,
+    kind: HugePages,
+}
+
+#[cfg(unix)]
+unsafe impl Send for HugeMemory {}
+
+#[cfg(unix)]
+impl AsMut<[u8]> for HugeMemory {
+    fn as_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for HugeMemory {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut c_void, self.len) };
+    }
+}
+
+/// Size of a huge page, `Hugepagesize` from `/proc/meminfo`.
+#[cfg(target_os = "linux")]
+fn huge_page_size() -> usize {
+    let kib = std::fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| {
+            let line = meminfo.lines().find(|l| l.starts_with("Hugepagesize:"))?;
+            line.split_whitespace().nth(1)?.parse::<usize>().ok()
+        });
+    kib.map_or(2 << 20, |kib| kib * 1024)
+}
+
+/// Whether transparent huge pages can be requested with `madvise`.
+#[cfg(target_os = "linux")]
+fn thp_enabled() -> bool {
+    std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+        .is_ok_and(|mode| !mode.contains("[never]"))
+}
+
+#[cfg(unix)]
+fn mmap_anon(len: usize, flags: libc::c_int) -> Option<*mut u8> {
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    (ptr != libc::MAP_FAILED).then_some(ptr as *mut u8)
+}
+
+/// Map `size` bytes with the largest pages available: reserved huge pages,
+/// then transparent ones, then ordinary pages.
+#[cfg(target_os = "linux")]
+fn map_huge(size: usize) -> Result<HugeMemory> {
+    let huge = huge_page_size();
+    let len = size.max(1).next_multiple_of(huge);
+    // fails when too few pages are reserved
+    if let Some(ptr) = mmap_anon(len, libc::MAP_HUGETLB) {
+        return Ok(HugeMemory {
+            ptr,
+            len,
+            kind: HugePages::Explicit,
+        });
+    }
+
+    // transparent huge pages need huge-page aligned memory: map one page
+    // more than needed, then trim both ends to the aligned range
+    let padded = len + huge;
+    let raw = mmap_anon(padded, 0).ok_or_else(std::io::Error::last_os_error)?;
+    let head = (raw as usize).next_multiple_of(huge) - raw as usize;
+    let ptr = unsafe { raw.add(head) };
+    unsafe {
+        if head > 0 {
+            libc::munmap(raw as *mut c_void, head);
+        }
+        libc::munmap(ptr.add(len) as *mut c_void, padded - head - len);
+    }
+    let advised = thp_enabled()
+        && unsafe { libc::madvise(ptr as *mut c_void, len, libc::MADV_HUGEPAGE) } == 0;
+    Ok(HugeMemory {
+        ptr,
+        len,
+        kind: if advised {
+            HugePages::Transparent
+        } else {
+            HugePages::Normal
+        },
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn map_huge(size: usize) -> Result<HugeMemory> {
+    let len = size.max(1);
+    let ptr = mmap_anon(len, 0).ok_or_else(std::io::Error::last_os_error)?;
+    Ok(HugeMemory {
+        ptr,
+        len,
+        kind: HugePages::Normal,
+    })
+}
+
+impl BackendBuffer {
+    /// A CPU buffer of at least `size` bytes backed by huge pages where the
+    /// OS provides them: pages reserved with `vm.nr_hugepages` if enough
+    /// are free, else transparent huge pages, else ordinary pages. Check
+    /// [`huge_pages`](Self::huge_pages) for what was used.
+    ///
+    /// Only Linux has huge pages; elsewhere this is an ordinary buffer.
+    pub fn cpu_huge_pages(size: usize) -> Result<Self> {
+        #[cfg(unix)]
+        return Self::cpu_from_owner(map_huge(size)?);
+        #[cfg(not(unix))]
+        return BufferType::cpu().alloc(size);
+    }
+
+    /// Which pages back the buffer, if it comes from
+    /// [`cpu_huge_pages`](Self::cpu_huge_pages) on a Unix system; `None`
+    /// for other buffers.
+    pub fn huge_pages(&self) -> Option<HugePages> {
+        #[cfg(unix)]
+        return self
+            .owner()
+            .and_then(|owner| owner.downcast_ref::<HugeMemory>())
+            .map(|mem| mem.kind);
+        #[cfg(not(unix))]
+        return None;
+    }
+}
+
+impl Context {
+    /// Place every tensor of this `no_alloc` context that has no data yet
+    /// in one new [`BackendBuffer::cpu_huge_pages`] buffer, sized to fit,
+    /// and report which pages back it. Meant for model weights computed
+    /// on the CPU:
+    ///
+    /// ```ignore
+    /// let weights = Context::with_params(ContextParams::new(meta_size).no_alloc(true))?;
+    /// // ... create the weight tensors ...
+    /// if weights.alloc_huge_pages()? == HugePages::Normal {
+    ///     eprintln!("no huge pages; reserve some with sysctl vm.nr_hugepages");
+    /// }
+    /// ```
+    pub fn alloc_huge_pages(&self) -> Result<HugePages> {
+        if !self.no_alloc() {
+            return Err(Error::InvalidArgument(
+                "backend allocation needs a no_alloc context".to_string(),
+            ));
+        }
+        let cpu = BufferType::cpu();
+        let align = cpu.alignment().max(1);
+        let is_view = |t: &Tensor<'_>| unsafe { !(*t.as_ptr()).view_src.is_null() };
+        let size = self
+            .tensors()
+            .filter(|t| t.data().is_null() && !is_view(t))
+            .map(|t| cpu.alloc_size(t).next_multiple_of(align))
+            .sum();
+        let buffer = BackendBuffer::cpu_huge_pages(size)?;
+        let kind = buffer.huge_pages().unwrap_or(HugePages::Normal);
+        self.alloc_in(buffer)?;
+        Ok(kind)
+    }
+}
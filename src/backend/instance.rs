@@ -0,0 +1,123 @@
+//! Owned backend instances (`ggml_backend_t`).
+
+use std::ffi::CStr;
+use std::fmt;
+use std::ptr::NonNull;
+
+use super::buffer::BufferType;
+use super::registry::{Device, DeviceType};
+use crate::context::Context;
+use crate::error::{check_status, Error, Result};
+use crate::graph::Graph;
+use crate::tensor::Tensor;
+
+/// An initialized backend: a stream of work on one [`Device`].
+pub struct Backend {
+    ptr: NonNull<crate::ggml_backend>,
+}
+
+// a backend can move between threads, it just can't be used from two at once
+unsafe impl Send for Backend {}
+
+impl Backend {
+    /// Initialize the device named `name` (see [`Device::by_name`]).
+    pub fn by_name(name: &str, params: Option<&str>) -> Result<Self> {
+        Device::by_name(name)
+            .ok_or_else(|| Error::InvalidArgument(format!("no backend device named {:?}", name)))?
+            .init(params)
+    }
+
+    /// Initialize the first device of type `ty`.
+    pub fn by_type(ty: DeviceType, params: Option<&str>) -> Result<Self> {
+        Device::by_type(ty)
+            .ok_or_else(|| Error::InvalidArgument(format!("no backend device of type {:?}", ty)))?
+            .init(params)
+    }
+
+    /// Initialize the first GPU if there is one, otherwise the CPU.
+    pub fn best() -> Result<Self> {
+        unsafe { Self::from_raw(crate::ggml_backend_init_best()) }
+            .ok_or(Error::NullPointer("ggml_backend_init_best"))
+    }
+
+    /// Take ownership of a backend; it is freed on drop.
+    ///
+    /// # Safety
+    /// `ptr` must be a backend that nothing else frees.
+    pub unsafe fn from_raw(ptr: crate::ggml_backend_t) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Backend { ptr })
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_t {
+        self.ptr.as_ptr()
+    }
+
+    pub fn name(&self) -> &str {
+        let ptr = unsafe { crate::ggml_backend_name(self.as_ptr()) };
+        if ptr.is_null() {
+            return "";
+        }
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("")
+    }
+
+    /// The device the backend computes on.
+    pub fn device(&self) -> Option<Device> {
+        unsafe { Device::from_raw(crate::ggml_backend_get_device(self.as_ptr())) }
+    }
+
+    /// The buffer type [`Context::alloc_on`] allocates from.
+    pub fn default_buffer_type(&self) -> Option<BufferType> {
+        unsafe { BufferType::from_raw(crate::ggml_backend_get_default_buffer_type(self.as_ptr())) }
+    }
+
+    /// Whether the backend can compute `op`; see [`Device::supports_op`].
+    pub fn supports_op(&self, op: Tensor<'_>) -> bool {
+        unsafe { crate::ggml_backend_supports_op(self.as_ptr(), op.as_ptr()) }
+    }
+
+    /// Whether the backend can compute on tensors in buffers of type `buft`.
+    pub fn supports_buft(&self, buft: BufferType) -> bool {
+        unsafe { crate::ggml_backend_supports_buft(self.as_ptr(), buft.as_ptr()) }
+    }
+
+    /// Whether `op` is worth offloading to this backend from host memory;
+    /// see [`Device::offload_op`].
+    pub fn offload_op(&self, op: Tensor<'_>) -> bool {
+        unsafe { crate::ggml_backend_offload_op(self.as_ptr(), op.as_ptr()) }
+    }
+
+    /// Compute `graph`, whose tensors must be allocated in buffers this
+    /// backend can use (see [`Context::alloc_on`]), and wait for it.
+    pub fn compute(&self, graph: &mut Graph<'_>) -> Result<()> {
+        check_status(unsafe { crate::ggml_backend_graph_compute(self.as_ptr(), graph.as_ptr()) })
+    }
+
+    /// Wait for all work queued on the backend to finish.
+    pub fn synchronize(&self) {
+        unsafe { crate::ggml_backend_synchronize(self.as_ptr()) }
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_backend_free(self.as_ptr()) }
+    }
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Backend")
+            .field("name", &self.name())
+            .field("device", &self.device())
+            .finish()
+    }
+}
+
+impl Context {
+    /// Allocate data for every tensor of this `no_alloc` context that has
+    /// none yet in a buffer of `backend`'s default buffer type. The buffer
+    /// lives as long as the context.
+    pub fn alloc_on(&self, backend: &Backend) -> Result<()> {
+        self.alloc_tensors_on(backend.as_ptr())
+    }
+}
@@ -0,0 +1,148 @@
+//! The Metal backend for Apple GPUs.
+//!
+//! The Metal shaders are embedded in the library at build time
+//! (`GGML_METAL_EMBED_LIBRARY`), so no `.metal` files need to ship with
+//! the binary.
+
+use std::ffi::c_void;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+use super::buffer::BackendBuffer;
+use super::cpu::{abort_trampoline, AbortFn};
+use super::instance::Backend;
+use crate::error::{Error, Result};
+
+/// The Metal backend on the system's default GPU. Derefs to [`Backend`].
+///
+/// Apple GPUs share memory with the CPU, so host memory such as a
+/// memory-mapped model can be used by the GPU without a copy:
+///
+/// ```ignore
+/// let metal = MetalBackend::new()?;
+/// let weights = unsafe { metal.buffer_from_ptr(map.as_ptr() as *mut _, map.len(), max_tensor)? };
+/// ```
+///
+/// [`capture_next_compute`](Self::capture_next_compute) records a GPU trace
+/// for Xcode, and [`set_residency`](Self::set_residency) lets idle buffers
+/// be paged out.
+pub struct MetalBackend {
+    // freed before the callback it refers to
+    backend: Backend,
+    abort: Option<Box<AbortFn>>,
+}
+
+impl MetalBackend {
+    pub fn new() -> Result<Self> {
+        let backend = unsafe { Backend::from_raw(crate::ggml_backend_metal_init()) }
+            .ok_or(Error::NullPointer("ggml_backend_metal_init"))?;
+        Ok(MetalBackend {
+            backend,
+            abort: None,
+        })
+    }
+
+    /// Whether the GPU belongs to Apple GPU family `family`, e.g. 7 for
+    /// `MTLGPUFamilyApple7` (M1).
+    pub fn supports_family(&self, family: u32) -> bool {
+        unsafe { crate::ggml_backend_metal_supports_family(self.as_ptr(), family as i32) }
+    }
+
+    /// Use `size` bytes of host memory at `ptr` as a GPU buffer without
+    /// copying; see [`Device::buffer_from_host_ptr`](super::Device::buffer_from_host_ptr).
+    /// Place tensors in it with [`Context::alloc_in`](crate::Context::alloc_in).
+    ///
+    /// A shared-storage `MTLBuffer` of another framework is imported the
+    /// same way, from its `contents` pointer and `length`, as long as both
+    /// are page aligned.
+    ///
+    /// # Safety
+    /// The memory must stay valid and in place until the buffer, and every
+    /// tensor placed in it, is gone. Metal maps whole pages, so the pages
+    /// around the range must be mapped too, as with `mmap`ed files.
+    pub unsafe fn buffer_from_ptr(
+        &self,
+        ptr: *mut c_void,
+        size: usize,
+        max_tensor_size: usize,
+    ) -> Result<BackendBuffer> {
+        self.device()
+            .ok_or(Error::NullPointer("ggml_backend_get_device"))?
+            .buffer_from_host_ptr(ptr, size, max_tensor_size)
+    }
+
+    /// A Metal extension looked up through the backend's registry, from
+    /// patches/ of the vendored ggml.
+    fn proc_address(&self, name: &'static str) -> Result<NonNull<c_void>> {
+        self.device()
+            .ok_or(Error::NullPointer("ggml_backend_get_device"))?
+            .registry()
+            .proc_address(name)
+            .ok_or(Error::NullPointer(name))
+    }
+
+    /// Record the next [`compute`](Backend::compute) in a GPU trace at
+    /// `/tmp/perf-metal.gputrace`, to open in Xcode. Capturing requires
+    /// the `MTL_CAPTURE_ENABLED=1` environment variable at startup.
+    pub fn capture_next_compute(&self) {
+        unsafe { crate::ggml_backend_metal_capture_next_compute(self.as_ptr()) }
+    }
+
+    /// Keep `buffer` resident in GPU memory, or let the OS page it out
+    /// until it is requested again, e.g. while a loaded model is idle.
+    /// Buffers are resident when allocated. Fails for buffers without a
+    /// residency set: those of other backends, on macOS before 15, or with
+    /// `GGML_METAL_NO_RESIDENCY` set; and with a system ggml, which lacks
+    /// the extension.
+    pub fn set_residency(&self, buffer: &BackendBuffer, resident: bool) -> Result<()> {
+        let f = self.proc_address("ggml_backend_metal_buffer_set_residency")?;
+        let f: unsafe extern "C" fn(crate::ggml_backend_buffer_t, bool) -> bool =
+            unsafe { std::mem::transmute(f) };
+        if unsafe { f(buffer.as_ptr(), resident) } {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(format!(
+                "buffer {} has no Metal residency set",
+                buffer.name()
+            )))
+        }
+    }
+
+    /// Call `f` between command buffers during compute; when it returns
+    /// `true` the compute stops. Replaces any previous callback.
+    pub fn set_abort_callback(&mut self, f: impl FnMut() -> bool + Send + 'static) {
+        let mut abort: Box<AbortFn> = Box::new(Box::new(f));
+        unsafe {
+            crate::ggml_backend_metal_set_abort_callback(
+                self.as_ptr(),
+                Some(abort_trampoline),
+                &mut *abort as *mut AbortFn as *mut c_void,
+            )
+        };
+        self.abort = Some(abort);
+    }
+
+    pub fn clear_abort_callback(&mut self) {
+        unsafe {
+            crate::ggml_backend_metal_set_abort_callback(self.as_ptr(), None, std::ptr::null_mut())
+        };
+        self.abort = None;
+    }
+}
+
+impl Deref for MetalBackend {
+    type Target = Backend;
+
+    fn deref(&self) -> &Backend {
+        &self.backend
+    }
+}
+
+impl std::fmt::Debug for MetalBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetalBackend")
+            .field("device", &self.device())
+            .field("abort_callback", &self.abort.is_some())
+            .finish()
+    }
+}
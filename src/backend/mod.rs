@@ -0,0 +1,98 @@
+//! Backends (`ggml_backend`): the CPU, GPUs and other devices graphs are
+//! computed on.
+//!
+//! [`BackendRegistry`] lists the backends compiled in (or loaded) and the
+//! [`Device`]s each provides; a device is initialized into an owned
+//! [`Backend`]:
+//!
+//! ```ignore
+//! for dev in Device::all() {
+//!     println!("{}: {} ({:?})", dev.name(), dev.description(), dev.device_type());
+//! }
+//! let backend = Backend::best()?;
+//! ctx.alloc_on(&backend)?;
+//! backend.compute(&mut graph)?;
+//! ```
+//!
+//! With the `backend-dl` feature the backends are built as modules, found
+//! and loaded at runtime with `BackendRegistry::load_all`, so one binary
+//! can ship with optional GPU backends next to it.
+//!
+//! [`CpuBackend`] adds the CPU backend's own settings: the thread count, an
+//! owned [`Threadpool`](crate::Threadpool) and an abort callback.
+//!
+//! With the `cuda` feature, [`CudaBackend`] selects a CUDA device by index
+//! and reports its memory and compute capability.
+//!
+//! With the `metal` feature, [`MetalBackend`] runs graphs on Apple GPUs and
+//! can use host memory as a GPU buffer without a copy.
+//!
+//! With the `vulkan` feature, [`VulkanBackend`] lists the Vulkan adapters
+//! and runs on the one picked by index.
+//!
+//! With the `rpc` feature, [`RpcBackend`] computes on a device of another
+//! machine over the network, which shares it with an [`RpcServer`].
+//!
+//! Tensors of a `no_alloc` context are placed in a backend's memory with
+//! [`Context::alloc_on`](crate::Context::alloc_on), in memory of a given
+//! [`BufferType`], such as pinned host memory, with
+//! [`Context::alloc_with`](crate::Context::alloc_with), or in a given
+//! [`BackendBuffer`] with [`Context::alloc_in`](crate::Context::alloc_in).
+//! [`Tensor::write_bytes`](crate::Tensor::write_bytes),
+//! [`read_bytes`](crate::Tensor::read_bytes) and
+//! [`copy_to`](crate::Tensor::copy_to) move data in and out of any of them.
+//! Weights computed on the CPU can go in huge pages with
+//! [`Context::alloc_huge_pages`](crate::Context::alloc_huge_pages).
+//!
+//! [`Backend::compute_async`] queues a graph and returns a
+//! [`PendingCompute`] to wait for later, from async code too with the
+//! `tokio` feature; [`Event`]s order work between backends.
+//!
+//! A [`Scheduler`] runs a graph over several backends at once, e.g. a GPU
+//! with the CPU taking the ops or layers the GPU cannot hold.
+//!
+//! The intermediate tensors of a graph computed on a single backend are
+//! placed by a [`GraphAllocator`], which keeps its compute buffer for the
+//! next graph. A [`GraphPlan`] keeps the graph itself too, allocated, for
+//! loops that compute the same graph on new inputs.
+//!
+//! Backend-specific extensions are reached through
+//! [`BackendRegistry::proc_address`].
+
+mod buffer;
+mod cpu;
+#[cfg(feature = "cuda")]
+mod cuda;
+#[cfg(feature = "backend-dl")]
+mod dl;
+mod event;
+mod gallocr;
+mod hugepage;
+mod instance;
+#[cfg(feature = "metal")]
+mod metal;
+mod plan;
+mod registry;
+#[cfg(feature = "rpc")]
+mod rpc;
+mod sched;
+#[cfg(feature = "vulkan")]
+mod vulkan;
+
+pub use buffer::{BackendBuffer, BufferType};
+pub use cpu::CpuBackend;
+#[cfg(feature = "cuda")]
+pub use cuda::{CudaBackend, CudaDevice};
+pub use event::{Event, PendingCompute};
+pub use gallocr::GraphAllocator;
+pub use hugepage::HugePages;
+pub use instance::Backend;
+#[cfg(feature = "metal")]
+pub use metal::MetalBackend;
+pub use plan::GraphPlan;
+pub use registry::{BackendRegistry, Device, DeviceType};
+#[cfg(feature = "rpc")]
+pub use rpc::{RpcBackend, RpcServer};
+pub use sched::Scheduler;
+#[cfg(feature = "vulkan")]
+pub use vulkan::{VulkanBackend, VulkanDevice};
@@ -0,0 +1,125 @@
+//! Graphs built and allocated once, then computed many times.
+
+use super::gallocr::GraphAllocator;
+use super::instance::Backend;
+use super::sched::Scheduler;
+use crate::error::Result;
+use crate::graph::Graph;
+
+enum Executor<'b> {
+    Backend {
+        backend: &'b Backend,
+        galloc: GraphAllocator,
+    },
+    Scheduler(Scheduler<'b>),
+}
+
+/// A graph whose tensors stay allocated between computations, so a loop
+/// that runs the same graph on new inputs, such as decoding one token at
+/// a time, skips building, splitting and allocating it each iteration.
+///
+/// ```ignore
+/// let x = ctx.new_tensor_1d(Type::F32, n_embd)?.set_input();
+/// let y = forward(&ctx, &weights, x)?.set_output();
+/// let mut graph = ctx.new_graph()?;
+/// graph.build_forward_expand(y);
+///
+/// let mut plan = GraphPlan::new(&backend, graph)?;
+/// for token in tokens {
+///     x.write_bytes(0, embed(token))?; // rebind the input
+///     plan.compute()?;
+///     y.read_bytes(0, &mut logits)?;
+/// }
+/// ```
+///
+/// Only the data of the graph's tensors may change between computations,
+/// not their shapes or the graph itself; build a new plan for that. Mark
+/// inputs with [`Tensor::set_input`](crate::Tensor::set_input) and write
+/// all of them before each [`compute`](Self::compute): intermediate
+/// results may reuse their memory. Mark outputs with
+/// [`Tensor::set_output`](crate::Tensor::set_output) so they survive.
+/// The graph's tensors live in the plan's compute buffers: read outputs
+/// before dropping it. Dropping the plan detaches them, so later reads
+/// fail with [`Error::NoData`](crate::Error::NoData) instead of reading
+/// freed memory.
+///
+/// `cargo run --release --bin graph-reuse-bench` compares this with
+/// rebuilding the graph every iteration (see "Command-line Tools" in
+/// USAGE.md for how the tools link ggml).
+pub struct GraphPlan<'ctx, 'b> {
+    graph: Graph<'ctx>,
+    executor: Executor<'b>,
+    n_computes: u64,
+}
+
+impl<'ctx, 'b> GraphPlan<'ctx, 'b> {
+    /// Allocate `graph` in a compute buffer of `backend` for good.
+    pub fn new(backend: &'b Backend, mut graph: Graph<'ctx>) -> Result<Self> {
+        let mut galloc = GraphAllocator::new(backend)?;
+        galloc.alloc_graph(&mut graph)?;
+        Ok(GraphPlan {
+            graph,
+            executor: Executor::Backend { backend, galloc },
+            n_computes: 0,
+        })
+    }
+
+    /// Split `graph` over the scheduler's backends and allocate it for
+    /// good. The scheduler's previous allocation is reset.
+    pub fn with_scheduler(mut sched: Scheduler<'b>, mut graph: Graph<'ctx>) -> Result<Self> {
+        sched.reset();
+        sched.alloc_graph(&mut graph)?;
+        Ok(GraphPlan {
+            graph,
+            executor: Executor::Scheduler(sched),
+            n_computes: 0,
+        })
+    }
+
+    pub fn graph(&self) -> &Graph<'ctx> {
+        &self.graph
+    }
+
+    /// The scheduler, for plans made with [`with_scheduler`](Self::with_scheduler).
+    pub fn scheduler(&self) -> Option<&Scheduler<'b>> {
+        match &self.executor {
+            Executor::Scheduler(sched) => Some(sched),
+            Executor::Backend { .. } => None,
+        }
+    }
+
+    /// Compute the graph with the current contents of its inputs, and wait
+    /// for it.
+    pub fn compute(&mut self) -> Result<()> {
+        match &mut self.executor {
+            Executor::Backend { backend, .. } => backend.compute(&mut self.graph)?,
+            Executor::Scheduler(sched) => sched.compute(&mut self.graph)?,
+        }
+        self.n_computes += 1;
+        Ok(())
+    }
+
+    /// How many times the graph has been computed.
+    pub fn n_computes(&self) -> u64 {
+        self.n_computes
+    }
+
+    /// Size in bytes of the compute buffers holding the graph's tensors.
+    pub fn buffer_size(&self) -> usize {
+        match &self.executor {
+            Executor::Backend { galloc, .. } => galloc.buffer_size(),
+            Executor::Scheduler(sched) => sched.buffer_sizes().iter().map(|&(_, n)| n).sum(),
+        }
+    }
+}
+
+impl std::fmt::Debug for GraphPlan<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphPlan")
+            .field("n_nodes", &self.graph.n_nodes())
+            .field("scheduled", &self.scheduler().is_some())
+            .field("buffer_size", &self.buffer_size())
+            .field("n_computes", &self.n_computes)
+            .finish()
+    }
+}
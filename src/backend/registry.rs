@@ -0,0 +1,388 @@
+//! The backend registry (`ggml_backend_reg`) and the devices it lists.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+use super::buffer::{BackendBuffer, BufferType};
+use super::instance::Backend;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+/// Strings owned by a registry or device live as long as it does.
+fn static_str(ptr: *const c_char) -> &'static str {
+    if ptr.is_null() {
+        return "";
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("")
+}
+
+/// `MemAvailable` from `/proc/meminfo`, in bytes.
+#[cfg(target_os = "linux")]
+fn mem_available() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kib: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// A registered backend such as `"CPU"`, `"CUDA"` or `"Metal"`, and the
+/// devices it provides.
+///
+/// Registries are owned by ggml and live for the rest of the process once
+/// registered, so this is a plain `Copy` handle.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct BackendRegistry {
+    ptr: NonNull<crate::ggml_backend_reg>,
+}
+
+// the registry is not mutated after registration
+unsafe impl Send for BackendRegistry {}
+unsafe impl Sync for BackendRegistry {}
+
+impl BackendRegistry {
+    /// # Safety
+    /// `ptr` must point to a registered backend.
+    pub unsafe fn from_raw(ptr: crate::ggml_backend_reg_t) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| BackendRegistry { ptr })
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_reg_t {
+        self.ptr.as_ptr()
+    }
+
+    /// Number of registered backends.
+    pub fn count() -> usize {
+        unsafe { crate::ggml_backend_reg_count() }
+    }
+
+    pub fn get(i: usize) -> Option<Self> {
+        if i >= Self::count() {
+            return None;
+        }
+        unsafe { Self::from_raw(crate::ggml_backend_reg_get(i)) }
+    }
+
+    /// Iterate over the registered backends in registration order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..Self::count()).filter_map(Self::get)
+    }
+
+    /// Look up a backend by name, ignoring case.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = CString::new(name).ok()?;
+        unsafe { Self::from_raw(crate::ggml_backend_reg_by_name(name.as_ptr())) }
+    }
+
+    /// The CPU backend, which is always built in.
+    pub fn cpu() -> Self {
+        unsafe { Self::from_raw(crate::ggml_backend_cpu_reg()) }
+            .expect("ggml_backend_cpu_reg returned a null pointer")
+    }
+
+    pub fn name(&self) -> &'static str {
+        static_str(unsafe { crate::ggml_backend_reg_name(self.as_ptr()) })
+    }
+
+    pub fn device_count(&self) -> usize {
+        unsafe { crate::ggml_backend_reg_dev_count(self.as_ptr()) }
+    }
+
+    pub fn device(&self, i: usize) -> Option<Device> {
+        if i >= self.device_count() {
+            return None;
+        }
+        unsafe { Device::from_raw(crate::ggml_backend_reg_dev_get(self.as_ptr(), i)) }
+    }
+
+    /// Iterate over the devices of this backend.
+    pub fn devices(&self) -> impl Iterator<Item = Device> + '_ {
+        (0..self.device_count()).filter_map(|i| self.device(i))
+    }
+
+    /// Address of a backend-specific function such as
+    /// `"ggml_backend_set_n_threads"`, or `None` if the backend has none by
+    /// that name. Cast it to the function's signature from the backend's
+    /// header before calling it.
+    pub fn proc_address(&self, name: &str) -> Option<NonNull<c_void>> {
+        let name = CString::new(name).ok()?;
+        NonNull::new(unsafe {
+            crate::ggml_backend_reg_get_proc_address(self.as_ptr(), name.as_ptr())
+        })
+    }
+
+    /// Allocate device buffers of this backend as unified (managed) memory,
+    /// which the driver pages between host and device, so models larger
+    /// than VRAM still load and integrated GPUs share system memory. Only
+    /// buffers allocated afterwards are affected. Supported by the CUDA and
+    /// ROCm (HIP) backends; overrides `GGML_CUDA_ENABLE_UNIFIED_MEMORY`.
+    ///
+    /// ```ignore
+    /// if let Some(reg) = BackendRegistry::by_name("CUDA") {
+    ///     reg.set_unified_memory(true)?;
+    /// }
+    /// ```
+    pub fn set_unified_memory(&self, enable: bool) -> Result<()> {
+        let f = self
+            .proc_address("ggml_backend_set_unified_memory")
+            .ok_or_else(|| {
+                Error::InvalidArgument(format!("{} has no unified memory option", self.name()))
+            })?;
+        let f: unsafe extern "C" fn(bool) = unsafe { std::mem::transmute(f.as_ptr()) };
+        unsafe { f(enable) };
+        Ok(())
+    }
+
+    /// Whether device buffers are allocated as unified memory, or `None`
+    /// if the backend has no such option.
+    pub fn unified_memory(&self) -> Option<bool> {
+        let f = self.proc_address("ggml_backend_get_unified_memory")?;
+        let f: unsafe extern "C" fn() -> bool = unsafe { std::mem::transmute(f.as_ptr()) };
+        Some(unsafe { f() })
+    }
+}
+
+impl fmt::Debug for BackendRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackendRegistry")
+            .field("name", &self.name())
+            .field("devices", &self.device_count())
+            .finish()
+    }
+}
+
+/// Kind of device, mirroring `enum ggml_backend_dev_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceType {
+    /// The host CPU.
+    Cpu,
+    /// A GPU with dedicated memory.
+    Gpu,
+    /// A GPU sharing memory with the host.
+    Igpu,
+    /// An accelerator used alongside the CPU, such as BLAS or AMX.
+    Accel,
+}
+
+impl DeviceType {
+    pub fn as_raw(self) -> crate::ggml_backend_dev_type {
+        match self {
+            DeviceType::Cpu => crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_CPU,
+            DeviceType::Gpu => crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_GPU,
+            DeviceType::Igpu => crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_IGPU,
+            DeviceType::Accel => crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_ACCEL,
+        }
+    }
+
+    pub fn from_raw(raw: crate::ggml_backend_dev_type) -> Option<Self> {
+        match raw {
+            crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_CPU => Some(DeviceType::Cpu),
+            crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_GPU => Some(DeviceType::Gpu),
+            crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_IGPU => Some(DeviceType::Igpu),
+            crate::ggml_backend_dev_type_GGML_BACKEND_DEVICE_TYPE_ACCEL => Some(DeviceType::Accel),
+            _ => None,
+        }
+    }
+}
+
+/// A device of a [`BackendRegistry`], such as one GPU. Like registries,
+/// devices are owned by ggml and live for the rest of the process.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Device {
+    ptr: NonNull<crate::ggml_backend_device>,
+}
+
+unsafe impl Send for Device {}
+unsafe impl Sync for Device {}
+
+impl Device {
+    /// # Safety
+    /// `ptr` must point to a registered device.
+    pub unsafe fn from_raw(ptr: crate::ggml_backend_dev_t) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Device { ptr })
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_dev_t {
+        self.ptr.as_ptr()
+    }
+
+    /// Number of devices over all registered backends.
+    pub fn count() -> usize {
+        unsafe { crate::ggml_backend_dev_count() }
+    }
+
+    pub fn get(i: usize) -> Option<Self> {
+        if i >= Self::count() {
+            return None;
+        }
+        unsafe { Self::from_raw(crate::ggml_backend_dev_get(i)) }
+    }
+
+    /// Iterate over the devices of all registered backends.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..Self::count()).filter_map(Self::get)
+    }
+
+    /// Look up a device by name, such as `"CUDA0"`, ignoring case.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let name = CString::new(name).ok()?;
+        unsafe { Self::from_raw(crate::ggml_backend_dev_by_name(name.as_ptr())) }
+    }
+
+    /// The first device of type `ty`.
+    pub fn by_type(ty: DeviceType) -> Option<Self> {
+        unsafe { Self::from_raw(crate::ggml_backend_dev_by_type(ty.as_raw())) }
+    }
+
+    pub fn name(&self) -> &'static str {
+        static_str(unsafe { crate::ggml_backend_dev_name(self.as_ptr()) })
+    }
+
+    /// Human-readable description, usually the hardware model.
+    pub fn description(&self) -> &'static str {
+        static_str(unsafe { crate::ggml_backend_dev_description(self.as_ptr()) })
+    }
+
+    /// Free and total memory of the device in bytes: VRAM for discrete
+    /// GPUs, the recommended working set for Metal, and physical memory for
+    /// the CPU. Free memory is measured at the call, so query again before
+    /// each budgeting decision.
+    ///
+    /// ```ignore
+    /// let (free, _) = gpu.memory();
+    /// let n_gpu_layers = (free.saturating_sub(compute_reserve) / layer_bytes).min(n_layers);
+    /// ```
+    pub fn memory(&self) -> (usize, usize) {
+        let (mut free, mut total) = (0, 0);
+        unsafe { crate::ggml_backend_dev_memory(self.as_ptr(), &mut free, &mut total) };
+        // ggml reports all memory as free for the CPU outside Windows
+        #[cfg(target_os = "linux")]
+        if self.device_type() == Some(DeviceType::Cpu) {
+            if let Some(available) = mem_available() {
+                free = available.min(total);
+            }
+        }
+        (free, total)
+    }
+
+    /// `None` for device types this crate does not know yet.
+    pub fn device_type(&self) -> Option<DeviceType> {
+        DeviceType::from_raw(unsafe { crate::ggml_backend_dev_type(self.as_ptr()) })
+    }
+
+    fn props(&self) -> crate::ggml_backend_dev_props {
+        let mut props = std::mem::MaybeUninit::uninit();
+        unsafe {
+            crate::ggml_backend_dev_get_props(self.as_ptr(), props.as_mut_ptr());
+            props.assume_init()
+        }
+    }
+
+    /// Whether the device can compute `op`, a node of a graph, with its
+    /// current types and shapes. Ops it cannot compute fall back to another
+    /// backend under a [`Scheduler`](super::Scheduler) and fail on this
+    /// device's own [`Backend`].
+    ///
+    /// ```ignore
+    /// let y = ctx.mul_mat(weight_iq2, x)?;
+    /// if !gpu.supports_op(y) {
+    ///     // keep this layer's weights in host memory
+    /// }
+    /// ```
+    pub fn supports_op(&self, op: Tensor<'_>) -> bool {
+        unsafe { crate::ggml_backend_dev_supports_op(self.as_ptr(), op.as_ptr()) }
+    }
+
+    /// Whether the device can compute on tensors in buffers of type `buft`.
+    pub fn supports_buft(&self, buft: BufferType) -> bool {
+        unsafe { crate::ggml_backend_dev_supports_buft(self.as_ptr(), buft.as_ptr()) }
+    }
+
+    /// Whether `op` is worth running on this device even though its weights
+    /// are in host memory, i.e. its batch is large enough to pay for the
+    /// copy. The scheduler asks this when `op_offload` is on.
+    pub fn offload_op(&self, op: Tensor<'_>) -> bool {
+        unsafe { crate::ggml_backend_dev_offload_op(self.as_ptr(), op.as_ptr()) }
+    }
+
+    /// Whether the device has [`Event`](super::Event)s, so its backends
+    /// can signal and wait for each other without blocking the host.
+    pub fn supports_events(&self) -> bool {
+        self.props().caps.events
+    }
+
+    /// The device's own memory.
+    pub fn buffer_type(&self) -> Option<BufferType> {
+        unsafe { BufferType::from_raw(crate::ggml_backend_dev_buffer_type(self.as_ptr())) }
+    }
+
+    /// Pinned host memory the device copies to and from quickly, if it has
+    /// such a type (CUDA, Vulkan, SYCL).
+    pub fn host_buffer_type(&self) -> Option<BufferType> {
+        unsafe { BufferType::from_raw(crate::ggml_backend_dev_host_buffer_type(self.as_ptr())) }
+    }
+
+    /// The backend this device belongs to.
+    pub fn registry(&self) -> BackendRegistry {
+        unsafe { BackendRegistry::from_raw(crate::ggml_backend_dev_backend_reg(self.as_ptr())) }
+            .expect("ggml_backend_dev_backend_reg returned a null pointer")
+    }
+
+    /// Create a backend computing on this device. `params` is passed to the
+    /// backend's init function and is backend specific; most ignore it.
+    pub fn init(&self, params: Option<&str>) -> Result<Backend> {
+        let params = params
+            .map(|p| {
+                CString::new(p).map_err(|_| {
+                    Error::InvalidArgument("backend params contain a NUL byte".to_string())
+                })
+            })
+            .transpose()?;
+        let ptr = unsafe {
+            crate::ggml_backend_dev_init(
+                self.as_ptr(),
+                params.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+            )
+        };
+        unsafe { Backend::from_raw(ptr) }.ok_or(Error::NullPointer("ggml_backend_dev_init"))
+    }
+}
+
+impl Device {
+    /// Wrap `size` bytes of host memory at `ptr` in a buffer of this device
+    /// without copying, for devices that support it (CPU, Metal). Tensors
+    /// no larger than `max_tensor_size` can be placed in it.
+    ///
+    /// # Safety
+    /// The memory must stay valid and in place until the buffer, and every
+    /// tensor placed in it, is gone. Some devices map whole pages, so the
+    /// pages around the range must be mapped too, as with `mmap`ed files.
+    pub unsafe fn buffer_from_host_ptr(
+        &self,
+        ptr: *mut c_void,
+        size: usize,
+        max_tensor_size: usize,
+    ) -> Result<BackendBuffer> {
+        // ggml calls the device's function pointer without checking it
+        if !self.props().caps.buffer_from_host_ptr {
+            return Err(Error::InvalidArgument(format!(
+                "{} cannot use host memory as a buffer",
+                self.name()
+            )));
+        }
+        let buffer =
+            crate::ggml_backend_dev_buffer_from_host_ptr(self.as_ptr(), ptr, size, max_tensor_size);
+        BackendBuffer::from_raw(buffer)
+            .ok_or(Error::NullPointer("ggml_backend_dev_buffer_from_host_ptr"))
+    }
+}
+
+impl fmt::Debug for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Device")
+            .field("name", &self.name())
+            .field("description", &self.description())
+            .field("type", &self.device_type())
+            .finish()
+    }
+}
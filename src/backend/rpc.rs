@@ -0,0 +1,220 @@
+//! The RPC backend: computing on a device of another machine running an
+//! RPC server (see the `ggml-rpc-server` binary).
+
+use std::ffi::CString;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use super::instance::Backend;
+use super::registry::{BackendRegistry, Device, DeviceType};
+use crate::error::{Error, Result};
+
+fn endpoint_cstr(endpoint: &str) -> Result<CString> {
+    CString::new(endpoint)
+        .map_err(|_| Error::InvalidArgument("endpoint contains a NUL byte".to_string()))
+}
+
+/// A backend on device `device` of the RPC server at `host:port`. Derefs
+/// to [`Backend`], so graphs, schedulers and buffers use it like a local
+/// device; tensor data travels over the connection.
+///
+/// ```ignore
+/// let remote = RpcBackend::connect("192.168.1.10:50052", 0)?;
+/// let cpu = CpuBackend::new(4)?;
+/// let mut sched = Scheduler::new(&[&*remote, &*cpu], GGML_DEFAULT_GRAPH_SIZE, false, true)?;
+/// ```
+///
+/// The protocol is unauthenticated and unencrypted: only connect to
+/// servers on a trusted network.
+pub struct RpcBackend {
+    backend: Backend,
+    endpoint: String,
+    device: u32,
+}
+
+impl RpcBackend {
+    /// Connect to the server at `endpoint` (`host:port`) and return a
+    /// registry, named `RPC[host:port]`, of its devices. Adding the same
+    /// endpoint again returns the same registry.
+    pub fn add_server(endpoint: &str) -> Result<BackendRegistry> {
+        let c_endpoint = endpoint_cstr(endpoint)?;
+        unsafe {
+            BackendRegistry::from_raw(crate::ggml_backend_rpc_add_server(c_endpoint.as_ptr()))
+        }
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!("no RPC server with devices at {}", endpoint))
+        })
+    }
+
+    /// Compute on device `device` of the server at `endpoint`.
+    pub fn connect(endpoint: &str, device: u32) -> Result<Self> {
+        // ggml_backend_rpc_init does not check that the server is reachable
+        let reg = Self::add_server(endpoint)?;
+        if device as usize >= reg.device_count() {
+            return Err(Error::InvalidArgument(format!(
+                "RPC device {} out of range ({} devices at {})",
+                device,
+                reg.device_count(),
+                endpoint
+            )));
+        }
+        let c_endpoint = endpoint_cstr(endpoint)?;
+        let backend =
+            unsafe { Backend::from_raw(crate::ggml_backend_rpc_init(c_endpoint.as_ptr(), device)) }
+                .ok_or(Error::NullPointer("ggml_backend_rpc_init"))?;
+        Ok(RpcBackend {
+            backend,
+            endpoint: endpoint.to_string(),
+            device,
+        })
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Index of the device on the server.
+    pub fn device_index(&self) -> u32 {
+        self.device
+    }
+
+    /// Free and total memory of the remote device in bytes, as the server
+    /// reports it.
+    pub fn memory(&self) -> (usize, usize) {
+        let (mut free, mut total) = (0, 0);
+        if let Ok(endpoint) = endpoint_cstr(&self.endpoint) {
+            unsafe {
+                crate::ggml_backend_rpc_get_device_memory(
+                    endpoint.as_ptr(),
+                    self.device,
+                    &mut free,
+                    &mut total,
+                )
+            };
+        }
+        (free, total)
+    }
+}
+
+impl Deref for RpcBackend {
+    type Target = Backend;
+
+    fn deref(&self) -> &Backend {
+        &self.backend
+    }
+}
+
+impl std::fmt::Debug for RpcBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcBackend")
+            .field("endpoint", &self.endpoint)
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+/// An RPC server sharing local devices with [`RpcBackend`] clients.
+///
+/// ```ignore
+/// RpcServer::new("0.0.0.0:50052")
+///     .devices(&[Device::by_name("CUDA0").unwrap()])
+///     .cache_dir("/var/cache/ggml-rpc")
+///     .run()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RpcServer {
+    endpoint: String,
+    devices: Vec<Device>,
+    n_threads: usize,
+    cache_dir: Option<PathBuf>,
+}
+
+impl RpcServer {
+    /// A server listening on `endpoint` (`host:port`), sharing every GPU,
+    /// or the CPU if there is none, with one thread per available core.
+    pub fn new(endpoint: &str) -> Self {
+        let gpus: Vec<Device> = Device::all()
+            .filter(|d| matches!(d.device_type(), Some(DeviceType::Gpu | DeviceType::Igpu)))
+            .collect();
+        let devices = if gpus.is_empty() {
+            Device::by_type(DeviceType::Cpu).into_iter().collect()
+        } else {
+            gpus
+        };
+        RpcServer {
+            endpoint: endpoint.to_string(),
+            devices,
+            n_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            cache_dir: None,
+        }
+    }
+
+    /// Share `devices` instead, in this order.
+    pub fn devices(mut self, devices: &[Device]) -> Self {
+        self.devices = devices.to_vec();
+        self
+    }
+
+    /// Threads of backends that take a thread count, such as the CPU.
+    pub fn n_threads(mut self, n_threads: usize) -> Self {
+        self.n_threads = n_threads.max(1);
+        self
+    }
+
+    /// Cache tensors that clients send by hash in `dir`, so a client
+    /// loading the same model again sends only the hashes.
+    pub fn cache_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Serve clients one at a time until an error stops the server; this
+    /// only returns on failure.
+    pub fn run(&self) -> Result<()> {
+        if self.devices.is_empty() {
+            return Err(Error::InvalidArgument(
+                "the RPC server needs at least one device".to_string(),
+            ));
+        }
+        let valid_port = self
+            .endpoint
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+        if !valid_port {
+            return Err(Error::InvalidArgument(format!(
+                "endpoint {:?} is not host:port",
+                self.endpoint
+            )));
+        }
+        let endpoint = endpoint_cstr(&self.endpoint)?;
+        let cache_dir = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| {
+                dir.to_str()
+                    .and_then(|d| CString::new(d).ok())
+                    .ok_or_else(|| {
+                        Error::InvalidArgument(format!(
+                            "cache directory {} is not valid UTF-8",
+                            dir.display()
+                        ))
+                    })
+            })
+            .transpose()?;
+        let mut devices: Vec<crate::ggml_backend_dev_t> =
+            self.devices.iter().map(|d| d.as_ptr()).collect();
+        unsafe {
+            crate::ggml_backend_rpc_start_server(
+                endpoint.as_ptr(),
+                cache_dir.as_ref().map_or(std::ptr::null(), |d| d.as_ptr()),
+                self.n_threads,
+                devices.len(),
+                devices.as_mut_ptr(),
+            )
+        };
+        Err(Error::InvalidArgument(format!(
+            "the RPC server on {} stopped; see its log for the reason",
+            self.endpoint
+        )))
+    }
+}
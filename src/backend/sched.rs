@@ -0,0 +1,308 @@
+//! The backend scheduler (`ggml_backend_sched`): running one graph across
+//! several backends.
+
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+use super::gallocr::Placements;
+use super::instance::Backend;
+use super::registry::DeviceType;
+use crate::error::{check_status, Error, Result};
+use crate::graph::Graph;
+use crate::tensor::Tensor;
+
+/// `GGML_SCHED_MAX_BACKENDS` in ggml-backend.cpp.
+const MAX_BACKENDS: usize = 16;
+
+struct EvalCallback {
+    select: Box<dyn FnMut(Tensor<'_>) -> bool + Send>,
+    inspect: Box<dyn FnMut(Tensor<'_>) -> bool + Send>,
+}
+
+unsafe extern "C" fn eval_trampoline(
+    t: *mut crate::ggml_tensor,
+    ask: bool,
+    data: *mut c_void,
+) -> bool {
+    let cb = &mut *(data as *mut EvalCallback);
+    let Some(t) = Tensor::from_raw(t) else {
+        return !ask;
+    };
+    if ask {
+        (cb.select)(t)
+    } else {
+        (cb.inspect)(t)
+    }
+}
+
+/// Splits graphs between backends, copies tensors between them where a
+/// split needs it, and allocates the intermediate tensors in per-backend
+/// compute buffers.
+///
+/// Backends earlier in the list are preferred: each node runs on the first
+/// backend that supports it and holds (or can cheaply reach) its weights.
+/// The last backend must be the CPU, which runs everything else.
+///
+/// ```ignore
+/// let gpu = CudaBackend::new(0)?;
+/// let cpu = CpuBackend::new(8)?;
+/// let mut sched = Scheduler::new(&[&*gpu, &*cpu], GGML_DEFAULT_GRAPH_SIZE, false, true)?;
+/// sched.reserve(&build_graph(&ctx, max_batch)?)?;
+/// for batch in batches {
+///     let mut graph = build_graph(&ctx, batch.len())?;
+///     sched.compute(&mut graph)?;
+///     sched.reset();
+/// }
+/// ```
+///
+/// Tensors the scheduler allocates live in its buffers: read the outputs
+/// before the next [`reset`](Self::reset) or dropping the scheduler. Both
+/// detach the tensors, which then fail with [`Error::NoData`].
+pub struct Scheduler<'b> {
+    ptr: NonNull<crate::ggml_backend_sched>,
+    backends: Vec<&'b Backend>,
+    eval: Option<Box<EvalCallback>>,
+    placed: Placements,
+    // whether a graph is allocated, as ggml tracks it: compute allocates
+    // the graph only when none is
+    allocated: bool,
+}
+
+impl<'b> Scheduler<'b> {
+    /// Schedule graphs of up to `graph_size` nodes over `backends`, in
+    /// order of preference. With `parallel`, copies between backends are
+    /// pipelined over several copies of the inputs; with `op_offload`,
+    /// ops with weights on the CPU may still run on a GPU for large batches.
+    pub fn new(
+        backends: &[&'b Backend],
+        graph_size: usize,
+        parallel: bool,
+        op_offload: bool,
+    ) -> Result<Self> {
+        let Some(last) = backends.last() else {
+            return Err(Error::InvalidArgument(
+                "the scheduler needs at least one backend".to_string(),
+            ));
+        };
+        if backends.len() > MAX_BACKENDS {
+            return Err(Error::InvalidArgument(format!(
+                "{} backends, the scheduler takes at most {}",
+                backends.len(),
+                MAX_BACKENDS
+            )));
+        }
+        // ggml asserts this
+        if last.device().and_then(|d| d.device_type()) != Some(DeviceType::Cpu) {
+            return Err(Error::InvalidArgument(format!(
+                "the last backend must be the CPU, not {}",
+                last.name()
+            )));
+        }
+        let mut raw: Vec<crate::ggml_backend_t> = backends.iter().map(|b| b.as_ptr()).collect();
+        let ptr = unsafe {
+            crate::ggml_backend_sched_new(
+                raw.as_mut_ptr(),
+                std::ptr::null_mut(),
+                raw.len() as i32,
+                graph_size,
+                parallel,
+                op_offload,
+            )
+        };
+        NonNull::new(ptr)
+            .map(|ptr| Scheduler {
+                ptr,
+                backends: backends.to_vec(),
+                eval: None,
+                placed: Placements::default(),
+                allocated: false,
+            })
+            .ok_or(Error::NullPointer("ggml_backend_sched_new"))
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_backend_sched_t {
+        self.ptr.as_ptr()
+    }
+
+    /// The backends in order of preference.
+    pub fn backends(&self) -> &[&'b Backend] {
+        &self.backends
+    }
+
+    fn find(&self, raw: crate::ggml_backend_t) -> Option<&'b Backend> {
+        self.backends.iter().copied().find(|b| b.as_ptr() == raw)
+    }
+
+    /// Size the compute buffers for `measure_graph`, usually the graph for
+    /// the largest batch, so later graphs no larger than it compute without
+    /// reallocating.
+    pub fn reserve(&mut self, measure_graph: &Graph<'_>) -> Result<()> {
+        // may reallocate the buffers, and resets the scheduler
+        self.placed.detach();
+        self.allocated = false;
+        if unsafe { crate::ggml_backend_sched_reserve(self.as_ptr(), measure_graph.as_ptr()) } {
+            Ok(())
+        } else {
+            Err(Error::AllocFailed)
+        }
+    }
+
+    /// Size in bytes of the compute buffer of each backend.
+    pub fn buffer_sizes(&self) -> Vec<(&'b Backend, usize)> {
+        self.backends
+            .iter()
+            .map(|&b| (b, self.buffer_size(b)))
+            .collect()
+    }
+
+    pub fn buffer_size(&self, backend: &Backend) -> usize {
+        unsafe { crate::ggml_backend_sched_get_buffer_size(self.as_ptr(), backend.as_ptr()) }
+    }
+
+    /// Run `node` on `backend` instead of letting the scheduler choose.
+    pub fn set_tensor_backend(&mut self, node: Tensor<'_>, backend: &'b Backend) -> Result<()> {
+        if self.find(backend.as_ptr()).is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "{} is not one of the scheduler's backends",
+                backend.name()
+            )));
+        }
+        unsafe {
+            crate::ggml_backend_sched_set_tensor_backend(
+                self.as_ptr(),
+                node.as_ptr(),
+                backend.as_ptr(),
+            )
+        };
+        Ok(())
+    }
+
+    /// The backend `node` was assigned to, once the graph has been split.
+    pub fn tensor_backend(&self, node: Tensor<'_>) -> Option<&'b Backend> {
+        self.find(unsafe {
+            crate::ggml_backend_sched_get_tensor_backend(self.as_ptr(), node.as_ptr())
+        })
+    }
+
+    /// Assign the nodes of `graph` to backends without allocating it.
+    pub fn split_graph(&mut self, graph: &mut Graph<'_>) {
+        unsafe { crate::ggml_backend_sched_split_graph(self.as_ptr(), graph.as_ptr()) }
+    }
+
+    /// Number of parts the last graph was split into.
+    pub fn n_splits(&self) -> usize {
+        unsafe { crate::ggml_backend_sched_get_n_splits(self.as_ptr()) as usize }
+    }
+
+    /// Number of copies of the graph inputs kept for pipelining.
+    pub fn n_copies(&self) -> usize {
+        unsafe { crate::ggml_backend_sched_get_n_copies(self.as_ptr()) as usize }
+    }
+
+    /// Split and allocate `graph` without computing it, e.g. to set its
+    /// inputs first. The previous allocation, if any, is reset.
+    pub fn alloc_graph(&mut self, graph: &mut Graph<'_>) -> Result<()> {
+        // ggml asserts that the last allocation was reset
+        if self.allocated {
+            self.reset();
+        }
+        self.placed.detach();
+        let unplaced = Placements::unplaced(graph);
+        let ok = unsafe { crate::ggml_backend_sched_alloc_graph(self.as_ptr(), graph.as_ptr()) };
+        self.placed.record(unplaced);
+        self.allocated = ok;
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::AllocFailed)
+        }
+    }
+
+    /// Compute `graph`, splitting and allocating it first if needed, and
+    /// wait for it.
+    pub fn compute(&mut self, graph: &mut Graph<'_>) -> Result<()> {
+        if self.allocated {
+            return check_status(unsafe {
+                crate::ggml_backend_sched_graph_compute(self.as_ptr(), graph.as_ptr())
+            });
+        }
+        self.placed.detach();
+        let unplaced = Placements::unplaced(graph);
+        let status =
+            unsafe { crate::ggml_backend_sched_graph_compute(self.as_ptr(), graph.as_ptr()) };
+        self.placed.record(unplaced);
+        self.allocated = status != crate::ggml_status_GGML_STATUS_ALLOC_FAILED;
+        check_status(status)
+    }
+
+    /// Watch nodes as they are computed, e.g. to dump activations or find
+    /// the first NaN. Before computing, `select` is asked about each node;
+    /// for those it accepts, the scheduler stops after the node, waits for
+    /// it and passes it to `inspect`, where its data can be read with
+    /// [`Tensor::read_bytes`]. Returning `false` from `inspect` skips the
+    /// rest of the node's split, leaving later results undefined.
+    ///
+    /// ```ignore
+    /// sched.set_eval_callback(
+    ///     |t| t.name().starts_with("ffn_out"),
+    ///     |t| {
+    ///         let mut first = [0u8; 4];
+    ///         t.read_bytes(0, &mut first).unwrap();
+    ///         println!("{}: {}", t.name(), f32::from_ne_bytes(first));
+    ///         true
+    ///     },
+    /// );
+    /// ```
+    ///
+    /// Every selected node is a synchronization point, so select few.
+    pub fn set_eval_callback(
+        &mut self,
+        select: impl FnMut(Tensor<'_>) -> bool + Send + 'static,
+        inspect: impl FnMut(Tensor<'_>) -> bool + Send + 'static,
+    ) {
+        let mut eval = Box::new(EvalCallback {
+            select: Box::new(select),
+            inspect: Box::new(inspect),
+        });
+        unsafe {
+            crate::ggml_backend_sched_set_eval_callback(
+                self.as_ptr(),
+                Some(eval_trampoline),
+                &mut *eval as *mut EvalCallback as *mut c_void,
+            )
+        };
+        self.eval = Some(eval);
+    }
+
+    pub fn clear_eval_callback(&mut self) {
+        unsafe {
+            crate::ggml_backend_sched_set_eval_callback(self.as_ptr(), None, std::ptr::null_mut())
+        };
+        self.eval = None;
+    }
+
+    /// Free the allocation of the last graph, before scheduling another.
+    /// Its tensors are detached.
+    pub fn reset(&mut self) {
+        self.placed.detach();
+        self.allocated = false;
+        unsafe { crate::ggml_backend_sched_reset(self.as_ptr()) }
+    }
+}
+
+impl Drop for Scheduler<'_> {
+    fn drop(&mut self) {
+        self.placed.detach();
+        unsafe { crate::ggml_backend_sched_free(self.as_ptr()) }
+    }
+}
+
+impl std::fmt::Debug for Scheduler<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("backends", &self.backends)
+            .field("n_splits", &self.n_splits())
+            .field("eval_callback", &self.eval.is_some())
+            .finish()
+    }
+}
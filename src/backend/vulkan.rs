@@ -0,0 +1,170 @@
+//! The Vulkan backend: device listing and selection.
+//!
+//! # Sharing memory with other Vulkan users
+//!
+//! ggml's Vulkan backend allocates its device memory privately, without
+//! `VkExportMemoryAllocateInfo`, and its C API hands out neither
+//! `VkDeviceMemory`/`VkBuffer` handles nor a way to wrap memory allocated
+//! elsewhere (as CUDA's `buffer_from_device_ptr` does). Exporting a buffer
+//! to, or importing one from, another Vulkan instance such as a wgpu
+//! renderer through external memory handles therefore needs changes to
+//! ggml itself and is not offered here.
+//!
+//! The closest supported path is [`VulkanBackend::host_buffer_type`]:
+//! pinned host memory the GPU reads and writes directly, so results land
+//! in host-visible memory without a separate readback, ready for the
+//! renderer's own upload:
+//!
+//! ```ignore
+//! let pinned = VulkanBackend::host_buffer_type().expect("no pinned memory");
+//! out_ctx.alloc_with(pinned)?; // graph outputs, written by the GPU
+//! vk.compute(&mut graph)?;
+//! let pixels = unsafe { std::slice::from_raw_parts(out.data() as *const u8, out.nbytes()) };
+//! queue.write_texture(texture, pixels, layout, extent); // wgpu
+//! ```
+
+use std::ffi::{c_char, c_int, CStr};
+use std::ops::Deref;
+
+use super::buffer::BufferType;
+use super::instance::Backend;
+use super::registry::{BackendRegistry, DeviceType};
+use crate::error::{Error, Result};
+
+fn check_device(device: usize) -> Result<()> {
+    let count = VulkanBackend::device_count();
+    if device >= count {
+        return Err(Error::InvalidArgument(format!(
+            "Vulkan device {} out of range ({} devices)",
+            device, count
+        )));
+    }
+    Ok(())
+}
+
+fn device_memory(device: usize) -> (usize, usize) {
+    let (mut free, mut total) = (0, 0);
+    unsafe { crate::ggml_backend_vk_get_device_memory(device as c_int, &mut free, &mut total) };
+    (free, total)
+}
+
+/// A Vulkan device as reported by [`VulkanBackend::devices`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VulkanDevice {
+    pub index: usize,
+    /// The adapter name, e.g. `"AMD Radeon RX 7900 XTX (RADV NAVI31)"`.
+    pub description: String,
+    /// Free and total memory in bytes at the time of the query.
+    pub memory_free: usize,
+    pub memory_total: usize,
+    /// [`DeviceType::Gpu`] for discrete GPUs, [`DeviceType::Igpu`] for
+    /// integrated ones.
+    pub device_type: Option<DeviceType>,
+}
+
+/// The Vulkan backend on one device. Derefs to [`Backend`].
+///
+/// Devices are numbered as ggml sees them, which can be narrowed with the
+/// `GGML_VK_VISIBLE_DEVICES` environment variable:
+///
+/// ```ignore
+/// let dev = VulkanBackend::devices()?
+///     .into_iter()
+///     .max_by_key(|d| (d.device_type == Some(DeviceType::Gpu), d.memory_total))
+///     .expect("no Vulkan device");
+/// let vk = VulkanBackend::new(dev.index)?;
+/// ```
+pub struct VulkanBackend {
+    backend: Backend,
+    device: usize,
+}
+
+impl VulkanBackend {
+    /// Initialize the backend on device `device`, counting from 0.
+    pub fn new(device: usize) -> Result<Self> {
+        check_device(device)?;
+        let backend = unsafe { Backend::from_raw(crate::ggml_backend_vk_init(device)) }
+            .ok_or(Error::NullPointer("ggml_backend_vk_init"))?;
+        Ok(VulkanBackend { backend, device })
+    }
+
+    /// Number of Vulkan devices ggml can use.
+    pub fn device_count() -> usize {
+        unsafe { crate::ggml_backend_vk_get_device_count() }.max(0) as usize
+    }
+
+    /// Describe every usable Vulkan device.
+    pub fn devices() -> Result<Vec<VulkanDevice>> {
+        (0..Self::device_count()).map(Self::device_info).collect()
+    }
+
+    /// Describe device `device`.
+    pub fn device_info(device: usize) -> Result<VulkanDevice> {
+        check_device(device)?;
+        let mut buf = [0 as c_char; 256];
+        unsafe {
+            crate::ggml_backend_vk_get_device_description(
+                device as c_int,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        let description = unsafe { CStr::from_ptr(buf.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        let (memory_free, memory_total) = device_memory(device);
+        // the registry lists the same devices in the same order
+        let device_type = unsafe { BackendRegistry::from_raw(crate::ggml_backend_vk_reg()) }
+            .and_then(|reg| reg.device(device))
+            .and_then(|dev| dev.device_type());
+        Ok(VulkanDevice {
+            index: device,
+            description,
+            memory_free,
+            memory_total,
+            device_type,
+        })
+    }
+
+    pub fn device_index(&self) -> usize {
+        self.device
+    }
+
+    /// Free and total memory of the backend's device in bytes.
+    pub fn memory(&self) -> (usize, usize) {
+        device_memory(self.device)
+    }
+
+    /// Device memory of this backend's device.
+    pub fn buffer_type(&self) -> BufferType {
+        unsafe { BufferType::from_raw(crate::ggml_backend_vk_buffer_type(self.device)) }
+            .expect("ggml_backend_vk_buffer_type returned a null pointer")
+    }
+
+    /// Pinned host memory the first device accesses directly, or `None`
+    /// when Vulkan is unavailable. Tensors in it are host tensors: readable
+    /// in place once a graph has written them, without a device-to-host
+    /// copy.
+    pub fn host_buffer_type() -> Option<BufferType> {
+        if Self::device_count() == 0 {
+            return None;
+        }
+        unsafe { BufferType::from_raw(crate::ggml_backend_vk_host_buffer_type()) }
+    }
+}
+
+impl Deref for VulkanBackend {
+    type Target = Backend;
+
+    fn deref(&self) -> &Backend {
+        &self.backend
+    }
+}
+
+impl std::fmt::Debug for VulkanBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VulkanBackend")
+            .field("device", &self.device)
+            .finish()
+    }
+}
@@ -0,0 +1,96 @@
+//! Share local devices with other machines over ggml's RPC protocol.
+//! Run with: cargo run --features rpc --bin ggml-rpc-server -- [--host ADDR] [--port N] [--device NAME]... [--threads N] [--cache DIR]
+//!
+//! Clients reach the devices with `RpcBackend::connect("host:port", i)`,
+//! where `i` counts the `--device`s in order. Without `--device`, every
+//! GPU is shared, or the CPU if there is none; `--list` prints the names.
+//! With `--cache`, tensors clients send are kept by hash in DIR, so
+//! reloading a model only sends the hashes.
+//!
+//! The protocol is unauthenticated: anyone who can connect can read and
+//! overwrite the devices' memory. The server binds to 127.0.0.1 unless
+//! `--host` says otherwise.
+
+use std::env;
+use std::process::ExitCode;
+
+use ggml_rs::backend::{Device, RpcServer};
+
+fn usage() -> ExitCode {
+    eprintln!(
+        "usage: ggml-rpc-server [--list] [--host ADDR] [--port N] [--device NAME]... \
+         [--threads N] [--cache DIR]"
+    );
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (mut host, mut port) = ("127.0.0.1", "50052");
+    let (mut threads, mut cache) = (None, None);
+    let mut device_names = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--list" {
+            for dev in Device::all() {
+                println!("{:<12} {}", dev.name(), dev.description());
+            }
+            return ExitCode::SUCCESS;
+        }
+        let Some(value) = iter.next() else {
+            return usage();
+        };
+        match arg.as_str() {
+            "--host" => host = value.as_str(),
+            "--port" => port = value.as_str(),
+            "--device" => device_names.push(value.as_str()),
+            "--threads" => match value.parse::<usize>() {
+                Ok(n) if n > 0 => threads = Some(n),
+                _ => return usage(),
+            },
+            "--cache" => cache = Some(value.as_str()),
+            _ => return usage(),
+        }
+    }
+
+    let endpoint = format!("{}:{}", host, port);
+    let mut server = RpcServer::new(&endpoint);
+    if !device_names.is_empty() {
+        let mut devices = Vec::new();
+        for name in device_names {
+            match Device::by_name(name) {
+                Some(dev) => devices.push(dev),
+                None => {
+                    eprintln!("ggml-rpc-server: no device {:?}, see --list", name);
+                    return ExitCode::from(2);
+                }
+            }
+        }
+        server = server.devices(&devices);
+    }
+    if let Some(n) = threads {
+        server = server.n_threads(n);
+    }
+    if let Some(dir) = cache {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("ggml-rpc-server: cannot create {}: {}", dir, e);
+            return ExitCode::from(2);
+        }
+        server = server.cache_dir(dir);
+    }
+
+    if !matches!(host, "127.0.0.1" | "localhost" | "::1") {
+        eprintln!(
+            "ggml-rpc-server: warning: listening on {}; the RPC protocol is unauthenticated, \
+             only expose it on a trusted network",
+            endpoint
+        );
+    }
+    match server.run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ggml-rpc-server: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Compare two GGUF files: metadata, and tensor data after dequantization.
+//! Run with: cargo run --bin gguf-diff -- a.gguf b.gguf [--max-rmse X]
+//!
+//! Exits with 0 if the files match (tensors within `--max-rmse`, default
+//! exact), 1 if they differ and 2 on errors.
+
+use std::env;
+use std::process::ExitCode;
+
+use ggml_rs::gguf::{self, GgufModel, TensorDiff};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: gguf-diff <a.gguf> <b.gguf> [--max-rmse X]");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut paths = Vec::new();
+    let mut max_rmse = 0.0;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--max-rmse" => match iter.next().and_then(|v| v.parse().ok()) {
+                Some(v) => max_rmse = v,
+                None => return usage(),
+            },
+            _ => paths.push(arg),
+        }
+    }
+    let [a, b] = paths[..] else {
+        return usage();
+    };
+
+    let result =
+        GgufModel::open(a).and_then(|ma| GgufModel::open(b).and_then(|mb| gguf::diff(&ma, &mb)));
+    let diff = match result {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("gguf-diff: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    print!("{}", diff);
+
+    let within = diff.metadata.is_empty()
+        && diff.tensors.iter().all(|t| match t {
+            TensorDiff::Compared { stats, .. } => stats.rmse <= max_rmse,
+            _ => false,
+        });
+    let compared = diff
+        .tensors
+        .iter()
+        .filter(|t| matches!(t, TensorDiff::Compared { .. }))
+        .count();
+    println!(
+        "{} metadata differences, {} of {} tensors compared",
+        diff.metadata.len(),
+        compared,
+        diff.tensors.len()
+    );
+    if within {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
@@ -0,0 +1,106 @@
+//! Print the header, metadata and tensor infos of a GGUF file.
+//! Run with: cargo run --bin gguf-dump -- model.gguf [--hash sha256|xxh64]
+//!
+//! `--hash` (with the `hash` feature) prints per-tensor digests and the
+//! model fingerprint instead of the tensor table.
+
+use std::env;
+use std::process::ExitCode;
+
+use ggml_rs::GgufFile;
+
+fn usage() -> ExitCode {
+    eprintln!("usage: gguf-dump <model.gguf> [--hash sha256|xxh64]");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut path = None;
+    let mut hash = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--hash" => match iter.next() {
+                Some(name) => hash = Some(name.as_str()),
+                None => return usage(),
+            },
+            _ if path.is_none() => path = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let Some(path) = path else {
+        return usage();
+    };
+
+    let file = match GgufFile::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("gguf-dump: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    if let Some(name) = hash {
+        return print_hashes(&file, name);
+    }
+
+    println!(
+        "GGUF v{}, alignment {}, {} kv, {} tensors, data at {}",
+        file.version(),
+        file.alignment(),
+        file.n_kv(),
+        file.n_tensors(),
+        file.data_offset()
+    );
+    for (key, value) in file.metadata() {
+        let mut text = value.to_string();
+        if text.len() > 120 {
+            let mut cut = 117;
+            while !text.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            text.truncate(cut);
+            text.push_str("...");
+        }
+        println!("{:<40} {:<4} {}", key, value.value_type(), text);
+    }
+    for info in file.tensor_infos() {
+        let ty = info.ty.map_or("?", |t| t.name());
+        let shape = &info.ne[..info.n_dims.max(1)];
+        println!(
+            "{:<48} {:<8} {:<28} {:>12} @ {}",
+            info.name,
+            ty,
+            format!("{:?}", shape),
+            info.size,
+            info.offset
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(feature = "hash")]
+fn print_hashes(file: &GgufFile, name: &str) -> ExitCode {
+    use ggml_rs::gguf::HashAlgorithm;
+
+    let Some(algorithm) = HashAlgorithm::from_name(name) else {
+        return usage();
+    };
+    match file.fingerprint(algorithm) {
+        Ok(fingerprint) => {
+            print!("{}", fingerprint);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("gguf-dump: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(not(feature = "hash"))]
+fn print_hashes(_file: &GgufFile, _name: &str) -> ExitCode {
+    eprintln!("gguf-dump: built without the `hash` feature");
+    ExitCode::from(2)
+}
@@ -0,0 +1,119 @@
+//! Requantize the tensors of a GGUF file to another type.
+//! Run with: cargo run --bin gguf-quantize -- in.gguf out.gguf q4_K [--include PAT] [--exclude PAT] [--set PAT=TYPE]
+//!
+//! Tensors whose name matches an `--include` pattern (all by default) and
+//! no `--exclude` pattern are converted; `--set` picks another type for
+//! tensors matching its pattern, and `--llama` applies llama.cpp's usual
+//! exceptions (see `QuantPlan::llama`). Patterns may use `*` as a wildcard.
+//! 1-d tensors and tensors whose rows do not split into blocks of the
+//! target type are copied as they are. Tensor data is streamed one tensor
+//! at a time.
+
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process::ExitCode;
+
+use ggml_rs::gguf::GgufFile;
+use ggml_rs::quant::{self, QuantPlan};
+use ggml_rs::Type;
+
+fn usage() -> ExitCode {
+    eprintln!(
+        "usage: gguf-quantize <in.gguf> <out.gguf> <type> [--llama] [--include PAT]... \
+         [--exclude PAT]... [--set PAT=TYPE]..."
+    );
+    ExitCode::from(2)
+}
+
+fn parse_type(name: &str) -> Option<Type> {
+    Type::from_name(name).filter(|t| t.can_quantize() && !t.requires_imatrix())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut positional = Vec::new();
+    let (mut include, mut exclude, mut set) = (Vec::new(), Vec::new(), Vec::new());
+    let mut llama = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let list = match arg.as_str() {
+            "--llama" => {
+                llama = true;
+                continue;
+            }
+            "--include" => &mut include,
+            "--exclude" => &mut exclude,
+            "--set" => &mut set,
+            _ => {
+                positional.push(arg.as_str());
+                continue;
+            }
+        };
+        match iter.next() {
+            Some(value) => list.push(value.as_str()),
+            None => return usage(),
+        }
+    }
+    let [input, output, type_name] = positional[..] else {
+        return usage();
+    };
+    let Some(target) = parse_type(type_name) else {
+        eprintln!("gguf-quantize: cannot quantize to {:?}", type_name);
+        return ExitCode::from(2);
+    };
+
+    // later rules win: exclusions override explicit types and inclusions
+    let mut plan = match (llama, include.is_empty()) {
+        (true, true) => QuantPlan::llama(target),
+        (true, false) => {
+            eprintln!("gguf-quantize: --llama converts all tensors, drop --include");
+            return ExitCode::from(2);
+        }
+        (false, true) => QuantPlan::new(target),
+        (false, false) => QuantPlan::keep_all(),
+    };
+    for pattern in include {
+        plan.set(pattern, target);
+    }
+    for rule in set {
+        let Some((pattern, ty)) = rule
+            .split_once('=')
+            .and_then(|(p, t)| Some((p, parse_type(t)?)))
+        else {
+            eprintln!("gguf-quantize: bad --set {:?}", rule);
+            return ExitCode::from(2);
+        };
+        plan.set(pattern, ty);
+    }
+    for pattern in exclude {
+        plan.keep(pattern);
+    }
+
+    let result = GgufFile::open(input).and_then(|file| {
+        let out = BufWriter::new(File::create(output)?);
+        quant::requantize(&file, &plan, out)
+    });
+    let tensors = match result {
+        Ok(tensors) => tensors,
+        Err(e) => {
+            eprintln!("gguf-quantize: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let (mut size_in, mut size_out) = (0, 0);
+    for t in &tensors {
+        println!(
+            "{:<48} {:<8} -> {:<8} {:>12} -> {:>12}",
+            t.name, t.from, t.to, t.size_in, t.size_out
+        );
+        size_in += t.size_in;
+        size_out += t.size_out;
+    }
+    println!(
+        "{:.2} MiB -> {:.2} MiB",
+        size_in as f64 / (1 << 20) as f64,
+        size_out as f64 / (1 << 20) as f64
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,141 @@
+//! Time a small decode-like loop with the graph rebuilt every iteration
+//! against the same graph kept in a `GraphPlan`.
+//! Run with: cargo run --release --bin graph-reuse-bench -- [--layers N] [--dim N] [--iters N] [--threads N]
+//!
+//! Each iteration feeds one F32 vector through `--layers` matrix-vector
+//! products on the CPU backend. Small dimensions make the per-iteration
+//! graph overhead stand out, as in single-token decoding.
+
+use std::env;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use ggml_rs::{
+    Context, ContextParams, CpuBackend, Graph, GraphAllocator, GraphPlan, Result, Rng, Tensor,
+    Type, UnaryOp,
+};
+
+// metadata only: the contexts are no_alloc
+const META_SIZE: usize = 16 << 20;
+
+fn usage() -> ExitCode {
+    eprintln!("usage: graph-reuse-bench [--layers N] [--dim N] [--iters N] [--threads N]");
+    ExitCode::from(2)
+}
+
+fn as_bytes(v: &[f32]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * 4) }
+}
+
+fn as_bytes_mut(v: &mut [f32]) -> &mut [u8] {
+    unsafe { std::slice::from_raw_parts_mut(v.as_mut_ptr() as *mut u8, v.len() * 4) }
+}
+
+/// `x -> relu(W_l x)` for every layer, with `x` an input and the result an
+/// output.
+fn build<'a>(
+    ctx: &'a Context,
+    weights: &[Tensor<'a>],
+    dim: usize,
+) -> Result<(Tensor<'a>, Tensor<'a>, Graph<'a>)> {
+    let x = ctx.new_tensor_1d(Type::F32, dim as i64)?.set_input();
+    let mut h = x;
+    for &w in weights {
+        h = ctx.unary(ctx.mul_mat(w, h)?, UnaryOp::Relu)?;
+    }
+    let y = h.set_output();
+    let mut graph = ctx.new_graph()?;
+    graph.build_forward_expand(y);
+    Ok((x, y, graph))
+}
+
+fn run(layers: usize, dim: usize, iters: usize, threads: usize) -> Result<()> {
+    let backend = CpuBackend::new(threads)?;
+    let wctx = Context::with_params(ContextParams::new(META_SIZE).no_alloc(true))?;
+    let weights = (0..layers)
+        .map(|_| wctx.new_tensor_2d(Type::F32, dim as i64, dim as i64))
+        .collect::<Result<Vec<_>>>()?;
+    wctx.alloc_on(&backend)?;
+    let mut rng = Rng::new(42);
+    for w in &weights {
+        w.fill_normal(&mut rng, 0.0, (1.0 / dim as f32).sqrt())?;
+    }
+    let input: Vec<f32> = (0..dim).map(|_| rng.normal(0.0, 1.0)).collect();
+    let mut rebuilt_out = vec![0f32; dim];
+    let mut planned_out = vec![0f32; dim];
+
+    // rebuild: a fresh context and graph every iteration, one allocator
+    let mut galloc = GraphAllocator::new(&backend)?;
+    let start = Instant::now();
+    for _ in 0..iters {
+        let ctx = Context::with_params(ContextParams::new(META_SIZE).no_alloc(true))?;
+        let (x, y, mut graph) = build(&ctx, &weights, dim)?;
+        galloc.alloc_graph(&mut graph)?;
+        x.write_bytes(0, as_bytes(&input))?;
+        backend.compute(&mut graph)?;
+        y.read_bytes(0, as_bytes_mut(&mut rebuilt_out))?;
+    }
+    let rebuilt = start.elapsed();
+
+    // plan: built and allocated once, only the input is rewritten
+    let ctx = Context::with_params(ContextParams::new(META_SIZE).no_alloc(true))?;
+    let start = Instant::now();
+    let (x, y, graph) = build(&ctx, &weights, dim)?;
+    let mut plan = GraphPlan::new(&backend, graph)?;
+    for _ in 0..iters {
+        x.write_bytes(0, as_bytes(&input))?;
+        plan.compute()?;
+        y.read_bytes(0, as_bytes_mut(&mut planned_out))?;
+    }
+    let planned = start.elapsed();
+
+    let per_iter = |d: Duration| d.as_secs_f64() * 1e6 / iters as f64;
+    println!(
+        "{} layers of {}x{}, {} iterations, {} threads",
+        layers, dim, dim, iters, threads
+    );
+    println!(
+        "rebuild every iteration: {:>9.1} us/iter",
+        per_iter(rebuilt)
+    );
+    println!(
+        "reuse a GraphPlan:       {:>9.1} us/iter",
+        per_iter(planned)
+    );
+    println!(
+        "speedup:                 {:>9.2}x",
+        rebuilt.as_secs_f64() / planned.as_secs_f64()
+    );
+    if rebuilt_out != planned_out {
+        eprintln!("graph-reuse-bench: warning: the two loops computed different outputs");
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (mut layers, mut dim, mut iters) = (32, 256, 1000);
+    let mut threads = std::thread::available_parallelism().map_or(4, |n| n.get().min(8));
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let slot = match arg.as_str() {
+            "--layers" => &mut layers,
+            "--dim" => &mut dim,
+            "--iters" => &mut iters,
+            "--threads" => &mut threads,
+            _ => return usage(),
+        };
+        match iter.next().and_then(|v| v.parse::<usize>().ok()) {
+            Some(n) if n > 0 => *slot = n,
+            _ => return usage(),
+        }
+    }
+
+    match run(layers, dim, iters, threads) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("graph-reuse-bench: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
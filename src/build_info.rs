@@ -0,0 +1,145 @@
+//! Build provenance: the ggml version and commit, the CMake options ggml was
+//! configured with and the enabled cargo features, recorded by build.rs, plus
+//! the CPU features the running machine offers the CPU backend.
+//!
+//! The [`Display`](fmt::Display) output of [`build_info`] is meant to be
+//! pasted into bug reports as is.
+
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+
+mod recorded {
+    include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+}
+
+/// How this build of ggml-rs and the ggml libraries it uses were made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub ggml_rs_version: &'static str,
+    /// Reported by the linked ggml library.
+    pub ggml_version: &'static str,
+    /// Git commit of the checkout ggml was built from, `"unknown"` when it
+    /// was not built from one.
+    pub ggml_commit: &'static str,
+    /// Enabled cargo features of ggml-rs, sorted.
+    pub features: &'static [&'static str],
+    /// ggml's CMake options as `KEY=VALUE`, defaults included; empty when
+    /// the build was skipped (docs.rs) or failed.
+    pub cmake_flags: &'static [&'static str],
+    pub target: &'static str,
+    pub profile: &'static str,
+    /// Whether ggml came from a `ggml-rs-prebuilt-*` crate or release archive.
+    pub prebuilt: bool,
+    /// Whether ggml is an installed library (`GGML_SYS_LIB_DIR` or the
+    /// `system` feature) rather than built by ggml-rs.
+    pub system: bool,
+}
+
+fn static_str(ptr: *const c_char) -> &'static str {
+    if ptr.is_null() {
+        return "unknown";
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("unknown")
+}
+
+/// Build information for this binary.
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        ggml_rs_version: env!("CARGO_PKG_VERSION"),
+        ggml_version: static_str(unsafe { crate::ggml_version() }),
+        ggml_commit: static_str(unsafe { crate::ggml_commit() }),
+        features: recorded::FEATURES,
+        cmake_flags: recorded::CMAKE_FLAGS,
+        target: recorded::TARGET,
+        profile: recorded::PROFILE,
+        prebuilt: recorded::PREBUILT,
+        system: recorded::SYSTEM,
+    }
+}
+
+impl BuildInfo {
+    /// Value of a recorded CMake option, e.g. `cmake_flag("GGML_CUDA")`.
+    pub fn cmake_flag(&self, key: &str) -> Option<&'static str> {
+        self.cmake_flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix(key)?.strip_prefix('='))
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ggml-rs {} ({}, {})",
+            self.ggml_rs_version, self.target, self.profile
+        )?;
+        writeln!(
+            f,
+            "ggml {} (commit {}{})",
+            self.ggml_version,
+            self.ggml_commit,
+            if self.system {
+                ", system"
+            } else if self.prebuilt {
+                ", prebuilt"
+            } else {
+                ""
+            }
+        )?;
+        writeln!(f, "features: {}", self.features.join(" "))?;
+        writeln!(f, "cpu: {}", cpu_features().join(" "))?;
+        write!(f, "cmake: {}", self.cmake_flags.join(" "))
+    }
+}
+
+const CPU_FEATURES: &[(&str, unsafe extern "C" fn() -> c_int)] = &[
+    ("sse3", crate::ggml_cpu_has_sse3),
+    ("ssse3", crate::ggml_cpu_has_ssse3),
+    ("avx", crate::ggml_cpu_has_avx),
+    ("avx_vnni", crate::ggml_cpu_has_avx_vnni),
+    ("avx2", crate::ggml_cpu_has_avx2),
+    ("bmi2", crate::ggml_cpu_has_bmi2),
+    ("f16c", crate::ggml_cpu_has_f16c),
+    ("fma", crate::ggml_cpu_has_fma),
+    ("avx512", crate::ggml_cpu_has_avx512),
+    ("avx512_vbmi", crate::ggml_cpu_has_avx512_vbmi),
+    ("avx512_vnni", crate::ggml_cpu_has_avx512_vnni),
+    ("avx512_bf16", crate::ggml_cpu_has_avx512_bf16),
+    ("amx_int8", crate::ggml_cpu_has_amx_int8),
+    ("neon", crate::ggml_cpu_has_neon),
+    ("arm_fma", crate::ggml_cpu_has_arm_fma),
+    ("fp16_va", crate::ggml_cpu_has_fp16_va),
+    ("dotprod", crate::ggml_cpu_has_dotprod),
+    ("matmul_int8", crate::ggml_cpu_has_matmul_int8),
+    ("sve", crate::ggml_cpu_has_sve),
+    ("sme", crate::ggml_cpu_has_sme),
+    ("riscv_v", crate::ggml_cpu_has_riscv_v),
+    ("vsx", crate::ggml_cpu_has_vsx),
+    ("vxe", crate::ggml_cpu_has_vxe),
+    ("wasm_simd", crate::ggml_cpu_has_wasm_simd),
+    ("llamafile", crate::ggml_cpu_has_llamafile),
+];
+
+/// Whether the CPU backend was built with and can use a feature, named as
+/// in `ggml_cpu_has_<name>` (`"avx2"`, `"neon"`, ...). `None` for unknown
+/// names.
+pub fn cpu_has(name: &str) -> Option<bool> {
+    CPU_FEATURES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, has)| unsafe { has() } != 0)
+}
+
+/// Names of all CPU features [`cpu_has`] reports as available.
+pub fn cpu_features() -> Vec<&'static str> {
+    CPU_FEATURES
+        .iter()
+        .filter(|(_, has)| unsafe { has() } != 0)
+        .map(|(name, _)| *name)
+        .collect()
+}
@@ -0,0 +1,395 @@
+//! A C ABI over the GGUF and quantization APIs, for applications that are
+//! not written in Rust. `include/ggml_rs.h` declares it; regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/ggml_rs.h` after
+//! changing this file.
+//!
+//! The library links one namespaced ggml build: `ggml_whisper` with the
+//! `namespace-whisper` feature, `ggml_llama` otherwise, which
+//! [`ggml_rs_variant`] reports. Build it with cargo-c (`cargo cbuild
+//! --features capi`), or as a plain cdylib:
+//!
+//! ```text
+//! cargo rustc --release --features capi --crate-type cdylib
+//! ```
+//!
+//! Functions returning `int` give 0 on success and -1 on failure, those
+//! returning a size or pointer give 0 or `NULL`; [`ggml_rs_last_error`]
+//! then describes the failure. Type ids are `enum ggml_type` values.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::error::{Error, Result};
+use crate::gguf::{GgufFile, GgufTensorInfo, GgufValue};
+use crate::quant::{self, QuantPlan};
+use crate::types::Type;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(e: &Error) {
+    let msg = CString::new(e.to_string().replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+/// `f()`, or `fail` with the error recorded for [`ggml_rs_last_error`].
+fn catch<T>(fail: T, f: impl FnOnce() -> Result<T>) -> T {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            set_error(&e);
+            fail
+        }
+        Err(_) => {
+            set_error(&Error::InvalidArgument("panic in ggml-rs".to_string()));
+            fail
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(Error::InvalidArgument(format!("{} is NULL", what)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Error::InvalidArgument(format!("{} is not UTF-8", what)))
+}
+
+unsafe fn slice_arg<'a, T>(p: *const T, len: usize, what: &str) -> Result<&'a [T]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if p.is_null() {
+        return Err(Error::InvalidArgument(format!("{} is NULL", what)));
+    }
+    Ok(std::slice::from_raw_parts(p, len))
+}
+
+unsafe fn slice_mut_arg<'a, T>(p: *mut T, len: usize, what: &str) -> Result<&'a mut [T]> {
+    if len == 0 {
+        return Ok(&mut []);
+    }
+    if p.is_null() {
+        return Err(Error::InvalidArgument(format!("{} is NULL", what)));
+    }
+    Ok(std::slice::from_raw_parts_mut(p, len))
+}
+
+fn type_arg(ty: i32) -> Result<Type> {
+    u32::try_from(ty)
+        .ok()
+        .and_then(|raw| Type::from_raw(raw as crate::ggml_type))
+        .ok_or_else(|| Error::InvalidArgument(format!("unknown ggml type id {}", ty)))
+}
+
+fn copy_out<T: Copy>(src: &[T], dst: &mut [T]) -> Result<usize> {
+    if dst.len() < src.len() {
+        return Err(Error::InvalidArgument(format!(
+            "output holds {} elements, {} needed",
+            dst.len(),
+            src.len()
+        )));
+    }
+    dst[..src.len()].copy_from_slice(src);
+    Ok(src.len())
+}
+
+/// The message of the last failure on this thread, or `NULL`. Valid until
+/// the next failing call on the thread.
+#[no_mangle]
+pub extern "C" fn ggml_rs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |s| s.as_ptr())
+    })
+}
+
+/// Base name of the ggml library this build links, `"ggml_llama"` or
+/// `"ggml_whisper"`.
+#[no_mangle]
+pub extern "C" fn ggml_rs_variant() -> *const c_char {
+    let name: &[u8] = if cfg!(feature = "namespace-whisper") {
+        b"ggml_whisper\0"
+    } else {
+        b"ggml_llama\0"
+    };
+    name.as_ptr() as *const c_char
+}
+
+/// Free a string returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The type id named `name` (e.g. `"q4_K"`), or -1.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_type_from_name(name: *const c_char) -> i32 {
+    catch(-1, || {
+        let name = str_arg(name, "name")?;
+        Type::from_name(name)
+            .map(|ty| ty.as_raw() as i32)
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown ggml type {:?}", name)))
+    })
+}
+
+/// Bytes of one row of `n_per_row` values of type `ty`, or 0.
+#[no_mangle]
+pub extern "C" fn ggml_rs_row_size(ty: i32, n_per_row: usize) -> usize {
+    catch(0, || {
+        let ty = type_arg(ty)?;
+        if !n_per_row.is_multiple_of(ty.block_size()) {
+            return Err(Error::InvalidArgument(format!(
+                "rows of {} values are not whole {} blocks",
+                n_per_row, ty
+            )));
+        }
+        Ok(ty.row_size(n_per_row as i64))
+    })
+}
+
+/// Quantize `n` values of `src`, rows of `n_per_row`, to `ty` into `dst`.
+/// `imatrix`, `n_per_row` column weights, may be `NULL` unless the type
+/// requires it. Returns the bytes written.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_quantize(
+    ty: i32,
+    src: *const f32,
+    n: usize,
+    n_per_row: usize,
+    imatrix: *const f32,
+    dst: *mut u8,
+    dst_size: usize,
+) -> usize {
+    catch(0, || {
+        let ty = type_arg(ty)?;
+        let src = slice_arg(src, n, "src")?;
+        let imatrix = if imatrix.is_null() {
+            None
+        } else {
+            Some(slice_arg(imatrix, n_per_row, "imatrix")?)
+        };
+        let q = quant::quantize(ty, src, n_per_row, imatrix)?;
+        copy_out(&q, slice_mut_arg(dst, dst_size, "dst")?)
+    })
+}
+
+/// Convert `src_size` bytes of whole `ty` blocks to f32 values in `dst`,
+/// which holds `dst_len`. Returns the values written.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_dequantize(
+    ty: i32,
+    src: *const u8,
+    src_size: usize,
+    dst: *mut f32,
+    dst_len: usize,
+) -> usize {
+    catch(0, || {
+        let values = quant::dequantize(type_arg(ty)?, slice_arg(src, src_size, "src")?)?;
+        copy_out(&values, slice_mut_arg(dst, dst_len, "dst")?)
+    })
+}
+
+/// Rewrite the GGUF file `src` to `dst` with its tensors converted to `ty`
+/// where [`QuantPlan`] can convert them (1D norms and biases keep their
+/// type).
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_requantize(
+    src: *const c_char,
+    dst: *const c_char,
+    ty: i32,
+) -> c_int {
+    catch(-1, || {
+        let plan = QuantPlan::new(type_arg(ty)?);
+        let file = GgufFile::open(str_arg(src, "src")?)?;
+        let out = BufWriter::new(File::create(str_arg(dst, "dst")?)?);
+        quant::requantize(&file, &plan, out)?;
+        Ok(0)
+    })
+}
+
+/// An open GGUF file.
+pub struct GgmlRsGguf {
+    file: GgufFile,
+    infos: Vec<GgufTensorInfo>,
+    names: Vec<CString>,
+}
+
+/// Location, type and shape of one tensor of a GGUF file. `name` lives as
+/// long as the file; `type` is -1 for types this ggml does not know.
+#[repr(C)]
+pub struct GgmlRsTensorInfo {
+    pub name: *const c_char,
+    pub r#type: i32,
+    pub n_dims: u32,
+    pub ne: [i64; 4],
+    pub offset: usize,
+    pub size: usize,
+}
+
+unsafe fn gguf_arg<'a>(f: *const GgmlRsGguf) -> Result<&'a GgmlRsGguf> {
+    f.as_ref()
+        .ok_or_else(|| Error::InvalidArgument("GGUF file is NULL".to_string()))
+}
+
+unsafe fn gguf_value(f: *const GgmlRsGguf, key: *const c_char) -> Result<GgufValue> {
+    let key = str_arg(key, "key")?;
+    gguf_arg(f)?
+        .file
+        .get(key)
+        .ok_or_else(|| Error::InvalidArgument(format!("no metadata key {:?}", key)))
+}
+
+/// Open the GGUF file at `path`, or `NULL`. Free it with
+/// [`ggml_rs_gguf_free`].
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_open(path: *const c_char) -> *mut GgmlRsGguf {
+    catch(std::ptr::null_mut(), || {
+        let file = GgufFile::open(str_arg(path, "path")?)?;
+        let infos: Vec<_> = file.tensor_infos().collect();
+        let names = infos
+            .iter()
+            .map(|info| CString::new(info.name.as_str()))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| Error::InvalidFormat("tensor name with a NUL byte".to_string()))?;
+        Ok(Box::into_raw(Box::new(GgmlRsGguf { file, infos, names })))
+    })
+}
+
+/// Close a file opened with [`ggml_rs_gguf_open`]; `NULL` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_free(f: *mut GgmlRsGguf) {
+    if !f.is_null() {
+        drop(Box::from_raw(f));
+    }
+}
+
+/// Number of tensors in the file.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_n_tensors(f: *const GgmlRsGguf) -> usize {
+    catch(0, || Ok(gguf_arg(f)?.infos.len()))
+}
+
+/// Fill `out` with tensor `i`.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_tensor_info(
+    f: *const GgmlRsGguf,
+    i: usize,
+    out: *mut GgmlRsTensorInfo,
+) -> c_int {
+    catch(-1, || {
+        let f = gguf_arg(f)?;
+        let info = f
+            .infos
+            .get(i)
+            .ok_or_else(|| Error::InvalidArgument(format!("tensor {} of {}", i, f.infos.len())))?;
+        let out = out
+            .as_mut()
+            .ok_or_else(|| Error::InvalidArgument("out is NULL".to_string()))?;
+        *out = GgmlRsTensorInfo {
+            name: f.names[i].as_ptr(),
+            r#type: info.ty.map_or(-1, |ty| ty.as_raw() as i32),
+            n_dims: info.n_dims as u32,
+            ne: info.ne,
+            offset: info.offset,
+            size: info.size,
+        };
+        Ok(0)
+    })
+}
+
+/// Read the data of tensor `name` into `dst`, which holds its `size`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_read_tensor(
+    f: *const GgmlRsGguf,
+    name: *const c_char,
+    dst: *mut u8,
+    dst_size: usize,
+) -> c_int {
+    catch(-1, || {
+        let data = gguf_arg(f)?.file.read_tensor_data(str_arg(name, "name")?)?;
+        copy_out(&data, slice_mut_arg(dst, dst_size, "dst")?)?;
+        Ok(0)
+    })
+}
+
+/// The string value of `key`, or `NULL` if it is missing or not a string.
+/// Free it with [`ggml_rs_string_free`].
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_get_string(
+    f: *const GgmlRsGguf,
+    key: *const c_char,
+) -> *mut c_char {
+    catch(std::ptr::null_mut(), || {
+        let value = gguf_value(f, key)?;
+        let s = value.as_str().ok_or_else(|| Error::TypeMismatch {
+            expected: "str".to_string(),
+            found: value.value_type().to_string(),
+        })?;
+        CString::new(s)
+            .map(CString::into_raw)
+            .map_err(|_| Error::InvalidFormat("string with a NUL byte".to_string()))
+    })
+}
+
+/// The value of `key`, any integer type or bool, into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_get_int(
+    f: *const GgmlRsGguf,
+    key: *const c_char,
+    out: *mut i64,
+) -> c_int {
+    catch(-1, || {
+        let value = gguf_value(f, key)?;
+        let v = match value {
+            GgufValue::Bool(b) => b.into(),
+            ref v => v
+                .as_i128()
+                .and_then(|v| i64::try_from(v).ok())
+                .ok_or_else(|| Error::TypeMismatch {
+                    expected: "an integer that fits i64".to_string(),
+                    found: v.value_type().to_string(),
+                })?,
+        };
+        write_out(out, v)?;
+        Ok(0)
+    })
+}
+
+/// The value of `key`, any float or integer type, into `out`.
+#[no_mangle]
+pub unsafe extern "C" fn ggml_rs_gguf_get_float(
+    f: *const GgmlRsGguf,
+    key: *const c_char,
+    out: *mut f64,
+) -> c_int {
+    catch(-1, || {
+        let value = gguf_value(f, key)?;
+        let v = match value {
+            GgufValue::F32(v) => v.into(),
+            GgufValue::F64(v) => v,
+            ref v => v.as_i128().ok_or_else(|| Error::TypeMismatch {
+                expected: "a number".to_string(),
+                found: v.value_type().to_string(),
+            })? as f64,
+        };
+        write_out(out, v)?;
+        Ok(0)
+    })
+}
+
+unsafe fn write_out<T>(out: *mut T, v: T) -> Result<()> {
+    let out = out
+        .as_mut()
+        .ok_or_else(|| Error::InvalidArgument("out is NULL".to_string()))?;
+    *out = v;
+    Ok(())
+}
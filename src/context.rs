@@ -0,0 +1,276 @@
+//! Owned `ggml_context` wrapper.
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::backend::BackendBuffer;
+use crate::error::{Error, Result};
+use crate::graph::Graph;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Parameters for [`Context::with_params`], mirroring `ggml_init_params`.
+#[derive(Debug, Clone, Copy)]
+pub struct ContextParams {
+    /// Size of the memory pool in bytes.
+    pub mem_size: usize,
+    /// Only allocate tensor metadata; data is provided later (e.g. by a backend buffer).
+    pub no_alloc: bool,
+}
+
+impl ContextParams {
+    pub fn new(mem_size: usize) -> Self {
+        ContextParams {
+            mem_size,
+            no_alloc: false,
+        }
+    }
+
+    pub fn no_alloc(mut self, no_alloc: bool) -> Self {
+        self.no_alloc = no_alloc;
+        self
+    }
+}
+
+/// An owned ggml context. Tensors and graphs created from it borrow it.
+pub struct Context {
+    ptr: NonNull<crate::ggml_context>,
+    auto_contiguous: Cell<bool>,
+    /// Backend buffers holding tensor data of a `no_alloc` context; freed
+    /// with the context.
+    buffers: RefCell<Vec<BackendBuffer>>,
+}
+
+// A context can move between threads, it just can't be used from two at once.
+unsafe impl Send for Context {}
+
+impl Context {
+    /// Create a context with an internally allocated pool of `mem_size` bytes.
+    pub fn new(mem_size: usize) -> Result<Self> {
+        Self::with_params(ContextParams::new(mem_size))
+    }
+
+    pub fn with_params(params: ContextParams) -> Result<Self> {
+        let raw = crate::ggml_init_params {
+            mem_size: params.mem_size,
+            mem_buffer: ptr::null_mut(),
+            no_alloc: params.no_alloc,
+        };
+        unsafe { Self::from_raw(crate::ggml_init(raw)) }.ok_or(Error::ContextInit)
+    }
+
+    /// Take ownership of a context created by ggml (e.g. by `gguf_init_from_file`).
+    pub(crate) unsafe fn from_raw(ptr: *mut crate::ggml_context) -> Option<Self> {
+        let ptr = NonNull::new(ptr)?;
+        let start = crate::ggml_get_mem_buffer(ptr.as_ptr()) as usize;
+        let end = start + crate::ggml_get_mem_size(ptr.as_ptr());
+        let id = NEXT_CONTEXT_ID.fetch_add(1, Ordering::Relaxed);
+        live_contexts()
+            .0
+            .insert(start, (end, id, ptr.as_ptr() as usize));
+        Some(Context {
+            ptr,
+            auto_contiguous: Cell::new(false),
+            buffers: RefCell::new(Vec::new()),
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::ggml_context {
+        self.ptr.as_ptr()
+    }
+
+    /// Bytes of the memory pool currently in use.
+    pub fn used_mem(&self) -> usize {
+        unsafe { crate::ggml_used_mem(self.as_ptr()) }
+    }
+
+    /// Total size of the memory pool.
+    pub fn mem_size(&self) -> usize {
+        unsafe { crate::ggml_get_mem_size(self.as_ptr()) }
+    }
+
+    pub fn no_alloc(&self) -> bool {
+        unsafe { crate::ggml_get_no_alloc(self.as_ptr()) }
+    }
+
+    pub fn set_no_alloc(&self, no_alloc: bool) {
+        unsafe { crate::ggml_set_no_alloc(self.as_ptr(), no_alloc) }
+    }
+
+    /// When enabled, ops that need contiguous inputs insert `ggml_cont` for
+    /// non-contiguous arguments instead of returning [`Error::NotContiguous`].
+    pub fn set_auto_contiguous(&self, enabled: bool) {
+        self.auto_contiguous.set(enabled);
+    }
+
+    pub fn auto_contiguous(&self) -> bool {
+        self.auto_contiguous.get()
+    }
+
+    /// Fail early instead of letting ggml abort when the pool is exhausted.
+    pub(crate) fn ensure_capacity(&self, data_bytes: usize) -> Result<()> {
+        let overhead = unsafe { crate::ggml_tensor_overhead() };
+        let data = if self.no_alloc() { 0 } else { data_bytes };
+        let needed = overhead + data + crate::GGML_MEM_ALIGN as usize;
+        let available = self.mem_size().saturating_sub(self.used_mem());
+        if needed > available {
+            return Err(Error::OutOfMemory { needed, available });
+        }
+        Ok(())
+    }
+
+    /// Create a new tensor with up to four dimensions (`ne[0]` is the innermost).
+    pub fn new_tensor(&self, ty: Type, ne: &[i64]) -> Result<Tensor<'_>> {
+        if ne.is_empty() || ne.len() > crate::GGML_MAX_DIMS as usize {
+            return Err(Error::InvalidArgument(format!(
+                "tensors must have 1 to {} dimensions, got {}",
+                crate::GGML_MAX_DIMS,
+                ne.len()
+            )));
+        }
+        if ne.iter().any(|&n| n < 0) {
+            return Err(Error::InvalidArgument(format!(
+                "negative dimension in {:?}",
+                ne
+            )));
+        }
+        if ne[0] % ty.block_size() as i64 != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "ne[0] = {} is not a multiple of the {} block size {}",
+                ne[0],
+                ty,
+                ty.block_size()
+            )));
+        }
+        let nbytes = ty.checked_nbytes(ne).ok_or_else(|| {
+            Error::InvalidArgument(format!("{} tensor of shape {:?} is too large", ty, ne))
+        })?;
+        self.ensure_capacity(nbytes)?;
+        let ptr = unsafe {
+            crate::ggml_new_tensor(self.as_ptr(), ty.as_raw(), ne.len() as i32, ne.as_ptr())
+        };
+        unsafe { Tensor::from_raw(ptr) }.ok_or(Error::NullPointer("ggml_new_tensor"))
+    }
+
+    pub fn new_tensor_1d(&self, ty: Type, ne0: i64) -> Result<Tensor<'_>> {
+        self.new_tensor(ty, &[ne0])
+    }
+
+    pub fn new_tensor_2d(&self, ty: Type, ne0: i64, ne1: i64) -> Result<Tensor<'_>> {
+        self.new_tensor(ty, &[ne0, ne1])
+    }
+
+    pub fn new_tensor_3d(&self, ty: Type, ne0: i64, ne1: i64, ne2: i64) -> Result<Tensor<'_>> {
+        self.new_tensor(ty, &[ne0, ne1, ne2])
+    }
+
+    pub fn new_tensor_4d(
+        &self,
+        ty: Type,
+        ne0: i64,
+        ne1: i64,
+        ne2: i64,
+        ne3: i64,
+    ) -> Result<Tensor<'_>> {
+        self.new_tensor(ty, &[ne0, ne1, ne2, ne3])
+    }
+
+    /// Create a tensor with the same type and shape as `src` (data is not copied).
+    pub fn dup_tensor(&self, src: Tensor<'_>) -> Result<Tensor<'_>> {
+        self.ensure_capacity(src.nbytes())?;
+        let ptr = unsafe { crate::ggml_dup_tensor(self.as_ptr(), src.as_ptr()) };
+        unsafe { Tensor::from_raw(ptr) }.ok_or(Error::NullPointer("ggml_dup_tensor"))
+    }
+
+    /// Look up a tensor in this context by name.
+    pub fn get_tensor(&self, name: &str) -> Option<Tensor<'_>> {
+        let name = CString::new(name).ok()?;
+        unsafe { Tensor::from_raw(crate::ggml_get_tensor(self.as_ptr(), name.as_ptr())) }
+    }
+
+    /// Iterate over all tensors allocated in this context.
+    pub fn tensors(&self) -> impl Iterator<Item = Tensor<'_>> + '_ {
+        let first = unsafe { Tensor::from_raw(crate::ggml_get_first_tensor(self.as_ptr())) };
+        std::iter::successors(first, move |t| unsafe {
+            Tensor::from_raw(crate::ggml_get_next_tensor(self.as_ptr(), t.as_ptr()))
+        })
+    }
+
+    /// Allocate data for every tensor of a `no_alloc` context that has none
+    /// yet in a buffer of `backend`. The buffer lives as long as the context.
+    pub(crate) fn alloc_tensors_on(&self, backend: crate::ggml_backend_t) -> Result<()> {
+        if !self.no_alloc() {
+            return Err(Error::InvalidArgument(
+                "backend allocation needs a no_alloc context".to_string(),
+            ));
+        }
+        if self.tensors().all(|t| !t.data().is_null()) {
+            return Ok(());
+        }
+        let buffer = unsafe { crate::ggml_backend_alloc_ctx_tensors(self.as_ptr(), backend) };
+        let buffer = unsafe { BackendBuffer::from_raw(buffer) }
+            .ok_or(Error::NullPointer("ggml_backend_alloc_ctx_tensors"))?;
+        self.buffers.borrow_mut().push(buffer);
+        Ok(())
+    }
+
+    /// Free `buffer` with the context, once no tensor can refer to it.
+    pub(crate) fn keep_buffer(&self, buffer: BackendBuffer) {
+        self.buffers.borrow_mut().push(buffer);
+    }
+
+    /// Allocate a graph with the default size (`GGML_DEFAULT_GRAPH_SIZE` nodes).
+    pub fn new_graph(&self) -> Result<Graph<'_>> {
+        self.new_graph_custom(crate::GGML_DEFAULT_GRAPH_SIZE as usize, false)
+    }
+
+    /// Allocate a graph with room for `size` nodes, optionally tracking gradients.
+    pub fn new_graph_custom(&self, size: usize, grads: bool) -> Result<Graph<'_>> {
+        let needed = unsafe { crate::ggml_graph_overhead_custom(size, grads) };
+        let available = self.mem_size().saturating_sub(self.used_mem());
+        if needed > available {
+            return Err(Error::OutOfMemory { needed, available });
+        }
+        let ptr = unsafe { crate::ggml_new_graph_custom(self.as_ptr(), size, grads) };
+        unsafe { Graph::from_raw_with_grads(ptr, grads) }
+            .ok_or(Error::NullPointer("ggml_new_graph_custom"))
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let start = unsafe { crate::ggml_get_mem_buffer(self.as_ptr()) } as usize;
+        live_contexts().0.remove(&start);
+        self.buffers.get_mut().clear();
+        unsafe { crate::ggml_free(self.as_ptr()) }
+    }
+}
+
+static NEXT_CONTEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The memory pools of the live contexts: start -> (end, id, context). The
+/// id tells a context from an earlier one freed at the same address.
+static LIVE_CONTEXTS: Mutex<BTreeMap<usize, (usize, u64, usize)>> = Mutex::new(BTreeMap::new());
+
+/// The registry of live contexts, locked: no context is freed while it is
+/// held. Graph allocators use it to only touch tensors whose context is
+/// still alive.
+pub(crate) struct LiveContexts(MutexGuard<'static, BTreeMap<usize, (usize, u64, usize)>>);
+
+pub(crate) fn live_contexts() -> LiveContexts {
+    LiveContexts(LIVE_CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+impl LiveContexts {
+    /// The id and pointer of the live context whose pool holds `ptr`, e.g.
+    /// a tensor or graph it allocated.
+    pub(crate) fn owner<T>(&self, ptr: *const T) -> Option<(u64, *mut crate::ggml_context)> {
+        let addr = ptr as usize;
+        let (_, &(end, id, ctx)) = self.0.range(..=addr).next_back()?;
+        (addr < end).then_some((id, ctx as *mut crate::ggml_context))
+    }
+}
@@ -0,0 +1,536 @@
+//! Training data for the [`Optimizer`]: shuffling and minibatches.
+//!
+//! [`Dataset`] wraps `ggml_opt_dataset`, which keeps all datapoints in host
+//! memory. [`StreamingDataset`] reads fixed-size records from any seekable
+//! reader, one batch at a time, for data that does not fit in RAM. Both
+//! implement [`BatchSource`], which [`Optimizer::epoch`] and
+//! [`Optimizer::fit_dataset`] consume.
+//!
+//! Datapoint `i` is column `i` of the data tensor (`[ne_datapoint, ndata]`)
+//! and its label column `i` of the labels (`[ne_label, ndata]`). Batches
+//! take their size from the optimizer inputs (`[ne_datapoint, batch]`).
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+use std::ptr::NonNull;
+
+use crate::error::{Error, Result};
+use crate::opt::{OptResult, Optimizer};
+use crate::random::Rng;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// A batch copied to the host: `(data, labels)`.
+type HostBatch = (Vec<u8>, Option<Vec<u8>>);
+
+/// A source of shuffled minibatches.
+pub trait BatchSource {
+    /// Number of datapoints.
+    fn ndata(&self) -> i64;
+
+    /// Datapoints are shuffled in groups of this size; batch sizes and
+    /// shuffle boundaries must be multiples of it.
+    fn shard_size(&self) -> i64 {
+        1
+    }
+
+    /// Shuffle the first `ndata` datapoints, or all of them if `None`.
+    fn shuffle(&mut self, opt: &Optimizer<'_>, ndata: Option<i64>) -> Result<()>;
+
+    /// Copy batch `ibatch` into `inputs`, and its labels into `labels`.
+    fn load_batch(
+        &mut self,
+        inputs: Tensor<'_>,
+        labels: Option<Tensor<'_>>,
+        ibatch: i64,
+    ) -> Result<()>;
+}
+
+fn check_batch_tensor(what: &str, t: Tensor<'_>, ty: Type, ne0: i64) -> Result<()> {
+    if t.ty() != ty {
+        return Err(Error::TypeMismatch {
+            expected: format!("{} batch {}", what, ty),
+            found: t.ty().to_string(),
+        });
+    }
+    if t.ne()[0] != ne0 {
+        return Err(Error::ShapeMismatch(format!(
+            "{} batch has {} values per datapoint, the dataset {}",
+            what,
+            t.ne()[0],
+            ne0
+        )));
+    }
+    if !t.is_contiguous() {
+        return Err(Error::NotContiguous);
+    }
+    if t.data().is_null() || !t.is_host() {
+        return Err(Error::NoData);
+    }
+    Ok(())
+}
+
+fn check_labels(expected: bool, labels: Option<Tensor<'_>>) -> Result<()> {
+    if expected != labels.is_some() {
+        return Err(Error::InvalidArgument(format!(
+            "dataset {} labels but the batch {}",
+            if expected { "has" } else { "has no" },
+            if labels.is_some() { "has" } else { "has none" }
+        )));
+    }
+    Ok(())
+}
+
+/// In-memory dataset (`ggml_opt_dataset`).
+pub struct Dataset {
+    ptr: NonNull<crate::ggml_opt_dataset>,
+    ndata_shard: i64,
+}
+
+impl Dataset {
+    /// Allocate room for `ndata` datapoints of `ne_datapoint` values, with
+    /// labels of `ne_label` values of type `label_type` if given. `ndata`
+    /// must be a multiple of `ndata_shard`.
+    pub fn new(
+        data_type: Type,
+        ne_datapoint: i64,
+        labels: Option<(Type, i64)>,
+        ndata: i64,
+        ndata_shard: i64,
+    ) -> Result<Self> {
+        if ne_datapoint <= 0 || ndata <= 0 || ndata_shard <= 0 {
+            return Err(Error::InvalidArgument(format!(
+                "dataset of {} datapoints of {} values in shards of {}",
+                ndata, ne_datapoint, ndata_shard
+            )));
+        }
+        if ndata % ndata_shard != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "{} datapoints do not split into shards of {}",
+                ndata, ndata_shard
+            )));
+        }
+        let (label_type, ne_label) = labels.unwrap_or((Type::F32, 0));
+        if ne_label < 0 || (labels.is_some() && ne_label == 0) {
+            return Err(Error::InvalidArgument(format!(
+                "labels of {} values",
+                ne_label
+            )));
+        }
+        let ptr = unsafe {
+            crate::ggml_opt_dataset_init(
+                data_type.as_raw(),
+                label_type.as_raw(),
+                ne_datapoint,
+                ne_label,
+                ndata,
+                ndata_shard,
+            )
+        };
+        NonNull::new(ptr)
+            .map(|ptr| Dataset { ptr, ndata_shard })
+            .ok_or(Error::NullPointer("ggml_opt_dataset_init"))
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_opt_dataset_t {
+        self.ptr.as_ptr()
+    }
+
+    /// All datapoints, `[ne_datapoint, ndata]`; fill it before training.
+    pub fn data(&self) -> Tensor<'_> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_dataset_data(self.as_ptr())) }
+            .expect("datasets always have data")
+    }
+
+    /// All labels, `[ne_label, ndata]`.
+    pub fn labels(&self) -> Option<Tensor<'_>> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_dataset_labels(self.as_ptr())) }
+    }
+
+    pub fn ndata_shard(&self) -> i64 {
+        self.ndata_shard
+    }
+
+    /// Number of whole batches of `batch_size` datapoints.
+    pub fn n_batches(&self, batch_size: i64) -> i64 {
+        self.ndata() / batch_size.max(1)
+    }
+
+    fn check_batch_size(&self, batch_size: i64) -> Result<()> {
+        if batch_size <= 0 || batch_size % self.ndata_shard != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "batch size {} is not a multiple of the shard size {}",
+                batch_size, self.ndata_shard
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_batch_index(&self, ibatch: i64, batch_size: i64) -> Result<()> {
+        if ibatch < 0 || ibatch >= self.n_batches(batch_size) {
+            return Err(Error::InvalidArgument(format!(
+                "batch {} of {}",
+                ibatch,
+                self.n_batches(batch_size)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Copy batch `ibatch` (in shuffled order) into host slices of
+    /// `batch_size` datapoints each.
+    pub fn get_batch_host(
+        &self,
+        data: &mut [u8],
+        labels: Option<&mut [u8]>,
+        batch_size: i64,
+        ibatch: i64,
+    ) -> Result<()> {
+        self.check_batch_size(batch_size)?;
+        self.check_batch_index(ibatch, batch_size)?;
+        let d = self.data();
+        let want = d.ty().row_size(d.ne()[0]) * batch_size as usize;
+        if data.len() != want {
+            return Err(Error::ShapeMismatch(format!(
+                "data batch of {} bytes, expected {}",
+                data.len(),
+                want
+            )));
+        }
+        let labels_ptr = match (self.labels(), labels) {
+            (Some(l), Some(buf)) => {
+                let want = l.ty().row_size(l.ne()[0]) * batch_size as usize;
+                if buf.len() != want {
+                    return Err(Error::ShapeMismatch(format!(
+                        "label batch of {} bytes, expected {}",
+                        buf.len(),
+                        want
+                    )));
+                }
+                buf.as_mut_ptr()
+            }
+            (None, None) => std::ptr::null_mut(),
+            (l, buf) => {
+                return Err(Error::InvalidArgument(format!(
+                    "dataset {} labels but the batch {}",
+                    if l.is_some() { "has" } else { "has no" },
+                    if buf.is_some() { "has" } else { "has none" }
+                )))
+            }
+        };
+        unsafe {
+            crate::ggml_opt_dataset_get_batch_host(
+                self.as_ptr(),
+                data.as_mut_ptr() as *mut _,
+                data.len(),
+                labels_ptr as *mut _,
+                ibatch,
+            )
+        };
+        Ok(())
+    }
+
+    /// Iterate over the batches in the current (shuffled) order as host
+    /// byte buffers: `(data, labels)`.
+    pub fn batches(&self, batch_size: i64) -> Result<impl Iterator<Item = Result<HostBatch>> + '_> {
+        self.check_batch_size(batch_size)?;
+        let d = self.data();
+        let data_bytes = d.ty().row_size(d.ne()[0]) * batch_size as usize;
+        let label_bytes = self
+            .labels()
+            .map(|l| l.ty().row_size(l.ne()[0]) * batch_size as usize);
+        Ok((0..self.n_batches(batch_size)).map(move |ibatch| {
+            let mut data = vec![0; data_bytes];
+            let mut labels = label_bytes.map(|n| vec![0; n]);
+            self.get_batch_host(&mut data, labels.as_deref_mut(), batch_size, ibatch)?;
+            Ok((data, labels))
+        }))
+    }
+}
+
+impl BatchSource for Dataset {
+    fn ndata(&self) -> i64 {
+        unsafe { crate::ggml_opt_dataset_ndata(self.as_ptr()) }
+    }
+
+    fn shard_size(&self) -> i64 {
+        self.ndata_shard
+    }
+
+    fn shuffle(&mut self, opt: &Optimizer<'_>, ndata: Option<i64>) -> Result<()> {
+        let idata = match ndata {
+            None => -1,
+            Some(n) if (0..=self.ndata()).contains(&n) && n % self.ndata_shard == 0 => n,
+            Some(n) => {
+                return Err(Error::InvalidArgument(format!(
+                    "cannot shuffle the first {} of {} datapoints in shards of {}",
+                    n,
+                    self.ndata(),
+                    self.ndata_shard
+                )))
+            }
+        };
+        unsafe { crate::ggml_opt_dataset_shuffle(opt.as_ptr(), self.as_ptr(), idata) };
+        Ok(())
+    }
+
+    fn load_batch(
+        &mut self,
+        inputs: Tensor<'_>,
+        labels: Option<Tensor<'_>>,
+        ibatch: i64,
+    ) -> Result<()> {
+        let d = self.data();
+        check_batch_tensor("input", inputs, d.ty(), d.ne()[0])?;
+        check_labels(self.labels().is_some(), labels)?;
+        let batch_size = inputs.nelements() / d.ne()[0];
+        if let (Some(l), Some(lb)) = (self.labels(), labels) {
+            check_batch_tensor("label", lb, l.ty(), l.ne()[0])?;
+            if lb.nelements() / l.ne()[0] != batch_size {
+                return Err(Error::ShapeMismatch(format!(
+                    "{} input datapoints but {} labels",
+                    batch_size,
+                    lb.nelements() / l.ne()[0]
+                )));
+            }
+        }
+        self.check_batch_size(batch_size)?;
+        self.check_batch_index(ibatch, batch_size)?;
+        unsafe {
+            crate::ggml_opt_dataset_get_batch(
+                self.as_ptr(),
+                inputs.as_ptr(),
+                labels.map_or(std::ptr::null_mut(), |l| l.as_ptr()),
+                ibatch,
+            )
+        };
+        Ok(())
+    }
+}
+
+impl Drop for Dataset {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_opt_dataset_free(self.as_ptr()) }
+    }
+}
+
+/// Dataset read from a stream of fixed-size records, each holding one
+/// datapoint followed by its label, in the raw layout of their types.
+///
+/// Only the current batch is held in memory. Shuffling permutes record
+/// indices with a seeded [`Rng`], so reads are random access.
+pub struct StreamingDataset<R> {
+    reader: R,
+    data_type: Type,
+    ne_datapoint: i64,
+    labels: Option<(Type, i64)>,
+    ndata: i64,
+    permutation: Vec<u64>,
+    rng: Rng,
+}
+
+impl<R: Read + Seek> StreamingDataset<R> {
+    /// The number of records is taken from the stream length, which must
+    /// be a whole number of records.
+    pub fn new(
+        mut reader: R,
+        data_type: Type,
+        ne_datapoint: i64,
+        labels: Option<(Type, i64)>,
+        seed: u64,
+    ) -> Result<Self> {
+        if ne_datapoint <= 0 || labels.is_some_and(|(_, n)| n <= 0) {
+            return Err(Error::InvalidArgument(format!(
+                "records of {} values with labels {:?}",
+                ne_datapoint, labels
+            )));
+        }
+        let record =
+            (data_type.row_size(ne_datapoint) + labels.map_or(0, |(ty, n)| ty.row_size(n))) as u64;
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len % record != 0 {
+            return Err(Error::InvalidFormat(format!(
+                "stream of {} bytes is not a whole number of {} byte records",
+                len, record
+            )));
+        }
+        Ok(StreamingDataset {
+            reader,
+            data_type,
+            ne_datapoint,
+            labels,
+            ndata: (len / record) as i64,
+            permutation: (0..len / record).collect(),
+            rng: Rng::new(seed),
+        })
+    }
+
+    fn data_size(&self) -> usize {
+        self.data_type.row_size(self.ne_datapoint)
+    }
+
+    fn label_size(&self) -> usize {
+        self.labels.map_or(0, |(ty, n)| ty.row_size(n))
+    }
+
+    /// Bytes per record.
+    pub fn record_size(&self) -> usize {
+        self.data_size() + self.label_size()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read + Seek> BatchSource for StreamingDataset<R> {
+    fn ndata(&self) -> i64 {
+        self.ndata
+    }
+
+    fn shuffle(&mut self, _opt: &Optimizer<'_>, ndata: Option<i64>) -> Result<()> {
+        let n = ndata.unwrap_or(self.ndata);
+        if !(0..=self.ndata).contains(&n) {
+            return Err(Error::InvalidArgument(format!(
+                "cannot shuffle the first {} of {} datapoints",
+                n, self.ndata
+            )));
+        }
+        // Fisher-Yates
+        for i in (1..n as usize).rev() {
+            let j = (self.rng.next_u64() % (i as u64 + 1)) as usize;
+            self.permutation.swap(i, j);
+        }
+        Ok(())
+    }
+
+    fn load_batch(
+        &mut self,
+        inputs: Tensor<'_>,
+        labels: Option<Tensor<'_>>,
+        ibatch: i64,
+    ) -> Result<()> {
+        check_batch_tensor("input", inputs, self.data_type, self.ne_datapoint)?;
+        check_labels(self.labels.is_some(), labels)?;
+        let batch_size = inputs.nelements() / self.ne_datapoint;
+        if let (Some((ty, n)), Some(lb)) = (self.labels, labels) {
+            check_batch_tensor("label", lb, ty, n)?;
+            if lb.nelements() / n != batch_size {
+                return Err(Error::ShapeMismatch(format!(
+                    "{} input datapoints but {} labels",
+                    batch_size,
+                    lb.nelements() / n
+                )));
+            }
+        }
+        let first = ibatch * batch_size;
+        if ibatch < 0 || first + batch_size > self.ndata {
+            return Err(Error::InvalidArgument(format!(
+                "batch {} of {} datapoints is past the end of {}",
+                ibatch, batch_size, self.ndata
+            )));
+        }
+        let (data_size, label_size) = (self.data_size(), self.label_size());
+        let record = self.record_size() as u64;
+        let data =
+            unsafe { std::slice::from_raw_parts_mut(inputs.data() as *mut u8, inputs.nbytes()) };
+        let mut label_data = labels
+            .map(|l| unsafe { std::slice::from_raw_parts_mut(l.data() as *mut u8, l.nbytes()) });
+        for j in 0..batch_size as usize {
+            let idx = self.permutation[first as usize + j];
+            self.reader.seek(SeekFrom::Start(idx * record))?;
+            self.reader
+                .read_exact(&mut data[j * data_size..(j + 1) * data_size])?;
+            if let Some(buf) = label_data.as_deref_mut() {
+                self.reader
+                    .read_exact(&mut buf[j * label_size..(j + 1) * label_size])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Optimizer<'_> {
+    /// One pass over `data`: batches before datapoint `idata_split` train
+    /// the model, the rest are only evaluated. Results are added to
+    /// `train` and `eval`. Datapoints past the last full batch are skipped.
+    pub fn epoch(
+        &mut self,
+        data: &mut impl BatchSource,
+        idata_split: i64,
+        mut train: Option<&mut OptResult>,
+        mut eval: Option<&mut OptResult>,
+    ) -> Result<()> {
+        let batch_size = self.inputs().ne()[1];
+        let ndata = data.ndata();
+        if idata_split % batch_size != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "split at {} does not divide into batches of {}",
+                idata_split, batch_size
+            )));
+        }
+        if !(0..=ndata).contains(&idata_split) {
+            return Err(Error::InvalidArgument(format!(
+                "split at {} of {} datapoints",
+                idata_split, ndata
+            )));
+        }
+        for ibatch in 0..ndata / batch_size {
+            data.load_batch(self.inputs(), self.labels(), ibatch)?;
+            if ibatch * batch_size < idata_split {
+                self.step(train.as_deref_mut());
+            } else {
+                self.evaluate(eval.as_deref_mut());
+            }
+        }
+        Ok(())
+    }
+
+    /// Train on `data` for `n_epochs`, holding out the last `val_split`
+    /// fraction for validation, as `ggml_opt_fit` does.
+    ///
+    /// All data is shuffled once up front and the training part again
+    /// before each epoch. `on_epoch(epoch, train, validation)` can stop
+    /// training early.
+    pub fn fit_dataset(
+        &mut self,
+        data: &mut impl BatchSource,
+        n_epochs: usize,
+        val_split: f32,
+        mut on_epoch: impl FnMut(usize, &OptResult, &OptResult) -> ControlFlow<()>,
+    ) -> Result<()> {
+        if !(0.0..1.0).contains(&val_split) {
+            return Err(Error::InvalidArgument(format!(
+                "validation split must be in [0, 1), got {}",
+                val_split
+            )));
+        }
+        let batch_size = self.inputs().ne()[1];
+        if batch_size % data.shard_size() != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "batch size {} is not a multiple of the shard size {}",
+                batch_size,
+                data.shard_size()
+            )));
+        }
+        let n_batches = data.ndata() / batch_size;
+        let idata_split = ((1.0 - val_split) * n_batches as f32) as i64 * batch_size;
+
+        let (mut train, mut val) = (OptResult::new()?, OptResult::new()?);
+        if batch_size < data.ndata() {
+            data.shuffle(self, None)?;
+        }
+        for epoch in 0..n_epochs {
+            if batch_size < idata_split {
+                data.shuffle(self, Some(idata_split))?;
+            }
+            train.reset();
+            val.reset();
+            self.epoch(data, idata_split, Some(&mut train), Some(&mut val))?;
+            if on_epoch(epoch, &train, &val).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
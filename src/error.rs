@@ -0,0 +1,89 @@
+//! Error type shared by the safe wrappers.
+
+use std::fmt;
+
+/// Result alias used throughout the safe API.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by the safe wrappers around the ggml C API.
+#[derive(Debug)]
+pub enum Error {
+    /// `ggml_init` (or another allocator) returned a null pointer.
+    ContextInit,
+    /// The context's memory pool cannot hold the requested object.
+    OutOfMemory { needed: usize, available: usize },
+    /// A ggml call that should produce an object returned null.
+    NullPointer(&'static str),
+    /// A tensor had a different element type than the caller expected.
+    TypeMismatch { expected: String, found: String },
+    /// Tensor shapes are incompatible for the requested operation.
+    ShapeMismatch(String),
+    /// The tensor has no host-accessible data (e.g. `no_alloc` or device buffer).
+    NoData,
+    /// The operation requires a contiguous tensor.
+    NotContiguous,
+    /// An argument was rejected before reaching ggml.
+    InvalidArgument(String),
+    /// Graph compute reported `GGML_STATUS_ALLOC_FAILED`.
+    AllocFailed,
+    /// Graph compute reported `GGML_STATUS_FAILED`.
+    ComputeFailed,
+    /// Graph compute was aborted by the abort callback.
+    Aborted,
+    /// I/O error from file based helpers.
+    Io(std::io::Error),
+    /// A file or buffer did not match the expected format.
+    InvalidFormat(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ContextInit => write!(f, "failed to initialize ggml context"),
+            Error::OutOfMemory { needed, available } => write!(
+                f,
+                "not enough space in the context's memory pool (needed {}, available {})",
+                needed, available
+            ),
+            Error::NullPointer(what) => write!(f, "{} returned a null pointer", what),
+            Error::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, found)
+            }
+            Error::ShapeMismatch(msg) => write!(f, "shape mismatch: {}", msg),
+            Error::NoData => write!(f, "tensor has no host-accessible data"),
+            Error::NotContiguous => write!(f, "tensor is not contiguous"),
+            Error::InvalidArgument(msg) => write!(f, "invalid argument: {}", msg),
+            Error::AllocFailed => write!(f, "graph compute failed to allocate memory"),
+            Error::ComputeFailed => write!(f, "graph compute failed"),
+            Error::Aborted => write!(f, "graph compute was aborted"),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::InvalidFormat(msg) => write!(f, "invalid format: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Map a `ggml_status` returned by compute functions to a `Result`.
+#[cfg(feature = "native")]
+pub(crate) fn check_status(status: crate::ggml_status) -> Result<()> {
+    match status {
+        crate::ggml_status_GGML_STATUS_SUCCESS => Ok(()),
+        crate::ggml_status_GGML_STATUS_ALLOC_FAILED => Err(Error::AllocFailed),
+        crate::ggml_status_GGML_STATUS_ABORTED => Err(Error::Aborted),
+        _ => Err(Error::ComputeFailed),
+    }
+}
@@ -0,0 +1,174 @@
+//! Reading GGUF files from async code with tokio.
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+
+use super::reader::{GgufFile, GgufTensorInfo};
+use super::value::GgufValue;
+use crate::context::Context;
+use crate::error::{Error, Result};
+
+/// Progress of [`AsyncGgufReader::read_all`], sent after each tensor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// Tensor just read.
+    pub tensor: String,
+    pub tensors_done: usize,
+    pub n_tensors: usize,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+}
+
+/// A GGUF file opened for async reading.
+///
+/// The header is parsed on tokio's blocking pool and kept as owned
+/// metadata; tensor data is read with `tokio::fs`. The reader and its
+/// futures are `Send`, so loading can run in a spawned task.
+pub struct AsyncGgufReader {
+    file: tokio::fs::File,
+    path: PathBuf,
+    metadata: Vec<(String, GgufValue)>,
+    tensors: Vec<GgufTensorInfo>,
+    data_offset: u64,
+}
+
+impl AsyncGgufReader {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let header_path = path.clone();
+        let (metadata, tensors, data_offset) = tokio::task::spawn_blocking(move || {
+            let gguf = GgufFile::open(&header_path)?;
+            Ok::<_, Error>((
+                gguf.metadata().collect::<Vec<_>>(),
+                gguf.tensor_infos().collect::<Vec<_>>(),
+                gguf.data_offset() as u64,
+            ))
+        })
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))??;
+        Ok(AsyncGgufReader {
+            file: tokio::fs::File::open(&path).await?,
+            path,
+            metadata,
+            tensors,
+            data_offset,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All key/value pairs in file order.
+    pub fn metadata(&self) -> &[(String, GgufValue)] {
+        &self.metadata
+    }
+
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Tensor infos in file order.
+    pub fn tensor_infos(&self) -> &[GgufTensorInfo] {
+        &self.tensors
+    }
+
+    /// Total size of the tensor data in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.tensors.iter().map(|t| t.size as u64).sum()
+    }
+
+    /// Read the data of tensor `name`.
+    pub async fn read_tensor_data(&mut self, name: &str) -> Result<Vec<u8>> {
+        let index = self
+            .tensors
+            .iter()
+            .position(|t| t.name == name)
+            .ok_or_else(|| Error::InvalidArgument(format!("no tensor named {:?}", name)))?;
+        self.read_at(index).await
+    }
+
+    async fn read_at(&mut self, index: usize) -> Result<Vec<u8>> {
+        let info = &self.tensors[index];
+        let mut data = vec![0u8; info.size];
+        self.file
+            .seek(SeekFrom::Start(self.data_offset + info.offset as u64))
+            .await?;
+        self.file.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
+    /// Read the data of every tensor, in file order, reporting to
+    /// `progress` after each one. A dropped receiver does not stop loading.
+    ///
+    /// The receiver side is the progress stream, e.g. for a UI:
+    /// `while let Some(p) = rx.recv().await { ... }`.
+    pub async fn read_all(
+        &mut self,
+        progress: Option<mpsc::Sender<LoadProgress>>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let total_bytes = self.total_bytes();
+        let n_tensors = self.tensors.len();
+        let mut bytes_done = 0;
+        let mut out = Vec::with_capacity(n_tensors);
+        for index in 0..n_tensors {
+            let data = self.read_at(index).await?;
+            bytes_done += data.len() as u64;
+            out.push(data);
+            if let Some(tx) = &progress {
+                let _ = tx
+                    .send(LoadProgress {
+                        tensor: self.tensors[index].name.clone(),
+                        tensors_done: index + 1,
+                        n_tensors,
+                        bytes_done,
+                        total_bytes,
+                    })
+                    .await;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Build a context holding every tensor, with the data returned by
+    /// [`AsyncGgufReader::read_all`].
+    pub fn build_context(&self, data: Vec<Vec<u8>>) -> Result<Context> {
+        if data.len() != self.tensors.len() {
+            return Err(Error::InvalidArgument(format!(
+                "{} data blobs for {} tensors",
+                data.len(),
+                self.tensors.len()
+            )));
+        }
+        let overhead = unsafe { crate::ggml_tensor_overhead() };
+        let mem_size = self
+            .tensors
+            .iter()
+            .map(|t| overhead + t.size + crate::GGML_MEM_ALIGN as usize)
+            .sum::<usize>()
+            .max(overhead);
+        let ctx = Context::new(mem_size)?;
+        for (info, bytes) in self.tensors.iter().zip(&data) {
+            let ty = info.ty.ok_or_else(|| {
+                Error::InvalidFormat(format!("tensor {:?} has an unknown type", info.name))
+            })?;
+            let t = ctx.new_tensor(ty, &info.ne[..info.n_dims.max(1)])?;
+            if t.nbytes() != bytes.len() {
+                return Err(Error::ShapeMismatch(format!(
+                    "tensor {:?} needs {} bytes, got {}",
+                    info.name,
+                    t.nbytes(),
+                    bytes.len()
+                )));
+            }
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), t.data() as *mut u8, bytes.len())
+            };
+            t.set_name(&info.name);
+        }
+        Ok(ctx)
+    }
+}
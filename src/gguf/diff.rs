@@ -0,0 +1,239 @@
+//! Comparing two GGUF files.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::mmap::GgufModel;
+use super::reader::GgufTensorInfo;
+use super::value::GgufValue;
+use crate::error::Result;
+use crate::types::Type;
+
+/// A metadata key whose presence or value differs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataDiff {
+    OnlyInA {
+        key: String,
+        value: GgufValue,
+    },
+    OnlyInB {
+        key: String,
+        value: GgufValue,
+    },
+    Changed {
+        key: String,
+        a: GgufValue,
+        b: GgufValue,
+    },
+}
+
+/// Element-wise statistics of `b - a` over two tensors of the same shape,
+/// computed on the dequantized f32 values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    pub n: usize,
+    pub max_abs: f64,
+    pub mean_abs: f64,
+    pub rmse: f64,
+    /// `||b - a|| / ||a||`; infinite if `a` is all zeros and `b` is not.
+    pub rel_l2: f64,
+    /// Cosine similarity of `a` and `b`; 1 for two all-zero tensors.
+    pub cosine: f64,
+}
+
+impl DiffStats {
+    pub fn is_identical(&self) -> bool {
+        self.max_abs == 0.0
+    }
+
+    pub(crate) fn compute(a: &[f32], b: &[f32]) -> Self {
+        let mut sums = DiffSums::default();
+        sums.add(a, b);
+        sums.stats()
+    }
+}
+
+/// Running sums behind [`DiffStats`], so statistics over several tensors
+/// can be accumulated.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DiffSums {
+    n: usize,
+    max_abs: f64,
+    sum_abs: f64,
+    sum_sq: f64,
+    dot: f64,
+    norm_a: f64,
+    norm_b: f64,
+}
+
+impl DiffSums {
+    pub(crate) fn add(&mut self, a: &[f32], b: &[f32]) {
+        for (&x, &y) in a.iter().zip(b) {
+            let (x, y) = (x as f64, y as f64);
+            let d = (y - x).abs();
+            self.max_abs = self.max_abs.max(d);
+            self.sum_abs += d;
+            self.sum_sq += d * d;
+            self.dot += x * y;
+            self.norm_a += x * x;
+            self.norm_b += y * y;
+        }
+        self.n += a.len().min(b.len());
+    }
+
+    pub(crate) fn merge(&mut self, other: &DiffSums) {
+        self.n += other.n;
+        self.max_abs = self.max_abs.max(other.max_abs);
+        self.sum_abs += other.sum_abs;
+        self.sum_sq += other.sum_sq;
+        self.dot += other.dot;
+        self.norm_a += other.norm_a;
+        self.norm_b += other.norm_b;
+    }
+
+    pub(crate) fn stats(&self) -> DiffStats {
+        let n = self.n;
+        let mean = |s: f64| if n == 0 { 0.0 } else { s / n as f64 };
+        let rel_l2 = if self.sum_sq == 0.0 {
+            0.0
+        } else if self.norm_a == 0.0 {
+            f64::INFINITY
+        } else {
+            (self.sum_sq / self.norm_a).sqrt()
+        };
+        let cosine = if self.norm_a == 0.0 && self.norm_b == 0.0 {
+            1.0
+        } else {
+            self.dot / (self.norm_a.sqrt() * self.norm_b.sqrt())
+        };
+        DiffStats {
+            n,
+            max_abs: self.max_abs,
+            mean_abs: mean(self.sum_abs),
+            rmse: mean(self.sum_sq).sqrt(),
+            rel_l2,
+            cosine,
+        }
+    }
+}
+
+/// A tensor present in only one file, or in both.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TensorDiff {
+    OnlyInA(GgufTensorInfo),
+    OnlyInB(GgufTensorInfo),
+    ShapeMismatch {
+        name: String,
+        a: [i64; 4],
+        b: [i64; 4],
+    },
+    Compared {
+        name: String,
+        a: Type,
+        b: Type,
+        stats: DiffStats,
+    },
+}
+
+/// Result of [`diff`]: metadata differences and, for every tensor, how it
+/// compares.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GgufDiff {
+    pub metadata: Vec<MetadataDiff>,
+    pub tensors: Vec<TensorDiff>,
+}
+
+impl GgufDiff {
+    /// Whether the files have equal metadata and tensors with equal values
+    /// (tensor types may still differ).
+    pub fn is_identical(&self) -> bool {
+        self.metadata.is_empty()
+            && self.tensors.iter().all(|t| match t {
+                TensorDiff::Compared { stats, .. } => stats.is_identical(),
+                _ => false,
+            })
+    }
+}
+
+/// Compare the metadata of `a` and `b`, and the dequantized data of every
+/// tensor the two share by name and shape.
+///
+/// Metadata is compared in key order; tensors in the order of `a`, then
+/// those only in `b`.
+pub fn diff(a: &GgufModel, b: &GgufModel) -> Result<GgufDiff> {
+    let meta_a: BTreeMap<_, _> = a.file().metadata().collect();
+    let mut meta_b: BTreeMap<_, _> = b.file().metadata().collect();
+    let mut metadata = Vec::new();
+    for (key, va) in meta_a {
+        match meta_b.remove(&key) {
+            None => metadata.push(MetadataDiff::OnlyInA { key, value: va }),
+            Some(vb) if vb != va => metadata.push(MetadataDiff::Changed { key, a: va, b: vb }),
+            Some(_) => {}
+        }
+    }
+    metadata.extend(
+        meta_b
+            .into_iter()
+            .map(|(key, value)| MetadataDiff::OnlyInB { key, value }),
+    );
+
+    let mut tensors = Vec::new();
+    for info in a.file().tensor_infos() {
+        let (Some(ta), Some(tb)) = (a.get_tensor(&info.name), b.get_tensor(&info.name)) else {
+            tensors.push(TensorDiff::OnlyInA(info));
+            continue;
+        };
+        if ta.ne() != tb.ne() {
+            tensors.push(TensorDiff::ShapeMismatch {
+                name: info.name,
+                a: ta.ne(),
+                b: tb.ne(),
+            });
+            continue;
+        }
+        let stats = DiffStats::compute(&ta.to_vec_f32()?, &tb.to_vec_f32()?);
+        tensors.push(TensorDiff::Compared {
+            name: info.name,
+            a: ta.ty(),
+            b: tb.ty(),
+            stats,
+        });
+    }
+    tensors.extend(
+        b.file()
+            .tensor_infos()
+            .filter(|info| a.get_tensor(&info.name).is_none())
+            .map(TensorDiff::OnlyInB),
+    );
+    Ok(GgufDiff { metadata, tensors })
+}
+
+impl fmt::Display for GgufDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for m in &self.metadata {
+            match m {
+                MetadataDiff::OnlyInA { key, value } => writeln!(f, "- {} = {}", key, value)?,
+                MetadataDiff::OnlyInB { key, value } => writeln!(f, "+ {} = {}", key, value)?,
+                MetadataDiff::Changed { key, a, b } => writeln!(f, "~ {} = {} -> {}", key, a, b)?,
+            }
+        }
+        for t in &self.tensors {
+            match t {
+                TensorDiff::OnlyInA(info) => writeln!(f, "- tensor {}", info.name)?,
+                TensorDiff::OnlyInB(info) => writeln!(f, "+ tensor {}", info.name)?,
+                TensorDiff::ShapeMismatch { name, a, b } => {
+                    writeln!(f, "~ tensor {} shape {:?} -> {:?}", name, a, b)?
+                }
+                TensorDiff::Compared { name, a, b, stats } if !stats.is_identical() || a != b => {
+                    writeln!(
+                        f,
+                        "~ tensor {} {} -> {}: max_abs {:.3e} rmse {:.3e} rel_l2 {:.3e} cos {:.6}",
+                        name, a, b, stats.max_abs, stats.rmse, stats.rel_l2, stats.cosine
+                    )?
+                }
+                TensorDiff::Compared { .. } => {}
+            }
+        }
+        Ok(())
+    }
+}
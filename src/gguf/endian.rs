@@ -0,0 +1,397 @@
+//! Byte order of GGUF files.
+//!
+//! GGUF has no byte-order marker: files written on big-endian machines
+//! (e.g. s390x) store every number big-endian, which gguf only reads on a
+//! host of the same order. [`convert_endian`] rewrites a file in the other
+//! order, swapping metadata and tensor data field by field.
+//!
+//! The same rewriting reads legacy files: GGUF v1 stored counts, lengths
+//! and dimensions as 32-bit numbers, widened to 64 bits in v2, which gguf
+//! no longer parses. [`upgrade`] writes them in the current layout.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use super::standalone::Endian;
+use crate::error::{Error, Result};
+use crate::types::Type;
+
+/// Copy the GGUF file `src` to `dst` with every number stored in `to`
+/// byte order. The source order is detected; converting to the order a
+/// file already has just copies it. v1 files are written as the current
+/// version, since no reader accepts v1 in the other order.
+///
+/// Tensor data of unquantized types, legacy and k-quants is supported;
+/// other quantized types are rejected because their blocks mix fields of
+/// different widths this converter does not know about.
+pub fn convert_endian(src: impl AsRef<Path>, dst: impl AsRef<Path>, to: Endian) -> Result<()> {
+    rewrite(src.as_ref(), dst.as_ref(), to, false)
+}
+
+/// Copy the GGUF file `src` to `dst` as the current GGUF version in host
+/// byte order. v1 headers are widened; v2 headers only change their
+/// version, having the same layout. Tensor data is copied as is.
+pub fn upgrade(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+    rewrite(src.as_ref(), dst.as_ref(), Endian::native(), true)
+}
+
+fn rewrite(src: &Path, dst: &Path, to: Endian, upgrade: bool) -> Result<()> {
+    let from = Endian::detect(src)?;
+    let mut converter = Converter::new(
+        BufReader::new(File::open(src)?),
+        BufWriter::new(File::create(dst)?),
+        from,
+        from != to,
+    );
+    converter.upgrade = upgrade;
+    converter.run()?;
+    converter.w.flush()?;
+    Ok(())
+}
+
+/// Length of the header (metadata and tensor infos, padded to the
+/// alignment) of the GGUF file starting with `prefix`; `None` if `prefix`
+/// ends before the header does.
+#[cfg(feature = "http")]
+pub(crate) fn header_len(prefix: &[u8]) -> Result<Option<u64>> {
+    let Some(header) = prefix.get(..8) else {
+        return Ok(None);
+    };
+    let from = Endian::from_header(header.try_into().unwrap())?;
+    let mut walker = Converter::new(prefix, std::io::sink(), from, false);
+    match walker.header() {
+        Ok(header) => Ok(Some(header.data_start())),
+        Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn truncated() -> Error {
+    std::io::Error::from(ErrorKind::UnexpectedEof).into()
+}
+
+/// Rewrite a native-order header as produced by `gguf_get_meta_data` for
+/// the alignment its `general.alignment` declares: tensor offsets are
+/// recomputed and the end is re-padded. gguf itself always lays out with
+/// the alignment of the context, which a writer cannot change.
+pub(crate) fn realign_header(meta: &[u8]) -> Result<Vec<u8>> {
+    let mut walker = Converter::new(meta, std::io::sink(), Endian::native(), false);
+    let header = walker.header()?;
+    let mut out = meta[..header.end as usize].to_vec();
+    let mut offset = 0u64;
+    for t in &header.tensors {
+        let at = t.offset_at as usize;
+        out[at..at + 8].copy_from_slice(&offset.to_ne_bytes());
+        offset += t.size.next_multiple_of(header.alignment);
+    }
+    out.resize(header.data_start() as usize, 0);
+    Ok(out)
+}
+
+struct TensorLayout {
+    ty: Type,
+    size: u64,
+    offset: u64,
+    /// Position of the offset field in the source header.
+    offset_at: u64,
+}
+
+struct HeaderLayout {
+    /// In header order.
+    tensors: Vec<TensorLayout>,
+    /// End of the tensor infos in the source, before padding.
+    end: u64,
+    alignment: u64,
+}
+
+impl HeaderLayout {
+    fn data_start(&self) -> u64 {
+        self.end.next_multiple_of(self.alignment)
+    }
+}
+
+struct Converter<R, W> {
+    r: R,
+    w: W,
+    swap: bool,
+    from: Endian,
+    /// Write the current version whatever the source's.
+    upgrade: bool,
+    /// Source is v1, whose 32-bit sizes are written as 64-bit.
+    legacy: bool,
+    /// Bytes read so far.
+    pos: u64,
+    /// Bytes written beyond `pos`, from widening.
+    grown: u64,
+}
+
+impl<R: Read, W: Write> Converter<R, W> {
+    fn new(r: R, w: W, from: Endian, swap: bool) -> Self {
+        Converter {
+            r,
+            w,
+            swap,
+            from,
+            upgrade: false,
+            legacy: false,
+            pos: 0,
+            grown: 0,
+        }
+    }
+
+    /// Copy `n` bytes unchanged.
+    fn raw(&mut self, n: u64) -> Result<()> {
+        let copied = std::io::copy(&mut (&mut self.r).take(n), &mut self.w)?;
+        if copied != n {
+            return Err(truncated());
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    /// Read a `width`-byte number.
+    fn read_word(&mut self, width: usize) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        let bytes = &mut buf[..width];
+        self.r.read_exact(bytes)?;
+        self.pos += width as u64;
+        let mut value = 0u64;
+        for i in 0..width {
+            let b = match self.from {
+                Endian::Little => bytes[width - 1 - i],
+                Endian::Big => bytes[i],
+            };
+            value = value << 8 | b as u64;
+        }
+        Ok(value)
+    }
+
+    /// Write `value` as a `width`-byte number in the target order.
+    fn write_word(&mut self, value: u64, width: usize) -> Result<()> {
+        let little = (self.from == Endian::Little) != self.swap;
+        let bytes = if little {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        let bytes = if little {
+            &bytes[..width]
+        } else {
+            &bytes[8 - width..]
+        };
+        self.w.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Copy a `width`-byte number, returning its value.
+    fn word(&mut self, width: usize) -> Result<u64> {
+        let value = self.read_word(width)?;
+        self.write_word(value, width)?;
+        Ok(value)
+    }
+
+    /// Copy a count, length or dimension: 32-bit in v1, 64-bit since.
+    fn size(&mut self) -> Result<u64> {
+        if !self.legacy {
+            return self.word(8);
+        }
+        let value = self.read_word(4)?;
+        self.write_word(value, 8)?;
+        self.grown += 4;
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.size()?;
+        let mut bytes = Vec::new();
+        (&mut self.r).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(truncated());
+        }
+        self.w.write_all(&bytes)?;
+        self.pos += len;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Copy a value of gguf type `ty`, returning it if it is an integer.
+    fn value(&mut self, ty: u64) -> Result<Option<u64>> {
+        let width = match ty as crate::gguf_type {
+            crate::gguf_type_GGUF_TYPE_UINT8
+            | crate::gguf_type_GGUF_TYPE_INT8
+            | crate::gguf_type_GGUF_TYPE_BOOL => 1,
+            crate::gguf_type_GGUF_TYPE_UINT16 | crate::gguf_type_GGUF_TYPE_INT16 => 2,
+            crate::gguf_type_GGUF_TYPE_UINT32
+            | crate::gguf_type_GGUF_TYPE_INT32
+            | crate::gguf_type_GGUF_TYPE_FLOAT32 => 4,
+            crate::gguf_type_GGUF_TYPE_UINT64
+            | crate::gguf_type_GGUF_TYPE_INT64
+            | crate::gguf_type_GGUF_TYPE_FLOAT64 => 8,
+            crate::gguf_type_GGUF_TYPE_STRING => {
+                self.string()?;
+                return Ok(None);
+            }
+            crate::gguf_type_GGUF_TYPE_ARRAY => {
+                let elem = self.word(4)?;
+                if elem == crate::gguf_type_GGUF_TYPE_ARRAY as u64 {
+                    return Err(Error::InvalidFormat("nested GGUF array".to_string()));
+                }
+                for _ in 0..self.size()? {
+                    self.value(elem)?;
+                }
+                return Ok(None);
+            }
+            _ => return Err(Error::InvalidFormat(format!("unknown GGUF type {}", ty))),
+        };
+        self.word(width).map(Some)
+    }
+
+    /// Copy the header up to the end of the tensor infos.
+    fn header(&mut self) -> Result<HeaderLayout> {
+        self.raw(4)?;
+        let version = self.read_word(4)?;
+        if version == 0 || version > crate::GGUF_VERSION as u64 {
+            return Err(Error::InvalidFormat(format!(
+                "GGUF version {} is not supported",
+                version
+            )));
+        }
+        self.legacy = version == 1;
+        if self.legacy || self.upgrade {
+            self.write_word(crate::GGUF_VERSION as u64, 4)?;
+        } else {
+            self.write_word(version, 4)?;
+        }
+        let n_tensors = self.size()?;
+        let n_kv = self.size()?;
+        let mut alignment = crate::GGUF_DEFAULT_ALIGNMENT as u64;
+        for _ in 0..n_kv {
+            let key = self.string()?;
+            let ty = self.word(4)?;
+            let value = self.value(ty)?;
+            if key == "general.alignment" {
+                alignment = value.filter(|&a| a.is_power_of_two()).ok_or_else(|| {
+                    Error::InvalidFormat("general.alignment must be a power of two".to_string())
+                })?;
+            }
+        }
+        let mut tensors = Vec::new();
+        for _ in 0..n_tensors {
+            let name = self.string()?;
+            let n_dims = self.word(4)?;
+            if n_dims > crate::GGML_MAX_DIMS as u64 {
+                return Err(Error::InvalidFormat(format!(
+                    "tensor {:?} has {} dimensions",
+                    name, n_dims
+                )));
+            }
+            let mut ne = [1i64; 4];
+            for d in ne.iter_mut().take(n_dims as usize) {
+                *d = self.size()? as i64;
+            }
+            let raw_ty = self.word(4)?;
+            let ty = Type::from_raw(raw_ty as crate::ggml_type).ok_or_else(|| {
+                Error::InvalidFormat(format!("tensor {:?} has unknown type {}", name, raw_ty))
+            })?;
+            let offset_at = self.pos;
+            let offset = self.word(8)?;
+            let rows: i64 = ne[1..].iter().product();
+            tensors.push(TensorLayout {
+                ty,
+                size: ty.row_size(ne[0]) as u64 * rows as u64,
+                offset,
+                offset_at,
+            });
+        }
+        Ok(HeaderLayout {
+            tensors,
+            end: self.pos,
+            alignment,
+        })
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let mut header = self.header()?;
+        let data_start = header.data_start();
+        if !self.legacy {
+            self.raw(data_start - self.pos)?;
+        } else {
+            // the widened header needs its own padding; offsets are
+            // relative to the data section and stay valid
+            let skip = data_start - self.pos;
+            let skipped = std::io::copy(&mut (&mut self.r).take(skip), &mut std::io::sink())?;
+            if skipped != skip {
+                return Err(truncated());
+            }
+            self.pos = data_start;
+            let end = header.end + self.grown;
+            let pad = end.next_multiple_of(header.alignment) - end;
+            self.w.write_all(&vec![0u8; pad as usize])?;
+        }
+        let tensors = &mut header.tensors;
+        tensors.sort_by_key(|t| t.offset);
+        for t in tensors.iter() {
+            let gap = (data_start + t.offset)
+                .checked_sub(self.pos)
+                .ok_or_else(|| Error::InvalidFormat("overlapping tensor data".to_string()))?;
+            self.raw(gap)?;
+            self.tensor_data(t)?;
+        }
+        // trailing padding
+        std::io::copy(&mut self.r, &mut self.w)?;
+        Ok(())
+    }
+
+    fn tensor_data(&mut self, t: &TensorLayout) -> Result<()> {
+        if !self.swap {
+            return self.raw(t.size);
+        }
+        let fields = swap_fields(t.ty).ok_or_else(|| {
+            Error::InvalidArgument(format!("cannot byte-swap {} tensor data", t.ty))
+        })?;
+        let block = t.ty.type_size();
+        let chunk_blocks = (1 << 20) / block;
+        let mut buf = vec![0u8; chunk_blocks * block];
+        let mut left = t.size as usize;
+        while left > 0 {
+            let n = left.min(buf.len());
+            let chunk = &mut buf[..n];
+            self.r.read_exact(chunk)?;
+            for b in chunk.chunks_exact_mut(block) {
+                for &(offset, width, count) in fields {
+                    for field in b[offset..offset + width * count].chunks_exact_mut(width) {
+                        field.reverse();
+                    }
+                }
+            }
+            self.w.write_all(chunk)?;
+            self.pos += n as u64;
+            left -= n;
+        }
+        Ok(())
+    }
+}
+
+/// Multi-byte fields of one block of `ty` as `(offset, width, count)`,
+/// following the block structs of ggml-common.h.
+fn swap_fields(ty: Type) -> Option<&'static [(usize, usize, usize)]> {
+    Some(match ty {
+        Type::I8 => &[],
+        Type::F16 | Type::BF16 | Type::I16 => &[(0, 2, 1)],
+        Type::F32 | Type::I32 => &[(0, 4, 1)],
+        Type::F64 | Type::I64 => &[(0, 8, 1)],
+        // d
+        Type::Q4_0 | Type::Q5_0 | Type::Q8_0 => &[(0, 2, 1)],
+        // d, m (or d, s)
+        Type::Q4_1 | Type::Q5_1 | Type::Q8_1 | Type::Q4_K | Type::Q5_K => &[(0, 2, 2)],
+        // scales[16], qs[64], d, dmin
+        Type::Q2_K => &[(80, 2, 2)],
+        // hmask[32], qs[64], scales[12], d
+        Type::Q3_K => &[(108, 2, 1)],
+        // ql[128], qh[64], scales[16], d
+        Type::Q6_K => &[(208, 2, 1)],
+        // f32 d, qs[256], i16 bsums[16]
+        Type::Q8_K => &[(0, 4, 1), (260, 2, 16)],
+        _ => return None,
+    })
+}
@@ -0,0 +1,129 @@
+//! Hashes of tensor data for verifying model provenance.
+//!
+//! Follows llama.cpp's `gguf-hash`: each tensor's data is hashed on its
+//! own, and the model fingerprint hashes the data of all tensors in file
+//! order. Metadata is not included, so re-tagging a file keeps its
+//! fingerprint.
+
+use std::fmt;
+
+use sha2::Digest;
+
+use super::reader::GgufFile;
+use crate::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Sha256,
+    /// xxHash64 with seed 0.
+    Xxh64,
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Xxh64 => "xxh64",
+        }
+    }
+
+    /// Parse a name as printed by [`HashAlgorithm::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "xxh64" => Some(HashAlgorithm::Xxh64),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Xxh64 => Hasher::Xxh64(xxhash_rust::xxh64::Xxh64::new(0)),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Xxh64(xxhash_rust::xxh64::Xxh64),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Xxh64(h) => h.update(data),
+        }
+    }
+
+    /// Lowercase hex digest; xxh64 is printed big-endian like `xxhsum`.
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(h) => h.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+            Hasher::Xxh64(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+/// Digest of one tensor's data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorHash {
+    pub name: String,
+    pub digest: String,
+}
+
+/// Per-tensor digests and the digest of all tensor data of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub algorithm: HashAlgorithm,
+    /// In file order.
+    pub tensors: Vec<TensorHash>,
+    pub model: String,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in &self.tensors {
+            writeln!(f, "{}  {}:{}", t.digest, self.algorithm, t.name)?;
+        }
+        writeln!(f, "{}  {}", self.model, self.algorithm)
+    }
+}
+
+impl GgufFile {
+    /// Digest of the data of tensor `name`.
+    pub fn hash_tensor(&self, name: &str, algorithm: HashAlgorithm) -> Result<String> {
+        let mut hasher = algorithm.hasher();
+        hasher.update(&self.read_tensor_data(name)?);
+        Ok(hasher.finish())
+    }
+
+    /// Hash every tensor, and all of them together for the model
+    /// fingerprint. Reads each tensor's data once.
+    pub fn fingerprint(&self, algorithm: HashAlgorithm) -> Result<Fingerprint> {
+        let mut model = algorithm.hasher();
+        let mut tensors = Vec::new();
+        for info in self.tensor_infos() {
+            let data = self.read_tensor_data(&info.name)?;
+            let mut hasher = algorithm.hasher();
+            hasher.update(&data);
+            model.update(&data);
+            tensors.push(TensorHash {
+                name: info.name,
+                digest: hasher.finish(),
+            });
+        }
+        Ok(Fingerprint {
+            algorithm,
+            tensors,
+            model: model.finish(),
+        })
+    }
+}
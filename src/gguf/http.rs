@@ -0,0 +1,311 @@
+//! Remote GGUF files read over HTTP range requests.
+//!
+//! Only the header is downloaded on open. It is stored at the start of a
+//! sparse cache file of the remote file's size, and tensor data is fetched
+//! into the same file when first read. A sidecar `.ranges` file records
+//! which byte ranges are present, so the cache survives between runs.
+//!
+//! The sidecar also records the remote file's ETag (or Last-Modified),
+//! which every later request sends as `If-Range`. When the file changes on
+//! the server the cache is discarded, rather than mixing its old and new
+//! bytes.
+
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::endian::header_len;
+use super::reader::GgufFile;
+use crate::error::{Error, Result};
+
+/// Size of the first request; grown until the header fits.
+const FIRST_FETCH: u64 = 1 << 20;
+
+pub(crate) struct Remote {
+    url: String,
+    agent: ureq::Agent,
+    len: u64,
+    /// ETag, or Last-Modified, of the remote file the cache holds.
+    validator: Option<String>,
+    ranges_path: PathBuf,
+    /// Byte ranges present in the cache file, sorted and disjoint.
+    fetched: RefCell<Vec<Range<u64>>>,
+}
+
+impl GgufFile {
+    /// Open a GGUF file served over HTTP(S), caching downloaded bytes in
+    /// `cache_dir`. The server must support range requests.
+    ///
+    /// Metadata is available immediately; [`GgufFile::read_tensor_data`]
+    /// downloads a tensor's bytes the first time they are read.
+    pub fn open_url(url: &str, cache_dir: impl AsRef<Path>) -> Result<Self> {
+        let cache_dir = cache_dir.as_ref();
+        std::fs::create_dir_all(cache_dir)?;
+        let stem = format!("{:016x}", fnv1a(url.as_bytes()));
+        let cache_path = cache_dir.join(format!("{}.gguf", stem));
+
+        let agent = ureq::AgentBuilder::new().build();
+        let first = get_range(&agent, url, 0..FIRST_FETCH, None)?;
+        let (mut header, len, validator) = (first.bytes, first.total, first.validator);
+        let header_end = loop {
+            if let Some(end) = header_len(&header)? {
+                break end;
+            }
+            let have = header.len() as u64;
+            if have >= len {
+                return Err(Error::InvalidFormat(format!(
+                    "{} ends inside the GGUF header",
+                    url
+                )));
+            }
+            let more = get_range(&agent, url, have..(have * 2).min(len), validator.as_deref())?;
+            if more.validator != validator {
+                return Err(changed(url));
+            }
+            header.extend_from_slice(&more.bytes);
+        };
+
+        let remote = Remote {
+            url: url.to_string(),
+            agent,
+            len,
+            validator,
+            ranges_path: cache_dir.join(format!("{}.ranges", stem)),
+            fetched: RefCell::new(Vec::new()),
+        };
+        remote.open_cache(&cache_path)?;
+        let fetched = 0..(header.len() as u64).min(len);
+        debug_assert!(header_end <= fetched.end);
+        let mut cache = OpenOptions::new().write(true).open(&cache_path)?;
+        cache.write_all(&header)?;
+        remote.record(fetched)?;
+
+        let mut file = GgufFile::parse(&cache_path, len, None)?;
+        file.remote = Some(remote);
+        Ok(file)
+    }
+
+    /// The URL this file was opened from with [`GgufFile::open_url`].
+    pub fn url(&self) -> Option<&str> {
+        self.remote.as_ref().map(|r| r.url.as_str())
+    }
+}
+
+impl Remote {
+    /// Reuse the cache file if it belongs to the same version of the remote
+    /// file, by validator and length, otherwise start over with an empty one.
+    fn open_cache(&self, path: &Path) -> Result<()> {
+        let cache = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let ranges = std::fs::read_to_string(&self.ranges_path).unwrap_or_default();
+        let mut lines = ranges.lines();
+        if cache.metadata()?.len() == self.len
+            && lines.next().and_then(|l| l.parse().ok()) == Some(self.len)
+            && lines.next() == Some(self.validator.as_deref().unwrap_or(""))
+        {
+            let mut fetched = self.fetched.borrow_mut();
+            for line in lines {
+                let Some((start, end)) = line.split_once(' ') else {
+                    continue;
+                };
+                if let (Ok(start), Ok(end)) = (start.parse(), end.parse()) {
+                    fetched.push(start..end);
+                }
+            }
+            return Ok(());
+        }
+        cache.set_len(0)?;
+        cache.set_len(self.len)?;
+        self.save()
+    }
+
+    /// Make sure `range` of the cache file at `path` is downloaded.
+    pub(crate) fn fetch(&self, path: &Path, range: Range<u64>) -> Result<()> {
+        if range.end > self.len {
+            return Err(Error::InvalidFormat(format!(
+                "range {:?} past the end of {} ({} bytes)",
+                range, self.url, self.len
+            )));
+        }
+        let missing = self.missing(range);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let mut cache = OpenOptions::new().write(true).open(path)?;
+        for gap in missing {
+            let part = get_range(
+                &self.agent,
+                &self.url,
+                gap.clone(),
+                self.validator.as_deref(),
+            )?;
+            if part.validator != self.validator {
+                self.discard(&cache)?;
+                return Err(changed(&self.url));
+            }
+            if part.bytes.len() as u64 != gap.end - gap.start {
+                return Err(Error::InvalidFormat(format!(
+                    "{} returned {} bytes for range {:?}",
+                    self.url,
+                    part.bytes.len(),
+                    gap
+                )));
+            }
+            cache.seek(SeekFrom::Start(gap.start))?;
+            cache.write_all(&part.bytes)?;
+            self.record(gap)?;
+        }
+        Ok(())
+    }
+
+    /// Parts of `range` not yet in the cache.
+    fn missing(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut gaps = Vec::new();
+        let mut pos = range.start;
+        for r in self.fetched.borrow().iter() {
+            if r.end <= pos {
+                continue;
+            }
+            if r.start >= range.end {
+                break;
+            }
+            if r.start > pos {
+                gaps.push(pos..r.start);
+            }
+            pos = pos.max(r.end);
+        }
+        if pos < range.end {
+            gaps.push(pos..range.end);
+        }
+        gaps
+    }
+
+    fn record(&self, range: Range<u64>) -> Result<()> {
+        {
+            let mut fetched = self.fetched.borrow_mut();
+            fetched.push(range);
+            fetched.sort_by_key(|r| r.start);
+            let mut merged: Vec<Range<u64>> = Vec::with_capacity(fetched.len());
+            for r in fetched.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                    _ => merged.push(r),
+                }
+            }
+            *fetched = merged;
+        }
+        self.save()
+    }
+
+    /// Empty the cache of a remote file that changed, so that the next
+    /// [`GgufFile::open_url`] starts over.
+    fn discard(&self, cache: &std::fs::File) -> Result<()> {
+        self.fetched.borrow_mut().clear();
+        cache.set_len(0)?;
+        match std::fs::remove_file(&self.ranges_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut out = format!(
+            "{}\n{}\n",
+            self.len,
+            self.validator.as_deref().unwrap_or("")
+        );
+        for r in self.fetched.borrow().iter() {
+            out.push_str(&format!("{} {}\n", r.start, r.end));
+        }
+        std::fs::write(&self.ranges_path, out)?;
+        Ok(())
+    }
+}
+
+/// A response to a range request.
+struct Part {
+    bytes: Vec<u8>,
+    /// Size of the whole remote file.
+    total: u64,
+    /// Its strong ETag, or else its Last-Modified date.
+    validator: Option<String>,
+}
+
+/// GET `range` of `url` (clamped by the server to the file size). With
+/// `if_range`, the validator of the version the bytes must come from, a
+/// changed file is answered with no bytes and its new validator.
+fn get_range(
+    agent: &ureq::Agent,
+    url: &str,
+    range: Range<u64>,
+    if_range: Option<&str>,
+) -> Result<Part> {
+    let mut request = agent
+        .get(url)
+        .set("Range", &format!("bytes={}-{}", range.start, range.end - 1));
+    if let Some(validator) = if_range {
+        request = request.set("If-Range", validator);
+    }
+    let response = request.call().map_err(http_error)?;
+    // If-Range takes no weak ETags
+    let validator = response
+        .header("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| response.header("Last-Modified"))
+        .map(str::to_string);
+    let stale = if_range.is_some() && validator.as_deref() != if_range;
+    let total = match response.status() {
+        206 => response
+            .header("Content-Range")
+            .and_then(|v| v.rsplit_once('/'))
+            .and_then(|(_, total)| total.parse().ok()),
+        // the whole file; fine as long as we wanted its start, and the
+        // answer to an If-Range that no longer matches
+        200 if range.start == 0 || stale => response
+            .header("Content-Length")
+            .and_then(|v| v.parse().ok()),
+        status => {
+            return Err(Error::InvalidFormat(format!(
+                "{} answered a range request with status {}",
+                url, status
+            )))
+        }
+    }
+    .ok_or_else(|| Error::InvalidFormat(format!("{} did not report its size", url)))?;
+    let mut bytes = Vec::new();
+    if !stale {
+        response
+            .into_reader()
+            .take(range.end - range.start)
+            .read_to_end(&mut bytes)?;
+    }
+    Ok(Part {
+        bytes,
+        total,
+        validator,
+    })
+}
+
+fn changed(url: &str) -> Error {
+    Error::InvalidFormat(format!(
+        "{} changed on the server since it was cached; open it again",
+        url
+    ))
+}
+
+fn http_error(e: ureq::Error) -> Error {
+    Error::Io(std::io::Error::other(e.to_string()))
+}
+
+/// 64-bit FNV-1a, a stable name for a URL's cache files.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
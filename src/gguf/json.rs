@@ -0,0 +1,189 @@
+//! Metadata as JSON, for review and editing outside Rust.
+//!
+//! Every key maps to an object holding its GGUF type and value, so a file
+//! can be exported, edited and applied back without losing the types:
+//!
+//! ```json
+//! {
+//!   "general.architecture": { "type": "str", "value": "llama" },
+//!   "llama.block_count": { "type": "u32", "value": 32 },
+//!   "tokenizer.ggml.scores": { "type": "arr", "element": "f32", "value": [0.0, -1.5] }
+//! }
+//! ```
+//!
+//! Non-finite floats are written as the strings `"nan"`, `"inf"` and
+//! `"-inf"`, which JSON has no numbers for.
+
+use serde_json::{Map, Number, Value};
+
+use super::reader::GgufFile;
+use super::value::{GgufType, GgufValue};
+use super::writer::GgufWriter;
+use crate::error::{Error, Result};
+
+fn parse_type(name: &str) -> Result<GgufType> {
+    GgufType::from_name(name)
+        .ok_or_else(|| Error::InvalidFormat(format!("unknown GGUF type {:?}", name)))
+}
+
+fn float_to_json(v: f64) -> Value {
+    match Number::from_f64(v) {
+        Some(n) => Value::Number(n),
+        None if v.is_nan() => Value::String("nan".to_string()),
+        None if v > 0.0 => Value::String("inf".to_string()),
+        None => Value::String("-inf".to_string()),
+    }
+}
+
+fn to_json(value: &GgufValue) -> Value {
+    match value {
+        GgufValue::U8(v) => Value::Number((*v).into()),
+        GgufValue::I8(v) => Value::Number((*v).into()),
+        GgufValue::U16(v) => Value::Number((*v).into()),
+        GgufValue::I16(v) => Value::Number((*v).into()),
+        GgufValue::U32(v) => Value::Number((*v).into()),
+        GgufValue::I32(v) => Value::Number((*v).into()),
+        GgufValue::U64(v) => Value::Number((*v).into()),
+        GgufValue::I64(v) => Value::Number((*v).into()),
+        GgufValue::F32(v) => float_to_json(*v as f64),
+        GgufValue::F64(v) => float_to_json(*v),
+        GgufValue::Bool(v) => Value::Bool(*v),
+        GgufValue::String(v) => Value::String(v.clone()),
+        GgufValue::Array(items) => Value::Array(items.iter().map(to_json).collect()),
+    }
+}
+
+fn entry_to_json(value: &GgufValue) -> Result<Value> {
+    let mut entry = Map::new();
+    entry.insert(
+        "type".to_string(),
+        Value::String(value.value_type().name().to_string()),
+    );
+    if let Some(ty) = value.array_type()? {
+        entry.insert("element".to_string(), Value::String(ty.name().to_string()));
+    }
+    entry.insert("value".to_string(), to_json(value));
+    Ok(Value::Object(entry))
+}
+
+fn from_json(key: &str, ty: GgufType, json: &Value) -> Result<GgufValue> {
+    let mismatch = || Error::TypeMismatch {
+        expected: format!("{} for {:?}", ty, key),
+        found: json.to_string(),
+    };
+    let int = || {
+        json.as_i64()
+            .map(i128::from)
+            .or(json.as_u64().map(i128::from))
+    };
+    let float = || match json.as_str() {
+        Some("nan") => Some(f64::NAN),
+        Some("inf") => Some(f64::INFINITY),
+        Some("-inf") => Some(f64::NEG_INFINITY),
+        _ => json.as_f64(),
+    };
+    macro_rules! integer {
+        ($variant:ident) => {
+            int()
+                .and_then(|v| v.try_into().ok())
+                .map(GgufValue::$variant)
+                .ok_or_else(mismatch)
+        };
+    }
+    match ty {
+        GgufType::U8 => integer!(U8),
+        GgufType::I8 => integer!(I8),
+        GgufType::U16 => integer!(U16),
+        GgufType::I16 => integer!(I16),
+        GgufType::U32 => integer!(U32),
+        GgufType::I32 => integer!(I32),
+        GgufType::U64 => integer!(U64),
+        GgufType::I64 => integer!(I64),
+        GgufType::F32 => float()
+            .map(|v| GgufValue::F32(v as f32))
+            .ok_or_else(mismatch),
+        GgufType::F64 => float().map(GgufValue::F64).ok_or_else(mismatch),
+        GgufType::Bool => json.as_bool().map(GgufValue::Bool).ok_or_else(mismatch),
+        GgufType::String => json
+            .as_str()
+            .map(|s| GgufValue::String(s.to_string()))
+            .ok_or_else(mismatch),
+        GgufType::Array => Err(Error::InvalidFormat(format!("nested array in {:?}", key))),
+    }
+}
+
+fn entry_from_json(key: &str, entry: &Value) -> Result<GgufValue> {
+    let field = |name: &str| {
+        entry
+            .get(name)
+            .ok_or_else(|| Error::InvalidFormat(format!("{:?} has no {:?}", key, name)))
+    };
+    let type_name = |name: &str| {
+        field(name)?
+            .as_str()
+            .ok_or_else(|| Error::InvalidFormat(format!("{:?} of {:?} is not a string", name, key)))
+    };
+    let ty = parse_type(type_name("type")?)?;
+    let value = field("value")?;
+    if ty != GgufType::Array {
+        return from_json(key, ty, value);
+    }
+    let items = value
+        .as_array()
+        .ok_or_else(|| Error::InvalidFormat(format!("value of {:?} is not an array", key)))?;
+    if items.is_empty() && entry.get("element").is_none() {
+        return Ok(GgufValue::Array(Vec::new()));
+    }
+    let element = parse_type(type_name("element")?)?;
+    items
+        .iter()
+        .map(|item| from_json(key, element, item))
+        .collect::<Result<_>>()
+        .map(GgufValue::Array)
+}
+
+impl GgufFile {
+    /// All metadata as a pretty-printed JSON object, in file order.
+    pub fn metadata_to_json(&self) -> Result<String> {
+        let mut root = Map::new();
+        for (key, value) in self.metadata() {
+            root.insert(key, entry_to_json(&value)?);
+        }
+        serde_json::to_string_pretty(&Value::Object(root))
+            .map_err(|e| Error::InvalidFormat(format!("cannot encode metadata: {}", e)))
+    }
+}
+
+impl GgufWriter<'_> {
+    /// Apply metadata edited as JSON, in the format of
+    /// [`GgufFile::metadata_to_json`]: every key is set to its value, and
+    /// keys mapped to `null` are removed. Keys not in `json` are kept.
+    ///
+    /// The whole document is checked before anything is changed.
+    pub fn apply_json_metadata(&mut self, json: &str) -> Result<&mut Self> {
+        let root: Value = serde_json::from_str(json)
+            .map_err(|e| Error::InvalidFormat(format!("invalid metadata JSON: {}", e)))?;
+        let root = root
+            .as_object()
+            .ok_or_else(|| Error::InvalidFormat("metadata JSON is not an object".to_string()))?;
+        let mut edits = Vec::new();
+        for (key, entry) in root {
+            let value = match entry {
+                Value::Null => None,
+                _ => Some(entry_from_json(key, entry)?),
+            };
+            edits.push((key, value));
+        }
+        for (key, value) in edits {
+            match value {
+                Some(value) => {
+                    self.set_value(key, &value)?;
+                }
+                None => {
+                    self.remove(key)?;
+                }
+            }
+        }
+        Ok(self)
+    }
+}
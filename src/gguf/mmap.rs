@@ -0,0 +1,357 @@
+//! Loading GGUF tensors from a memory-mapped file.
+
+use std::fs::File;
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use super::reader::GgufFile;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+/// Expected access pattern of a mapping, passed to `madvise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MmapAdvice {
+    /// Let the kernel read ahead moderately.
+    #[default]
+    Normal,
+    /// Start reading the whole file in the background now
+    /// (`MADV_WILLNEED`).
+    WillNeed,
+    /// Disable read-ahead (`MADV_RANDOM`): only touched pages are read,
+    /// which suits models that run a fraction of their weights, such as
+    /// mixtures of experts.
+    Random,
+    /// Read ahead aggressively (`MADV_SEQUENTIAL`).
+    Sequential,
+}
+
+/// How [`GgufModel::open_with`] maps a file: trade a longer open for a
+/// faster first compute, or the reverse.
+///
+/// ```ignore
+/// // return at once and warm the page cache while the caller sets up
+/// let model = GgufModel::open_with(path, MmapParams::new().prefetch(true))?;
+/// // block until the whole file is in memory
+/// let model = GgufModel::open_with(path, MmapParams::new().populate(true))?;
+/// ```
+///
+/// Without `mmap` (on non-Unix systems) the file is read up front and
+/// these have no effect.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmapParams {
+    populate: bool,
+    advice: MmapAdvice,
+    prefetch: bool,
+}
+
+impl MmapParams {
+    /// Map lazily: pages are read from disk when first touched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the whole file while mapping it (`MAP_POPULATE` on Linux), so
+    /// opening blocks but no compute waits for the disk afterwards.
+    pub fn populate(mut self, populate: bool) -> Self {
+        self.populate = populate;
+        self
+    }
+
+    pub fn advice(mut self, advice: MmapAdvice) -> Self {
+        self.advice = advice;
+        self
+    }
+
+    /// Touch every page of the tensor data on a background thread, in
+    /// file order, so the first compute finds most of it in memory. The
+    /// thread stops when the model is dropped.
+    pub fn prefetch(mut self, prefetch: bool) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+}
+
+/// A private, copy-on-write mapping of a whole file. Pages are read on first
+/// access and writes never reach the file.
+#[cfg(all(unix, not(target_os = "emscripten")))]
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+#[cfg(all(unix, not(target_os = "emscripten")))]
+impl Mmap {
+    fn map(file: &File, params: &MmapParams) -> Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| Error::InvalidArgument("file too large to map".to_string()))?;
+        if len == 0 {
+            // mmap rejects empty mappings; gguf would reject the file anyway
+            return Ok(Mmap {
+                ptr: NonNull::dangling(),
+                len,
+            });
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let flags = if params.populate {
+            libc::MAP_PRIVATE | libc::MAP_POPULATE
+        } else {
+            libc::MAP_PRIVATE
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let flags = libc::MAP_PRIVATE;
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let map = Mmap {
+            ptr: NonNull::new(ptr as *mut u8).ok_or(Error::NullPointer("mmap"))?,
+            len,
+        };
+        let advice = match params.advice {
+            MmapAdvice::Normal => libc::MADV_NORMAL,
+            MmapAdvice::WillNeed => libc::MADV_WILLNEED,
+            MmapAdvice::Random => libc::MADV_RANDOM,
+            MmapAdvice::Sequential => libc::MADV_SEQUENTIAL,
+        };
+        // only a hint: mapping works the same if the kernel ignores it
+        unsafe { libc::madvise(ptr, len, advice) };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        if params.populate {
+            map.touch(0, len, &AtomicBool::new(false));
+        }
+        Ok(map)
+    }
+
+    /// Read one byte of every page in `start..end`, stopping early once
+    /// `stop` is set.
+    fn touch(&self, start: usize, end: usize, stop: &AtomicBool) {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(4096) as usize;
+        let end = end.min(self.len);
+        for offset in (start..end).step_by(page) {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            unsafe { ptr::read_volatile(self.ptr.as_ptr().add(offset)) };
+        }
+    }
+
+    fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(all(unix, not(target_os = "emscripten")))]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len) };
+        }
+    }
+}
+
+/// Without `mmap`, or in the browser where emscripten's `mmap` copies the
+/// file anyway, the file is read up front into an 8-byte aligned buffer.
+#[cfg(any(not(unix), target_os = "emscripten"))]
+struct Mmap {
+    buf: Vec<u64>,
+    len: usize,
+}
+
+#[cfg(any(not(unix), target_os = "emscripten"))]
+impl Mmap {
+    fn map(file: &File, _params: &MmapParams) -> Result<Self> {
+        use std::io::Read;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| Error::InvalidArgument("file too large to map".to_string()))?;
+        let mut buf = vec![0u64; len.div_ceil(8)];
+        let bytes = unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, len) };
+        (&*file).read_exact(bytes)?;
+        Ok(Mmap { buf, len })
+    }
+
+    fn touch(&self, _start: usize, _end: usize, _stop: &AtomicBool) {}
+
+    fn as_ptr(&self) -> *mut u8 {
+        self.buf.as_ptr() as *mut u8
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// The background thread of [`MmapParams::prefetch`]; dropping it stops
+/// the thread and waits for it.
+struct Prefetch {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Prefetch {
+    fn spawn(map: &Mmap, start: usize) -> Result<Self> {
+        // the data was read up front: nothing to prefetch, and no thread to
+        // start in single-threaded wasm
+        #[cfg(any(not(unix), target_os = "emscripten"))]
+        return Ok(Prefetch {
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        });
+        // the mapping outlives the thread: Drop joins it before unmapping
+        struct MapRef(*const Mmap);
+        unsafe impl Send for MapRef {}
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (map, len, flag) = (MapRef(map), map.len(), stop.clone());
+        let thread = std::thread::Builder::new()
+            .name("gguf-prefetch".to_string())
+            .spawn(move || {
+                let map = map; // capture the wrapper, not its raw pointer
+                unsafe { &*map.0 }.touch(start, len, &flag)
+            })?;
+        Ok(Prefetch {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.thread.as_ref().is_none_or(|t| t.is_finished())
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.join();
+    }
+}
+
+/// A GGUF file whose tensors are backed by a mapping of the file itself.
+///
+/// Opening only parses the header: the tensors live in a `no_alloc`
+/// context with their data pointing into the mapping, so pages are read
+/// from disk when first touched. Writing to a tensor modifies a private
+/// copy of the page, never the file. [`open_with`](Self::open_with) reads
+/// the pages earlier instead.
+pub struct GgufModel {
+    // drop order: the prefetch thread, then the tensors, before the
+    // mapping they point into
+    prefetch: Option<Prefetch>,
+    file: GgufFile,
+    // boxed so the prefetch thread's pointer to it survives moves
+    map: Box<Mmap>,
+}
+
+impl GgufModel {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with(path, MmapParams::new())
+    }
+
+    /// Open with control over when the file's pages are read.
+    pub fn open_with(path: impl AsRef<Path>, params: MmapParams) -> Result<Self> {
+        let file = GgufFile::open(path)?;
+        let map = Box::new(Mmap::map(&File::open(file.parsed_path())?, &params)?);
+        let mut model = GgufModel {
+            prefetch: None,
+            file,
+            map,
+        };
+        model.bind_tensors()?;
+        if params.prefetch {
+            model.prefetch = Some(Prefetch::spawn(&model.map, model.file.data_offset())?);
+        }
+        Ok(model)
+    }
+
+    /// Whether the pages requested by [`MmapParams::prefetch`] have all been
+    /// read, or no prefetch was requested.
+    pub fn prefetch_done(&self) -> bool {
+        self.prefetch.as_ref().is_none_or(Prefetch::is_done)
+    }
+
+    /// Block until the prefetch thread, if any, has read every page.
+    pub fn wait_prefetch(&mut self) {
+        if let Some(prefetch) = &mut self.prefetch {
+            prefetch.join();
+        }
+    }
+
+    /// Point every tensor's data at its offset in the mapping.
+    fn bind_tensors(&self) -> Result<()> {
+        let base = self.file.data_offset();
+        let alignment = self.file.alignment();
+        for info in self.file.tensor_infos() {
+            let tensor = self.context().get_tensor(&info.name).ok_or_else(|| {
+                Error::InvalidFormat(format!("tensor {:?} missing from context", info.name))
+            })?;
+            let offset = base + info.offset;
+            if !offset.is_multiple_of(alignment) {
+                return Err(Error::InvalidFormat(format!(
+                    "tensor {:?} at offset {} is not aligned to {} bytes",
+                    info.name, offset, alignment
+                )));
+            }
+            let end = offset + tensor.nbytes();
+            if end > self.map.len() {
+                return Err(Error::InvalidFormat(format!(
+                    "tensor {:?} ends at byte {} past the end of the file ({} bytes)",
+                    info.name,
+                    end,
+                    self.map.len()
+                )));
+            }
+            unsafe { (*tensor.as_ptr()).data = self.map.as_ptr().add(offset) as *mut _ };
+        }
+        Ok(())
+    }
+
+    /// The file's metadata.
+    pub fn file(&self) -> &GgufFile {
+        &self.file
+    }
+
+    /// The `no_alloc` context holding the mapped tensors.
+    pub fn context(&self) -> &Context {
+        self.file.meta_context()
+    }
+
+    pub fn get_tensor(&self, name: &str) -> Option<Tensor<'_>> {
+        self.context().get_tensor(name)
+    }
+
+    /// Tensors in file order.
+    pub fn tensors(&self) -> impl Iterator<Item = Tensor<'_>> + '_ {
+        self.context().tensors()
+    }
+
+    /// Size of the mapped file in bytes.
+    pub fn mapped_len(&self) -> usize {
+        self.map.len()
+    }
+}
@@ -0,0 +1,70 @@
+//! GGUF model files.
+//!
+//! [`GgufFile`] reads the metadata of an existing file as [`GgufValue`]s;
+//! [`GgufWriter`] builds a file from typed metadata and tensor data, and
+//! [`GgufStreamWriter`] streams tensor data too large to hold in memory;
+//! [`GgufModel`] maps a file and exposes its tensors without copying them,
+//! reading the pages on demand or ahead of time as [`MmapParams`] asks.
+//! [`GgufFile::validate`] checks a file's layout and lints its names.
+//! Files in either byte order and of any GGUF version can be read;
+//! [`convert_endian`] and [`upgrade`] rewrite them.
+//! With the `http` feature, `GgufFile::open_url` reads remote files lazily;
+//! with `tokio`, `AsyncGgufReader` loads files without blocking an executor.
+//! [`diff`] compares two files' metadata and dequantized tensor data, and
+//! with `hash`, `GgufFile::fingerprint` hashes it for provenance checks.
+//! With `json`, `GgufFile::metadata_to_json` exports the metadata for
+//! editing and `GgufWriter::apply_json_metadata` applies it back.
+//!
+//! All of the above calls into ggml's gguf and needs the `native` feature.
+//! [`GgufHeader`] and [`GgufBuilder`] read and write files in pure Rust
+//! and build without it.
+
+mod standalone;
+mod value;
+
+cfg_native! {
+    #[cfg(feature = "tokio")]
+    mod async_reader;
+    mod diff;
+    mod endian;
+    #[cfg(feature = "hash")]
+    mod hash;
+    #[cfg(feature = "http")]
+    mod http;
+    #[cfg(feature = "json")]
+    mod json;
+    mod mmap;
+    mod reader;
+    mod stream;
+    mod validate;
+    mod writer;
+}
+
+pub use standalone::{
+    ggml_type_id, ggml_type_name, Endian, GgufBuilder, GgufHeader, GgufTensorHeader,
+};
+pub use value::{GgufType, GgufValue};
+
+cfg_native! {
+    #[cfg(feature = "tokio")]
+    pub use async_reader::{AsyncGgufReader, LoadProgress};
+    pub(crate) use diff::DiffSums;
+    pub use diff::{diff, DiffStats, GgufDiff, MetadataDiff, TensorDiff};
+    pub use endian::{convert_endian, upgrade};
+    #[cfg(feature = "hash")]
+    pub use hash::{Fingerprint, HashAlgorithm, TensorHash};
+    pub use mmap::{GgufModel, MmapAdvice, MmapParams};
+    pub use reader::{GgufFile, GgufTensorInfo};
+    pub use stream::GgufStreamWriter;
+    pub use validate::{Issue, Severity, ValidationReport};
+    pub use writer::{GgufScalar, GgufWriter};
+
+    use crate::error::{Error, Result};
+    use std::ffi::CString;
+
+    /// Keys and tensor names must be valid C strings.
+    pub(crate) fn c_string(what: &str, s: &str) -> Result<CString> {
+        CString::new(s)
+            .map_err(|_| Error::InvalidArgument(format!("{} {:?} contains a NUL byte", what, s)))
+    }
+}
@@ -0,0 +1,318 @@
+//! Reading GGUF metadata.
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::ptr::{self, NonNull};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::c_string;
+use super::endian::upgrade;
+use super::standalone::{detect_version, Endian};
+use super::value::{GgufType, GgufValue};
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::types::Type;
+
+/// An opened GGUF file's header, metadata and tensor infos
+/// (`gguf_init_from_file` without tensor data).
+pub struct GgufFile {
+    ptr: NonNull<crate::gguf_context>,
+    /// `no_alloc` context holding a data-less tensor per tensor info.
+    meta: Context,
+    path: PathBuf,
+    file_size: u64,
+    /// Native-order, current-version copy parsed in place of a foreign-order
+    /// or v1 file; deleted on drop.
+    converted: Option<PathBuf>,
+    /// Where to fetch missing ranges of a file opened with `open_url`.
+    #[cfg(feature = "http")]
+    pub(super) remote: Option<super::http::Remote>,
+}
+
+/// Location, type and shape of one tensor in a GGUF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GgufTensorInfo {
+    pub name: String,
+    /// `None` for type ids this ggml no longer knows.
+    pub ty: Option<Type>,
+    pub ne: [i64; 4],
+    pub n_dims: usize,
+    /// Offset from the start of the data section.
+    pub offset: usize,
+    /// Size of the data in bytes.
+    pub size: usize,
+}
+
+impl GgufFile {
+    /// Parse the header, metadata and tensor infos of `path`.
+    ///
+    /// Files in the other byte order, and GGUF v1 files, which gguf no
+    /// longer parses, are first [upgraded](super::upgrade) to a temporary
+    /// copy, which is what [`GgufModel`](super::GgufModel) maps. v2 files
+    /// are read as they are; writing one back with
+    /// [`GgufWriter::from_file`](super::GgufWriter::from_file) saves it as
+    /// the current version.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        // surface missing files and permissions as I/O errors
+        let file_size = std::fs::metadata(path)?.len();
+        let converted = match detect_version(path) {
+            Ok((endian, version)) if endian != Endian::native() || version == 1 => {
+                let tmp = temp_path();
+                if let Err(e) = upgrade(path, &tmp) {
+                    let _ = std::fs::remove_file(&tmp);
+                    return Err(e);
+                }
+                Some(tmp)
+            }
+            // let gguf report malformed headers
+            _ => None,
+        };
+        let result = Self::parse(path, file_size, converted.clone());
+        if let (Err(_), Some(tmp)) = (&result, &converted) {
+            let _ = std::fs::remove_file(tmp);
+        }
+        result
+    }
+
+    pub(super) fn parse(path: &Path, file_size: u64, converted: Option<PathBuf>) -> Result<Self> {
+        let parsed = converted.as_deref().unwrap_or(path);
+        let c_path = c_string("path", &parsed.to_string_lossy())?;
+        let mut meta = ptr::null_mut();
+        let params = crate::gguf_init_params {
+            no_alloc: true,
+            ctx: &mut meta,
+        };
+        let ptr = unsafe { crate::gguf_init_from_file(c_path.as_ptr(), params) };
+        let ptr = NonNull::new(ptr).ok_or_else(|| {
+            Error::InvalidFormat(format!("{} is not a valid GGUF file", path.display()))
+        })?;
+        let Some(meta) = (unsafe { Context::from_raw(meta) }) else {
+            unsafe { crate::gguf_free(ptr.as_ptr()) };
+            return Err(Error::ContextInit);
+        };
+        Ok(GgufFile {
+            ptr,
+            meta,
+            path: path.to_path_buf(),
+            file_size,
+            converted,
+            #[cfg(feature = "http")]
+            remote: None,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::gguf_context {
+        self.ptr.as_ptr()
+    }
+
+    /// Version of the parsed header: that of the file, or the current
+    /// version for files read through an upgraded copy.
+    pub fn version(&self) -> u32 {
+        unsafe { crate::gguf_get_version(self.as_ptr()) }
+    }
+
+    pub fn alignment(&self) -> usize {
+        unsafe { crate::gguf_get_alignment(self.as_ptr()) }
+    }
+
+    /// Offset of the tensor data section from the start of the file.
+    pub fn data_offset(&self) -> usize {
+        unsafe { crate::gguf_get_data_offset(self.as_ptr()) }
+    }
+
+    pub fn n_kv(&self) -> i64 {
+        unsafe { crate::gguf_get_n_kv(self.as_ptr()) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file gguf parsed: `path()` itself unless it had to be byte-swapped.
+    pub(crate) fn parsed_path(&self) -> &Path {
+        self.converted.as_deref().unwrap_or(&self.path)
+    }
+
+    /// Size of the file in bytes when it was opened.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// The `no_alloc` context holding one data-less tensor per tensor info.
+    pub fn meta_context(&self) -> &Context {
+        &self.meta
+    }
+
+    pub fn n_tensors(&self) -> i64 {
+        unsafe { crate::gguf_get_n_tensors(self.as_ptr()) }
+    }
+
+    pub fn tensor_info(&self, name: &str) -> Option<GgufTensorInfo> {
+        let c_name = c_string("tensor name", name).ok()?;
+        let id = unsafe { crate::gguf_find_tensor(self.as_ptr(), c_name.as_ptr()) };
+        (id >= 0).then(|| self.tensor_info_at(id))
+    }
+
+    /// Tensor infos in file order.
+    pub fn tensor_infos(&self) -> impl Iterator<Item = GgufTensorInfo> + '_ {
+        (0..self.n_tensors()).map(|id| self.tensor_info_at(id))
+    }
+
+    /// Read the data of tensor `name` from the file.
+    pub fn read_tensor_data(&self, name: &str) -> Result<Vec<u8>> {
+        let info = self
+            .tensor_info(name)
+            .ok_or_else(|| Error::InvalidArgument(format!("no tensor named {:?}", name)))?;
+        let start = (self.data_offset() + info.offset) as u64;
+        #[cfg(feature = "http")]
+        if let Some(remote) = &self.remote {
+            remote.fetch(self.parsed_path(), start..start + info.size as u64)?;
+        }
+        let mut file = File::open(self.parsed_path())?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut data = vec![0u8; info.size];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn tensor_info_at(&self, id: i64) -> GgufTensorInfo {
+        let ctx = self.as_ptr();
+        let name = unsafe { CStr::from_ptr(crate::gguf_get_tensor_name(ctx, id)) }
+            .to_string_lossy()
+            .into_owned();
+        // the parser creates a tensor of the same name in the meta context
+        let (ne, n_dims) = self
+            .meta
+            .get_tensor(&name)
+            .map_or(([0; 4], 0), |t| (t.ne(), t.n_dims()));
+        GgufTensorInfo {
+            ty: Type::from_raw(unsafe { crate::gguf_get_tensor_type(ctx, id) }),
+            ne,
+            n_dims,
+            offset: unsafe { crate::gguf_get_tensor_offset(ctx, id) },
+            size: unsafe { crate::gguf_get_tensor_size(ctx, id) },
+            name,
+        }
+    }
+
+    fn find_key(&self, key: &str) -> Option<i64> {
+        let key = c_string("key", key).ok()?;
+        let id = unsafe { crate::gguf_find_key(self.as_ptr(), key.as_ptr()) };
+        (id >= 0).then_some(id)
+    }
+
+    fn key_at(&self, id: i64) -> String {
+        unsafe { CStr::from_ptr(crate::gguf_get_key(self.as_ptr(), id)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.find_key(key).is_some()
+    }
+
+    /// Metadata keys in file order.
+    pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        (0..self.n_kv()).map(|id| self.key_at(id))
+    }
+
+    /// Type of the value stored under `key`.
+    pub fn value_type(&self, key: &str) -> Option<GgufType> {
+        let id = self.find_key(key)?;
+        GgufType::from_raw(unsafe { crate::gguf_get_kv_type(self.as_ptr(), id) })
+    }
+
+    /// The value stored under `key`.
+    pub fn get(&self, key: &str) -> Option<GgufValue> {
+        self.find_key(key).map(|id| self.value_at(id))
+    }
+
+    /// The value under `key` converted to `T`; `Ok(None)` if missing.
+    pub fn get_as<T: TryFrom<GgufValue, Error = Error>>(&self, key: &str) -> Result<Option<T>> {
+        self.get(key).map(T::try_from).transpose()
+    }
+
+    /// All key/value pairs in file order.
+    pub fn metadata(&self) -> impl Iterator<Item = (String, GgufValue)> + '_ {
+        (0..self.n_kv()).map(|id| (self.key_at(id), self.value_at(id)))
+    }
+
+    fn value_at(&self, id: i64) -> GgufValue {
+        let ctx = self.as_ptr();
+        let ty = unsafe { crate::gguf_get_kv_type(ctx, id) };
+        if ty != crate::gguf_type_GGUF_TYPE_ARRAY {
+            return unsafe { scalar_at(ctx, id, ty) };
+        }
+        let n = unsafe { crate::gguf_get_arr_n(ctx, id) };
+        let elem = unsafe { crate::gguf_get_arr_type(ctx, id) };
+        let items = if elem == crate::gguf_type_GGUF_TYPE_STRING {
+            (0..n)
+                .map(|i| {
+                    let s = unsafe { CStr::from_ptr(crate::gguf_get_arr_str(ctx, id, i)) };
+                    GgufValue::String(s.to_string_lossy().into_owned())
+                })
+                .collect()
+        } else {
+            let data = unsafe { crate::gguf_get_arr_data(ctx, id) };
+            (0..n)
+                .map(|i| unsafe { read_elem(data as *const u8, i, elem) })
+                .collect()
+        };
+        GgufValue::Array(items)
+    }
+}
+
+impl Drop for GgufFile {
+    fn drop(&mut self) {
+        unsafe { crate::gguf_free(self.as_ptr()) }
+        if let Some(tmp) = &self.converted {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+}
+
+/// A fresh path in the temporary directory for a byte-swapped copy.
+fn temp_path() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("ggml-rs-{}-{}.gguf", std::process::id(), n))
+}
+
+unsafe fn scalar_at(ctx: *const crate::gguf_context, id: i64, ty: crate::gguf_type) -> GgufValue {
+    match ty {
+        crate::gguf_type_GGUF_TYPE_STRING => GgufValue::String(
+            CStr::from_ptr(crate::gguf_get_val_str(ctx, id))
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        _ => read_elem(crate::gguf_get_val_data(ctx, id) as *const u8, 0, ty),
+    }
+}
+
+/// Element `i` of a packed array of non-string scalars of type `ty`.
+unsafe fn read_elem(data: *const u8, i: usize, ty: crate::gguf_type) -> GgufValue {
+    macro_rules! at {
+        ($t:ty) => {
+            ptr::read_unaligned((data as *const $t).add(i))
+        };
+    }
+    match ty {
+        crate::gguf_type_GGUF_TYPE_UINT8 => GgufValue::U8(at!(u8)),
+        crate::gguf_type_GGUF_TYPE_INT8 => GgufValue::I8(at!(i8)),
+        crate::gguf_type_GGUF_TYPE_UINT16 => GgufValue::U16(at!(u16)),
+        crate::gguf_type_GGUF_TYPE_INT16 => GgufValue::I16(at!(i16)),
+        crate::gguf_type_GGUF_TYPE_UINT32 => GgufValue::U32(at!(u32)),
+        crate::gguf_type_GGUF_TYPE_INT32 => GgufValue::I32(at!(i32)),
+        crate::gguf_type_GGUF_TYPE_FLOAT32 => GgufValue::F32(at!(f32)),
+        crate::gguf_type_GGUF_TYPE_BOOL => GgufValue::Bool(at!(u8) != 0),
+        crate::gguf_type_GGUF_TYPE_UINT64 => GgufValue::U64(at!(u64)),
+        crate::gguf_type_GGUF_TYPE_INT64 => GgufValue::I64(at!(i64)),
+        crate::gguf_type_GGUF_TYPE_FLOAT64 => GgufValue::F64(at!(f64)),
+        // the parser rejects every other type
+        _ => unreachable!("gguf type {}", ty),
+    }
+}
@@ -0,0 +1,804 @@
+//! GGUF reading and writing in pure Rust, without the ggml library.
+//!
+//! This module is all of [`gguf`](super) that builds with the `native`
+//! feature disabled, for metadata tooling on platforms where the CMake
+//! build is not available, and for crates that only inspect or rewrite
+//! model files and would rather not compile ggml:
+//!
+//! ```toml
+//! ggml-rs = { version = "0.1", default-features = false }
+//! ```
+//!
+//! [`GgufHeader`] parses the metadata and tensor infos of a file of any
+//! version, in either byte order; [`GgufBuilder`] writes a file from
+//! metadata and raw tensor data. Tensor types are the ggml type ids stored
+//! in the file; [`ggml_type_name`] names the ones this crate knows.
+//!
+//! ```ignore
+//! let header = GgufHeader::open("model.gguf")?;
+//! println!("{:?}", header.get("general.architecture"));
+//! for t in &header.tensors {
+//!     println!("{} {:?} {:?}", t.name, t.type_name(), &t.ne[..t.n_dims]);
+//! }
+//!
+//! let mut out = GgufBuilder::from_file("model.gguf")?;
+//! out.set("general.name", "renamed")?;
+//! out.write_to_file("renamed.gguf")?;
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::value::{GgufType, GgufValue};
+use crate::error::{Error, Result};
+
+/// Latest GGUF version, `GGUF_VERSION` in gguf.h.
+const VERSION: u32 = 3;
+/// `GGUF_DEFAULT_ALIGNMENT` in gguf.h.
+const DEFAULT_ALIGNMENT: u64 = 32;
+/// `GGML_MAX_DIMS` in ggml.h.
+const MAX_DIMS: usize = 4;
+
+/// Name, block size and block bytes of the ggml types, by id, as in
+/// ggml.c's type traits. Ids 4, 5 and 31..=38 were removed from ggml.
+const GGML_TYPES: &[(u32, &str, u64, u64)] = &[
+    (0, "f32", 1, 4),
+    (1, "f16", 1, 2),
+    (2, "q4_0", 32, 18),
+    (3, "q4_1", 32, 20),
+    (6, "q5_0", 32, 22),
+    (7, "q5_1", 32, 24),
+    (8, "q8_0", 32, 34),
+    (9, "q8_1", 32, 36),
+    (10, "q2_K", 256, 84),
+    (11, "q3_K", 256, 110),
+    (12, "q4_K", 256, 144),
+    (13, "q5_K", 256, 176),
+    (14, "q6_K", 256, 210),
+    (15, "q8_K", 256, 292),
+    (16, "iq2_xxs", 256, 66),
+    (17, "iq2_xs", 256, 74),
+    (18, "iq3_xxs", 256, 98),
+    (19, "iq1_s", 256, 50),
+    (20, "iq4_nl", 32, 18),
+    (21, "iq3_s", 256, 110),
+    (22, "iq2_s", 256, 82),
+    (23, "iq4_xs", 256, 136),
+    (24, "i8", 1, 1),
+    (25, "i16", 1, 2),
+    (26, "i32", 1, 4),
+    (27, "i64", 1, 8),
+    (28, "f64", 1, 8),
+    (29, "iq1_m", 256, 56),
+    (30, "bf16", 1, 2),
+    (34, "tq1_0", 256, 54),
+    (35, "tq2_0", 256, 66),
+    (39, "mxfp4", 32, 17),
+];
+
+fn ggml_type(id: u32) -> Option<&'static (u32, &'static str, u64, u64)> {
+    GGML_TYPES.iter().find(|t| t.0 == id)
+}
+
+/// Name of the ggml type `id` (e.g. `"q4_K"` for 12), `None` for ids this
+/// crate does not know.
+pub fn ggml_type_name(id: u32) -> Option<&'static str> {
+    ggml_type(id).map(|t| t.1)
+}
+
+/// Id of the ggml type named `name`, the inverse of [`ggml_type_name`].
+pub fn ggml_type_id(name: &str) -> Option<u32> {
+    GGML_TYPES.iter().find(|t| t.1 == name).map(|t| t.0)
+}
+
+/// Bytes of a contiguous tensor of type `id` and shape `ne`; `None` for
+/// unknown types and rows that are not whole blocks.
+fn data_size(id: u32, ne: &[i64]) -> Option<u64> {
+    let &(_, _, block, bytes) = ggml_type(id)?;
+    let ne0 = u64::try_from(ne[0]).ok()?;
+    if ne0 % block != 0 {
+        return None;
+    }
+    ne[1..].iter().try_fold(ne0 / block * bytes, |n, &d| {
+        n.checked_mul(u64::try_from(d).ok()?)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    /// Byte order of the host.
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+
+    /// Byte order of a GGUF file, told apart by which reading of the
+    /// version field is plausible.
+    pub fn detect(path: impl AsRef<Path>) -> Result<Self> {
+        detect_version(path).map(|(endian, _)| endian)
+    }
+
+    /// Byte order from the magic and version, the first 8 bytes of a file.
+    pub(crate) fn from_header(header: [u8; 8]) -> Result<Self> {
+        if &header[..4] != b"GGUF" {
+            return Err(Error::InvalidFormat("missing GGUF magic".to_string()));
+        }
+        let version = [header[4], header[5], header[6], header[7]];
+        let plausible = |v: u32| (1..=VERSION).contains(&v);
+        if plausible(u32::from_le_bytes(version)) {
+            Ok(Endian::Little)
+        } else if plausible(u32::from_be_bytes(version)) {
+            Ok(Endian::Big)
+        } else {
+            Err(Error::InvalidFormat(format!(
+                "unrecognized GGUF version bytes {:02x?}",
+                version
+            )))
+        }
+    }
+}
+
+/// Byte order and version of a GGUF file, from its first 8 bytes.
+pub(crate) fn detect_version(path: impl AsRef<Path>) -> Result<(Endian, u32)> {
+    let mut header = [0u8; 8];
+    File::open(path)?.read_exact(&mut header)?;
+    let endian = Endian::from_header(header)?;
+    let version = [header[4], header[5], header[6], header[7]];
+    let version = match endian {
+        Endian::Little => u32::from_le_bytes(version),
+        Endian::Big => u32::from_be_bytes(version),
+    };
+    Ok((endian, version))
+}
+
+/// Name, type, shape and data offset of one tensor in a GGUF header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GgufTensorHeader {
+    pub name: String,
+    /// The ggml type id.
+    pub type_id: u32,
+    pub ne: [i64; 4],
+    pub n_dims: usize,
+    /// Offset from the start of the data section.
+    pub offset: u64,
+}
+
+impl GgufTensorHeader {
+    /// Name of the tensor's type, `None` if this crate does not know it.
+    pub fn type_name(&self) -> Option<&'static str> {
+        ggml_type_name(self.type_id)
+    }
+
+    /// Size of the data in bytes, `None` for types this crate does not
+    /// know.
+    pub fn size(&self) -> Option<u64> {
+        data_size(self.type_id, &self.ne)
+    }
+}
+
+/// The metadata and tensor infos of a GGUF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GgufHeader {
+    /// Version as stored; v1 counts and lengths are widened when read.
+    pub version: u32,
+    pub endian: Endian,
+    /// Alignment of the data section and of every tensor in it.
+    pub alignment: u64,
+    /// In file order, `general.alignment` included.
+    pub metadata: Vec<(String, GgufValue)>,
+    /// In file order.
+    pub tensors: Vec<GgufTensorHeader>,
+    /// Offset of the data section from the start of the file.
+    pub data_offset: u64,
+}
+
+impl GgufHeader {
+    /// Parse the header of the GGUF file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse a header from the start of `r`, which is left at the end of
+    /// the tensor infos, before the padding.
+    pub fn read_from(r: impl Read) -> Result<Self> {
+        Parser {
+            r,
+            endian: Endian::Little,
+            legacy: false,
+            pos: 0,
+        }
+        .header()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn tensor(&self, name: &str) -> Option<&GgufTensorHeader> {
+        self.tensors.iter().find(|t| t.name == name)
+    }
+
+    /// Read the data of tensor `name` from `r`, the file this header was
+    /// parsed from, as stored: in the file's byte order.
+    pub fn read_tensor_data<R: Read + Seek>(&self, r: &mut R, name: &str) -> Result<Vec<u8>> {
+        let t = self
+            .tensor(name)
+            .ok_or_else(|| Error::InvalidArgument(format!("no tensor named {:?}", name)))?;
+        let size = t.size().ok_or_else(|| {
+            Error::InvalidFormat(format!(
+                "tensor {:?} has unknown type {}",
+                t.name, t.type_id
+            ))
+        })?;
+        r.seek(SeekFrom::Start(self.data_offset + t.offset))?;
+        let mut data = Vec::new();
+        r.take(size).read_to_end(&mut data)?;
+        if data.len() as u64 != size {
+            return Err(truncated());
+        }
+        Ok(data)
+    }
+}
+
+fn truncated() -> Error {
+    std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()
+}
+
+struct Parser<R> {
+    r: R,
+    endian: Endian,
+    /// v1, whose counts, lengths and dimensions are 32-bit.
+    legacy: bool,
+    /// Bytes read so far.
+    pos: u64,
+}
+
+impl<R: Read> Parser<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.r.read_exact(&mut buf)?;
+        self.pos += N as u64;
+        if self.endian != Endian::native() {
+            buf.reverse();
+        }
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.bytes().map(u32::from_ne_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.bytes().map(u64::from_ne_bytes)
+    }
+
+    /// A count, length or dimension: 32-bit in v1, 64-bit since.
+    fn size(&mut self) -> Result<u64> {
+        if self.legacy {
+            self.u32().map(u64::from)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.size()?;
+        let mut bytes = Vec::new();
+        (&mut self.r).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(truncated());
+        }
+        self.pos += len;
+        String::from_utf8(bytes)
+            .map_err(|_| Error::InvalidFormat("GGUF string is not UTF-8".to_string()))
+    }
+
+    fn value(&mut self, ty: GgufType) -> Result<GgufValue> {
+        Ok(match ty {
+            GgufType::U8 => GgufValue::U8(u8::from_ne_bytes(self.bytes()?)),
+            GgufType::I8 => GgufValue::I8(i8::from_ne_bytes(self.bytes()?)),
+            GgufType::U16 => GgufValue::U16(u16::from_ne_bytes(self.bytes()?)),
+            GgufType::I16 => GgufValue::I16(i16::from_ne_bytes(self.bytes()?)),
+            GgufType::U32 => GgufValue::U32(u32::from_ne_bytes(self.bytes()?)),
+            GgufType::I32 => GgufValue::I32(i32::from_ne_bytes(self.bytes()?)),
+            GgufType::F32 => GgufValue::F32(f32::from_ne_bytes(self.bytes()?)),
+            GgufType::U64 => GgufValue::U64(u64::from_ne_bytes(self.bytes()?)),
+            GgufType::I64 => GgufValue::I64(i64::from_ne_bytes(self.bytes()?)),
+            GgufType::F64 => GgufValue::F64(f64::from_ne_bytes(self.bytes()?)),
+            GgufType::Bool => match self.bytes::<1>()? {
+                [0] => GgufValue::Bool(false),
+                [1] => GgufValue::Bool(true),
+                [b] => return Err(Error::InvalidFormat(format!("GGUF bool {}", b))),
+            },
+            GgufType::String => GgufValue::String(self.string()?),
+            GgufType::Array => {
+                let elem = self.value_type()?;
+                if elem == GgufType::Array {
+                    return Err(Error::InvalidFormat("nested GGUF array".to_string()));
+                }
+                let n = self.size()?;
+                // grown as read: a corrupt count fails at the end of the
+                // file, not in the allocator
+                let mut items = Vec::new();
+                for _ in 0..n {
+                    items.push(self.value(elem)?);
+                }
+                GgufValue::Array(items)
+            }
+        })
+    }
+
+    fn value_type(&mut self) -> Result<GgufType> {
+        let id = self.u32()?;
+        GgufType::from_id(id)
+            .ok_or_else(|| Error::InvalidFormat(format!("unknown GGUF type {}", id)))
+    }
+
+    fn header(mut self) -> Result<GgufHeader> {
+        let mut first = [0u8; 8];
+        self.r.read_exact(&mut first)?;
+        self.pos = 8;
+        self.endian = Endian::from_header(first)?;
+        let version = [first[4], first[5], first[6], first[7]];
+        let version = match self.endian {
+            Endian::Little => u32::from_le_bytes(version),
+            Endian::Big => u32::from_be_bytes(version),
+        };
+        self.legacy = version == 1;
+
+        let n_tensors = self.size()?;
+        let n_kv = self.size()?;
+        let mut metadata: Vec<(String, GgufValue)> = Vec::new();
+        let mut alignment = DEFAULT_ALIGNMENT;
+        for _ in 0..n_kv {
+            let key = self.string()?;
+            let ty = self.value_type()?;
+            let value = self.value(ty)?;
+            if metadata.iter().any(|(k, _)| *k == key) {
+                return Err(Error::InvalidFormat(format!("duplicate key {:?}", key)));
+            }
+            if key == "general.alignment" {
+                alignment = value
+                    .as_i128()
+                    .and_then(|a| u64::try_from(a).ok())
+                    .filter(|a| a.is_power_of_two())
+                    .ok_or_else(|| {
+                        Error::InvalidFormat("general.alignment must be a power of two".to_string())
+                    })?;
+            }
+            metadata.push((key, value));
+        }
+
+        let mut tensors: Vec<GgufTensorHeader> = Vec::new();
+        for _ in 0..n_tensors {
+            let name = self.string()?;
+            let n_dims = self.u32()? as usize;
+            if n_dims > MAX_DIMS {
+                return Err(Error::InvalidFormat(format!(
+                    "tensor {:?} has {} dimensions",
+                    name, n_dims
+                )));
+            }
+            let mut ne = [1i64; 4];
+            for d in ne.iter_mut().take(n_dims) {
+                *d = i64::try_from(self.size()?)
+                    .map_err(|_| Error::InvalidFormat(format!("tensor {:?} is too large", name)))?;
+            }
+            let type_id = self.u32()?;
+            let offset = self.u64()?;
+            if offset % alignment != 0 {
+                return Err(Error::InvalidFormat(format!(
+                    "tensor {:?} at offset {} is not aligned to {}",
+                    name, offset, alignment
+                )));
+            }
+            if tensors.iter().any(|t| t.name == name) {
+                return Err(Error::InvalidFormat(format!("duplicate tensor {:?}", name)));
+            }
+            tensors.push(GgufTensorHeader {
+                name,
+                type_id,
+                ne,
+                n_dims,
+                offset,
+            });
+        }
+
+        Ok(GgufHeader {
+            version,
+            endian: self.endian,
+            alignment,
+            metadata,
+            tensors,
+            data_offset: self.pos.next_multiple_of(alignment),
+        })
+    }
+}
+
+/// Builds a GGUF file from metadata and raw tensor data, in host byte
+/// order and the current version, laid out as gguf does.
+#[derive(Debug, Clone, Default)]
+pub struct GgufBuilder {
+    metadata: Vec<(String, GgufValue)>,
+    tensors: Vec<(GgufTensorHeader, Vec<u8>)>,
+    alignment: Option<u64>,
+}
+
+impl GgufBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder holding the metadata and tensor data of the file at
+    /// `path`, to write it back with changes. Files in the other byte order
+    /// are rejected, since their tensor data would need swapping.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        let header = GgufHeader::read_from(&mut r)?;
+        if header.endian != Endian::native() {
+            return Err(Error::InvalidArgument(format!(
+                "cannot copy tensor data of a {:?}-endian file",
+                header.endian
+            )));
+        }
+        let mut b = GgufBuilder::new();
+        for (key, value) in &header.metadata {
+            b.set(key, value.clone())?;
+        }
+        for t in &header.tensors {
+            let data = header.read_tensor_data(&mut r, &t.name)?;
+            b.add_tensor(&t.name, t.type_id, &t.ne[..t.n_dims.max(1)], data)?;
+        }
+        Ok(b)
+    }
+
+    /// Alignment of the data section and of every tensor in it.
+    pub fn alignment(&self) -> u64 {
+        self.alignment.unwrap_or(DEFAULT_ALIGNMENT)
+    }
+
+    /// Set a value, replacing any previous value of `key`. Array elements
+    /// must share one type; `general.alignment` must be a `u32` power of
+    /// two.
+    pub fn set(&mut self, key: &str, value: impl Into<GgufValue>) -> Result<&mut Self> {
+        let value = value.into();
+        value.array_type()?;
+        if key == "general.alignment" {
+            self.alignment = Some(match value {
+                GgufValue::U32(a) if a.is_power_of_two() => a.into(),
+                _ => {
+                    return Err(Error::InvalidArgument(format!(
+                        "general.alignment must be a u32 power of two, not {}",
+                        value
+                    )))
+                }
+            });
+        }
+        match self.metadata.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.metadata.push((key.to_string(), value)),
+        }
+        Ok(self)
+    }
+
+    /// Remove `key`; returns whether it was present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let n = self.metadata.len();
+        self.metadata.retain(|(k, _)| k != key);
+        if key == "general.alignment" {
+            self.alignment = None;
+        }
+        self.metadata.len() != n
+    }
+
+    pub fn metadata(&self) -> &[(String, GgufValue)] {
+        &self.metadata
+    }
+
+    /// Add a tensor of ggml type `type_id` and shape `ne` from its raw
+    /// bytes, which must be its exact size if the type is known.
+    pub fn add_tensor(
+        &mut self,
+        name: &str,
+        type_id: u32,
+        ne: &[i64],
+        data: Vec<u8>,
+    ) -> Result<&mut Self> {
+        if ne.is_empty() || ne.len() > MAX_DIMS || ne.iter().any(|&n| n < 0) {
+            return Err(Error::InvalidArgument(format!(
+                "invalid tensor shape {:?}",
+                ne
+            )));
+        }
+        if self.tensors.iter().any(|(t, _)| t.name == name) {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate tensor {:?}",
+                name
+            )));
+        }
+        let mut full = [1i64; 4];
+        full[..ne.len()].copy_from_slice(ne);
+        if let Some(size) = data_size(type_id, &full) {
+            if data.len() as u64 != size {
+                return Err(Error::ShapeMismatch(format!(
+                    "{} bytes for tensor {:?} of {} bytes",
+                    data.len(),
+                    name,
+                    size
+                )));
+            }
+        } else if ggml_type(type_id).is_some() {
+            return Err(Error::ShapeMismatch(format!(
+                "ne[0] = {} of tensor {:?} is not whole {} blocks",
+                ne[0],
+                name,
+                ggml_type_name(type_id).unwrap()
+            )));
+        }
+        self.tensors.push((
+            GgufTensorHeader {
+                name: name.to_string(),
+                type_id,
+                ne: full,
+                n_dims: ne.len(),
+                offset: 0,
+            },
+            data,
+        ));
+        Ok(self)
+    }
+
+    pub fn tensors(&self) -> impl Iterator<Item = &GgufTensorHeader> {
+        self.tensors.iter().map(|(t, _)| t)
+    }
+
+    /// Write the file to `w`.
+    pub fn write_to(&self, w: &mut impl Write) -> Result<()> {
+        let align = self.alignment();
+        let mut header = Vec::new();
+        header.extend_from_slice(b"GGUF");
+        header.extend_from_slice(&VERSION.to_ne_bytes());
+        header.extend_from_slice(&(self.tensors.len() as u64).to_ne_bytes());
+        header.extend_from_slice(&(self.metadata.len() as u64).to_ne_bytes());
+        for (key, value) in &self.metadata {
+            push_string(&mut header, key);
+            push_type(&mut header, value.value_type());
+            push_value(&mut header, value);
+        }
+        let mut offset = 0u64;
+        for (t, data) in &self.tensors {
+            push_string(&mut header, &t.name);
+            header.extend_from_slice(&(t.n_dims as u32).to_ne_bytes());
+            for &d in &t.ne[..t.n_dims] {
+                header.extend_from_slice(&(d as u64).to_ne_bytes());
+            }
+            header.extend_from_slice(&t.type_id.to_ne_bytes());
+            header.extend_from_slice(&offset.to_ne_bytes());
+            offset += (data.len() as u64).next_multiple_of(align);
+        }
+        header.resize((header.len() as u64).next_multiple_of(align) as usize, 0);
+        w.write_all(&header)?;
+        for (_, data) in &self.tensors {
+            w.write_all(data)?;
+            let pad = (data.len() as u64).next_multiple_of(align) - data.len() as u64;
+            w.write_all(&vec![0u8; pad as usize])?;
+        }
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+fn push_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u64).to_ne_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn push_type(out: &mut Vec<u8>, ty: GgufType) {
+    out.extend_from_slice(&ty.id().to_ne_bytes());
+}
+
+/// A value in host byte order; arrays were checked to share one type.
+fn push_value(out: &mut Vec<u8>, v: &GgufValue) {
+    match v {
+        GgufValue::U8(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I8(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::U16(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I16(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::U32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::F32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::Bool(x) => out.push(*x as u8),
+        GgufValue::U64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::F64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::String(s) => push_string(out, s),
+        GgufValue::Array(items) => {
+            // an empty array is stored as u8, as GgufWriter does
+            push_type(
+                out,
+                items.first().map_or(GgufType::U8, GgufValue::value_type),
+            );
+            out.extend_from_slice(&(items.len() as u64).to_ne_bytes());
+            for item in items {
+                push_value(out, item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Writes a GGUF header by hand in either byte order and version.
+    struct Fixture {
+        out: Vec<u8>,
+        big: bool,
+        legacy: bool,
+    }
+
+    impl Fixture {
+        fn new(version: u32, big: bool) -> Self {
+            let mut f = Fixture {
+                out: b"GGUF".to_vec(),
+                big,
+                legacy: version == 1,
+            };
+            f.u32(version);
+            f
+        }
+
+        fn u32(&mut self, v: u32) -> &mut Self {
+            let bytes = if self.big {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            };
+            self.out.extend_from_slice(&bytes);
+            self
+        }
+
+        fn u64(&mut self, v: u64) -> &mut Self {
+            let bytes = if self.big {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            };
+            self.out.extend_from_slice(&bytes);
+            self
+        }
+
+        fn size(&mut self, v: u64) -> &mut Self {
+            if self.legacy {
+                self.u32(v as u32)
+            } else {
+                self.u64(v)
+            }
+        }
+
+        fn string(&mut self, s: &str) -> &mut Self {
+            self.size(s.len() as u64);
+            self.out.extend_from_slice(s.as_bytes());
+            self
+        }
+
+        /// A tensor info of `ne0` f32 values at `offset`.
+        fn tensor(&mut self, name: &str, ne0: u64, offset: u64) -> &mut Self {
+            self.string(name).u32(1).size(ne0).u32(0).u64(offset)
+        }
+
+        fn parse(&self) -> Result<GgufHeader> {
+            GgufHeader::read_from(Cursor::new(&self.out))
+        }
+    }
+
+    #[test]
+    fn builder_round_trip() {
+        let mut b = GgufBuilder::new();
+        b.set("general.name", "tiny").unwrap();
+        b.set("general.alignment", 64u32).unwrap();
+        b.set("tokens", vec!["a", "b"]).unwrap();
+        b.set("general.name", "renamed").unwrap();
+        let weights: Vec<u8> = [1.0f32, 2.0, 3.0]
+            .iter()
+            .flat_map(|x| x.to_ne_bytes())
+            .collect();
+        b.add_tensor("w", 0, &[3], weights.clone()).unwrap();
+        b.add_tensor("b", 0, &[1], 4.0f32.to_ne_bytes().to_vec())
+            .unwrap();
+        let mut file = Vec::new();
+        b.write_to(&mut file).unwrap();
+
+        let mut r = Cursor::new(file);
+        let h = GgufHeader::read_from(&mut r).unwrap();
+        assert_eq!(h.version, VERSION);
+        assert_eq!(h.endian, Endian::native());
+        assert_eq!(h.alignment, 64);
+        assert_eq!(h.metadata, b.metadata());
+        assert_eq!(h.get("general.name"), Some(&GgufValue::from("renamed")));
+        assert_eq!(h.data_offset % 64, 0);
+        let offsets: Vec<u64> = h.tensors.iter().map(|t| t.offset).collect();
+        assert_eq!(offsets, [0, 64]);
+        assert_eq!(h.tensor("w").unwrap().ne, [3, 1, 1, 1]);
+        assert_eq!(h.read_tensor_data(&mut r, "w").unwrap(), weights);
+        assert_eq!(
+            h.read_tensor_data(&mut r, "b").unwrap(),
+            4.0f32.to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn big_endian_header() {
+        let mut f = Fixture::new(VERSION, true);
+        f.u64(1).u64(2);
+        f.string("answer").u32(GgufType::U32.id()).u32(42);
+        f.string("dims").u32(GgufType::Array.id());
+        f.u32(GgufType::I16.id())
+            .u64(2)
+            .out
+            .extend_from_slice(&[0x01, 0x00, 0xff, 0xfe]);
+        f.tensor("t", 8, 0);
+        let h = f.parse().unwrap();
+        assert_eq!(h.endian, Endian::Big);
+        assert_eq!(h.get("answer"), Some(&GgufValue::U32(42)));
+        assert_eq!(h.get("dims"), Some(&GgufValue::from(vec![256i16, -2])));
+        assert_eq!(h.tensor("t").unwrap().ne, [8, 1, 1, 1]);
+        assert_eq!(h.tensor("t").unwrap().size(), Some(32));
+    }
+
+    #[test]
+    fn legacy_v1_sizes() {
+        let mut f = Fixture::new(1, false);
+        f.u32(1).u32(1);
+        f.string("k").u32(GgufType::String.id()).string("v");
+        f.tensor("t", 4, 0);
+        let h = f.parse().unwrap();
+        assert_eq!(h.version, 1);
+        assert_eq!(h.get("k"), Some(&GgufValue::from("v")));
+        assert_eq!(h.tensor("t").unwrap().ne, [4, 1, 1, 1]);
+        assert_eq!(h.data_offset, (f.out.len() as u64).next_multiple_of(32));
+    }
+
+    #[test]
+    fn rejects_misaligned_offset() {
+        let mut f = Fixture::new(VERSION, false);
+        f.u64(2).u64(0);
+        f.tensor("a", 4, 0).tensor("b", 4, 16);
+        assert!(matches!(f.parse(), Err(Error::InvalidFormat(_))));
+
+        // aligned under a custom general.alignment
+        let mut f = Fixture::new(VERSION, false);
+        f.u64(2).u64(1);
+        f.string("general.alignment")
+            .u32(GgufType::U32.id())
+            .u32(16);
+        f.tensor("a", 4, 0).tensor("b", 4, 16);
+        assert_eq!(f.parse().unwrap().alignment, 16);
+    }
+
+    #[test]
+    fn rejects_duplicate_key() {
+        let mut f = Fixture::new(VERSION, false);
+        f.u64(0).u64(2);
+        f.string("k").u32(GgufType::U8.id()).out.push(1);
+        f.string("k").u32(GgufType::U8.id()).out.push(2);
+        assert!(matches!(f.parse(), Err(Error::InvalidFormat(_))));
+    }
+}
@@ -0,0 +1,214 @@
+//! Writing GGUF files whose tensor data does not fit in memory.
+
+use std::collections::VecDeque;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use super::writer::GgufWriter;
+use crate::error::{Error, Result};
+use crate::types::Type;
+
+/// Writes a GGUF file tensor by tensor.
+///
+/// Tensor infos are declared up front with
+/// [`declare_tensor`](GgufStreamWriter::declare_tensor); the first write
+/// reserves the header, payloads are then streamed in declaration order,
+/// and [`finish`](GgufStreamWriter::finish) writes the header over the
+/// reservation. Metadata values may still change until then as long as the
+/// header keeps its size (e.g. a `u32` count filled in at the end).
+///
+/// ```ignore
+/// let mut meta = GgufWriter::new()?;
+/// meta.set_str("general.architecture", "llama")?;
+/// let mut w = GgufStreamWriter::new(meta, File::create("out.gguf")?)?;
+/// for t in &source_tensors {
+///     w.declare_tensor(&t.name, t.ty, &t.ne)?;
+/// }
+/// for t in &source_tensors {
+///     w.write_tensor(&t.name, t.open_reader()?)?;
+/// }
+/// w.finish()?;
+/// ```
+pub struct GgufStreamWriter<'a, W: Write + Seek> {
+    meta: GgufWriter<'a>,
+    out: W,
+    /// Position of the start of the file in `out`.
+    start: u64,
+    /// Tensors in `meta`: those given with data, plus those declared.
+    n_tensors: i64,
+    /// Declared tensors not yet written: name and size in bytes.
+    pending: VecDeque<(String, usize)>,
+    /// Header size reserved by the first write.
+    reserved: Option<usize>,
+}
+
+impl<'a, W: Write + Seek> GgufStreamWriter<'a, W> {
+    /// Stream into `out` starting at its current position. Tensors already
+    /// added to `meta` with their data are written first.
+    pub fn new(meta: GgufWriter<'a>, mut out: W) -> Result<Self> {
+        let n_initial = meta.n_tensors();
+        if meta.tensor_data().len() as i64 != n_initial {
+            return Err(Error::InvalidArgument(
+                "tensors declared for streaming have no data".to_string(),
+            ));
+        }
+        Ok(GgufStreamWriter {
+            start: out.stream_position()?,
+            meta,
+            out,
+            n_tensors: n_initial,
+            pending: VecDeque::new(),
+            reserved: None,
+        })
+    }
+
+    /// Metadata, for setting keys. Adding tensors here is rejected by
+    /// [`GgufStreamWriter::finish`].
+    pub fn metadata(&mut self) -> &mut GgufWriter<'a> {
+        &mut self.meta
+    }
+
+    /// Declare the next tensor to be streamed. Must come before the first
+    /// write, since the header holds every tensor's info.
+    pub fn declare_tensor(&mut self, name: &str, ty: Type, ne: &[i64]) -> Result<&mut Self> {
+        if self.reserved.is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "tensor {:?} declared after streaming started",
+                name
+            )));
+        }
+        self.check_tensors()?;
+        let size = self.meta.add_tensor_info(name, ty, ne)?;
+        self.n_tensors += 1;
+        self.pending.push_back((name.to_string(), size));
+        Ok(self)
+    }
+
+    /// Tensors declared but not yet written, in the order they are expected.
+    pub fn pending(&self) -> impl Iterator<Item = &str> {
+        self.pending.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Reserve the header and write the data of tensors given with `meta`.
+    fn begin(&mut self) -> Result<usize> {
+        if let Some(reserved) = self.reserved {
+            return Ok(reserved);
+        }
+        self.check_tensors()?;
+        let reserved = self.meta.meta_bytes().len();
+        let alignment = self.meta.alignment();
+        std::io::copy(&mut std::io::repeat(0).take(reserved as u64), &mut self.out)?;
+        for bytes in self.meta.tensor_data() {
+            self.out.write_all(bytes)?;
+            write_padding(&mut self.out, bytes.len(), alignment)?;
+        }
+        self.reserved = Some(reserved);
+        Ok(reserved)
+    }
+
+    fn next_pending(&mut self, name: &str) -> Result<usize> {
+        match self.pending.front() {
+            Some((next, size)) if next == name => Ok(*size),
+            Some((next, _)) => Err(Error::InvalidArgument(format!(
+                "expected data for tensor {:?}, got {:?}",
+                next, name
+            ))),
+            None => Err(Error::InvalidArgument(format!(
+                "tensor {:?} was not declared",
+                name
+            ))),
+        }
+    }
+
+    /// Stream the data of the next declared tensor from `r`, which must
+    /// hold at least its size in bytes.
+    pub fn write_tensor(&mut self, name: &str, r: impl Read) -> Result<&mut Self> {
+        let size = self.next_pending(name)?;
+        self.begin()?;
+        let copied = std::io::copy(&mut r.take(size as u64), &mut self.out)?;
+        if copied != size as u64 {
+            return Err(Error::ShapeMismatch(format!(
+                "tensor {:?} needs {} bytes, reader ended after {}",
+                name, size, copied
+            )));
+        }
+        self.end_tensor(size)
+    }
+
+    /// Stream the data of the next declared tensor from chunks whose
+    /// lengths add up to its size.
+    pub fn write_tensor_chunks<I, C>(&mut self, name: &str, chunks: I) -> Result<&mut Self>
+    where
+        I: IntoIterator<Item = C>,
+        C: AsRef<[u8]>,
+    {
+        let size = self.next_pending(name)?;
+        self.begin()?;
+        let mut written = 0;
+        for chunk in chunks {
+            let chunk = chunk.as_ref();
+            if written + chunk.len() > size {
+                return Err(Error::ShapeMismatch(format!(
+                    "tensor {:?} needs {} bytes, got more",
+                    name, size
+                )));
+            }
+            self.out.write_all(chunk)?;
+            written += chunk.len();
+        }
+        if written != size {
+            return Err(Error::ShapeMismatch(format!(
+                "tensor {:?} needs {} bytes, got {}",
+                name, size, written
+            )));
+        }
+        self.end_tensor(size)
+    }
+
+    fn end_tensor(&mut self, size: usize) -> Result<&mut Self> {
+        write_padding(&mut self.out, size, self.meta.alignment())?;
+        self.pending.pop_front();
+        Ok(self)
+    }
+
+    /// Write the header over the reservation and return the output,
+    /// positioned at the end of the file.
+    pub fn finish(mut self) -> Result<W> {
+        if let Some(name) = self.pending().next() {
+            return Err(Error::InvalidArgument(format!(
+                "tensor {:?} was declared but not written",
+                name
+            )));
+        }
+        self.check_tensors()?;
+        let reserved = self.begin()?;
+        let meta = self.meta.meta_bytes();
+        if meta.len() != reserved {
+            return Err(Error::InvalidArgument(format!(
+                "header changed from {} to {} bytes while streaming",
+                reserved,
+                meta.len()
+            )));
+        }
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(self.start))?;
+        self.out.write_all(&meta)?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn check_tensors(&self) -> Result<()> {
+        if self.meta.n_tensors() != self.n_tensors {
+            return Err(Error::InvalidArgument(
+                "tensors added to the metadata after streaming was set up".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn write_padding(w: &mut impl Write, len: usize, alignment: usize) -> Result<()> {
+    let pad = (alignment - len % alignment) % alignment;
+    w.write_all(&vec![0u8; pad])?;
+    Ok(())
+}
@@ -0,0 +1,294 @@
+//! Consistency checks and naming lints for GGUF files.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+
+use super::reader::{GgufFile, GgufTensorInfo};
+use super::value::GgufType;
+use crate::error::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Legal, but unconventional; loaders may still cope.
+    Warning,
+    /// The file is malformed or cannot be loaded as is.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// One finding of [`GgufFile::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The metadata key or tensor name concerned, if any.
+    pub subject: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        if let Some(subject) = &self.subject {
+            write!(f, "{}: ", subject)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Findings of [`GgufFile::validate`], in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    /// Whether there are no errors (warnings are allowed).
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == Severity::Warning)
+    }
+
+    fn push(&mut self, severity: Severity, subject: Option<&str>, message: String) {
+        self.issues.push(Issue {
+            severity,
+            subject: subject.map(str::to_string),
+            message,
+        });
+    }
+
+    fn error(&mut self, subject: Option<&str>, message: String) {
+        self.push(Severity::Error, subject, message)
+    }
+
+    fn warn(&mut self, subject: Option<&str>, message: String) {
+        self.push(Severity::Warning, subject, message)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+const MAGIC: &[u8; 4] = b"GGUF";
+
+impl GgufFile {
+    /// Check the header, alignment and tensor layout against the file on
+    /// disk, and lint key and tensor names.
+    ///
+    /// Only I/O errors while re-reading the header are returned as `Err`;
+    /// everything else is reported.
+    pub fn validate(&self) -> Result<ValidationReport> {
+        let mut report = ValidationReport::default();
+        self.check_header(&mut report)?;
+        self.check_alignment(&mut report);
+        self.check_tensors(&mut report);
+        self.lint_keys(&mut report);
+        Ok(report)
+    }
+
+    fn check_header(&self, report: &mut ValidationReport) -> Result<()> {
+        let mut header = [0u8; 8];
+        std::fs::File::open(self.parsed_path())?.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            report.error(None, format!("bad magic {:02x?}", &header[..4]));
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != self.version() {
+            report.error(
+                None,
+                format!(
+                    "header version {} does not match parsed version {}",
+                    version,
+                    self.version()
+                ),
+            );
+        }
+        if version == 1 || version > crate::GGUF_VERSION {
+            report.error(None, format!("unsupported GGUF version {}", version));
+        }
+        Ok(())
+    }
+
+    fn check_alignment(&self, report: &mut ValidationReport) {
+        let key = "general.alignment";
+        match self.value_type(key) {
+            Some(GgufType::U32) | None => {}
+            Some(ty) => report.error(Some(key), format!("must be u32, found {}", ty)),
+        }
+        let alignment = self.alignment();
+        if alignment == 0 || !alignment.is_power_of_two() {
+            report.error(Some(key), format!("{} is not a power of two", alignment));
+        }
+        if self.data_offset() as u64 > self.file_size() {
+            report.error(
+                None,
+                format!(
+                    "data section starts at {} past the end of the file ({} bytes)",
+                    self.data_offset(),
+                    self.file_size()
+                ),
+            );
+        }
+    }
+
+    fn check_tensors(&self, report: &mut ValidationReport) {
+        let alignment = self.alignment().max(1);
+        let data_size = self.file_size().saturating_sub(self.data_offset() as u64);
+        let mut names = HashSet::new();
+        let mut infos: Vec<GgufTensorInfo> = self.tensor_infos().collect();
+        for info in &infos {
+            let subject = Some(info.name.as_str());
+            if !names.insert(info.name.as_str()) {
+                report.error(subject, "duplicate tensor name".to_string());
+            }
+            check_shape(report, info);
+            if info.offset % alignment != 0 {
+                report.error(
+                    subject,
+                    format!(
+                        "offset {} is not aligned to {} bytes",
+                        info.offset, alignment
+                    ),
+                );
+            }
+            if (info.offset + info.size) as u64 > data_size {
+                report.error(
+                    subject,
+                    format!(
+                        "data [{}, {}) extends past the end of the data section ({} bytes)",
+                        info.offset,
+                        info.offset + info.size,
+                        data_size
+                    ),
+                );
+            }
+            lint_tensor_name(report, &info.name);
+        }
+        infos.sort_by_key(|info| info.offset);
+        for pair in infos.windows(2) {
+            if pair[0].offset + pair[0].size > pair[1].offset {
+                report.error(
+                    Some(&pair[1].name),
+                    format!("data overlaps tensor {:?}", pair[0].name),
+                );
+            }
+        }
+    }
+
+    fn lint_keys(&self, report: &mut ValidationReport) {
+        if !self.contains_key("general.architecture") {
+            report.warn(
+                Some("general.architecture"),
+                "missing; loaders use it to pick the model".to_string(),
+            );
+        }
+        for key in self.keys() {
+            if !is_conventional_name(&key) {
+                report.warn(
+                    Some(&key),
+                    "keys should be lowercase dotted names of [a-z0-9_]".to_string(),
+                );
+            }
+        }
+    }
+}
+
+fn check_shape(report: &mut ValidationReport, info: &GgufTensorInfo) {
+    let subject = Some(info.name.as_str());
+    let Some(ty) = info.ty else {
+        report.error(subject, "unknown tensor type".to_string());
+        return;
+    };
+    if let Some(d) = info.ne.iter().find(|&&d| d < 0) {
+        report.error(subject, format!("negative dimension {}", d));
+        return;
+    }
+    if info.ne.contains(&0) {
+        report.warn(subject, format!("empty tensor of shape {:?}", info.ne));
+    }
+    let block = ty.block_size() as i64;
+    if info.ne[0] % block != 0 {
+        report.error(
+            subject,
+            format!(
+                "row length {} is not a multiple of the {} block size {}",
+                info.ne[0], ty, block
+            ),
+        );
+        return;
+    }
+    let rows: i64 = info.ne[1..].iter().product();
+    let expected = ty.row_size(info.ne[0]) * rows as usize;
+    if expected != info.size {
+        report.error(
+            subject,
+            format!(
+                "{} {:?} needs {} bytes, file records {}",
+                ty, info.ne, expected, info.size
+            ),
+        );
+    }
+}
+
+fn lint_tensor_name(report: &mut ValidationReport, name: &str) {
+    let subject = Some(name);
+    if name.is_empty() {
+        report.error(subject, "empty tensor name".to_string());
+        return;
+    }
+    if name.len() >= crate::GGML_MAX_NAME as usize {
+        report.error(
+            subject,
+            format!("name longer than {} bytes", crate::GGML_MAX_NAME - 1),
+        );
+    }
+    if !is_conventional_name(name) {
+        report.warn(
+            subject,
+            "tensor names should be lowercase dotted names of [a-z0-9_]".to_string(),
+        );
+    }
+    // llama.cpp layout: "blk.<n>.<name>"
+    if let Some(rest) = name.strip_prefix("blk.") {
+        let layer = rest.split('.').next().unwrap_or("");
+        if layer.parse::<u32>().is_err() {
+            report.warn(
+                subject,
+                format!("expected a layer number after \"blk.\", found {:?}", layer),
+            );
+        }
+    }
+}
+
+fn is_conventional_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        })
+}
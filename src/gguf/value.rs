@@ -0,0 +1,353 @@
+//! Typed GGUF metadata values.
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+/// Value type tag of a metadata entry, mirroring `enum gguf_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GgufType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    Bool,
+    String,
+    Array,
+    U64,
+    I64,
+    F64,
+}
+
+impl GgufType {
+    pub const ALL: [GgufType; 13] = [
+        GgufType::U8,
+        GgufType::I8,
+        GgufType::U16,
+        GgufType::I16,
+        GgufType::U32,
+        GgufType::I32,
+        GgufType::F32,
+        GgufType::Bool,
+        GgufType::String,
+        GgufType::Array,
+        GgufType::U64,
+        GgufType::I64,
+        GgufType::F64,
+    ];
+
+    /// The type's id in GGUF files, its `enum gguf_type` value.
+    pub fn id(self) -> u32 {
+        match self {
+            GgufType::U8 => 0,
+            GgufType::I8 => 1,
+            GgufType::U16 => 2,
+            GgufType::I16 => 3,
+            GgufType::U32 => 4,
+            GgufType::I32 => 5,
+            GgufType::F32 => 6,
+            GgufType::Bool => 7,
+            GgufType::String => 8,
+            GgufType::Array => 9,
+            GgufType::U64 => 10,
+            GgufType::I64 => 11,
+            GgufType::F64 => 12,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        GgufType::ALL.into_iter().find(|ty| ty.id() == id)
+    }
+
+    #[cfg(feature = "native")]
+    pub fn from_raw(raw: crate::gguf_type) -> Option<Self> {
+        Self::from_id(raw)
+    }
+
+    #[cfg(feature = "native")]
+    pub fn as_raw(self) -> crate::gguf_type {
+        self.id() as crate::gguf_type
+    }
+
+    /// Name used by gguf (`"u32"`, `"str"`, `"arr"`, ...).
+    pub fn name(self) -> &'static str {
+        match self {
+            GgufType::U8 => "u8",
+            GgufType::I8 => "i8",
+            GgufType::U16 => "u16",
+            GgufType::I16 => "i16",
+            GgufType::U32 => "u32",
+            GgufType::I32 => "i32",
+            GgufType::F32 => "f32",
+            GgufType::Bool => "bool",
+            GgufType::String => "str",
+            GgufType::Array => "arr",
+            GgufType::U64 => "u64",
+            GgufType::I64 => "i64",
+            GgufType::F64 => "f64",
+        }
+    }
+
+    /// Parse a name as printed by [`GgufType::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        GgufType::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+}
+
+impl fmt::Display for GgufType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A metadata value.
+///
+/// Arrays hold elements of a single type; GGUF does not nest arrays.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    pub fn value_type(&self) -> GgufType {
+        match self {
+            GgufValue::U8(_) => GgufType::U8,
+            GgufValue::I8(_) => GgufType::I8,
+            GgufValue::U16(_) => GgufType::U16,
+            GgufValue::I16(_) => GgufType::I16,
+            GgufValue::U32(_) => GgufType::U32,
+            GgufValue::I32(_) => GgufType::I32,
+            GgufValue::F32(_) => GgufType::F32,
+            GgufValue::Bool(_) => GgufType::Bool,
+            GgufValue::String(_) => GgufType::String,
+            GgufValue::Array(_) => GgufType::Array,
+            GgufValue::U64(_) => GgufType::U64,
+            GgufValue::I64(_) => GgufType::I64,
+            GgufValue::F64(_) => GgufType::F64,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[GgufValue]> {
+        match self {
+            GgufValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Any integer variant, widened.
+    pub fn as_i128(&self) -> Option<i128> {
+        Some(match *self {
+            GgufValue::U8(v) => v.into(),
+            GgufValue::I8(v) => v.into(),
+            GgufValue::U16(v) => v.into(),
+            GgufValue::I16(v) => v.into(),
+            GgufValue::U32(v) => v.into(),
+            GgufValue::I32(v) => v.into(),
+            GgufValue::U64(v) => v.into(),
+            GgufValue::I64(v) => v.into(),
+            _ => return None,
+        })
+    }
+
+    /// Element type of an array, `None` for empty arrays and non-arrays.
+    /// Errors if the elements do not share one type or are arrays.
+    pub fn array_type(&self) -> Result<Option<GgufType>> {
+        let Some(items) = self.as_array() else {
+            return Ok(None);
+        };
+        let Some(first) = items.first() else {
+            return Ok(None);
+        };
+        let ty = first.value_type();
+        if ty == GgufType::Array {
+            return Err(Error::InvalidArgument(
+                "GGUF arrays cannot be nested".to_string(),
+            ));
+        }
+        if let Some(other) = items.iter().find(|v| v.value_type() != ty) {
+            return Err(Error::TypeMismatch {
+                expected: format!("array elements of type {}", ty),
+                found: other.value_type().to_string(),
+            });
+        }
+        Ok(Some(ty))
+    }
+
+    fn mismatch(&self, expected: &str) -> Error {
+        Error::TypeMismatch {
+            expected: expected.to_string(),
+            found: self.value_type().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GgufValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GgufValue::U8(v) => write!(f, "{}", v),
+            GgufValue::I8(v) => write!(f, "{}", v),
+            GgufValue::U16(v) => write!(f, "{}", v),
+            GgufValue::I16(v) => write!(f, "{}", v),
+            GgufValue::U32(v) => write!(f, "{}", v),
+            GgufValue::I32(v) => write!(f, "{}", v),
+            GgufValue::F32(v) => write!(f, "{}", v),
+            GgufValue::Bool(v) => write!(f, "{}", v),
+            GgufValue::String(v) => write!(f, "{:?}", v),
+            GgufValue::U64(v) => write!(f, "{}", v),
+            GgufValue::I64(v) => write!(f, "{}", v),
+            GgufValue::F64(v) => write!(f, "{}", v),
+            GgufValue::Array(items) => {
+                f.write_str("[")?;
+                for (i, v) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+macro_rules! value_from {
+    ($($t:ty => $variant:ident;)*) => {
+        $(
+            impl From<$t> for GgufValue {
+                fn from(v: $t) -> Self {
+                    GgufValue::$variant(v)
+                }
+            }
+        )*
+    };
+}
+
+value_from! {
+    u8 => U8;
+    i8 => I8;
+    u16 => U16;
+    i16 => I16;
+    u32 => U32;
+    i32 => I32;
+    f32 => F32;
+    bool => Bool;
+    String => String;
+    u64 => U64;
+    i64 => I64;
+    f64 => F64;
+}
+
+impl From<&str> for GgufValue {
+    fn from(v: &str) -> Self {
+        GgufValue::String(v.to_string())
+    }
+}
+
+impl<T: Into<GgufValue>> From<Vec<T>> for GgufValue {
+    fn from(v: Vec<T>) -> Self {
+        GgufValue::Array(v.into_iter().map(Into::into).collect())
+    }
+}
+
+// Integers convert from any integer variant whose value fits, so readers
+// don't depend on the exact width a converter chose.
+macro_rules! int_try_from {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<GgufValue> for $t {
+                type Error = Error;
+
+                fn try_from(v: GgufValue) -> Result<Self> {
+                    let wide = v.as_i128().ok_or_else(|| v.mismatch(stringify!($t)))?;
+                    <$t>::try_from(wide).map_err(|_| {
+                        Error::InvalidArgument(format!(
+                            "{} does not fit in {}",
+                            wide,
+                            stringify!($t)
+                        ))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+int_try_from!(u8, i8, u16, i16, u32, i32, u64, i64, usize);
+
+impl TryFrom<GgufValue> for f32 {
+    type Error = Error;
+
+    fn try_from(v: GgufValue) -> Result<Self> {
+        match v {
+            GgufValue::F32(x) => Ok(x),
+            other => Err(other.mismatch("f32")),
+        }
+    }
+}
+
+impl TryFrom<GgufValue> for f64 {
+    type Error = Error;
+
+    fn try_from(v: GgufValue) -> Result<Self> {
+        match v {
+            GgufValue::F32(x) => Ok(x.into()),
+            GgufValue::F64(x) => Ok(x),
+            other => Err(other.mismatch("f64")),
+        }
+    }
+}
+
+impl TryFrom<GgufValue> for bool {
+    type Error = Error;
+
+    fn try_from(v: GgufValue) -> Result<Self> {
+        match v {
+            GgufValue::Bool(x) => Ok(x),
+            other => Err(other.mismatch("bool")),
+        }
+    }
+}
+
+impl TryFrom<GgufValue> for String {
+    type Error = Error;
+
+    fn try_from(v: GgufValue) -> Result<Self> {
+        match v {
+            GgufValue::String(x) => Ok(x),
+            other => Err(other.mismatch("string")),
+        }
+    }
+}
+
+impl<T: TryFrom<GgufValue, Error = Error>> TryFrom<GgufValue> for Vec<T> {
+    type Error = Error;
+
+    fn try_from(v: GgufValue) -> Result<Self> {
+        match v {
+            GgufValue::Array(items) => items.into_iter().map(T::try_from).collect(),
+            other => Err(other.mismatch("array")),
+        }
+    }
+}
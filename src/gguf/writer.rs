@@ -0,0 +1,461 @@
+//! Building GGUF files.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::ptr::NonNull;
+
+use super::c_string;
+use super::endian::realign_header;
+use super::reader::GgufFile;
+use super::value::{GgufType, GgufValue};
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::{Element, Type};
+
+/// Rust types stored as a single GGUF metadata value.
+pub trait GgufScalar: Copy + 'static {
+    /// The `enum gguf_type` tag.
+    const GGUF_TYPE: crate::gguf_type;
+
+    #[doc(hidden)]
+    unsafe fn set_raw(ctx: *mut crate::gguf_context, key: *const std::os::raw::c_char, v: Self);
+}
+
+macro_rules! gguf_scalar {
+    ($($t:ty => $tag:ident, $set:ident;)*) => {
+        $(
+            impl GgufScalar for $t {
+                const GGUF_TYPE: crate::gguf_type = crate::$tag;
+
+                unsafe fn set_raw(
+                    ctx: *mut crate::gguf_context,
+                    key: *const std::os::raw::c_char,
+                    v: Self,
+                ) {
+                    crate::$set(ctx, key, v)
+                }
+            }
+        )*
+    };
+}
+
+gguf_scalar! {
+    u8 => gguf_type_GGUF_TYPE_UINT8, gguf_set_val_u8;
+    i8 => gguf_type_GGUF_TYPE_INT8, gguf_set_val_i8;
+    u16 => gguf_type_GGUF_TYPE_UINT16, gguf_set_val_u16;
+    i16 => gguf_type_GGUF_TYPE_INT16, gguf_set_val_i16;
+    u32 => gguf_type_GGUF_TYPE_UINT32, gguf_set_val_u32;
+    i32 => gguf_type_GGUF_TYPE_INT32, gguf_set_val_i32;
+    f32 => gguf_type_GGUF_TYPE_FLOAT32, gguf_set_val_f32;
+    u64 => gguf_type_GGUF_TYPE_UINT64, gguf_set_val_u64;
+    i64 => gguf_type_GGUF_TYPE_INT64, gguf_set_val_i64;
+    f64 => gguf_type_GGUF_TYPE_FLOAT64, gguf_set_val_f64;
+    bool => gguf_type_GGUF_TYPE_BOOL, gguf_set_val_bool;
+}
+
+/// Builder for a GGUF file: metadata key/value pairs plus tensors.
+///
+/// Tensor data is borrowed where possible and only read by
+/// [`GgufWriter::write_to_file`]. Tensors are laid out in insertion order,
+/// each padded to the file alignment (32 bytes unless changed with
+/// [`GgufWriter::set_alignment`]).
+///
+/// ```ignore
+/// let mut w = GgufWriter::new()?;
+/// w.set_str("general.architecture", "llama")?;
+/// w.set("llama.block_count", 32u32)?;
+/// w.add_tensor_data("token_embd.weight", &[4096, 32000], &embeddings)?;
+/// w.write_to_file("model.gguf")?;
+/// ```
+pub struct GgufWriter<'a> {
+    ptr: NonNull<crate::gguf_context>,
+    data: Vec<Cow<'a, [u8]>>,
+    alignment: usize,
+}
+
+impl<'a> GgufWriter<'a> {
+    pub fn new() -> Result<Self> {
+        NonNull::new(unsafe { crate::gguf_init_empty() })
+            .map(|ptr| GgufWriter {
+                ptr,
+                data: Vec::new(),
+                alignment: crate::GGUF_DEFAULT_ALIGNMENT as usize,
+            })
+            .ok_or(Error::NullPointer("gguf_init_empty"))
+    }
+
+    /// A writer holding the metadata and tensors of `file`, to save it
+    /// again with changes. Tensor data is read into memory; files of older
+    /// GGUF versions are written as the current one.
+    pub fn from_file(file: &GgufFile) -> Result<Self> {
+        let mut w = Self::new()?;
+        for (key, value) in file.metadata() {
+            w.set_value(&key, &value)?;
+        }
+        for info in file.tensor_infos() {
+            let ty = info.ty.ok_or_else(|| {
+                Error::InvalidFormat(format!("tensor {:?} has an unknown type", info.name))
+            })?;
+            let data = file.read_tensor_data(&info.name)?;
+            w.add_tensor_bytes(&info.name, ty, &info.ne[..info.n_dims.max(1)], data)?;
+        }
+        Ok(w)
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::gguf_context {
+        self.ptr.as_ptr()
+    }
+
+    fn key(key: &str) -> Result<std::ffi::CString> {
+        if key == "general.alignment" {
+            return Err(Error::InvalidArgument(
+                "general.alignment is set with GgufWriter::set_alignment".to_string(),
+            ));
+        }
+        c_string("key", key)
+    }
+
+    /// Align the data section and every tensor to `alignment` bytes, a
+    /// power of two. Stored as `general.alignment` unless it is the default.
+    pub fn set_alignment(&mut self, alignment: usize) -> Result<&mut Self> {
+        if !alignment.is_power_of_two() || u32::try_from(alignment).is_err() {
+            return Err(Error::InvalidArgument(format!(
+                "alignment {} is not a 32-bit power of two",
+                alignment
+            )));
+        }
+        let key = c_string("key", "general.alignment")?;
+        if alignment == crate::GGUF_DEFAULT_ALIGNMENT as usize {
+            unsafe { crate::gguf_remove_key(self.as_ptr(), key.as_ptr()) };
+        } else {
+            unsafe { crate::gguf_set_val_u32(self.as_ptr(), key.as_ptr(), alignment as u32) };
+        }
+        self.alignment = alignment;
+        Ok(self)
+    }
+
+    /// Align to the host's memory page size, so every tensor of the file
+    /// starts on its own page when mapped (see
+    /// [`GgufModel`](super::GgufModel)).
+    pub fn set_page_alignment(&mut self) -> Result<&mut Self> {
+        self.set_alignment(page_size())
+    }
+
+    /// Alignment of the data section and of every tensor in it.
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    /// Set a scalar value, replacing any previous value of `key`.
+    pub fn set<T: GgufScalar>(&mut self, key: &str, value: T) -> Result<&mut Self> {
+        let key = Self::key(key)?;
+        unsafe { T::set_raw(self.as_ptr(), key.as_ptr(), value) };
+        Ok(self)
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) -> Result<&mut Self> {
+        let key = Self::key(key)?;
+        let value = c_string("value", value)?;
+        unsafe { crate::gguf_set_val_str(self.as_ptr(), key.as_ptr(), value.as_ptr()) };
+        Ok(self)
+    }
+
+    /// Set an array of scalars.
+    pub fn set_array<T: GgufScalar>(&mut self, key: &str, values: &[T]) -> Result<&mut Self> {
+        let key = Self::key(key)?;
+        unsafe {
+            crate::gguf_set_arr_data(
+                self.as_ptr(),
+                key.as_ptr(),
+                T::GGUF_TYPE,
+                values.as_ptr() as *const _,
+                values.len(),
+            )
+        };
+        Ok(self)
+    }
+
+    /// Set an array of strings, e.g. a tokenizer vocabulary.
+    pub fn set_str_array<S: AsRef<str>>(&mut self, key: &str, values: &[S]) -> Result<&mut Self> {
+        let key = Self::key(key)?;
+        let owned = values
+            .iter()
+            .map(|s| c_string("value", s.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut ptrs: Vec<_> = owned.iter().map(|s| s.as_ptr()).collect();
+        unsafe {
+            crate::gguf_set_arr_str(self.as_ptr(), key.as_ptr(), ptrs.as_mut_ptr(), ptrs.len())
+        };
+        Ok(self)
+    }
+
+    /// Set any [`GgufValue`]. Array elements must share one type; an empty
+    /// array is stored as `u8`.
+    ///
+    /// `general.alignment` is accepted here, as a `u32`, so metadata can be
+    /// copied from another file as is.
+    pub fn set_value(&mut self, key: &str, value: &GgufValue) -> Result<&mut Self> {
+        if key == "general.alignment" {
+            return match *value {
+                GgufValue::U32(alignment) => self.set_alignment(alignment as usize),
+                _ => Err(Error::TypeMismatch {
+                    expected: "u32".to_string(),
+                    found: value.value_type().to_string(),
+                }),
+            };
+        }
+        match value {
+            GgufValue::U8(v) => self.set(key, *v),
+            GgufValue::I8(v) => self.set(key, *v),
+            GgufValue::U16(v) => self.set(key, *v),
+            GgufValue::I16(v) => self.set(key, *v),
+            GgufValue::U32(v) => self.set(key, *v),
+            GgufValue::I32(v) => self.set(key, *v),
+            GgufValue::F32(v) => self.set(key, *v),
+            GgufValue::Bool(v) => self.set(key, *v),
+            GgufValue::U64(v) => self.set(key, *v),
+            GgufValue::I64(v) => self.set(key, *v),
+            GgufValue::F64(v) => self.set(key, *v),
+            GgufValue::String(v) => self.set_str(key, v),
+            GgufValue::Array(items) => match value.array_type()? {
+                None => self.set_array::<u8>(key, &[]),
+                Some(GgufType::String) => {
+                    let strings: Vec<&str> = items.iter().filter_map(GgufValue::as_str).collect();
+                    self.set_str_array(key, &strings)
+                }
+                Some(ty) => {
+                    let mut bytes = Vec::new();
+                    for v in items {
+                        push_ne_bytes(&mut bytes, v);
+                    }
+                    let c_key = Self::key(key)?;
+                    unsafe {
+                        crate::gguf_set_arr_data(
+                            self.as_ptr(),
+                            c_key.as_ptr(),
+                            ty.as_raw(),
+                            bytes.as_ptr() as *const _,
+                            items.len(),
+                        )
+                    };
+                    Ok(self)
+                }
+            },
+        }
+    }
+
+    /// Remove `key`; returns whether it was present.
+    pub fn remove(&mut self, key: &str) -> Result<bool> {
+        if key == "general.alignment" {
+            let was_set = self.alignment != crate::GGUF_DEFAULT_ALIGNMENT as usize;
+            self.set_alignment(crate::GGUF_DEFAULT_ALIGNMENT as usize)?;
+            return Ok(was_set);
+        }
+        let key = c_string("key", key)?;
+        Ok(unsafe { crate::gguf_remove_key(self.as_ptr(), key.as_ptr()) } >= 0)
+    }
+
+    pub fn n_kv(&self) -> i64 {
+        unsafe { crate::gguf_get_n_kv(self.as_ptr()) }
+    }
+
+    pub fn n_tensors(&self) -> i64 {
+        unsafe { crate::gguf_get_n_tensors(self.as_ptr()) }
+    }
+
+    fn check_new_name(&self, name: &str) -> Result<std::ffi::CString> {
+        if name.is_empty() || name.len() >= crate::GGML_MAX_NAME as usize {
+            return Err(Error::InvalidArgument(format!(
+                "tensor name {:?} must be 1 to {} bytes",
+                name,
+                crate::GGML_MAX_NAME - 1
+            )));
+        }
+        let c = c_string("tensor name", name)?;
+        if unsafe { crate::gguf_find_tensor(self.as_ptr(), c.as_ptr()) } >= 0 {
+            return Err(Error::InvalidArgument(format!(
+                "duplicate tensor name {:?}",
+                name
+            )));
+        }
+        Ok(c)
+    }
+
+    /// Add a tensor under its own name. Its data must be host memory and
+    /// contiguous, and is read when the file is written.
+    pub fn add_tensor(&mut self, t: Tensor<'a>) -> Result<&mut Self> {
+        self.check_new_name(t.name())?;
+        if t.data().is_null() || !t.is_host() {
+            return Err(Error::NoData);
+        }
+        if !t.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(t.data() as *const u8, t.nbytes()) };
+        unsafe { crate::gguf_add_tensor(self.as_ptr(), t.as_ptr()) };
+        self.data.push(Cow::Borrowed(bytes));
+        Ok(self)
+    }
+
+    /// Add a tensor of shape `ne` from a slice of elements.
+    pub fn add_tensor_data<T: Element>(
+        &mut self,
+        name: &str,
+        ne: &[i64],
+        values: &'a [T],
+    ) -> Result<&mut Self> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+        };
+        self.add_tensor_bytes(name, T::TYPE, ne, bytes)
+    }
+
+    /// Add a tensor of any type, including quantized ones, from its raw
+    /// bytes in ggml layout.
+    pub fn add_tensor_bytes(
+        &mut self,
+        name: &str,
+        ty: Type,
+        ne: &[i64],
+        bytes: impl Into<Cow<'a, [u8]>>,
+    ) -> Result<&mut Self> {
+        let bytes = bytes.into();
+        let expected = tensor_nbytes(ty, ne)?;
+        if bytes.len() != expected {
+            return Err(Error::ShapeMismatch(format!(
+                "{} bytes for a {} tensor of shape {:?}, expected {}",
+                bytes.len(),
+                ty,
+                ne,
+                expected
+            )));
+        }
+        self.add_tensor_info(name, ty, ne)?;
+        self.data.push(bytes);
+        Ok(self)
+    }
+
+    /// Add the info of a tensor whose data is written separately, returning
+    /// its size in bytes.
+    pub(super) fn add_tensor_info(&mut self, name: &str, ty: Type, ne: &[i64]) -> Result<usize> {
+        let c_name = self.check_new_name(name)?;
+        let size = tensor_nbytes(ty, ne)?;
+        let mut full = [1i64; 4];
+        full[..ne.len()].copy_from_slice(ne);
+
+        // metadata-only tensor; gguf copies the struct and we write the data
+        let mut info: crate::ggml_tensor = unsafe { std::mem::zeroed() };
+        info.type_ = ty.as_raw();
+        info.ne = full;
+        info.nb[0] = ty.type_size();
+        info.nb[1] = ty.row_size(full[0]);
+        for i in 2..4 {
+            info.nb[i] = info.nb[i - 1] * full[i - 1] as usize;
+        }
+        for (dst, &src) in info.name.iter_mut().zip(c_name.as_bytes_with_nul()) {
+            *dst = src as std::os::raw::c_char;
+        }
+        unsafe { crate::gguf_add_tensor(self.as_ptr(), &info) };
+        Ok(size)
+    }
+
+    /// Data of the tensors added with their data, in insertion order.
+    pub(super) fn tensor_data(&self) -> &[Cow<'a, [u8]>] {
+        &self.data
+    }
+
+    /// Header, metadata and tensor infos, padded to the data section.
+    pub fn meta_bytes(&self) -> Vec<u8> {
+        let size = unsafe { crate::gguf_get_meta_size(self.as_ptr()) };
+        let mut meta = vec![0u8; size];
+        unsafe { crate::gguf_get_meta_data(self.as_ptr(), meta.as_mut_ptr() as *mut _) };
+        if self.alignment == unsafe { crate::gguf_get_alignment(self.as_ptr()) } {
+            return meta;
+        }
+        // gguf laid the header out for its own alignment
+        realign_header(&meta).expect("gguf wrote a header it cannot parse")
+    }
+
+    /// Write the complete file to `w`.
+    pub fn write_to_writer(&self, w: &mut impl Write) -> Result<()> {
+        if self.data.len() as i64 != self.n_tensors() {
+            return Err(Error::InvalidArgument(
+                "tensors declared for streaming have no data".to_string(),
+            ));
+        }
+        let alignment = self.alignment();
+        w.write_all(&self.meta_bytes())?;
+        let zeros = vec![0u8; alignment];
+        for bytes in &self.data {
+            w.write_all(bytes)?;
+            let pad = (alignment - bytes.len() % alignment) % alignment;
+            w.write_all(&zeros[..pad])?;
+        }
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to_writer(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Memory page size of the host.
+fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if size > 0 {
+            return size as usize;
+        }
+    }
+    4096
+}
+
+/// Size in bytes of a contiguous tensor of type `ty` and shape `ne`.
+fn tensor_nbytes(ty: Type, ne: &[i64]) -> Result<usize> {
+    if ne.is_empty() || ne.len() > crate::GGML_MAX_DIMS as usize || ne.iter().any(|&n| n < 0) {
+        return Err(Error::InvalidArgument(format!(
+            "invalid tensor shape {:?}",
+            ne
+        )));
+    }
+    if ne[0] % ty.block_size() as i64 != 0 {
+        return Err(Error::ShapeMismatch(format!(
+            "ne[0] = {} is not a multiple of the {} block size {}",
+            ne[0],
+            ty,
+            ty.block_size()
+        )));
+    }
+    let rows: i64 = ne[1..].iter().product();
+    Ok(ty.row_size(ne[0]) * rows as usize)
+}
+
+/// Native-endian bytes of a scalar value, as `gguf_set_arr_data` expects.
+fn push_ne_bytes(out: &mut Vec<u8>, v: &GgufValue) {
+    match *v {
+        GgufValue::U8(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I8(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::U16(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I16(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::U32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::F32(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::Bool(x) => out.push(x as u8),
+        GgufValue::U64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::I64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::F64(x) => out.extend_from_slice(&x.to_ne_bytes()),
+        GgufValue::String(_) | GgufValue::Array(_) => {}
+    }
+}
+
+impl Drop for GgufWriter<'_> {
+    fn drop(&mut self) {
+        unsafe { crate::gguf_free(self.as_ptr()) }
+    }
+}
@@ -0,0 +1,89 @@
+//! Computation graphs (`ggml_cgraph`) and CPU compute.
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::context::Context;
+use crate::error::{check_status, Result};
+use crate::tensor::Tensor;
+
+/// A computation graph allocated inside a [`Context`].
+pub struct Graph<'ctx> {
+    ptr: NonNull<crate::ggml_cgraph>,
+    grads: bool,
+    _ctx: PhantomData<&'ctx Context>,
+}
+
+impl<'ctx> Graph<'ctx> {
+    /// Wrap a graph without gradient storage.
+    ///
+    /// # Safety
+    /// `ptr` must point to a graph that lives at least as long as `'ctx`.
+    pub unsafe fn from_raw(ptr: *mut crate::ggml_cgraph) -> Option<Self> {
+        Self::from_raw_with_grads(ptr, false)
+    }
+
+    /// Wrap a graph, recording whether it was created with `grads = true`.
+    ///
+    /// # Safety
+    /// As for [`Graph::from_raw`], and `grads` must match how the graph was
+    /// allocated.
+    pub unsafe fn from_raw_with_grads(ptr: *mut crate::ggml_cgraph, grads: bool) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Graph {
+            ptr,
+            grads,
+            _ctx: PhantomData,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::ggml_cgraph {
+        self.ptr.as_ptr()
+    }
+
+    /// Add `tensor` and everything it depends on to the graph.
+    pub fn build_forward_expand(&mut self, tensor: Tensor<'_>) {
+        unsafe { crate::ggml_build_forward_expand(self.as_ptr(), tensor.as_ptr()) }
+    }
+
+    /// Whether the graph has storage for gradients (see
+    /// [`Context::new_graph_custom`]).
+    pub fn has_grads(&self) -> bool {
+        self.grads
+    }
+
+    /// Maximum number of nodes the graph can hold.
+    pub fn size(&self) -> usize {
+        unsafe { crate::ggml_graph_size(self.as_ptr()) as usize }
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        unsafe { crate::ggml_graph_n_nodes(self.as_ptr()) as usize }
+    }
+
+    /// Node `i` in execution order.
+    pub fn node(&self, i: usize) -> Option<Tensor<'ctx>> {
+        if i >= self.n_nodes() {
+            return None;
+        }
+        unsafe { Tensor::from_raw(crate::ggml_graph_node(self.as_ptr(), i as i32)) }
+    }
+
+    /// Iterate over nodes in execution order.
+    pub fn nodes(&self) -> impl Iterator<Item = Tensor<'ctx>> + '_ {
+        (0..self.n_nodes()).filter_map(move |i| self.node(i))
+    }
+
+    /// The last node, usually the graph output.
+    pub fn last_node(&self) -> Option<Tensor<'ctx>> {
+        self.n_nodes().checked_sub(1).and_then(|i| self.node(i))
+    }
+
+    /// Compute the graph on the CPU, allocating the work buffer from `ctx`.
+    pub fn compute(&mut self, ctx: &Context, n_threads: usize) -> Result<()> {
+        let n_threads = n_threads.clamp(1, crate::threadpool::max_threads());
+        let status = unsafe {
+            crate::ggml_graph_compute_with_ctx(ctx.as_ptr(), self.as_ptr(), n_threads as i32)
+        };
+        check_status(status)
+    }
+}
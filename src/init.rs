@@ -0,0 +1,81 @@
+//! Convenience constructors for filled tensors.
+//!
+//! These write their values immediately, so they need a context that
+//! allocates data (not `no_alloc`). [`Context::arange`] is the exception: it
+//! is a graph op whose values appear once the graph is computed.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+impl Context {
+    fn new_filled(&self, ty: Type, ne: &[i64]) -> Result<Tensor<'_>> {
+        if self.no_alloc() {
+            return Err(Error::NoData);
+        }
+        self.new_tensor(ty, ne)
+    }
+
+    /// One-element F32 tensor holding `value`.
+    pub fn scalar_f32(&self, value: f32) -> Result<Tensor<'_>> {
+        let t = self.new_filled(Type::F32, &[1])?;
+        t.write(&[value])?;
+        Ok(t)
+    }
+
+    /// One-element I32 tensor holding `value`.
+    pub fn scalar_i32(&self, value: i32) -> Result<Tensor<'_>> {
+        let t = self.new_filled(Type::I32, &[1])?;
+        t.write(&[value])?;
+        Ok(t)
+    }
+
+    /// Tensor of shape `ne` with every byte set to zero. Works for any type,
+    /// including quantized ones.
+    pub fn zeros(&self, ne: &[i64], ty: Type) -> Result<Tensor<'_>> {
+        let t = self.new_filled(ty, ne)?;
+        unsafe { crate::ggml_set_zero(t.as_ptr()) };
+        Ok(t)
+    }
+
+    /// Tensor of shape `ne` with every element set to one.
+    pub fn ones(&self, ne: &[i64], ty: Type) -> Result<Tensor<'_>> {
+        self.full(ne, ty, 1.0)
+    }
+
+    /// Tensor of shape `ne` with every element set to `value`, converted to
+    /// `ty`. Only F32, F16, BF16 and the integer types are supported.
+    pub fn full(&self, ne: &[i64], ty: Type, value: f32) -> Result<Tensor<'_>> {
+        if !matches!(
+            ty,
+            Type::F32 | Type::F16 | Type::BF16 | Type::I8 | Type::I16 | Type::I32
+        ) {
+            return Err(Error::InvalidArgument(format!(
+                "cannot fill a {} tensor with a scalar",
+                ty
+            )));
+        }
+        let t = self.new_filled(ty, ne)?;
+        unsafe { crate::ggml_set_f32(t.as_ptr(), value) };
+        Ok(t)
+    }
+
+    /// F32 tensor `[start, start + step, ...]` up to but excluding `stop`.
+    ///
+    /// This is a graph op: the values are written when the graph containing
+    /// the result is computed.
+    pub fn arange(&self, start: f32, stop: f32, step: f32) -> Result<Tensor<'_>> {
+        if !(step > 0.0 && stop > start) {
+            return Err(Error::InvalidArgument(format!(
+                "arange({}, {}, {}) is empty",
+                start, stop, step
+            )));
+        }
+        let n = ((stop - start) / step).ceil() as i64;
+        self.ensure_capacity(Type::F32.row_size(n))?;
+        self.op_result("ggml_arange", unsafe {
+            crate::ggml_arange(self.as_ptr(), start, stop, step)
+        })
+    }
+}
@@ -0,0 +1,201 @@
+//! In-place op variants.
+//!
+//! ggml's `_inplace` ops return a view that writes over its input, which saves
+//! graph memory but means the input no longer holds its original value once
+//! the graph runs. To keep that visible in the types, in-place ops take and
+//! return a [`TensorMut`]: a handle that is not `Copy`, so the pre-op handle is
+//! moved into the op and cannot be reused by accident.
+//!
+//! This is a lint, not a guarantee. [`Tensor`] is `Copy`, so copies made
+//! before [`Tensor::into_mut`], or through `*x` on a `TensorMut`, still refer
+//! to the same tensor and see the overwritten data. Exclusivity is not
+//! enforced.
+//!
+//! ```ignore
+//! let x = ctx.new_tensor_1d(Type::F32, 8)?.into_mut();
+//! let x = ctx.scale_inplace(x, 0.5)?;
+//! let x = ctx.add_inplace(x, bias)?;
+//! let y = ctx.soft_max(x.freeze())?;
+//! ```
+
+use std::ops::Deref;
+
+use crate::context::Context;
+use crate::error::Result;
+use crate::tensor::Tensor;
+
+/// A tensor handle that in-place ops can write through.
+///
+/// Not `Copy`, but not unique either: other [`Tensor`] copies of the same
+/// tensor may exist, and they see the overwritten data. Exclusivity is not
+/// enforced. Dereferences to [`Tensor`] for read-only metadata access. Use
+/// [`TensorMut::freeze`] to get a `Copy` handle once no further in-place ops
+/// will be applied.
+#[derive(Debug)]
+pub struct TensorMut<'a> {
+    tensor: Tensor<'a>,
+}
+
+impl<'a> TensorMut<'a> {
+    /// Return a `Copy` handle to the same tensor.
+    pub fn freeze(self) -> Tensor<'a> {
+        self.tensor
+    }
+
+    /// The tensor whose memory this handle ultimately writes to.
+    ///
+    /// For a chain of in-place ops this is the original input tensor.
+    pub fn storage(&self) -> Tensor<'a> {
+        let mut t = self.tensor;
+        while let Some(src) = unsafe { Tensor::from_raw((*t.as_ptr()).view_src) } {
+            t = src;
+        }
+        t
+    }
+}
+
+impl<'a> Deref for TensorMut<'a> {
+    type Target = Tensor<'a>;
+
+    fn deref(&self) -> &Tensor<'a> {
+        &self.tensor
+    }
+}
+
+impl<'a> Tensor<'a> {
+    /// Convert into a handle for use with in-place ops.
+    ///
+    /// Other copies of `self` stay valid, but after an in-place op they
+    /// observe the overwritten data; prefer converting fresh tensors.
+    pub fn into_mut(self) -> TensorMut<'a> {
+        TensorMut { tensor: self }
+    }
+}
+
+impl Context {
+    fn inplace_result<'a>(
+        &'a self,
+        what: &'static str,
+        ptr: *mut crate::ggml_tensor,
+    ) -> Result<TensorMut<'a>> {
+        self.op_result(what, ptr).map(Tensor::into_mut)
+    }
+
+    /// Fresh copy of `a` that can be modified in place without touching `a`.
+    pub fn dup_mut<'a>(&'a self, a: Tensor<'a>) -> Result<TensorMut<'a>> {
+        self.dup(a).map(Tensor::into_mut)
+    }
+
+    /// `a += b`, with `b` broadcast to the shape of `a`.
+    pub fn add_inplace<'a>(&'a self, a: TensorMut<'a>, b: Tensor<'a>) -> Result<TensorMut<'a>> {
+        Self::check_can_repeat(b, *a)?;
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_add_inplace(self.as_ptr(), a.as_ptr(), b.as_ptr()) };
+        self.inplace_result("ggml_add_inplace", ptr)
+    }
+
+    /// `a -= b`, with `b` broadcast to the shape of `a`.
+    pub fn sub_inplace<'a>(&'a self, a: TensorMut<'a>, b: Tensor<'a>) -> Result<TensorMut<'a>> {
+        Self::check_can_repeat(b, *a)?;
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_sub_inplace(self.as_ptr(), a.as_ptr(), b.as_ptr()) };
+        self.inplace_result("ggml_sub_inplace", ptr)
+    }
+
+    /// `a *= b`, with `b` broadcast to the shape of `a`.
+    pub fn mul_inplace<'a>(&'a self, a: TensorMut<'a>, b: Tensor<'a>) -> Result<TensorMut<'a>> {
+        Self::check_can_repeat(b, *a)?;
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_mul_inplace(self.as_ptr(), a.as_ptr(), b.as_ptr()) };
+        self.inplace_result("ggml_mul_inplace", ptr)
+    }
+
+    /// `a /= b`, with `b` broadcast to the shape of `a`.
+    pub fn div_inplace<'a>(&'a self, a: TensorMut<'a>, b: Tensor<'a>) -> Result<TensorMut<'a>> {
+        Self::check_can_repeat(b, *a)?;
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_div_inplace(self.as_ptr(), a.as_ptr(), b.as_ptr()) };
+        self.inplace_result("ggml_div_inplace", ptr)
+    }
+
+    /// `a *= s`. `a` must be F32.
+    pub fn scale_inplace<'a>(&'a self, a: TensorMut<'a>, s: f32) -> Result<TensorMut<'a>> {
+        self.scale_bias_inplace(a, s, 0.0)
+    }
+
+    /// Softmax along rows, written over `a`. `a` must be contiguous.
+    pub fn soft_max_inplace<'a>(&'a self, a: TensorMut<'a>) -> Result<TensorMut<'a>> {
+        if !a.is_contiguous() {
+            return Err(crate::Error::NotContiguous);
+        }
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_soft_max_inplace(self.as_ptr(), a.as_ptr()) };
+        self.inplace_result("ggml_soft_max_inplace", ptr)
+    }
+
+    /// [`Context::soft_max_ext`] written over `a`. `a` must be contiguous.
+    pub fn soft_max_ext_inplace<'a>(
+        &'a self,
+        a: TensorMut<'a>,
+        mask: Option<Tensor<'a>>,
+        scale: f32,
+        max_bias: f32,
+    ) -> Result<TensorMut<'a>> {
+        if !a.is_contiguous() {
+            return Err(crate::Error::NotContiguous);
+        }
+        Self::check_soft_max_ext(*a, mask, max_bias)?;
+        self.ensure_capacity(0)?;
+        let mask = mask.map_or(std::ptr::null_mut(), |m| m.as_ptr());
+        let ptr = unsafe {
+            crate::ggml_soft_max_ext_inplace(self.as_ptr(), a.as_ptr(), mask, scale, max_bias)
+        };
+        self.inplace_result("ggml_soft_max_ext_inplace", ptr)
+    }
+
+    /// Row normalization written over `a`.
+    pub fn norm_inplace<'a>(&'a self, a: TensorMut<'a>, eps: f32) -> Result<TensorMut<'a>> {
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_norm_inplace(self.as_ptr(), a.as_ptr(), eps) };
+        self.inplace_result("ggml_norm_inplace", ptr)
+    }
+
+    /// RMS normalization written over `a`.
+    pub fn rms_norm_inplace<'a>(&'a self, a: TensorMut<'a>, eps: f32) -> Result<TensorMut<'a>> {
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_rms_norm_inplace(self.as_ptr(), a.as_ptr(), eps) };
+        self.inplace_result("ggml_rms_norm_inplace", ptr)
+    }
+
+    /// Causal `-inf` mask written over `a`; see [`Context::diag_mask_inf`].
+    pub fn diag_mask_inf_inplace<'a>(
+        &'a self,
+        a: TensorMut<'a>,
+        n_past: usize,
+    ) -> Result<TensorMut<'a>> {
+        Self::check_f32("diag_mask_inf", *a)?;
+        let n_past = Self::check_n_past(n_past)?;
+        if a.nb()[0] != a.element_size() {
+            return Err(crate::Error::NotContiguous);
+        }
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_diag_mask_inf_inplace(self.as_ptr(), a.as_ptr(), n_past) };
+        self.inplace_result("ggml_diag_mask_inf_inplace", ptr)
+    }
+
+    /// Causal zero mask written over `a`; see [`Context::diag_mask_zero`].
+    pub fn diag_mask_zero_inplace<'a>(
+        &'a self,
+        a: TensorMut<'a>,
+        n_past: usize,
+    ) -> Result<TensorMut<'a>> {
+        Self::check_f32("diag_mask_zero", *a)?;
+        let n_past = Self::check_n_past(n_past)?;
+        if a.nb()[0] != a.element_size() {
+            return Err(crate::Error::NotContiguous);
+        }
+        self.ensure_capacity(0)?;
+        let ptr = unsafe { crate::ggml_diag_mask_zero_inplace(self.as_ptr(), a.as_ptr(), n_past) };
+        self.inplace_result("ggml_diag_mask_zero_inplace", ptr)
+    }
+}
@@ -0,0 +1,144 @@
+//! Minimal binary dump format for single tensors.
+//!
+//! Meant for exchanging intermediate activations with reference code, not
+//! for model storage (use GGUF for that). All integers are little-endian:
+//!
+//! | field   | type       | notes                                  |
+//! |---------|------------|----------------------------------------|
+//! | magic   | `[u8; 4]`  | `b"GGTR"`                              |
+//! | version | `u32`      | `1`                                    |
+//! | type    | `u32`      | `enum ggml_type` value                 |
+//! | n_dims  | `u32`      | 1 to 4                                 |
+//! | ne      | `[i64; 4]` | `ne[0]` innermost; unused dims are `1` |
+//! | name    | `u32`, bytes | byte length, then UTF-8            |
+//! | data    | bytes      | contiguous, `ggml_nbytes` long         |
+//!
+//! With numpy, an F32 dump reads as
+//! `np.fromfile(p, np.float32, offset=52 + name_len).reshape(ne[::-1])`.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+const MAGIC: &[u8; 4] = b"GGTR";
+const VERSION: u32 = 1;
+
+fn read_u32(r: &mut impl Read) -> Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_i64(r: &mut impl Read) -> Result<i64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(i64::from_le_bytes(b))
+}
+
+impl Tensor<'_> {
+    /// Dump the tensor to `path` in the format described in this module.
+    pub fn write_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_to_writer(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Dump the tensor to any writer. The data must be host memory and
+    /// contiguous.
+    pub fn write_to_writer(&self, w: &mut impl Write) -> Result<()> {
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        let name = self.name().as_bytes();
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.ty().as_raw().to_le_bytes())?;
+        w.write_all(&(self.n_dims() as u32).to_le_bytes())?;
+        for ne in self.ne() {
+            w.write_all(&ne.to_le_bytes())?;
+        }
+        w.write_all(&(name.len() as u32).to_le_bytes())?;
+        w.write_all(name)?;
+        let data = unsafe { std::slice::from_raw_parts(self.data() as *const u8, self.nbytes()) };
+        w.write_all(data)?;
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Load a tensor written by [`Tensor::write_to`] into this context.
+    pub fn read_tensor(&self, path: impl AsRef<Path>) -> Result<Tensor<'_>> {
+        self.read_tensor_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Load a tensor from any reader. The context must allocate data (not
+    /// `no_alloc`).
+    pub fn read_tensor_from(&self, r: &mut impl Read) -> Result<Tensor<'_>> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "bad tensor dump magic {:?}",
+                magic
+            )));
+        }
+        let version = read_u32(r)?;
+        if version != VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "unsupported tensor dump version {}",
+                version
+            )));
+        }
+        let raw_type = read_u32(r)?;
+        let ty = Type::from_raw(raw_type)
+            .ok_or_else(|| Error::InvalidFormat(format!("unknown ggml type {}", raw_type)))?;
+        let n_dims = read_u32(r)? as usize;
+        if !(1..=crate::GGML_MAX_DIMS as usize).contains(&n_dims) {
+            return Err(Error::InvalidFormat(format!("invalid n_dims {}", n_dims)));
+        }
+        let mut ne = [0i64; 4];
+        for n in &mut ne {
+            *n = read_i64(r)?;
+        }
+        if ne[n_dims..].iter().any(|&n| n != 1) {
+            return Err(Error::InvalidFormat(format!(
+                "shape {:?} has more than {} dimensions",
+                ne, n_dims
+            )));
+        }
+        if ty.checked_nbytes(&ne).is_none() {
+            return Err(Error::InvalidFormat(format!(
+                "{} tensor of shape {:?} is invalid or too large",
+                ty, ne
+            )));
+        }
+        let name_len = read_u32(r)? as usize;
+        if name_len >= crate::GGML_MAX_NAME as usize {
+            return Err(Error::InvalidFormat(format!(
+                "tensor name of {} bytes is too long",
+                name_len
+            )));
+        }
+        let mut name = vec![0; name_len];
+        r.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| Error::InvalidFormat("tensor name is not UTF-8".to_string()))?;
+
+        if self.no_alloc() {
+            return Err(Error::NoData);
+        }
+        let t = self.new_tensor(ty, &ne[..n_dims])?;
+        let data = unsafe { std::slice::from_raw_parts_mut(t.data() as *mut u8, t.nbytes()) };
+        r.read_exact(data)?;
+        Ok(t.set_name(&name))
+    }
+}
@@ -1,8 +1,123 @@
-#![allow(non_upper_case_globals)]
-#![allow(non_camel_case_types)]
-#![allow(non_snake_case)]
-#![allow(clippy::all)]
+/// Items that need the compiled ggml library, built with the `native`
+/// feature. Without it only the pure-Rust parts of [`gguf`] remain.
+macro_rules! cfg_native {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "native")]
+            $item
+        )*
+    };
+}
 
-// Include the generated bindings
-include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+// Include the generated bindings, re-exported at the crate root
+#[cfg(feature = "native")]
+#[allow(non_upper_case_globals, non_camel_case_types, non_snake_case)]
+#[allow(clippy::all)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+#[cfg(feature = "native")]
+pub use bindings::*;
 
+// Safe wrappers over the raw bindings above
+mod error;
+pub mod gguf;
+#[cfg(feature = "reference")]
+pub mod reference;
+
+cfg_native! {
+    #[cfg(feature = "arrow")]
+    mod arrow;
+    mod attention;
+    mod audio;
+    mod autodiff;
+    mod build_info;
+    pub mod backend;
+    #[cfg(feature = "capi")]
+    mod capi;
+    mod context;
+    mod dataset;
+    mod graph;
+    mod init;
+    mod inplace;
+    mod io;
+    #[cfg(feature = "nalgebra")]
+    mod linalg;
+    mod loss;
+    mod math;
+    mod npy;
+    mod ops;
+    mod opt;
+    #[cfg(feature = "bytemuck")]
+    pub mod pod;
+    #[cfg(feature = "image")]
+    mod preprocess;
+    mod profile;
+    #[cfg(feature = "python")]
+    mod python;
+    pub mod quant;
+    mod random;
+    mod rope;
+    #[cfg(feature = "rayon")]
+    mod rows;
+    mod slice;
+    #[cfg(feature = "serde")]
+    mod snapshot;
+    mod ssm;
+    mod tensor;
+    pub mod testing;
+    mod threadpool;
+    #[cfg(feature = "tch")]
+    mod torch;
+    mod types;
+    mod unary;
+    mod vision;
+}
+
+pub use error::{Error, Result};
+pub use gguf::{Endian, GgufBuilder, GgufHeader, GgufTensorHeader, GgufType, GgufValue};
+
+cfg_native! {
+    pub use attention::Precision;
+    pub use audio::{MelFilters, MelParams, MelSpectrogram, WHISPER_N_SAMPLES, WHISPER_SAMPLE_RATE};
+    #[cfg(feature = "metal")]
+    pub use backend::MetalBackend;
+    pub use backend::{
+        Backend, BackendBuffer, BackendRegistry, BufferType, CpuBackend, Device, DeviceType, Event,
+        GraphAllocator, GraphPlan, HugePages, PendingCompute, Scheduler,
+    };
+    #[cfg(feature = "cuda")]
+    pub use backend::{CudaBackend, CudaDevice};
+    #[cfg(feature = "rpc")]
+    pub use backend::{RpcBackend, RpcServer};
+    #[cfg(feature = "vulkan")]
+    pub use backend::{VulkanBackend, VulkanDevice};
+    pub use build_info::{build_info, cpu_features, cpu_has, BuildInfo};
+    pub use context::{Context, ContextParams};
+    pub use dataset::{BatchSource, Dataset, StreamingDataset};
+    pub use gguf::{
+        convert_endian, GgufFile, GgufModel, GgufScalar, GgufStreamWriter, GgufTensorInfo,
+        GgufWriter, Issue, MmapAdvice, MmapParams, Severity, ValidationReport,
+    };
+    #[cfg(feature = "tokio")]
+    pub use gguf::{AsyncGgufReader, LoadProgress};
+    pub use graph::Graph;
+    pub use inplace::TensorMut;
+    pub use npy::write_npz;
+    pub use ops::{Reduction, SortOrder};
+    pub use opt::{AdamW, LossType, OptResult, Optimizer, OptimizerConfig, OptimizerParams, Sgd};
+    #[cfg(feature = "image")]
+    pub use preprocess::{ImageNorm, ImagePreprocess, Resize};
+    pub use profile::{NodeProfile, OpProfile, ProfileReport};
+    pub use quant::QuantType;
+    pub use random::Rng;
+    pub use rope::{MropeMode, MropeSections, RopeParams};
+    pub use slice::SliceArg;
+    #[cfg(feature = "serde")]
+    pub use snapshot::{ContextSnapshot, TensorSnapshot};
+    pub use tensor::Tensor;
+    pub use threadpool::{NumaStrategy, Priority, Threadpool, ThreadpoolParams};
+    pub use types::{Element, Type};
+    pub use unary::UnaryOp;
+    pub use vision::ScaleMode;
+}
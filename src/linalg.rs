@@ -0,0 +1,151 @@
+//! Conversions between `nalgebra` matrices and vectors and 1D/2D tensors.
+//!
+//! A matrix with `r` rows and `c` columns maps to a tensor of shape
+//! `[c, r]`: tensor rows are matrix rows, so `ne[0]` runs along a row. A
+//! vector of length `n` maps to a 1D tensor `[n]`. nalgebra stores
+//! matrices column-major, so conversions copy element by element; they are
+//! meant for small pre- and post-processing, not model weights.
+//!
+//! ```ignore
+//! let rot = Matrix3::new(0.0, -1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0);
+//! let r = ctx.new_tensor_from_matrix(&rot)?; // [3, 3]
+//! let p = ctx.new_tensor_from_vector(&point)?; // [3]
+//! let q = ctx.mul_mat(r, p)?; // rot * point
+//! // ... compute ...
+//! let q: DVector<f32> = q.to_dvector()?;
+//! ```
+
+use nalgebra::{DMatrix, DVector, Dim, Matrix, RawStorage, SMatrix, Scalar, U1};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Element;
+
+/// Elements of `m` in row-major order.
+fn row_major<T, R, C, S>(m: &Matrix<T, R, C, S>) -> Vec<T>
+where
+    T: Scalar + Copy,
+    R: Dim,
+    C: Dim,
+    S: RawStorage<T, R, C>,
+{
+    let (rows, cols) = m.shape();
+    (0..rows)
+        .flat_map(|i| (0..cols).map(move |j| m[(i, j)]))
+        .collect()
+}
+
+impl Context {
+    /// A new tensor `[ncols, nrows]` holding `m`, of the ggml type matching
+    /// `T`.
+    pub fn new_tensor_from_matrix<T, R, C, S>(&self, m: &Matrix<T, R, C, S>) -> Result<Tensor<'_>>
+    where
+        T: Element + Scalar,
+        R: Dim,
+        C: Dim,
+        S: RawStorage<T, R, C>,
+    {
+        let (rows, cols) = m.shape();
+        let t = self.new_tensor_2d(T::TYPE, cols as i64, rows as i64)?;
+        t.write(&row_major(m))?;
+        Ok(t)
+    }
+
+    /// A new 1D tensor holding `v`, of the ggml type matching `T`.
+    pub fn new_tensor_from_vector<T, R, S>(&self, v: &Matrix<T, R, U1, S>) -> Result<Tensor<'_>>
+    where
+        T: Element + Scalar,
+        R: Dim,
+        S: RawStorage<T, R, U1>,
+    {
+        let t = self.new_tensor_1d(T::TYPE, v.len() as i64)?;
+        t.write(&row_major(v))?;
+        Ok(t)
+    }
+}
+
+impl Tensor<'_> {
+    /// `(nrows, ncols)` of the matrix this tensor holds, if it is 2D.
+    fn matrix_shape(&self) -> Result<(usize, usize)> {
+        let ne = self.ne();
+        if ne[2] != 1 || ne[3] != 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "a matrix needs a 1D or 2D tensor, not {:?}",
+                ne
+            )));
+        }
+        Ok((ne[1] as usize, ne[0] as usize))
+    }
+
+    /// Overwrite the tensor with `m`, e.g. to update a graph input. The
+    /// tensor must be `[ncols, nrows]` of the type matching `T`.
+    pub fn write_matrix<T, R, C, S>(&self, m: &Matrix<T, R, C, S>) -> Result<()>
+    where
+        T: Element + Scalar,
+        R: Dim,
+        C: Dim,
+        S: RawStorage<T, R, C>,
+    {
+        if self.matrix_shape()? != m.shape() {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot write a {}x{} matrix to a tensor of shape {:?}",
+                m.nrows(),
+                m.ncols(),
+                self.ne()
+            )));
+        }
+        self.write(&row_major(m))
+    }
+
+    /// Overwrite the 1D tensor with `v`.
+    pub fn write_vector<T, R, S>(&self, v: &Matrix<T, R, U1, S>) -> Result<()>
+    where
+        T: Element + Scalar,
+        R: Dim,
+        S: RawStorage<T, R, U1>,
+    {
+        if self.n_dims() > 1 || self.nelements() as usize != v.len() {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot write a vector of {} elements to a tensor of shape {:?}",
+                v.len(),
+                self.ne()
+            )));
+        }
+        self.write(&row_major(v))
+    }
+
+    /// Copy a 1D or 2D tensor into a matrix, one tensor row per matrix row.
+    /// The tensor's type must match `T`.
+    pub fn to_dmatrix<T: Element + Scalar>(&self) -> Result<DMatrix<T>> {
+        let (rows, cols) = self.matrix_shape()?;
+        Ok(DMatrix::from_row_slice(rows, cols, &self.to_vec::<T>()?))
+    }
+
+    /// Like [`to_dmatrix`](Self::to_dmatrix), with the size known at
+    /// compile time.
+    pub fn to_smatrix<T: Element + Scalar, const R: usize, const C: usize>(
+        &self,
+    ) -> Result<SMatrix<T, R, C>> {
+        if self.matrix_shape()? != (R, C) {
+            return Err(Error::ShapeMismatch(format!(
+                "a tensor of shape {:?} is not a {}x{} matrix",
+                self.ne(),
+                R,
+                C
+            )));
+        }
+        Ok(SMatrix::from_row_slice(&self.to_vec::<T>()?))
+    }
+
+    /// Copy a 1D tensor into a vector. The tensor's type must match `T`.
+    pub fn to_dvector<T: Element + Scalar>(&self) -> Result<DVector<T>> {
+        if self.n_dims() > 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "a vector needs a 1D tensor, not {:?}",
+                self.ne()
+            )));
+        }
+        Ok(DVector::from_vec(self.to_vec::<T>()?))
+    }
+}
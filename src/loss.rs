@@ -0,0 +1,122 @@
+//! Loss functions and training targets.
+//!
+//! Targets are probability distributions laid out like the logits,
+//! `[n_classes, n]`: one column per datapoint (or per token for a causal LM,
+//! with the targets being the input tokens shifted by one).
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+fn check_same_shape(what: &str, a: Tensor<'_>, b: Tensor<'_>) -> Result<()> {
+    if !a.same_shape(b) {
+        return Err(Error::ShapeMismatch(format!(
+            "{} of {:?} and {:?}",
+            what,
+            a.ne(),
+            b.ne()
+        )));
+    }
+    Ok(())
+}
+
+impl Context {
+    /// Mean over columns of the cross-entropy between `softmax(logits)` and
+    /// `labels`, as an F32 scalar. Both must be F32 with the same shape;
+    /// each column of `labels` should sum to 1.
+    pub fn cross_entropy_loss<'a>(
+        &'a self,
+        logits: Tensor<'a>,
+        labels: Tensor<'a>,
+    ) -> Result<Tensor<'a>> {
+        Self::check_f32("cross_entropy_loss", logits)?;
+        Self::check_f32("cross_entropy_loss", labels)?;
+        check_same_shape("cross-entropy", logits, labels)?;
+        let logits = self.require_contiguous(logits)?;
+        let labels = self.require_contiguous(labels)?;
+        self.ensure_capacity(std::mem::size_of::<f32>())?;
+        self.op_result("ggml_cross_entropy_loss", unsafe {
+            crate::ggml_cross_entropy_loss(self.as_ptr(), logits.as_ptr(), labels.as_ptr())
+        })
+    }
+
+    /// Gradient of [`Context::cross_entropy_loss`] with respect to the
+    /// logits, scaled by the scalar upstream gradient `grad`.
+    pub fn cross_entropy_loss_back<'a>(
+        &'a self,
+        grad: Tensor<'a>,
+        logits: Tensor<'a>,
+        labels: Tensor<'a>,
+    ) -> Result<Tensor<'a>> {
+        Self::check_f32("cross_entropy_loss_back", grad)?;
+        Self::check_f32("cross_entropy_loss_back", logits)?;
+        Self::check_f32("cross_entropy_loss_back", labels)?;
+        if grad.nelements() != 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "cross-entropy gradient must be a scalar, got {:?}",
+                grad.ne()
+            )));
+        }
+        check_same_shape("cross-entropy", logits, labels)?;
+        let grad = self.require_contiguous(grad)?;
+        let logits = self.require_contiguous(logits)?;
+        let labels = self.require_contiguous(labels)?;
+        self.ensure_capacity(logits.nbytes())?;
+        self.op_result("ggml_cross_entropy_loss_back", unsafe {
+            crate::ggml_cross_entropy_loss_back(
+                self.as_ptr(),
+                grad.as_ptr(),
+                logits.as_ptr(),
+                labels.as_ptr(),
+            )
+        })
+    }
+
+    /// Label smoothing: `labels * (1 - eps) + eps / n_classes`, with
+    /// `n_classes = ne[0]`. `labels` must be F32.
+    pub fn smooth_labels<'a>(&'a self, labels: Tensor<'a>, eps: f32) -> Result<Tensor<'a>> {
+        if !(0.0..=1.0).contains(&eps) {
+            return Err(Error::InvalidArgument(format!(
+                "label smoothing must be in [0, 1], got {}",
+                eps
+            )));
+        }
+        let n_classes = labels.ne()[0] as f32;
+        self.scale_bias(labels, 1.0 - eps, eps / n_classes)
+    }
+}
+
+impl Tensor<'_> {
+    /// Fill a `[n_classes, n]` target tensor with one column per entry of
+    /// `classes`, holding `1 - smoothing + smoothing / n_classes` at the
+    /// class and `smoothing / n_classes` elsewhere.
+    pub fn write_one_hot(&self, classes: &[i32], smoothing: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&smoothing) {
+            return Err(Error::InvalidArgument(format!(
+                "label smoothing must be in [0, 1], got {}",
+                smoothing
+            )));
+        }
+        let n_classes = self.ne()[0];
+        if classes.len() as i64 * n_classes != self.nelements() {
+            return Err(Error::ShapeMismatch(format!(
+                "{} targets of {} classes for a tensor of shape {:?}",
+                classes.len(),
+                n_classes,
+                self.ne()
+            )));
+        }
+        if let Some(c) = classes.iter().find(|&&c| c < 0 || c as i64 >= n_classes) {
+            return Err(Error::InvalidArgument(format!(
+                "class {} out of range for {} classes",
+                c, n_classes
+            )));
+        }
+        let off = smoothing / n_classes as f32;
+        let mut values = vec![off; self.nelements() as usize];
+        for (col, &c) in values.chunks_mut(n_classes as usize).zip(classes) {
+            col[c as usize] += 1.0 - smoothing;
+        }
+        self.write_f32(&values)
+    }
+}
@@ -0,0 +1,173 @@
+//! Elementwise math ops.
+//!
+//! The functions of one argument accept F32, F16 and BF16 tensors with
+//! contiguous rows; `scale` and `clamp` have narrower type support, noted on
+//! each.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::inplace::TensorMut;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+fn check_float(what: &str, a: Tensor<'_>) -> Result<()> {
+    if !matches!(a.ty(), Type::F32 | Type::F16 | Type::BF16) {
+        return Err(Error::TypeMismatch {
+            expected: format!("{} input F32, F16 or BF16", what),
+            found: a.ty().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Element stride is the type size and dims 2 and 3 are packed; what
+/// `ggml_scale` needs.
+fn is_padded_1d(a: Tensor<'_>) -> bool {
+    let (ne, nb) = (a.ne(), a.nb());
+    nb[0] == a.ty().type_size()
+        && nb[2] == nb[1] * ne[1] as usize
+        && nb[3] == nb[2] * ne[2] as usize
+}
+
+macro_rules! elementwise {
+    ($($(#[$doc:meta])* $name:ident, $inplace:ident => $raw:ident, $raw_inplace:ident;)*) => {
+        impl Context {
+            $(
+                $(#[$doc])*
+                pub fn $name<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+                    check_float(stringify!($name), a)?;
+                    let a = if unsafe { crate::ggml_is_contiguous_1(a.as_ptr()) } {
+                        a
+                    } else {
+                        self.require_contiguous(a)?
+                    };
+                    self.ensure_capacity(a.nbytes())?;
+                    self.op_result(stringify!($raw), unsafe {
+                        crate::$raw(self.as_ptr(), a.as_ptr())
+                    })
+                }
+
+                #[doc = concat!("[`Context::", stringify!($name), "`] written over `a`.")]
+                pub fn $inplace<'a>(&'a self, a: TensorMut<'a>) -> Result<TensorMut<'a>> {
+                    check_float(stringify!($name), *a)?;
+                    if !unsafe { crate::ggml_is_contiguous_1(a.as_ptr()) } {
+                        return Err(Error::NotContiguous);
+                    }
+                    self.ensure_capacity(0)?;
+                    self.op_result(stringify!($raw_inplace), unsafe {
+                        crate::$raw_inplace(self.as_ptr(), a.as_ptr())
+                    })
+                    .map(Tensor::into_mut)
+                }
+            )*
+        }
+    };
+}
+
+elementwise! {
+    /// `x * x` for every element.
+    sqr, sqr_inplace => ggml_sqr, ggml_sqr_inplace;
+    /// Square root of every element.
+    sqrt, sqrt_inplace => ggml_sqrt, ggml_sqrt_inplace;
+    /// Natural logarithm of every element.
+    log, log_inplace => ggml_log, ggml_log_inplace;
+    /// `e^x` for every element.
+    exp, exp_inplace => ggml_exp, ggml_exp_inplace;
+    /// Sine of every element (radians).
+    sin, sin_inplace => ggml_sin, ggml_sin_inplace;
+    /// Cosine of every element (radians).
+    cos, cos_inplace => ggml_cos, ggml_cos_inplace;
+}
+
+impl Context {
+    /// `a * s`. `a` must be F32.
+    pub fn scale<'a>(&'a self, a: Tensor<'a>, s: f32) -> Result<Tensor<'a>> {
+        self.scale_bias(a, s, 0.0)
+    }
+
+    /// `a * s + b`. `a` must be F32.
+    pub fn scale_bias<'a>(&'a self, a: Tensor<'a>, s: f32, b: f32) -> Result<Tensor<'a>> {
+        Self::check_f32("scale", a)?;
+        let a = if is_padded_1d(a) {
+            a
+        } else {
+            self.require_contiguous(a)?
+        };
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_scale_bias", unsafe {
+            crate::ggml_scale_bias(self.as_ptr(), a.as_ptr(), s, b)
+        })
+    }
+
+    /// `a * s + b`, written over `a`. `a` must be F32.
+    pub fn scale_bias_inplace<'a>(
+        &'a self,
+        a: TensorMut<'a>,
+        s: f32,
+        b: f32,
+    ) -> Result<TensorMut<'a>> {
+        Self::check_f32("scale", *a)?;
+        if !is_padded_1d(*a) {
+            return Err(Error::NotContiguous);
+        }
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_scale_bias_inplace", unsafe {
+            crate::ggml_scale_bias_inplace(self.as_ptr(), a.as_ptr(), s, b)
+        })
+        .map(Tensor::into_mut)
+    }
+
+    fn check_clamp(a: Tensor<'_>, min: f32, max: f32) -> Result<()> {
+        if !matches!(a.ty(), Type::F32 | Type::F16) {
+            return Err(Error::TypeMismatch {
+                expected: "clamp input F32 or F16".to_string(),
+                found: a.ty().to_string(),
+            });
+        }
+        if min > max {
+            return Err(Error::InvalidArgument(format!(
+                "clamp range {}..={} is empty",
+                min, max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Limit every element to `min..=max`. `a` must be F32 or F16.
+    ///
+    /// `ggml_clamp` always writes over its input, so this clamps a copy;
+    /// use [`Context::clamp_inplace`] to avoid it.
+    pub fn clamp<'a>(&'a self, a: Tensor<'a>, min: f32, max: f32) -> Result<Tensor<'a>> {
+        Self::check_clamp(a, min, max)?;
+        let copy = self.dup_mut(a)?;
+        self.clamp_inplace(copy, min, max).map(TensorMut::freeze)
+    }
+
+    /// Limit every element of `a` to `min..=max` in place.
+    pub fn clamp_inplace<'a>(
+        &'a self,
+        a: TensorMut<'a>,
+        min: f32,
+        max: f32,
+    ) -> Result<TensorMut<'a>> {
+        Self::check_clamp(*a, min, max)?;
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_clamp", unsafe {
+            crate::ggml_clamp(self.as_ptr(), a.as_ptr(), min, max)
+        })
+        .map(Tensor::into_mut)
+    }
+
+    /// Logit soft-capping, `cap * tanh(a / cap)`, as used by Gemma 2.
+    pub fn softcap<'a>(&'a self, a: Tensor<'a>, cap: f32) -> Result<Tensor<'a>> {
+        if cap <= 0.0 {
+            return Err(Error::InvalidArgument(format!(
+                "softcap must be positive, got {}",
+                cap
+            )));
+        }
+        let x = self.scale(a, 1.0 / cap)?.into_mut();
+        let x = self.unary_inplace(x, crate::UnaryOp::Tanh)?;
+        self.scale_bias_inplace(x, cap, 0.0).map(TensorMut::freeze)
+    }
+}
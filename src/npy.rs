@@ -0,0 +1,545 @@
+//! NumPy `.npy` and `.npz` files, for exchanging tensors with Python
+//! scripts.
+//!
+//! A numpy array of shape `(a, b, c)` is a tensor with `ne = [c, b, a]`:
+//! the last numpy axis is `ne[0]`, so C-ordered data is copied as is.
+//! Little-endian `f2`, `f4`, `f8`, `i1`, `i2`, `i4` and `i8` arrays map to
+//! F16, F32, F64, I8, I16, I32 and I64. Fortran-ordered and big-endian
+//! arrays are rejected; `np.ascontiguousarray(a.astype('<f4'))` fixes
+//! both.
+//!
+//! `.npz` files are read and written uncompressed (`np.savez`, not
+//! `np.savez_compressed`), one `.npy` entry per tensor:
+//!
+//! ```ignore
+//! hidden.write_npy("hidden.npy")?;
+//! write_npz("layer0.npz", &[q, k, v])?; // np.load(...)["q"], ...
+//! let expected = ctx.read_npy("reference.npy")?;
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+fn type_to_descr(ty: Type) -> Option<&'static str> {
+    Some(match ty {
+        Type::F16 => "<f2",
+        Type::F32 => "<f4",
+        Type::F64 => "<f8",
+        Type::I8 => "|i1",
+        Type::I16 => "<i2",
+        Type::I32 => "<i4",
+        Type::I64 => "<i8",
+        _ => return None,
+    })
+}
+
+fn descr_to_type(descr: &str) -> Result<Type> {
+    Ok(match descr {
+        "<f2" => Type::F16,
+        "<f4" => Type::F32,
+        "<f8" => Type::F64,
+        "|i1" | "<i1" => Type::I8,
+        "<i2" => Type::I16,
+        "<i4" => Type::I32,
+        "<i8" => Type::I64,
+        d if d.starts_with('>') => {
+            return Err(Error::InvalidFormat(format!(
+                "npy dtype '{}' is big-endian; convert with a.astype('<{}')",
+                d,
+                &d[1..]
+            )))
+        }
+        d => {
+            return Err(Error::TypeMismatch {
+                expected: "a numpy dtype with a ggml type (f2, f4, f8, i1, i2, i4, i8)".to_string(),
+                found: format!("'{}'", d),
+            })
+        }
+    })
+}
+
+/// The text of `key`'s value in a header dict like
+/// `{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }`.
+fn dict_value<'h>(header: &'h str, key: &str) -> Result<&'h str> {
+    let missing = || Error::InvalidFormat(format!("npy header has no '{}': {}", key, header));
+    let start = header.find(&format!("'{}'", key)).ok_or_else(missing)? + key.len() + 2;
+    let rest = header[start..]
+        .trim_start()
+        .strip_prefix(':')
+        .ok_or_else(missing)?;
+    let rest = rest.trim_start();
+    let end = match rest.chars().next() {
+        Some('(') => rest.find(')').map(|i| i + 1),
+        Some(q @ ('\'' | '"')) => rest[1..].find(q).map(|i| i + 2),
+        _ => rest.find([',', '}']),
+    };
+    Ok(rest[..end.ok_or_else(missing)?].trim())
+}
+
+/// Type and `ne` (with the number of dimensions) of an array header.
+fn parse_header(header: &str) -> Result<(Type, Vec<i64>)> {
+    let descr = dict_value(header, "descr")?.trim_matches(['\'', '"']);
+    let ty = descr_to_type(descr)?;
+    match dict_value(header, "fortran_order")? {
+        "False" => {}
+        "True" => {
+            return Err(Error::InvalidFormat(
+                "npy array is Fortran-ordered (column-major); save \
+                 np.ascontiguousarray(a) instead"
+                    .to_string(),
+            ))
+        }
+        v => return Err(Error::InvalidFormat(format!("npy fortran_order is {}", v))),
+    }
+    let shape = dict_value(header, "shape")?;
+    let mut ne = shape
+        .trim_matches(['(', ')'])
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<i64>()
+                .map_err(|_| Error::InvalidFormat(format!("npy shape {} is not numeric", shape)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if ne.len() > crate::GGML_MAX_DIMS as usize {
+        return Err(Error::ShapeMismatch(format!(
+            "npy shape {} has more than {} dimensions",
+            shape,
+            crate::GGML_MAX_DIMS
+        )));
+    }
+    ne.reverse();
+    if ne.is_empty() {
+        // a numpy scalar
+        ne.push(1);
+    }
+    Ok((ty, ne))
+}
+
+/// The `.npy` header of `t`, padded so the data starts 64-byte aligned.
+fn header_for(t: &Tensor<'_>, descr: &str) -> Vec<u8> {
+    let shape: Vec<String> = t.ne()[..t.n_dims()]
+        .iter()
+        .rev()
+        .map(|n| n.to_string())
+        .collect();
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!("({})", shape.join(", ")),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    let unpadded = MAGIC.len() + 2 + 2 + dict.len() + 1;
+    dict.extend(std::iter::repeat_n(
+        ' ',
+        unpadded.next_multiple_of(64) - unpadded,
+    ));
+    dict.push('\n');
+    // version 1.0: a u16 header length, plenty for four dimensions
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&[1, 0]);
+    out.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    out.extend_from_slice(dict.as_bytes());
+    out
+}
+
+impl Tensor<'_> {
+    /// Write the tensor to `path` as a `.npy` file.
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_npy_to(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Write the tensor in `.npy` format to any writer. Tensors with a
+    /// numpy dtype are written as is (and must be contiguous host memory);
+    /// BF16 and quantized tensors are dequantized to `<f4` with
+    /// [`Tensor::to_vec_f32`].
+    pub fn write_npy_to(&self, w: &mut impl Write) -> Result<()> {
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        match type_to_descr(self.ty()) {
+            Some(descr) => {
+                if !self.is_contiguous() {
+                    return Err(Error::NotContiguous);
+                }
+                w.write_all(&header_for(self, descr))?;
+                let data =
+                    unsafe { std::slice::from_raw_parts(self.data() as *const u8, self.nbytes()) };
+                w.write_all(data)?;
+            }
+            None => {
+                let values = self.to_vec_f32()?;
+                w.write_all(&header_for(self, "<f4"))?;
+                for v in values {
+                    w.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Load a `.npy` file into a new tensor of the matching type.
+    pub fn read_npy(&self, path: impl AsRef<Path>) -> Result<Tensor<'_>> {
+        self.read_npy_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Load a `.npy` array from any reader. The context must allocate data
+    /// (not `no_alloc`).
+    pub fn read_npy_from(&self, r: &mut impl Read) -> Result<Tensor<'_>> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic[..6] != MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "bad npy magic {:?}",
+                &magic[..6]
+            )));
+        }
+        let header_len = match magic[6] {
+            1 => {
+                let mut b = [0; 2];
+                r.read_exact(&mut b)?;
+                u16::from_le_bytes(b) as usize
+            }
+            2 | 3 => {
+                let mut b = [0; 4];
+                r.read_exact(&mut b)?;
+                u32::from_le_bytes(b) as usize
+            }
+            v => {
+                return Err(Error::InvalidFormat(format!(
+                    "unsupported npy version {}.{}",
+                    v, magic[7]
+                )))
+            }
+        };
+        let mut header = vec![0; header_len];
+        r.read_exact(&mut header)?;
+        let header = String::from_utf8(header)
+            .map_err(|_| Error::InvalidFormat("npy header is not UTF-8".to_string()))?;
+        let (ty, ne) = parse_header(&header)?;
+
+        if self.no_alloc() {
+            return Err(Error::NoData);
+        }
+        let t = self.new_tensor(ty, &ne)?;
+        let data = unsafe { std::slice::from_raw_parts_mut(t.data() as *mut u8, t.nbytes()) };
+        r.read_exact(data)?;
+        Ok(t)
+    }
+
+    /// Load every array of an uncompressed `.npz` file, in file order, each
+    /// named after its entry (without `.npy`).
+    pub fn read_npz(&self, path: impl AsRef<Path>) -> Result<Vec<Tensor<'_>>> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut out = Vec::new();
+        loop {
+            let mut sig = [0; 4];
+            r.read_exact(&mut sig)?;
+            match u32::from_le_bytes(sig) {
+                ZIP_LOCAL => {}
+                // the central directory follows the last entry
+                ZIP_CENTRAL | ZIP_END => return Ok(out),
+                sig => {
+                    return Err(Error::InvalidFormat(format!(
+                        "bad zip record signature {:#x}",
+                        sig
+                    )))
+                }
+            }
+            let mut h = [0; 26];
+            r.read_exact(&mut h)?;
+            let u16_at = |i: usize| u16::from_le_bytes([h[i], h[i + 1]]);
+            let u32_at = |i: usize| u32::from_le_bytes([h[i], h[i + 1], h[i + 2], h[i + 3]]);
+            let (flags, method) = (u16_at(2), u16_at(4));
+            let mut size = u32_at(18) as u64;
+            let mut name = vec![0; u16_at(22) as usize];
+            let mut extra = vec![0; u16_at(24) as usize];
+            r.read_exact(&mut name)?;
+            r.read_exact(&mut extra)?;
+            let name = String::from_utf8_lossy(&name).into_owned();
+            if method != 0 {
+                return Err(Error::InvalidFormat(format!(
+                    "npz entry {} is compressed; save with np.savez, not np.savez_compressed",
+                    name
+                )));
+            }
+            if size == u32::MAX as u64 {
+                size = zip64_size(&extra).ok_or_else(|| {
+                    Error::InvalidFormat(format!("npz entry {} has no zip64 size", name))
+                })?;
+            } else if flags & 0x8 != 0 {
+                return Err(Error::InvalidFormat(format!(
+                    "npz entry {} was streamed without a size",
+                    name
+                )));
+            }
+            let start = r.stream_position()?;
+            let t = self.read_npy_from(&mut r)?;
+            out.push(t.set_name(name.strip_suffix(".npy").unwrap_or(&name)));
+            r.seek(SeekFrom::Start(start + size))?;
+        }
+    }
+}
+
+const ZIP_LOCAL: u32 = 0x0403_4b50;
+const ZIP_CENTRAL: u32 = 0x0201_4b50;
+const ZIP_END: u32 = 0x0605_4b50;
+
+/// The uncompressed size in a local header's zip64 extra field.
+fn zip64_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let body = extra.get(4..4 + len)?;
+        if id == 1 && len >= 8 {
+            return Some(u64::from_le_bytes(body[..8].try_into().unwrap()));
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Write `tensors` to `path` as an uncompressed `.npz`, each under its name
+/// (or `arr_<i>` if it has none), as [`Tensor::write_npy_to`] encodes it.
+pub fn write_npz(path: impl AsRef<Path>, tensors: &[Tensor<'_>]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    let mut central = Vec::new();
+    let mut offset = 0u64;
+    let mut names = Vec::with_capacity(tensors.len());
+    for (i, t) in tensors.iter().enumerate() {
+        let name = match t.name() {
+            "" => format!("arr_{}", i),
+            name => name.to_string(),
+        };
+        if names.contains(&name) {
+            return Err(Error::InvalidArgument(format!(
+                "two tensors are named {}",
+                name
+            )));
+        }
+        let mut data = Vec::new();
+        t.write_npy_to(&mut data)?;
+        let file_name = format!("{}.npy", name);
+        names.push(name);
+        let (size, local) = (u32::try_from(data.len()), u32::try_from(offset));
+        let (Ok(size), Ok(local)) = (size, local) else {
+            return Err(Error::InvalidArgument(
+                "npz files over 4 GiB are not supported".to_string(),
+            ));
+        };
+        let crc = crc32(&data);
+
+        // version 2.0, no flags, stored, no timestamp
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(file_name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&[0, 0]);
+
+        w.write_all(&ZIP_LOCAL.to_le_bytes())?;
+        w.write_all(&common)?;
+        w.write_all(file_name.as_bytes())?;
+        w.write_all(&data)?;
+
+        central.extend_from_slice(&ZIP_CENTRAL.to_le_bytes());
+        central.extend_from_slice(&[20, 0]);
+        central.extend_from_slice(&common);
+        // comment length, disk, internal and external attributes
+        central.extend_from_slice(&[0; 10]);
+        central.extend_from_slice(&local.to_le_bytes());
+        central.extend_from_slice(file_name.as_bytes());
+
+        offset += (30 + file_name.len() + data.len()) as u64;
+    }
+    let Ok(central_offset) = u32::try_from(offset) else {
+        return Err(Error::InvalidArgument(
+            "npz files over 4 GiB are not supported".to_string(),
+        ));
+    };
+    w.write_all(&central)?;
+    w.write_all(&ZIP_END.to_le_bytes())?;
+    w.write_all(&[0; 4])?;
+    w.write_all(&(tensors.len() as u16).to_le_bytes())?;
+    w.write_all(&(tensors.len() as u16).to_le_bytes())?;
+    w.write_all(&(central.len() as u32).to_le_bytes())?;
+    w.write_all(&central_offset.to_le_bytes())?;
+    w.write_all(&[0, 0])?;
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `.npy` file with a hand-written header dict.
+    fn npy(dict: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = dict.to_string();
+        while !(MAGIC.len() + 4 + header.len() + 1).is_multiple_of(64) {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&[1, 0]);
+        out.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(data);
+        out
+    }
+
+    /// A stored zip entry as `np.savez` writes it: sizes in a zip64 extra
+    /// field, the local header fields set to `u32::MAX`.
+    fn zip64_entry(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut extra = vec![1, 0, 16, 0];
+        extra.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        extra.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        let mut out = ZIP_LOCAL.to_le_bytes().to_vec();
+        out.extend_from_slice(&[45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        out.extend_from_slice(&crc32(data).to_le_bytes());
+        out.extend_from_slice(&u32::MAX.to_le_bytes());
+        out.extend_from_slice(&u32::MAX.to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&extra);
+        out.extend_from_slice(data);
+        out
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ggml-rs-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn header_dict() {
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }";
+        assert_eq!(parse_header(header).unwrap(), (Type::F32, vec![3, 2]));
+        // key order and trailing separators vary between writers
+        let header = "{'shape':(4, 1, 2),'fortran_order':False,'descr':'|i1'}";
+        assert_eq!(parse_header(header).unwrap(), (Type::I8, vec![2, 1, 4]));
+    }
+
+    #[test]
+    fn scalar_and_1d_shapes() {
+        let scalar = "{'descr': '<i4', 'fortran_order': False, 'shape': (), }";
+        assert_eq!(parse_header(scalar).unwrap(), (Type::I32, vec![1]));
+        let vector = "{'descr': '<f8', 'fortran_order': False, 'shape': (5,), }";
+        assert_eq!(parse_header(vector).unwrap(), (Type::F64, vec![5]));
+    }
+
+    #[test]
+    fn rejected_headers() {
+        let fortran = "{'descr': '<f4', 'fortran_order': True, 'shape': (2, 3), }";
+        assert!(matches!(
+            parse_header(fortran),
+            Err(Error::InvalidFormat(_))
+        ));
+        let big = "{'descr': '>f4', 'fortran_order': False, 'shape': (2,), }";
+        assert!(matches!(parse_header(big), Err(Error::InvalidFormat(_))));
+        let bool = "{'descr': '|b1', 'fortran_order': False, 'shape': (2,), }";
+        assert!(matches!(
+            parse_header(bool),
+            Err(Error::TypeMismatch { .. })
+        ));
+        let five_d = "{'descr': '<f4', 'fortran_order': False, 'shape': (1, 1, 1, 1, 2), }";
+        assert!(matches!(parse_header(five_d), Err(Error::ShapeMismatch(_))));
+    }
+
+    #[test]
+    fn zip64_extra_field() {
+        // an unrelated field (extended timestamp) before the zip64 one
+        let mut extra = vec![0x55, 0x54, 5, 0, 1, 0, 0, 0, 0];
+        extra.extend_from_slice(&[1, 0, 16, 0]);
+        extra.extend_from_slice(&300u64.to_le_bytes());
+        extra.extend_from_slice(&300u64.to_le_bytes());
+        assert_eq!(zip64_size(&extra), Some(300));
+        assert_eq!(zip64_size(&extra[..9]), None);
+        // truncated field body
+        assert_eq!(zip64_size(&extra[..15]), None);
+    }
+
+    #[test]
+    fn read_npz_zip64() {
+        let a: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let b: Vec<u8> = 7i32.to_le_bytes().to_vec();
+        let mut file = zip64_entry(
+            "a.npy",
+            &npy(
+                "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }",
+                &a,
+            ),
+        );
+        file.extend(zip64_entry(
+            "b.npy",
+            &npy(
+                "{'descr': '<i4', 'fortran_order': False, 'shape': (), }",
+                &b,
+            ),
+        ));
+        file.extend_from_slice(&ZIP_CENTRAL.to_le_bytes());
+        let path = temp_path("zip64.npz");
+        std::fs::write(&path, &file).unwrap();
+
+        let ctx = Context::new(1 << 20).unwrap();
+        let tensors = ctx.read_npz(&path);
+        std::fs::remove_file(&path).unwrap();
+        let tensors = tensors.unwrap();
+        assert_eq!(tensors.len(), 2);
+        assert_eq!(tensors[0].name(), "a");
+        assert_eq!(tensors[0].ne(), [3, 2, 1, 1]);
+        assert_eq!(
+            tensors[0].to_vec_f32().unwrap(),
+            [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        );
+        assert_eq!(tensors[1].name(), "b");
+        assert_eq!(tensors[1].to_vec::<i32>().unwrap(), [7]);
+    }
+
+    #[test]
+    fn npz_round_trip() {
+        let ctx = Context::new(1 << 20).unwrap();
+        let t = ctx.new_tensor(Type::F32, &[2, 3]).unwrap().set_name("x");
+        let values: Vec<f32> = (0..6).map(|i| i as f32 * 0.5).collect();
+        unsafe { std::slice::from_raw_parts_mut(t.data() as *mut f32, 6) }.copy_from_slice(&values);
+        let path = temp_path("round_trip.npz");
+        write_npz(&path, &[t]).unwrap();
+        let read = ctx.read_npz(&path);
+        std::fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+        assert_eq!(read[0].name(), "x");
+        assert_eq!(read[0].ne(), [2, 3, 1, 1]);
+        assert_eq!(read[0].to_vec_f32().unwrap(), values);
+    }
+}
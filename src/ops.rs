@@ -0,0 +1,627 @@
+//! Tensor operations.
+//!
+//! Ops are methods on [`Context`], mirroring the C API where every op takes the
+//! context that will own its result. Preconditions that ggml would `GGML_ASSERT`
+//! (and abort the process on) are checked here and reported as [`Error`]s.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Sort direction for [`Context::argsort`], mirroring `enum ggml_sort_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_raw(self) -> crate::ggml_sort_order {
+        match self {
+            SortOrder::Asc => crate::ggml_sort_order_GGML_SORT_ORDER_ASC,
+            SortOrder::Desc => crate::ggml_sort_order_GGML_SORT_ORDER_DESC,
+        }
+    }
+}
+
+/// Reduction applied by [`Context::reduce`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    Sum,
+    Mean,
+}
+
+impl Context {
+    /// Wrap the result of an op constructor.
+    pub(crate) fn op_result<'a>(
+        &'a self,
+        what: &'static str,
+        ptr: *mut crate::ggml_tensor,
+    ) -> Result<Tensor<'a>> {
+        unsafe { Tensor::from_raw(ptr) }.ok_or(Error::NullPointer(what))
+    }
+
+    /// Return `t` if it is contiguous, otherwise insert a `ggml_cont` when
+    /// auto-contiguous mode is on or fail with [`Error::NotContiguous`].
+    pub(crate) fn require_contiguous<'a>(&'a self, t: Tensor<'a>) -> Result<Tensor<'a>> {
+        if t.is_contiguous() {
+            Ok(t)
+        } else if self.auto_contiguous() {
+            self.cont(t)
+        } else {
+            Err(Error::NotContiguous)
+        }
+    }
+
+    pub(crate) fn check_can_repeat(b: Tensor<'_>, a: Tensor<'_>) -> Result<()> {
+        if unsafe { crate::ggml_can_repeat(b.as_ptr(), a.as_ptr()) } {
+            Ok(())
+        } else {
+            Err(Error::ShapeMismatch(format!(
+                "{:?} cannot be broadcast to {:?}",
+                b.ne(),
+                a.ne()
+            )))
+        }
+    }
+
+    //
+    // copies and layout
+    //
+
+    /// Copy of `a` (same layout).
+    pub fn dup<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_dup", unsafe {
+            crate::ggml_dup(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    /// Contiguous copy of `a`.
+    pub fn cont<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        self.ensure_capacity(a.ty().row_size(a.ne()[0]) * a.nrows() as usize)?;
+        self.op_result("ggml_cont", unsafe {
+            crate::ggml_cont(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    /// Copy `a` into `b`, converting to `b`'s type. Returns a view of `b`.
+    pub fn cpy<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        if a.nelements() != b.nelements() {
+            return Err(Error::ShapeMismatch(format!(
+                "cpy of {} elements into {}",
+                a.nelements(),
+                b.nelements()
+            )));
+        }
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_cpy", unsafe {
+            crate::ggml_cpy(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    /// View of `a` with a new shape (`ne[0]` innermost). `a` must be contiguous.
+    pub fn reshape<'a>(&'a self, a: Tensor<'a>, ne: &[i64]) -> Result<Tensor<'a>> {
+        let a = self.require_contiguous(a)?;
+        let n: i64 = ne.iter().product();
+        if n != a.nelements() {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot reshape {:?} into {:?}",
+                a.shape(),
+                ne
+            )));
+        }
+        self.ensure_capacity(0)?;
+        let ctx = self.as_ptr();
+        let ptr = unsafe {
+            match *ne {
+                [ne0] => crate::ggml_reshape_1d(ctx, a.as_ptr(), ne0),
+                [ne0, ne1] => crate::ggml_reshape_2d(ctx, a.as_ptr(), ne0, ne1),
+                [ne0, ne1, ne2] => crate::ggml_reshape_3d(ctx, a.as_ptr(), ne0, ne1, ne2),
+                [ne0, ne1, ne2, ne3] => crate::ggml_reshape_4d(ctx, a.as_ptr(), ne0, ne1, ne2, ne3),
+                _ => {
+                    return Err(Error::InvalidArgument(format!(
+                        "reshape needs 1 to 4 dimensions, got {}",
+                        ne.len()
+                    )))
+                }
+            }
+        };
+        self.op_result("ggml_reshape", ptr)
+    }
+
+    /// Permute axes: source axis `i` moves to position `axes[i]`.
+    pub fn permute<'a>(&'a self, a: Tensor<'a>, axes: [usize; 4]) -> Result<Tensor<'a>> {
+        let mut seen = [false; 4];
+        for &ax in &axes {
+            if ax >= 4 || seen[ax] {
+                return Err(Error::InvalidArgument(format!(
+                    "invalid permutation {:?}",
+                    axes
+                )));
+            }
+            seen[ax] = true;
+        }
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_permute", unsafe {
+            crate::ggml_permute(
+                self.as_ptr(),
+                a.as_ptr(),
+                axes[0] as i32,
+                axes[1] as i32,
+                axes[2] as i32,
+                axes[3] as i32,
+            )
+        })
+    }
+
+    /// Swap the first two dimensions (a view, not a copy).
+    pub fn transpose<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_transpose", unsafe {
+            crate::ggml_transpose(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    //
+    // joining
+    //
+
+    /// Join `a` and `b` along `axis`. All other dimensions and the type must
+    /// match.
+    pub fn concat<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>, axis: usize) -> Result<Tensor<'a>> {
+        if a.ty() != b.ty() {
+            return Err(Error::TypeMismatch {
+                expected: a.ty().to_string(),
+                found: b.ty().to_string(),
+            });
+        }
+        if a.ty().is_quantized() {
+            return Err(Error::InvalidArgument(format!(
+                "cannot concat quantized {} tensors",
+                a.ty()
+            )));
+        }
+        let (an, bn) = (a.ne(), b.ne());
+        if axis >= crate::GGML_MAX_DIMS as usize
+            || (0..an.len()).any(|d| d != axis && an[d] != bn[d])
+        {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot concat {:?} and {:?} along axis {}",
+                an, bn, axis
+            )));
+        }
+        self.ensure_capacity(a.nbytes() + b.nbytes())?;
+        self.op_result("ggml_concat", unsafe {
+            crate::ggml_concat(self.as_ptr(), a.as_ptr(), b.as_ptr(), axis as i32)
+        })
+    }
+
+    /// Join all `tensors` along `axis`, left to right.
+    pub fn concat_all<'a>(&'a self, tensors: &[Tensor<'a>], axis: usize) -> Result<Tensor<'a>> {
+        let (&first, rest) = tensors
+            .split_first()
+            .ok_or_else(|| Error::InvalidArgument("concat of an empty tensor list".to_string()))?;
+        rest.iter()
+            .try_fold(first, |acc, &t| self.concat(acc, t, axis))
+    }
+
+    /// Stack same-shaped tensors along a new axis inserted at `axis`, e.g.
+    /// stacking `n` tensors of shape `[a, b]` at axis 2 gives `[a, b, n]`.
+    /// Inputs must have at most three dimensions.
+    pub fn stack<'a>(&'a self, tensors: &[Tensor<'a>], axis: usize) -> Result<Tensor<'a>> {
+        let first = *tensors
+            .first()
+            .ok_or_else(|| Error::InvalidArgument("stack of an empty tensor list".to_string()))?;
+        let ne = first.ne();
+        if let Some(t) = tensors.iter().find(|t| !t.same_shape(first)) {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot stack {:?} with {:?}",
+                ne,
+                t.ne()
+            )));
+        }
+        if ne[3] != 1 || axis >= crate::GGML_MAX_DIMS as usize {
+            return Err(Error::InvalidArgument(format!(
+                "cannot stack {:?} along new axis {}",
+                ne, axis
+            )));
+        }
+        let mut stacked = ne[..3].to_vec();
+        stacked.insert(axis, 1);
+        let parts = tensors
+            .iter()
+            .map(|&t| self.reshape(t, &stacked))
+            .collect::<Result<Vec<_>>>()?;
+        self.concat_all(&parts, axis)
+    }
+
+    //
+    // arithmetic
+    //
+
+    /// `a + b`, with `b` broadcast to the shape of `a`.
+    pub fn add<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_can_repeat(b, a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_add", unsafe {
+            crate::ggml_add(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    /// `a - b`, with `b` broadcast to the shape of `a`.
+    pub fn sub<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_can_repeat(b, a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_sub", unsafe {
+            crate::ggml_sub(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    /// `a * b` elementwise, with `b` broadcast to the shape of `a`.
+    pub fn mul<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_can_repeat(b, a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_mul", unsafe {
+            crate::ggml_mul(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    /// `a / b` elementwise, with `b` broadcast to the shape of `a`.
+    pub fn div<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_can_repeat(b, a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_div", unsafe {
+            crate::ggml_div(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    /// Matrix product `b * a^T`: `a` is `[k, m]`, `b` is `[k, n]`, result is
+    /// F32 `[m, n]` (batched over dims 2 and 3, with `a` broadcast). Backends
+    /// may accumulate F16 inputs in F16; see [`Tensor::set_precision`].
+    pub fn mul_mat<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        let (an, bn) = (a.ne(), b.ne());
+        if an[0] != bn[0] || bn[2] % an[2].max(1) != 0 || bn[3] % an[3].max(1) != 0 {
+            return Err(Error::ShapeMismatch(format!(
+                "mul_mat of {:?} and {:?}",
+                an, bn
+            )));
+        }
+        if unsafe { crate::ggml_is_transposed(a.as_ptr()) } {
+            return Err(Error::InvalidArgument(
+                "mul_mat: `a` must not be transposed".to_string(),
+            ));
+        }
+        let out = Type::F32.row_size(an[1]) * (bn[1] * bn[2] * bn[3]) as usize;
+        self.ensure_capacity(out)?;
+        self.op_result("ggml_mul_mat", unsafe {
+            crate::ggml_mul_mat(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    //
+    // normalization
+    //
+
+    /// Normalize each row to zero mean and unit variance.
+    pub fn norm<'a>(&'a self, a: Tensor<'a>, eps: f32) -> Result<Tensor<'a>> {
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_norm", unsafe {
+            crate::ggml_norm(self.as_ptr(), a.as_ptr(), eps)
+        })
+    }
+
+    /// Scale each row by the inverse of its root mean square.
+    pub fn rms_norm<'a>(&'a self, a: Tensor<'a>, eps: f32) -> Result<Tensor<'a>> {
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_rms_norm", unsafe {
+            crate::ggml_rms_norm(self.as_ptr(), a.as_ptr(), eps)
+        })
+    }
+
+    /// Softmax along rows. `a` must be contiguous.
+    pub fn soft_max<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        let a = self.require_contiguous(a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_soft_max", unsafe {
+            crate::ggml_soft_max(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    pub(crate) fn check_soft_max_ext(
+        a: Tensor<'_>,
+        mask: Option<Tensor<'_>>,
+        max_bias: f32,
+    ) -> Result<()> {
+        let Some(mask) = mask else {
+            if max_bias > 0.0 {
+                return Err(Error::InvalidArgument(
+                    "soft_max_ext: ALiBi (max_bias > 0) needs a mask".to_string(),
+                ));
+            }
+            return Ok(());
+        };
+        if !matches!(mask.ty(), Type::F32 | Type::F16) {
+            return Err(Error::TypeMismatch {
+                expected: "soft_max_ext mask F32 or F16".to_string(),
+                found: mask.ty().to_string(),
+            });
+        }
+        if !mask.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        let (an, mn) = (a.ne(), mask.ne());
+        // an empty mask broadcasts over nothing, and would divide by zero
+        if mn[2] == 0
+            || mn[3] == 0
+            || mn[0] != an[0]
+            || mn[1] < an[1]
+            || an[2] % mn[2] != 0
+            || an[3] % mn[3] != 0
+        {
+            return Err(Error::ShapeMismatch(format!(
+                "soft_max_ext mask {:?} does not fit scores {:?}",
+                mn, an
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fused `soft_max(a * scale + mask * slope)` along rows, for attention.
+    ///
+    /// `a` holds the scores `[n_kv, n_tokens, n_head, n_seq]` and must be
+    /// contiguous. The optional `mask` is F16 or F32 `[n_kv, n_rows, ne2,
+    /// ne3]`: `n_rows` may exceed `n_tokens` (masks shared with
+    /// [`Context::flash_attn_ext`] are padded to `GGML_KQ_MASK_PAD` rows),
+    /// and dims 2 and 3 broadcast over the heads and sequences of `a`.
+    /// Masked positions hold `-inf`, visible ones `0`.
+    ///
+    /// With `max_bias > 0` the mask is scaled per head by the ALiBi slope;
+    /// this requires a mask. Use `max_bias = 0.0` for no ALiBi.
+    pub fn soft_max_ext<'a>(
+        &'a self,
+        a: Tensor<'a>,
+        mask: Option<Tensor<'a>>,
+        scale: f32,
+        max_bias: f32,
+    ) -> Result<Tensor<'a>> {
+        let a = self.require_contiguous(a)?;
+        Self::check_soft_max_ext(a, mask, max_bias)?;
+        self.ensure_capacity(a.nbytes())?;
+        let mask = mask.map_or(std::ptr::null_mut(), |m| m.as_ptr());
+        self.op_result("ggml_soft_max_ext", unsafe {
+            crate::ggml_soft_max_ext(self.as_ptr(), a.as_ptr(), mask, scale, max_bias)
+        })
+    }
+
+    /// Gather rows of `a` by the I32 indices in `b`. For `a` of shape
+    /// `[ne0, ne1, ne2, ne3]`, `b` is `[n, ne2, ne3]` and the result
+    /// `[ne0, n, ne2, ne3]`: each matrix of `a` is indexed by its own row of
+    /// `b`.
+    pub fn get_rows<'a>(&'a self, a: Tensor<'a>, b: Tensor<'a>) -> Result<Tensor<'a>> {
+        if b.ty() != Type::I32 {
+            return Err(Error::TypeMismatch {
+                expected: Type::I32.to_string(),
+                found: b.ty().to_string(),
+            });
+        }
+        let (an, bn) = (a.ne(), b.ne());
+        if an[2] != bn[1] || an[3] != bn[2] || bn[3] != 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "get_rows indices {:?} do not match rows {:?}: need [n, {}, {}, 1]",
+                bn, an, an[2], an[3]
+            )));
+        }
+        let out_ty = if a.ty() == Type::I32 {
+            Type::I32
+        } else {
+            Type::F32
+        };
+        self.ensure_capacity(out_ty.row_size(a.ne()[0]) * b.nelements() as usize)?;
+        self.op_result("ggml_get_rows", unsafe {
+            crate::ggml_get_rows(self.as_ptr(), a.as_ptr(), b.as_ptr())
+        })
+    }
+
+    //
+    // causal masking
+    //
+
+    pub(crate) fn check_n_past(n_past: usize) -> Result<i32> {
+        i32::try_from(n_past)
+            .map_err(|_| Error::InvalidArgument(format!("n_past = {} exceeds i32", n_past)))
+    }
+
+    /// Causal mask for attention scores: element `i` of row `j` is set to
+    /// `-inf` when `i > n_past + j`. `a` is F32 `[n_kv, n_tokens, ...]` and
+    /// must be contiguous.
+    pub fn diag_mask_inf<'a>(&'a self, a: Tensor<'a>, n_past: usize) -> Result<Tensor<'a>> {
+        Self::check_f32("diag_mask_inf", a)?;
+        let n_past = Self::check_n_past(n_past)?;
+        let a = self.require_contiguous(a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_diag_mask_inf", unsafe {
+            crate::ggml_diag_mask_inf(self.as_ptr(), a.as_ptr(), n_past)
+        })
+    }
+
+    /// Like [`Context::diag_mask_inf`], but masked elements are set to zero.
+    pub fn diag_mask_zero<'a>(&'a self, a: Tensor<'a>, n_past: usize) -> Result<Tensor<'a>> {
+        Self::check_f32("diag_mask_zero", a)?;
+        let n_past = Self::check_n_past(n_past)?;
+        let a = self.require_contiguous(a)?;
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_diag_mask_zero", unsafe {
+            crate::ggml_diag_mask_zero(self.as_ptr(), a.as_ptr(), n_past)
+        })
+    }
+
+    //
+    // reductions
+    //
+
+    /// Sum of all elements of `a`, as a one-element tensor of the same type.
+    /// `a` must be F32, F16 or BF16.
+    pub fn sum<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        if !matches!(a.ty(), Type::F32 | Type::F16 | Type::BF16) {
+            return Err(Error::TypeMismatch {
+                expected: "sum input F32, F16 or BF16".to_string(),
+                found: a.ty().to_string(),
+            });
+        }
+        self.ensure_capacity(a.ty().type_size())?;
+        self.op_result("ggml_sum", unsafe {
+            crate::ggml_sum(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    /// Sum of each row: `[ne0, ne1, ne2, ne3]` becomes `[1, ne1, ne2, ne3]`.
+    /// `a` must be F32.
+    pub fn sum_rows<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_f32("sum_rows", a)?;
+        let a = self.require_contiguous_rows(a)?;
+        self.ensure_capacity(Type::F32.row_size(a.nrows()))?;
+        self.op_result("ggml_sum_rows", unsafe {
+            crate::ggml_sum_rows(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    /// Mean of each row: `[ne0, ne1, ne2, ne3]` becomes `[1, ne1, ne2, ne3]`.
+    /// `a` must be F32.
+    pub fn mean<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_f32("mean", a)?;
+        let a = self.require_contiguous_rows(a)?;
+        self.ensure_capacity(Type::F32.row_size(a.nrows()))?;
+        self.op_result("ggml_mean", unsafe {
+            crate::ggml_mean(self.as_ptr(), a.as_ptr())
+        })
+    }
+
+    /// Reduce `a` along `axis` (0 is innermost), keeping that axis with size 1.
+    ///
+    /// Axis 0 maps directly to `sum_rows`/`mean`. Other axes are swapped into
+    /// position 0, made contiguous, reduced and swapped back, so the result is
+    /// always contiguous. `a` must be F32.
+    pub fn reduce<'a>(&'a self, a: Tensor<'a>, axis: usize, op: Reduction) -> Result<Tensor<'a>> {
+        if axis >= crate::GGML_MAX_DIMS as usize {
+            return Err(Error::InvalidArgument(format!(
+                "reduce: axis {} out of range for a {}-d tensor",
+                axis,
+                crate::GGML_MAX_DIMS
+            )));
+        }
+        Self::check_f32("reduce", a)?;
+        let rows = |t| match op {
+            Reduction::Sum => self.sum_rows(t),
+            Reduction::Mean => self.mean(t),
+        };
+        if axis == 0 {
+            return rows(a);
+        }
+        // swapping two axes is its own inverse
+        let mut swap = [0, 1, 2, 3];
+        swap.swap(0, axis);
+        let moved = self.cont(self.permute(a, swap)?)?;
+        self.cont(self.permute(rows(moved)?, swap)?)
+    }
+
+    /// Rows of `a` must be densely packed for the row reductions.
+    fn require_contiguous_rows<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        if a.nb()[0] == a.element_size() {
+            Ok(a)
+        } else {
+            self.require_contiguous(a)
+        }
+    }
+
+    //
+    // sorting and ranking
+    //
+
+    pub(crate) fn check_f32(what: &str, a: Tensor<'_>) -> Result<()> {
+        if a.ty() != Type::F32 {
+            return Err(Error::TypeMismatch {
+                expected: format!("{} input {}", what, Type::F32),
+                found: a.ty().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Indices that sort each row of `a`. The result is I32 with the shape of
+    /// `a`; `a` must be F32.
+    pub fn argsort<'a>(&'a self, a: Tensor<'a>, order: SortOrder) -> Result<Tensor<'a>> {
+        Self::check_f32("argsort", a)?;
+        if a.ne()[0] > i32::MAX as i64 {
+            return Err(Error::InvalidArgument(format!(
+                "argsort rows of {} elements exceed i32 indices",
+                a.ne()[0]
+            )));
+        }
+        self.ensure_capacity(Type::I32.row_size(a.nelements()))?;
+        self.op_result("ggml_argsort", unsafe {
+            crate::ggml_argsort(self.as_ptr(), a.as_ptr(), order.as_raw())
+        })
+    }
+
+    /// Indices of the `k` largest elements of each row, largest first. The
+    /// result is an I32 view of shape `[k, ne1, ne2, ne3]`; `a` must be F32.
+    pub fn top_k<'a>(&'a self, a: Tensor<'a>, k: usize) -> Result<Tensor<'a>> {
+        Self::check_f32("top_k", a)?;
+        // top_k sorts whole rows with I32 indices, and takes k as an int
+        if a.ne()[0] > i32::MAX as i64 {
+            return Err(Error::InvalidArgument(format!(
+                "top_k rows of {} elements exceed i32 indices",
+                a.ne()[0]
+            )));
+        }
+        if k == 0 || i64::try_from(k).map_or(true, |k| k > a.ne()[0]) {
+            return Err(Error::InvalidArgument(format!(
+                "top_k: k = {} must be in 1..={}",
+                k,
+                a.ne()[0]
+            )));
+        }
+        // argsort result plus the view over it
+        let overhead = unsafe { crate::ggml_tensor_overhead() };
+        self.ensure_capacity(Type::I32.row_size(a.nelements()) + overhead)?;
+        self.op_result("ggml_top_k", unsafe {
+            crate::ggml_top_k(self.as_ptr(), a.as_ptr(), k as i32)
+        })
+    }
+
+    /// Index of the largest element of each row of the F32 matrix `a`, as an
+    /// I32 vector of length `ne1`.
+    pub fn argmax<'a>(&'a self, a: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_f32("argmax", a)?;
+        let ne = a.ne();
+        if ne[2] != 1 || ne[3] != 1 {
+            return Err(Error::ShapeMismatch(format!(
+                "argmax needs a matrix, got {:?}",
+                ne
+            )));
+        }
+        if ne[0] > i32::MAX as i64 {
+            return Err(Error::InvalidArgument(format!(
+                "argmax rows of {} elements exceed i32 indices",
+                ne[0]
+            )));
+        }
+        self.ensure_capacity(Type::I32.row_size(ne[1]))?;
+        self.op_result("ggml_argmax", unsafe {
+            crate::ggml_argmax(self.as_ptr(), a.as_ptr())
+        })
+    }
+}
+
+impl<'a> Tensor<'a> {
+    /// This tensor if it is already contiguous, otherwise a contiguous copy.
+    pub fn contiguous(self, ctx: &'a Context) -> Result<Tensor<'a>> {
+        if self.is_contiguous() {
+            Ok(self)
+        } else {
+            ctx.cont(self)
+        }
+    }
+}
@@ -0,0 +1,572 @@
+//! Training with ggml-opt (`ggml_opt_*`): AdamW and SGD over a static graph.
+//!
+//! The model weights and the input batch live in a `no_alloc` context that
+//! the [`Optimizer`] allocates on the CPU backend; the forward pass is built
+//! in a second `no_alloc` context whose tensors the backend scheduler
+//! allocates per step:
+//!
+//! ```ignore
+//! let model = Context::with_params(ContextParams::new(1 << 20).no_alloc(true))?;
+//! let compute = Context::with_params(
+//!     ContextParams::new(Optimizer::compute_mem_size()).no_alloc(true),
+//! )?;
+//! let x = model.new_tensor(Type::F32, &[784, 32])?.set_input();
+//! let w = model.new_tensor(Type::F32, &[784, 10])?.set_param()?;
+//! let logits = compute.mul_mat(w, x)?;
+//!
+//! let config = OptimizerConfig { loss: LossType::CrossEntropy, ..Default::default() };
+//! let mut opt = Optimizer::new(&model, &compute, x, logits, config)?;
+//! w.fill_xavier_uniform(&mut Rng::new(0))?;
+//! opt.fit(10, n_batches, |i, x, labels| load(i, x, labels.unwrap()), |epoch, res| {
+//!     println!("epoch {}: loss {:.4}", epoch, res.loss());
+//!     ControlFlow::Continue(())
+//! })?;
+//! ```
+//!
+//! Weights are written in place, so they can be read back or saved once
+//! training is done. The second dimension of the inputs and outputs is the
+//! batch.
+
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
+use std::ptr::NonNull;
+
+use crate::backend::CpuBackend;
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// What the optimizer minimizes, mirroring `enum ggml_opt_loss_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LossType {
+    /// Mean of the outputs, for graphs that compute their own loss.
+    #[default]
+    Mean,
+    /// Sum of the outputs.
+    Sum,
+    /// Cross-entropy between softmax of the outputs (logits) and labels;
+    /// also reports predictions and accuracy.
+    CrossEntropy,
+    /// Mean squared error between outputs and labels.
+    MeanSquaredError,
+}
+
+impl LossType {
+    fn as_raw(self) -> crate::ggml_opt_loss_type {
+        match self {
+            LossType::Mean => crate::ggml_opt_loss_type_GGML_OPT_LOSS_TYPE_MEAN,
+            LossType::Sum => crate::ggml_opt_loss_type_GGML_OPT_LOSS_TYPE_SUM,
+            LossType::CrossEntropy => crate::ggml_opt_loss_type_GGML_OPT_LOSS_TYPE_CROSS_ENTROPY,
+            LossType::MeanSquaredError => {
+                crate::ggml_opt_loss_type_GGML_OPT_LOSS_TYPE_MEAN_SQUARED_ERROR
+            }
+        }
+    }
+
+    /// Whether the loss compares against labels.
+    pub fn has_labels(self) -> bool {
+        matches!(self, LossType::CrossEntropy | LossType::MeanSquaredError)
+    }
+}
+
+/// AdamW hyperparameters. Defaults match `ggml_opt_get_default_optimizer_params`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdamW {
+    pub lr: f32,
+    pub beta1: f32,
+    pub beta2: f32,
+    pub eps: f32,
+    /// Decoupled weight decay in `[0, 1]`; `0` disables it.
+    pub weight_decay: f32,
+}
+
+impl Default for AdamW {
+    fn default() -> Self {
+        AdamW {
+            lr: 1e-3,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            weight_decay: 0.0,
+        }
+    }
+}
+
+/// Plain SGD hyperparameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sgd {
+    pub lr: f32,
+    /// Weight decay in `[0, 1]`; `0` disables it.
+    pub weight_decay: f32,
+}
+
+impl Default for Sgd {
+    fn default() -> Self {
+        Sgd {
+            lr: 1e-3,
+            weight_decay: 0.0,
+        }
+    }
+}
+
+/// Optimizer algorithm and its hyperparameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimizerParams {
+    AdamW(AdamW),
+    Sgd(Sgd),
+}
+
+impl Default for OptimizerParams {
+    fn default() -> Self {
+        OptimizerParams::AdamW(AdamW::default())
+    }
+}
+
+impl From<AdamW> for OptimizerParams {
+    fn from(p: AdamW) -> Self {
+        OptimizerParams::AdamW(p)
+    }
+}
+
+impl From<Sgd> for OptimizerParams {
+    fn from(p: Sgd) -> Self {
+        OptimizerParams::Sgd(p)
+    }
+}
+
+impl OptimizerParams {
+    fn kind(&self) -> crate::ggml_opt_optimizer_type {
+        match self {
+            OptimizerParams::AdamW(_) => {
+                crate::ggml_opt_optimizer_type_GGML_OPT_OPTIMIZER_TYPE_ADAMW
+            }
+            OptimizerParams::Sgd(_) => crate::ggml_opt_optimizer_type_GGML_OPT_OPTIMIZER_TYPE_SGD,
+        }
+    }
+
+    /// Name ggml uses for the algorithm (`"adamw"` or `"sgd"`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizerParams::AdamW(_) => "adamw",
+            OptimizerParams::Sgd(_) => "sgd",
+        }
+    }
+
+    pub fn lr(&self) -> f32 {
+        match self {
+            OptimizerParams::AdamW(p) => p.lr,
+            OptimizerParams::Sgd(p) => p.lr,
+        }
+    }
+
+    /// The same parameters with learning rate `lr`.
+    pub fn with_lr(mut self, lr: f32) -> Self {
+        match &mut self {
+            OptimizerParams::AdamW(p) => p.lr = lr,
+            OptimizerParams::Sgd(p) => p.lr = lr,
+        }
+        self
+    }
+
+    /// The ranges `ggml_opt_eval` asserts. Negated comparisons so NaN fails.
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    fn validate(&self) -> Result<()> {
+        let unit = |name: &str, v: f32| {
+            if (0.0..=1.0).contains(&v) {
+                Ok(())
+            } else {
+                Err(Error::InvalidArgument(format!(
+                    "{} {} must be in [0, 1], got {}",
+                    self.name(),
+                    name,
+                    v
+                )))
+            }
+        };
+        if !(self.lr() > 0.0) {
+            return Err(Error::InvalidArgument(format!(
+                "learning rate must be positive, got {}",
+                self.lr()
+            )));
+        }
+        match self {
+            OptimizerParams::AdamW(p) => {
+                unit("beta1", p.beta1)?;
+                unit("beta2", p.beta2)?;
+                unit("weight_decay", p.weight_decay)?;
+                if !(p.eps >= 0.0) {
+                    return Err(Error::InvalidArgument(format!(
+                        "adamw eps must be non-negative, got {}",
+                        p.eps
+                    )));
+                }
+            }
+            OptimizerParams::Sgd(p) => unit("weight_decay", p.weight_decay)?,
+        }
+        Ok(())
+    }
+
+    fn to_raw(self) -> crate::ggml_opt_optimizer_params {
+        let mut raw = unsafe { crate::ggml_opt_get_default_optimizer_params(std::ptr::null_mut()) };
+        match self {
+            OptimizerParams::AdamW(p) => {
+                raw.adamw.alpha = p.lr;
+                raw.adamw.beta1 = p.beta1;
+                raw.adamw.beta2 = p.beta2;
+                raw.adamw.eps = p.eps;
+                raw.adamw.wd = p.weight_decay;
+            }
+            OptimizerParams::Sgd(p) => {
+                raw.sgd.alpha = p.lr;
+                raw.sgd.wd = p.weight_decay;
+            }
+        }
+        raw
+    }
+}
+
+/// Settings for [`Optimizer::new`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizerConfig {
+    pub loss: LossType,
+    pub params: OptimizerParams,
+    /// Number of batches whose gradients are summed before each optimizer
+    /// update.
+    pub grad_accumulation: usize,
+    /// CPU threads used for each step.
+    pub n_threads: usize,
+}
+
+impl Default for OptimizerConfig {
+    /// Mean loss, default AdamW, no accumulation, one thread per core.
+    fn default() -> Self {
+        OptimizerConfig {
+            loss: LossType::default(),
+            params: OptimizerParams::default(),
+            grad_accumulation: 1,
+            n_threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Loss, predictions and accuracy accumulated over evaluated batches
+/// (`ggml_opt_result`).
+pub struct OptResult {
+    ptr: NonNull<crate::ggml_opt_result>,
+}
+
+impl OptResult {
+    pub fn new() -> Result<Self> {
+        NonNull::new(unsafe { crate::ggml_opt_result_init() })
+            .map(|ptr| OptResult { ptr })
+            .ok_or(Error::NullPointer("ggml_opt_result_init"))
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_opt_result_t {
+        self.ptr.as_ptr()
+    }
+
+    pub fn reset(&mut self) {
+        unsafe { crate::ggml_opt_result_reset(self.as_ptr()) }
+    }
+
+    /// Number of datapoints evaluated.
+    pub fn ndata(&self) -> i64 {
+        let mut ndata = 0;
+        unsafe { crate::ggml_opt_result_ndata(self.as_ptr(), &mut ndata) };
+        ndata
+    }
+
+    /// Loss over all evaluated batches; per datapoint for the mean losses.
+    pub fn loss(&self) -> f64 {
+        self.loss_with_uncertainty().0
+    }
+
+    /// Loss and its standard error, which needs at least two batches.
+    pub fn loss_with_uncertainty(&self) -> (f64, Option<f64>) {
+        let (mut loss, mut unc) = (0.0, 0.0);
+        unsafe { crate::ggml_opt_result_loss(self.as_ptr(), &mut loss, &mut unc) };
+        (loss, Some(unc).filter(|u| !u.is_nan()))
+    }
+
+    /// Fraction of correct predictions; only with [`LossType::CrossEntropy`].
+    pub fn accuracy(&self) -> Option<f64> {
+        self.accuracy_with_uncertainty().0
+    }
+
+    /// Accuracy and its standard error.
+    pub fn accuracy_with_uncertainty(&self) -> (Option<f64>, Option<f64>) {
+        let (mut acc, mut unc) = (0.0, 0.0);
+        unsafe { crate::ggml_opt_result_accuracy(self.as_ptr(), &mut acc, &mut unc) };
+        (
+            Some(acc).filter(|a| !a.is_nan()),
+            Some(unc).filter(|u| !u.is_nan()),
+        )
+    }
+
+    /// Predicted class of every evaluated datapoint; empty unless the loss
+    /// is [`LossType::CrossEntropy`].
+    pub fn predictions(&self) -> Vec<i32> {
+        if self.accuracy().is_none() {
+            return Vec::new();
+        }
+        let mut pred = vec![0; self.ndata() as usize];
+        unsafe { crate::ggml_opt_result_pred(self.as_ptr(), pred.as_mut_ptr()) };
+        pred
+    }
+}
+
+impl Drop for OptResult {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_opt_result_free(self.as_ptr()) }
+    }
+}
+
+/// CPU backend and the scheduler ggml-opt runs its graphs on.
+struct CpuSched {
+    sched: crate::ggml_backend_sched_t,
+    // freed after the scheduler
+    backend: CpuBackend,
+}
+
+impl CpuSched {
+    fn new(n_threads: usize) -> Result<Self> {
+        let backend = CpuBackend::new(n_threads)?;
+        let mut backends = [backend.as_ptr()];
+        let sched = unsafe {
+            crate::ggml_backend_sched_new(
+                backends.as_mut_ptr(),
+                std::ptr::null_mut(),
+                1,
+                crate::GGML_DEFAULT_GRAPH_SIZE as usize,
+                false,
+                false,
+            )
+        };
+        if sched.is_null() {
+            return Err(Error::NullPointer("ggml_backend_sched_new"));
+        }
+        Ok(CpuSched { sched, backend })
+    }
+}
+
+impl Drop for CpuSched {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_backend_sched_free(self.sched) }
+    }
+}
+
+/// A ggml-opt context training the parameters reachable from `outputs` on
+/// the CPU backend.
+pub struct Optimizer<'ctx> {
+    ptr: NonNull<crate::ggml_opt_context>,
+    // dropped after `ptr` is freed
+    cpu: CpuSched,
+    loss: LossType,
+    /// Read by `ggml_opt_get_constant_optimizer_params` on every update.
+    params: Box<crate::ggml_opt_optimizer_params>,
+    current: OptimizerParams,
+    _ctx: PhantomData<&'ctx Context>,
+}
+
+impl<'ctx> Optimizer<'ctx> {
+    /// Recommended `mem_size` of the compute context: room for the forward,
+    /// gradient and optimizer graphs and their tensors.
+    pub fn compute_mem_size() -> usize {
+        let size = crate::GGML_DEFAULT_GRAPH_SIZE as usize;
+        unsafe {
+            size * crate::ggml_tensor_overhead() + 3 * crate::ggml_graph_overhead_custom(size, true)
+        }
+    }
+
+    /// Build the training graphs.
+    ///
+    /// `model` and `compute` must be `no_alloc`. Tensors of `model` without
+    /// data (weights and `inputs`) are allocated on the CPU backend and stay
+    /// valid as long as `model`; at least one must be marked with
+    /// [`Tensor::set_param`]. `outputs` is computed from `inputs` in
+    /// `compute` and must be F32 with shape `[n_out, batch]`.
+    pub fn new(
+        model: &'ctx Context,
+        compute: &'ctx Context,
+        inputs: Tensor<'ctx>,
+        outputs: Tensor<'ctx>,
+        config: OptimizerConfig,
+    ) -> Result<Self> {
+        config.params.validate()?;
+        if config.grad_accumulation == 0 || config.grad_accumulation > i32::MAX as usize {
+            return Err(Error::InvalidArgument(format!(
+                "gradient accumulation of {} batches",
+                config.grad_accumulation
+            )));
+        }
+        if !compute.no_alloc() {
+            return Err(Error::InvalidArgument(
+                "the compute context must be no_alloc".to_string(),
+            ));
+        }
+        if outputs.ty() != Type::F32 {
+            return Err(Error::TypeMismatch {
+                expected: "F32 outputs".to_string(),
+                found: outputs.ty().to_string(),
+            });
+        }
+        if !model.tensors().any(|t| t.is_param()) {
+            return Err(Error::InvalidArgument(
+                "no parameters to train; mark them with Tensor::set_param".to_string(),
+            ));
+        }
+        let needed = 3 * unsafe {
+            crate::ggml_graph_overhead_custom(crate::GGML_DEFAULT_GRAPH_SIZE as usize, true)
+        };
+        let available = compute.mem_size().saturating_sub(compute.used_mem());
+        if needed > available {
+            return Err(Error::OutOfMemory { needed, available });
+        }
+
+        let cpu = CpuSched::new(config.n_threads)?;
+        model.alloc_on(&cpu.backend)?;
+        if inputs.data().is_null() {
+            return Err(Error::InvalidArgument(
+                "inputs must be a tensor of the model context".to_string(),
+            ));
+        }
+
+        let mut params = Box::new(config.params.to_raw());
+        let mut raw = unsafe { crate::ggml_opt_default_params(cpu.sched, config.loss.as_raw()) };
+        raw.ctx_compute = compute.as_ptr();
+        raw.inputs = inputs.as_ptr();
+        raw.outputs = outputs.as_ptr();
+        raw.opt_period = config.grad_accumulation as i32;
+        raw.get_opt_pars = Some(crate::ggml_opt_get_constant_optimizer_params);
+        raw.get_opt_pars_ud = &mut *params as *mut crate::ggml_opt_optimizer_params as *mut _;
+        raw.optimizer = config.params.kind();
+        let ptr = NonNull::new(unsafe { crate::ggml_opt_init(raw) })
+            .ok_or(Error::NullPointer("ggml_opt_init"))?;
+        Ok(Optimizer {
+            ptr,
+            cpu,
+            loss: config.loss,
+            params,
+            current: config.params,
+            _ctx: PhantomData,
+        })
+    }
+
+    pub fn as_ptr(&self) -> crate::ggml_opt_context_t {
+        self.ptr.as_ptr()
+    }
+
+    pub fn loss_type(&self) -> LossType {
+        self.loss
+    }
+
+    pub fn params(&self) -> OptimizerParams {
+        self.current
+    }
+
+    /// Change hyperparameters between steps, e.g. for a learning rate
+    /// schedule. The algorithm cannot change.
+    pub fn set_params(&mut self, params: OptimizerParams) -> Result<()> {
+        if params.kind() != self.current.kind() {
+            return Err(Error::InvalidArgument(format!(
+                "optimizer was built for {}, not {}",
+                self.current.name(),
+                params.name()
+            )));
+        }
+        params.validate()?;
+        *self.params = params.to_raw();
+        self.current = params;
+        Ok(())
+    }
+
+    pub fn set_lr(&mut self, lr: f32) -> Result<()> {
+        self.set_params(self.current.with_lr(lr))
+    }
+
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        self.cpu.backend.set_n_threads(n_threads)
+    }
+
+    /// The batch input tensor; write a batch here before each step.
+    pub fn inputs(&self) -> Tensor<'_> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_inputs(self.as_ptr())) }
+            .expect("static graphs have inputs")
+    }
+
+    /// Label tensor, shaped like the outputs, for losses that use labels.
+    pub fn labels(&self) -> Option<Tensor<'_>> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_labels(self.as_ptr())) }
+    }
+
+    /// The scalar loss tensor.
+    pub fn loss(&self) -> Tensor<'_> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_loss(self.as_ptr())) }
+            .expect("static graphs have a loss")
+    }
+
+    /// Accumulated gradient of parameter `t`.
+    pub fn grad_acc(&self, t: Tensor<'_>) -> Option<Tensor<'_>> {
+        unsafe { Tensor::from_raw(crate::ggml_opt_grad_acc(self.as_ptr(), t.as_ptr())) }
+    }
+
+    /// Zero the accumulated gradients, and with `optimizer` also the
+    /// optimizer state (AdamW momenta and step count).
+    pub fn reset(&mut self, optimizer: bool) {
+        unsafe { crate::ggml_opt_reset(self.as_ptr(), optimizer) }
+    }
+
+    fn eval(&mut self, backward: bool, result: Option<&mut OptResult>) {
+        let result = result.map_or(std::ptr::null_mut(), |r| r.as_ptr());
+        unsafe {
+            crate::ggml_opt_alloc(self.as_ptr(), backward);
+            crate::ggml_opt_eval(self.as_ptr(), result);
+        }
+    }
+
+    /// Forward and backward pass on the current batch; every
+    /// `grad_accumulation` steps also updates the parameters. Loss and
+    /// predictions are added to `result`.
+    pub fn step(&mut self, result: Option<&mut OptResult>) {
+        self.eval(true, result)
+    }
+
+    /// Forward pass only, e.g. on validation data.
+    pub fn evaluate(&mut self, result: Option<&mut OptResult>) {
+        self.eval(false, result)
+    }
+
+    /// Train for `n_epochs` of `n_batches` steps each.
+    ///
+    /// `load_batch(i, inputs, labels)` fills the tensors with batch `i` of
+    /// the epoch. `on_epoch(epoch, result)` is called after each epoch with
+    /// that epoch's results and can stop training early.
+    pub fn fit(
+        &mut self,
+        n_epochs: usize,
+        n_batches: usize,
+        mut load_batch: impl FnMut(usize, Tensor<'_>, Option<Tensor<'_>>) -> Result<()>,
+        mut on_epoch: impl FnMut(usize, &OptResult) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let mut result = OptResult::new()?;
+        for epoch in 0..n_epochs {
+            result.reset();
+            for i in 0..n_batches {
+                load_batch(i, self.inputs(), self.labels())?;
+                self.step(Some(&mut result));
+            }
+            if on_epoch(epoch, &result).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Optimizer<'_> {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_opt_free(self.as_ptr()) }
+    }
+}
@@ -0,0 +1,243 @@
+//! Typed views of tensor data through `bytemuck`.
+//!
+//! [`Tensor::data_as`] borrows a host tensor's data as a slice of any
+//! [`TensorData`] type: the scalar types, the 16-bit floats [`Fp16`] and
+//! [`Bf16`], and the quantization blocks, whose fields mirror ggml's
+//! `block_*` structs:
+//!
+//! ```ignore
+//! let blocks: &[BlockQ8_0] = unsafe { weights.data_as()? };
+//! let first_scale = blocks[0].d.to_f32();
+//!
+//! let mut x = ctx.new_tensor_1d(Type::F16, 64)?.into_mut();
+//! unsafe { x.data_as_mut::<Fp16>()? }.fill(Fp16::from_f32(0.5));
+//! ```
+//!
+//! Both are `unsafe`: [`Tensor`] is `Copy` and [`TensorMut`] is not unique,
+//! so nothing stops another handle to the same tensor from writing its data
+//! while a slice is held.
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::error::{Error, Result};
+use crate::inplace::TensorMut;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Plain data laid out like one element, or one block of elements, of a
+/// ggml type.
+pub trait TensorData: Pod {
+    const TYPE: Type;
+}
+
+macro_rules! tensor_data {
+    ($($t:ty => $ty:ident),* $(,)?) => {
+        $(impl TensorData for $t {
+            const TYPE: Type = Type::$ty;
+        })*
+    };
+}
+
+tensor_data!(
+    f32 => F32, f64 => F64, i8 => I8, i16 => I16, i32 => I32, i64 => I64,
+    Fp16 => F16, Bf16 => BF16,
+    BlockQ4_0 => Q4_0, BlockQ4_1 => Q4_1, BlockQ5_0 => Q5_0, BlockQ5_1 => Q5_1,
+    BlockQ8_0 => Q8_0, BlockQ2_K => Q2_K, BlockQ3_K => Q3_K, BlockQ4_K => Q4_K,
+    BlockQ5_K => Q5_K, BlockQ6_K => Q6_K,
+);
+
+/// An IEEE half-precision float (`ggml_fp16_t`), as stored in F16 tensors.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fp16(pub u16);
+
+impl Fp16 {
+    pub fn from_f32(value: f32) -> Self {
+        Fp16(unsafe { crate::ggml_fp32_to_fp16(value) })
+    }
+
+    pub fn to_f32(self) -> f32 {
+        unsafe { crate::ggml_fp16_to_fp32(self.0) }
+    }
+}
+
+/// A bfloat16 (`ggml_bf16_t`), as stored in BF16 tensors.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bf16(pub u16);
+
+impl Bf16 {
+    pub fn from_f32(value: f32) -> Self {
+        Bf16(unsafe { crate::ggml_fp32_to_bf16(value) }.bits)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        unsafe { crate::ggml_bf16_to_fp32(crate::ggml_bf16_t { bits: self.0 }) }
+    }
+}
+
+/// 32 weights `d * (q - 8)`, with `q` in the nibbles of `qs`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ4_0 {
+    pub d: Fp16,
+    pub qs: [u8; 16],
+}
+
+/// 32 weights `d * q + m`, with `q` in the nibbles of `qs`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ4_1 {
+    pub d: Fp16,
+    pub m: Fp16,
+    pub qs: [u8; 16],
+}
+
+/// 32 weights `d * (q - 16)`; the fifth bit of each `q` is in `qh`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ5_0 {
+    pub d: Fp16,
+    pub qh: [u8; 4],
+    pub qs: [u8; 16],
+}
+
+/// 32 weights `d * q + m`; the fifth bit of each `q` is in `qh`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ5_1 {
+    pub d: Fp16,
+    pub m: Fp16,
+    pub qh: [u8; 4],
+    pub qs: [u8; 16],
+}
+
+/// 32 weights `d * q`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ8_0 {
+    pub d: Fp16,
+    pub qs: [i8; 32],
+}
+
+/// A 256-weight super-block of 2-bit quants with 4-bit scales and mins.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ2_K {
+    pub scales: [u8; 16],
+    pub qs: [u8; 64],
+    pub d: Fp16,
+    pub dmin: Fp16,
+}
+
+/// A 256-weight super-block of 3-bit quants with 6-bit scales.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ3_K {
+    pub hmask: [u8; 32],
+    pub qs: [u8; 64],
+    pub scales: [u8; 12],
+    pub d: Fp16,
+}
+
+/// A 256-weight super-block of 4-bit quants with 6-bit scales and mins.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ4_K {
+    pub d: Fp16,
+    pub dmin: Fp16,
+    pub scales: [u8; 12],
+    pub qs: [u8; 128],
+}
+
+/// A 256-weight super-block of 5-bit quants with 6-bit scales and mins.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ5_K {
+    pub d: Fp16,
+    pub dmin: Fp16,
+    pub scales: [u8; 12],
+    pub qh: [u8; 32],
+    pub qs: [u8; 128],
+}
+
+/// A 256-weight super-block of 6-bit quants with 8-bit scales.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockQ6_K {
+    pub ql: [u8; 128],
+    pub qh: [u8; 64],
+    pub scales: [i8; 16],
+    pub d: Fp16,
+}
+
+// every field is an integer or array of integers, laid out without padding
+macro_rules! pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Zeroable for $t {}
+        unsafe impl Pod for $t {})*
+    };
+}
+
+pod!(
+    Fp16, Bf16, BlockQ4_0, BlockQ4_1, BlockQ5_0, BlockQ5_1, BlockQ8_0, BlockQ2_K, BlockQ3_K,
+    BlockQ4_K, BlockQ5_K, BlockQ6_K
+);
+
+impl<'a> Tensor<'a> {
+    fn check_data_as<T: TensorData>(&self) -> Result<()> {
+        if self.ty() != T::TYPE {
+            return Err(Error::TypeMismatch {
+                expected: T::TYPE.to_string(),
+                found: self.ty().to_string(),
+            });
+        }
+        // a block struct out of step with ggml's
+        if std::mem::size_of::<T>() != T::TYPE.type_size() {
+            return Err(Error::InvalidArgument(format!(
+                "{} takes {} bytes, {} takes {}",
+                std::any::type_name::<T>(),
+                std::mem::size_of::<T>(),
+                T::TYPE,
+                T::TYPE.type_size()
+            )));
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        Ok(())
+    }
+
+    /// Borrow the data of a contiguous host tensor as elements or blocks of
+    /// its type.
+    ///
+    /// # Safety
+    /// While the slice is alive, the tensor's data must not be written:
+    /// not through any handle to it ([`Tensor::write_bytes`],
+    /// [`TensorMut::data_as_mut`], ...), nor by computing a graph that
+    /// writes it.
+    pub unsafe fn data_as<T: TensorData>(&self) -> Result<&'a [T]> {
+        self.check_data_as::<T>()?;
+        let bytes = unsafe { std::slice::from_raw_parts(self.data() as *const u8, self.nbytes()) };
+        bytemuck::try_cast_slice(bytes)
+            .map_err(|e| Error::InvalidArgument(format!("cannot view tensor data: {}", e)))
+    }
+}
+
+impl TensorMut<'_> {
+    /// Like [`Tensor::data_as`], writable.
+    ///
+    /// # Safety
+    /// While the slice is alive, the tensor's data must not be read or
+    /// written through any other handle to it, nor by computing a graph.
+    pub unsafe fn data_as_mut<T: TensorData>(&mut self) -> Result<&mut [T]> {
+        self.check_data_as::<T>()?;
+        let bytes =
+            unsafe { std::slice::from_raw_parts_mut(self.data() as *mut u8, self.nbytes()) };
+        bytemuck::try_cast_slice_mut(bytes)
+            .map_err(|e| Error::InvalidArgument(format!("cannot view tensor data: {}", e)))
+    }
+}
@@ -0,0 +1,251 @@
+//! Image preprocessing for vision encoders: load, resize, normalize and
+//! lay out as a planar F32 tensor.
+//!
+//! Pixels are scaled to `[0, 1]`, then normalized per channel as
+//! `(x - mean) / std`. An image of `width x height` becomes a tensor with
+//! `ne = [width, height, 3, n]`, which is NCHW read outermost first — the
+//! layout ggml's 2D convolutions (and so patch embeddings) take.
+//!
+//! ```ignore
+//! let pp = ImagePreprocess::clip(336);
+//! let pixels = ctx.load_image("cat.jpg", &pp)?; // [336, 336, 3, 1]
+//! let embeddings = vision_encoder(&ctx, pixels)?;
+//! ```
+
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageError, Rgb, RgbImage};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Per-channel (RGB) mean and standard deviation, applied after scaling
+/// pixels to `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageNorm {
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl ImageNorm {
+    /// OpenAI CLIP and the LLaVA family.
+    #[allow(clippy::excessive_precision)] // the digits CLIP publishes
+    pub const CLIP: ImageNorm = ImageNorm {
+        mean: [0.481_454_66, 0.457_827_5, 0.408_210_73],
+        std: [0.268_629_54, 0.261_302_58, 0.275_777_1],
+    };
+    /// SigLIP: maps `[0, 1]` to `[-1, 1]`.
+    pub const SIGLIP: ImageNorm = ImageNorm {
+        mean: [0.5; 3],
+        std: [0.5; 3],
+    };
+    /// torchvision's ImageNet statistics (ViT, DINOv2, ResNet).
+    pub const IMAGENET: ImageNorm = ImageNorm {
+        mean: [0.485, 0.456, 0.406],
+        std: [0.229, 0.224, 0.225],
+    };
+    /// Only scale to `[0, 1]`.
+    pub const NONE: ImageNorm = ImageNorm {
+        mean: [0.0; 3],
+        std: [1.0; 3],
+    };
+}
+
+/// How an image is brought to the target size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resize {
+    /// Scale each axis independently, ignoring the aspect ratio.
+    Stretch,
+    /// Scale so the shorter side covers the target, then crop the center.
+    /// CLIP's `resize` + `center_crop`.
+    CenterCrop,
+    /// Scale so the longer side fits the target, then center the image on
+    /// a background of the given color.
+    Pad([u8; 3]),
+}
+
+/// Resizing and normalization for one vision encoder. Built by value:
+///
+/// ```ignore
+/// let pp = ImagePreprocess::new(448, 448)
+///     .resize(Resize::Pad([127, 127, 127]))
+///     .norm(ImageNorm::IMAGENET);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImagePreprocess {
+    width: u32,
+    height: u32,
+    resize: Resize,
+    filter: FilterType,
+    norm: ImageNorm,
+}
+
+impl ImagePreprocess {
+    /// Stretch to `width x height` with a bicubic filter, normalized with
+    /// [`ImageNorm::CLIP`].
+    pub fn new(width: u32, height: u32) -> Self {
+        ImagePreprocess {
+            width,
+            height,
+            resize: Resize::Stretch,
+            filter: FilterType::CatmullRom,
+            norm: ImageNorm::CLIP,
+        }
+    }
+
+    /// CLIP: bicubic resize of the shorter side to `size`, center crop to
+    /// `size x size`, [`ImageNorm::CLIP`].
+    pub fn clip(size: u32) -> Self {
+        Self::new(size, size).resize(Resize::CenterCrop)
+    }
+
+    /// SigLIP: bilinear resize to `size x size`, [`ImageNorm::SIGLIP`].
+    pub fn siglip(size: u32) -> Self {
+        Self::new(size, size)
+            .filter(FilterType::Triangle)
+            .norm(ImageNorm::SIGLIP)
+    }
+
+    pub fn resize(mut self, resize: Resize) -> Self {
+        self.resize = resize;
+        self
+    }
+
+    pub fn filter(mut self, filter: FilterType) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn norm(mut self, norm: ImageNorm) -> Self {
+        self.norm = norm;
+        self
+    }
+
+    /// `(width, height)` of the output.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "image size {}x{} is empty",
+                self.width, self.height
+            )));
+        }
+        if self.norm.std.contains(&0.0) {
+            return Err(Error::InvalidArgument(format!(
+                "image norm std {:?} has a zero",
+                self.norm.std
+            )));
+        }
+        Ok(())
+    }
+
+    /// `img` as RGB at the output size.
+    fn resized(&self, img: &DynamicImage) -> RgbImage {
+        let rgb = img.to_rgb8();
+        let (w, h) = (self.width, self.height);
+        if rgb.dimensions() == (w, h) {
+            return rgb;
+        }
+        let (iw, ih) = (rgb.width() as f64, rgb.height() as f64);
+        let fit = |scale: f64| {
+            (
+                ((iw * scale).round() as u32).max(1),
+                ((ih * scale).round() as u32).max(1),
+            )
+        };
+        match self.resize {
+            Resize::Stretch => imageops::resize(&rgb, w, h, self.filter),
+            Resize::CenterCrop => {
+                let (sw, sh) = fit((w as f64 / iw).max(h as f64 / ih));
+                let (sw, sh) = (sw.max(w), sh.max(h));
+                let scaled = imageops::resize(&rgb, sw, sh, self.filter);
+                imageops::crop_imm(&scaled, (sw - w) / 2, (sh - h) / 2, w, h).to_image()
+            }
+            Resize::Pad(color) => {
+                let (sw, sh) = fit((w as f64 / iw).min(h as f64 / ih));
+                let (sw, sh) = (sw.min(w), sh.min(h));
+                let scaled = imageops::resize(&rgb, sw, sh, self.filter);
+                let mut out = RgbImage::from_pixel(w, h, Rgb(color));
+                imageops::replace(
+                    &mut out,
+                    &scaled,
+                    ((w - sw) / 2).into(),
+                    ((h - sh) / 2).into(),
+                );
+                out
+            }
+        }
+    }
+
+    /// Resize and normalize `img`, appending its planes (R, G, B, each
+    /// row-major) to `out`.
+    fn extend_planar(&self, img: &DynamicImage, out: &mut Vec<f32>) {
+        let rgb = self.resized(img);
+        let ImageNorm { mean, std } = self.norm;
+        for c in 0..3 {
+            let (m, s) = (mean[c], 255.0 * std[c]);
+            out.extend(rgb.pixels().map(|p| (p.0[c] as f32 - 255.0 * m) / s));
+        }
+    }
+
+    /// Resize and normalize `img` into planar CHW order, `3 * width *
+    /// height` values.
+    pub fn apply(&self, img: &DynamicImage) -> Result<Vec<f32>> {
+        self.check()?;
+        let mut out = Vec::with_capacity(3 * (self.width * self.height) as usize);
+        self.extend_planar(img, &mut out);
+        Ok(out)
+    }
+}
+
+fn image_error(e: ImageError) -> Error {
+    match e {
+        ImageError::IoError(e) => Error::Io(e),
+        e => Error::InvalidFormat(format!("cannot decode image: {}", e)),
+    }
+}
+
+impl Context {
+    /// A new F32 tensor `[width, height, 3, 1]` holding `img` after `pp`.
+    pub fn new_tensor_from_image(
+        &self,
+        img: &DynamicImage,
+        pp: &ImagePreprocess,
+    ) -> Result<Tensor<'_>> {
+        self.new_tensor_from_images(std::slice::from_ref(img), pp)
+    }
+
+    /// A new F32 tensor `[width, height, 3, n]` holding a batch of `n`
+    /// images after `pp`. The images may differ in size.
+    pub fn new_tensor_from_images(
+        &self,
+        imgs: &[DynamicImage],
+        pp: &ImagePreprocess,
+    ) -> Result<Tensor<'_>> {
+        pp.check()?;
+        if imgs.is_empty() {
+            return Err(Error::InvalidArgument("empty image batch".to_string()));
+        }
+        let (w, h) = (pp.width as usize, pp.height as usize);
+        let mut data = Vec::with_capacity(3 * w * h * imgs.len());
+        for img in imgs {
+            pp.extend_planar(img, &mut data);
+        }
+        let t = self.new_tensor_4d(Type::F32, w as i64, h as i64, 3, imgs.len() as i64)?;
+        t.write(&data)?;
+        Ok(t)
+    }
+
+    /// Decode the image file at `path` (any format `image` supports) into a
+    /// new tensor, as [`new_tensor_from_image`](Self::new_tensor_from_image).
+    pub fn load_image(&self, path: impl AsRef<Path>, pp: &ImagePreprocess) -> Result<Tensor<'_>> {
+        let img = image::open(path).map_err(image_error)?;
+        self.new_tensor_from_image(&img, pp)
+    }
+}
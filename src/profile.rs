@@ -0,0 +1,175 @@
+//! Opt-in per-node timing for CPU graph compute.
+//!
+//! [`Graph::compute_profiled`] runs the graph one node at a time and records
+//! the wall time of each node. This serializes the graph (no overlap between
+//! nodes), so totals are slightly higher than a plain [`Graph::compute`], but
+//! the relative cost of each op is accurate.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::context::Context;
+use crate::error::{check_status, Result};
+use crate::graph::Graph;
+use crate::threadpool::{Threadpool, ThreadpoolParams};
+
+/// Timing of a single graph node.
+#[derive(Debug, Clone)]
+pub struct NodeProfile {
+    /// Position of the node in execution order.
+    pub index: usize,
+    pub name: String,
+    /// Op description as reported by `ggml_op_desc`.
+    pub op: &'static str,
+    pub ne: [i64; 4],
+    pub duration: Duration,
+}
+
+/// Aggregated timing of all nodes sharing an op.
+#[derive(Debug, Clone)]
+pub struct OpProfile {
+    pub op: &'static str,
+    pub count: usize,
+    pub total: Duration,
+}
+
+/// Result of [`Graph::compute_profiled`].
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub nodes: Vec<NodeProfile>,
+    /// Sum of all node durations.
+    pub total: Duration,
+}
+
+impl ProfileReport {
+    /// Sort nodes by duration, slowest first.
+    pub fn sort_by_duration(&mut self) {
+        self.nodes.sort_by_key(|node| Reverse(node.duration));
+    }
+
+    /// Sort nodes by op, then by duration (slowest first) within an op.
+    pub fn sort_by_op(&mut self) {
+        self.nodes
+            .sort_by(|a, b| a.op.cmp(b.op).then(b.duration.cmp(&a.duration)));
+    }
+
+    /// Sort nodes by name, keeping execution order for equal names.
+    pub fn sort_by_name(&mut self) {
+        self.nodes
+            .sort_by(|a, b| a.name.cmp(&b.name).then(a.index.cmp(&b.index)));
+    }
+
+    /// Restore execution order.
+    pub fn sort_by_index(&mut self) {
+        self.nodes.sort_by_key(|n| n.index);
+    }
+
+    /// Per-op totals, most expensive op first.
+    pub fn by_op(&self) -> Vec<OpProfile> {
+        let mut totals: HashMap<&'static str, OpProfile> = HashMap::new();
+        for node in &self.nodes {
+            let entry = totals.entry(node.op).or_insert(OpProfile {
+                op: node.op,
+                count: 0,
+                total: Duration::ZERO,
+            });
+            entry.count += 1;
+            entry.total += node.duration;
+        }
+        let mut ops: Vec<OpProfile> = totals.into_values().collect();
+        ops.sort_by(|a, b| b.total.cmp(&a.total).then(a.op.cmp(b.op)));
+        ops
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5}  {:<16} {:<32} {:>12}",
+            "node", "op", "name", "time (us)"
+        )?;
+        for node in &self.nodes {
+            writeln!(
+                f,
+                "{:>5}  {:<16} {:<32} {:>12}",
+                node.index,
+                node.op,
+                node.name,
+                node.duration.as_micros()
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>12} {:>7}",
+            "op", "count", "time (us)", "%"
+        )?;
+        let total = self.total.as_secs_f64().max(f64::EPSILON);
+        for op in self.by_op() {
+            writeln!(
+                f,
+                "{:<16} {:>6} {:>12} {:>6.2}%",
+                op.op,
+                op.count,
+                op.total.as_micros(),
+                100.0 * op.total.as_secs_f64() / total
+            )?;
+        }
+        write!(f, "total: {} us", self.total.as_micros())
+    }
+}
+
+impl Graph<'_> {
+    /// Compute the graph on the CPU one node at a time, timing each node.
+    ///
+    /// The results written to the graph's tensors are identical to
+    /// [`Graph::compute`]. Planning, work buffer allocation and starting the
+    /// worker threads, shared by all nodes, are excluded from the per-node
+    /// timings.
+    pub fn compute_profiled(&mut self, n_threads: usize) -> Result<ProfileReport> {
+        // a single-node graph that each node is swapped into
+        let scratch = Context::new(unsafe { crate::ggml_graph_overhead_custom(1, false) } + 1024)?;
+        let single = scratch.new_graph_custom(1, false)?;
+        let threadpool = Threadpool::new(&ThreadpoolParams::new(n_threads))?;
+        let mut work: Vec<u8> = Vec::new();
+        let mut report = ProfileReport::default();
+
+        for (index, node) in self.nodes().enumerate() {
+            unsafe {
+                crate::ggml_graph_clear(single.as_ptr());
+                crate::ggml_graph_add_node(single.as_ptr(), node.as_ptr());
+            }
+            let mut plan = unsafe {
+                crate::ggml_graph_plan(
+                    single.as_ptr(),
+                    threadpool.n_threads() as i32,
+                    threadpool.as_ptr(),
+                )
+            };
+            if plan.work_size > work.len() {
+                work.resize(plan.work_size, 0);
+            }
+            if plan.work_size > 0 {
+                plan.work_data = work.as_mut_ptr();
+            }
+
+            let start = Instant::now();
+            let status = unsafe { crate::ggml_graph_compute(single.as_ptr(), &mut plan) };
+            let duration = start.elapsed();
+            check_status(status)?;
+
+            report.total += duration;
+            report.nodes.push(NodeProfile {
+                index,
+                name: node.name().to_string(),
+                op: node.op_desc(),
+                ne: node.ne(),
+                duration,
+            });
+        }
+        Ok(report)
+    }
+}
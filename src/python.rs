@@ -0,0 +1,353 @@
+//! The `ggml_rs` Python extension module: GGUF reading and writing and the
+//! quantization routines, the same code the Rust API runs.
+//!
+//! Build it with maturin (see `pyproject.toml`), which enables this feature:
+//!
+//! ```text
+//! $ maturin develop --release
+//! >>> import ggml_rs, numpy as np
+//! >>> f = ggml_rs.GgufFile("model.gguf")
+//! >>> f["general.architecture"]
+//! 'llama'
+//! >>> info = f.tensors()[0]
+//! >>> w = np.frombuffer(ggml_rs.dequantize(info["type"], f.read_tensor_data(info["name"])), np.float32)
+//! >>> q = ggml_rs.quantize("q4_K", w.tobytes(), info["shape"][0])
+//! ```
+//!
+//! The module links the ggml variant's shared libraries. `maturin develop`
+//! does not install them, so point the loader at the build's copies
+//! (`LD_LIBRARY_PATH=target/release`, `DYLD_LIBRARY_PATH` on macOS); wheels
+//! bundle them, see `pyproject.toml`.
+//!
+//! Type names are ggml's (`"f16"`, `"q4_K"`) and GGUF's (`"u32"`, `"str"`).
+//! Shapes are `ne`, innermost first, as in the file. `f32` data is passed
+//! as little-endian bytes, i.e. `np.float32` arrays' `tobytes()`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString};
+
+use crate::error::Error;
+use crate::gguf::{GgufFile, GgufType, GgufValue, GgufWriter};
+use crate::quant::{self, QuantPlan};
+use crate::types::Type;
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> PyErr {
+        match e {
+            Error::Io(e) => e.into(),
+            e @ (Error::InvalidArgument(_)
+            | Error::InvalidFormat(_)
+            | Error::TypeMismatch { .. }
+            | Error::ShapeMismatch(_)) => PyValueError::new_err(e.to_string()),
+            e => PyRuntimeError::new_err(e.to_string()),
+        }
+    }
+}
+
+fn parse_type(name: &str) -> PyResult<Type> {
+    Type::from_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown ggml type {:?}", name)))
+}
+
+fn parse_gguf_type(name: &str) -> PyResult<GgufType> {
+    GgufType::from_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown GGUF type {:?}", name)))
+}
+
+fn f32_from_bytes(data: &[u8]) -> PyResult<Vec<f32>> {
+    if !data.len().is_multiple_of(4) {
+        return Err(PyValueError::new_err(format!(
+            "{} bytes are not whole f32 values",
+            data.len()
+        )));
+    }
+    Ok(data
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect())
+}
+
+fn f32_to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn value_to_py(py: Python<'_>, value: &GgufValue) -> PyObject {
+    match value {
+        GgufValue::U8(v) => v.into_py(py),
+        GgufValue::I8(v) => v.into_py(py),
+        GgufValue::U16(v) => v.into_py(py),
+        GgufValue::I16(v) => v.into_py(py),
+        GgufValue::U32(v) => v.into_py(py),
+        GgufValue::I32(v) => v.into_py(py),
+        GgufValue::U64(v) => v.into_py(py),
+        GgufValue::I64(v) => v.into_py(py),
+        GgufValue::F32(v) => v.into_py(py),
+        GgufValue::F64(v) => v.into_py(py),
+        GgufValue::Bool(v) => v.into_py(py),
+        GgufValue::String(v) => v.into_py(py),
+        GgufValue::Array(items) => {
+            PyList::new_bound(py, items.iter().map(|v| value_to_py(py, v))).into_py(py)
+        }
+    }
+}
+
+/// `value` as GGUF metadata of type `ty`, or by default of the type
+/// gguf-py picks: `bool`, `i32`, `f32` or `str`. Lists become arrays of
+/// `ty` elements.
+fn py_to_value(value: &Bound<'_, PyAny>, ty: Option<GgufType>) -> PyResult<GgufValue> {
+    if let Ok(list) = value.downcast::<PyList>() {
+        if ty == Some(GgufType::Array) {
+            return Err(PyValueError::new_err("GGUF arrays cannot nest"));
+        }
+        let items = list
+            .iter()
+            .map(|item| match py_to_value(&item, ty)? {
+                GgufValue::Array(_) => Err(PyValueError::new_err("GGUF arrays cannot nest")),
+                v => Ok(v),
+            })
+            .collect::<PyResult<_>>()?;
+        return Ok(GgufValue::Array(items));
+    }
+    let ty = match ty {
+        Some(ty) => ty,
+        None if value.is_instance_of::<PyBool>() => GgufType::Bool,
+        None if value.is_instance_of::<PyInt>() => GgufType::I32,
+        None if value.is_instance_of::<PyFloat>() => GgufType::F32,
+        None if value.is_instance_of::<PyString>() => GgufType::String,
+        None => {
+            return Err(PyTypeError::new_err(format!(
+                "no GGUF type for {}",
+                value.get_type().name()?
+            )))
+        }
+    };
+    Ok(match ty {
+        GgufType::U8 => GgufValue::U8(value.extract()?),
+        GgufType::I8 => GgufValue::I8(value.extract()?),
+        GgufType::U16 => GgufValue::U16(value.extract()?),
+        GgufType::I16 => GgufValue::I16(value.extract()?),
+        GgufType::U32 => GgufValue::U32(value.extract()?),
+        GgufType::I32 => GgufValue::I32(value.extract()?),
+        GgufType::U64 => GgufValue::U64(value.extract()?),
+        GgufType::I64 => GgufValue::I64(value.extract()?),
+        GgufType::F32 => GgufValue::F32(value.extract()?),
+        GgufType::F64 => GgufValue::F64(value.extract()?),
+        GgufType::Bool => GgufValue::Bool(value.extract()?),
+        GgufType::String => GgufValue::String(value.extract()?),
+        GgufType::Array => {
+            return Err(PyTypeError::new_err(
+                "an \"arr\" value must be a list; pass its element type instead",
+            ))
+        }
+    })
+}
+
+/// `GgufFile(path)`: the metadata and tensor infos of a GGUF file.
+#[pyclass(name = "GgufFile", module = "ggml_rs", unsendable)]
+struct PyGgufFile {
+    file: GgufFile,
+}
+
+#[pymethods]
+impl PyGgufFile {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        Ok(PyGgufFile {
+            file: GgufFile::open(path)?,
+        })
+    }
+
+    #[getter]
+    fn version(&self) -> u32 {
+        self.file.version()
+    }
+
+    #[getter]
+    fn alignment(&self) -> usize {
+        self.file.alignment()
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.file.keys().collect()
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.file.contains_key(key)
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &str) -> PyResult<PyObject> {
+        self.file
+            .get(key)
+            .map(|v| value_to_py(py, &v))
+            .ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
+
+    #[pyo3(signature = (key, default = None))]
+    fn get(&self, py: Python<'_>, key: &str, default: Option<PyObject>) -> PyObject {
+        match self.file.get(key) {
+            Some(v) => value_to_py(py, &v),
+            None => default.unwrap_or_else(|| py.None()),
+        }
+    }
+
+    /// Every key and value, in file order.
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (key, value) in self.file.metadata() {
+            dict.set_item(key, value_to_py(py, &value))?;
+        }
+        Ok(dict)
+    }
+
+    /// One dict per tensor: `name`, `type` (`None` if unknown), `shape`,
+    /// `offset` and `size` in bytes.
+    fn tensors<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let list = PyList::empty_bound(py);
+        for info in self.file.tensor_infos() {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("name", &info.name)?;
+            dict.set_item("type", info.ty.map(|t| t.name()))?;
+            dict.set_item("shape", &info.ne[..info.n_dims.max(1)])?;
+            dict.set_item("offset", info.offset)?;
+            dict.set_item("size", info.size)?;
+            list.append(dict)?;
+        }
+        Ok(list)
+    }
+
+    /// The raw data of tensor `name`, in its own type.
+    fn read_tensor_data<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyBytes>> {
+        let data = self.file.read_tensor_data(name)?;
+        Ok(PyBytes::new_bound(py, &data))
+    }
+}
+
+/// `GgufWriter()`: builds a GGUF file from metadata and tensor data.
+#[pyclass(name = "GgufWriter", module = "ggml_rs", unsendable)]
+struct PyGgufWriter {
+    writer: GgufWriter<'static>,
+}
+
+#[pymethods]
+impl PyGgufWriter {
+    #[new]
+    fn new() -> PyResult<Self> {
+        Ok(PyGgufWriter {
+            writer: GgufWriter::new()?,
+        })
+    }
+
+    /// A writer holding everything in `file`, to save it with changes.
+    #[staticmethod]
+    fn from_file(file: &PyGgufFile) -> PyResult<Self> {
+        Ok(PyGgufWriter {
+            writer: GgufWriter::from_file(&file.file)?,
+        })
+    }
+
+    /// Set `key`; `type` names the GGUF type (of the elements, for lists).
+    #[pyo3(signature = (key, value, r#type = None))]
+    fn set(&mut self, key: &str, value: &Bound<'_, PyAny>, r#type: Option<&str>) -> PyResult<()> {
+        let ty = r#type.map(parse_gguf_type).transpose()?;
+        self.writer.set_value(key, &py_to_value(value, ty)?)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> PyResult<bool> {
+        Ok(self.writer.remove(key)?)
+    }
+
+    fn set_alignment(&mut self, alignment: usize) -> PyResult<()> {
+        self.writer.set_alignment(alignment)?;
+        Ok(())
+    }
+
+    /// Add a tensor of ggml type `type` and shape `shape` from its raw
+    /// bytes.
+    fn add_tensor(
+        &mut self,
+        name: &str,
+        r#type: &str,
+        shape: Vec<i64>,
+        data: &[u8],
+    ) -> PyResult<()> {
+        let ty = parse_type(r#type)?;
+        self.writer
+            .add_tensor_bytes(name, ty, &shape, data.to_vec())?;
+        Ok(())
+    }
+
+    fn write(&self, path: PathBuf) -> PyResult<()> {
+        Ok(self.writer.write_to_file(path)?)
+    }
+}
+
+/// Quantize rows of `n_per_row` f32 values (as bytes) to `type`.
+#[pyfunction]
+#[pyo3(signature = (r#type, data, n_per_row, imatrix = None))]
+fn quantize<'py>(
+    py: Python<'py>,
+    r#type: &str,
+    data: &[u8],
+    n_per_row: usize,
+    imatrix: Option<&[u8]>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let ty = parse_type(r#type)?;
+    let src = f32_from_bytes(data)?;
+    let imatrix = imatrix.map(f32_from_bytes).transpose()?;
+    let q = py.allow_threads(|| quant::quantize(ty, &src, n_per_row, imatrix.as_deref()))?;
+    Ok(PyBytes::new_bound(py, &q))
+}
+
+/// Convert whole blocks of `type` back to f32 values (as bytes).
+#[pyfunction]
+#[pyo3(signature = (r#type, data))]
+fn dequantize<'py>(py: Python<'py>, r#type: &str, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let ty = parse_type(r#type)?;
+    let values = py.allow_threads(|| quant::dequantize(ty, data))?;
+    Ok(PyBytes::new_bound(py, &f32_to_bytes(&values)))
+}
+
+/// Rewrite the GGUF file `src` to `dst` with its tensors converted to
+/// `type`, except those matching a `keep` pattern, and those matching an
+/// `overrides` pattern, which get the paired type (later pairs win).
+/// Returns `(name, from, to)` per tensor.
+#[pyfunction]
+#[pyo3(signature = (src, dst, r#type, keep = Vec::new(), overrides = Vec::new()))]
+fn requantize(
+    src: PathBuf,
+    dst: PathBuf,
+    r#type: &str,
+    keep: Vec<String>,
+    overrides: Vec<(String, String)>,
+) -> PyResult<Vec<(String, &'static str, &'static str)>> {
+    let mut plan = QuantPlan::new(parse_type(r#type)?);
+    for (pattern, ty) in &overrides {
+        plan.set(pattern, parse_type(ty)?);
+    }
+    for pattern in &keep {
+        plan.keep(pattern);
+    }
+    let file = GgufFile::open(src)?;
+    let out = BufWriter::new(File::create(dst)?);
+    let done = quant::requantize(&file, &plan, out)?;
+    Ok(done
+        .into_iter()
+        .map(|r| (r.name, r.from.name(), r.to.name()))
+        .collect())
+}
+
+#[pymodule]
+fn ggml_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGgufFile>()?;
+    m.add_class::<PyGgufWriter>()?;
+    m.add_function(wrap_pyfunction!(quantize, m)?)?;
+    m.add_function(wrap_pyfunction!(dequantize, m)?)?;
+    m.add_function(wrap_pyfunction!(requantize, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! Conversion between `f32` values and ggml types.
+
+use std::os::raw::c_void;
+
+use crate::error::{Error, Result};
+use crate::types::Type;
+
+impl Type {
+    /// Whether [`quantize`] can convert to this type.
+    pub fn can_quantize(self) -> bool {
+        !matches!(
+            self,
+            Type::Q8_1 | Type::Q8_K | Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::F64
+        )
+    }
+
+    /// Whether [`dequantize`] can convert from this type.
+    pub fn can_dequantize(self) -> bool {
+        self == Type::F32
+            || unsafe { (*crate::ggml_get_type_traits(self.as_raw())).to_float }.is_some()
+    }
+
+    /// Whether quantizing to this type needs an importance matrix (the
+    /// smallest IQ types).
+    pub fn requires_imatrix(self) -> bool {
+        unsafe { crate::ggml_quantize_requires_imatrix(self.as_raw()) }
+    }
+}
+
+/// Check that `src` holds whole rows of `n_per_row` values that `ty` can
+/// quantize, and that `imatrix` has one weight per column.
+fn check_quantize(ty: Type, src: &[f32], n_per_row: usize, imatrix: Option<&[f32]>) -> Result<()> {
+    if !ty.can_quantize() {
+        return Err(Error::InvalidArgument(format!("cannot quantize to {}", ty)));
+    }
+    if n_per_row == 0 || !n_per_row.is_multiple_of(ty.block_size()) {
+        return Err(Error::ShapeMismatch(format!(
+            "rows of {} values do not split into {} blocks of {}",
+            n_per_row,
+            ty,
+            ty.block_size()
+        )));
+    }
+    if !src.len().is_multiple_of(n_per_row) {
+        return Err(Error::ShapeMismatch(format!(
+            "{} values are not a multiple of the row length {}",
+            src.len(),
+            n_per_row
+        )));
+    }
+    match imatrix {
+        Some(w) if w.len() != n_per_row => Err(Error::ShapeMismatch(format!(
+            "importance matrix has {} weights for rows of {}",
+            w.len(),
+            n_per_row
+        ))),
+        None if ty.requires_imatrix() => Err(Error::InvalidArgument(format!(
+            "quantizing to {} needs an importance matrix",
+            ty
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Fewest values quantized by one rayon task, so small tensors are not
+/// split into tasks that cost more to schedule than to run.
+#[cfg(feature = "rayon")]
+pub const MIN_CHUNK_VALUES: usize = 1 << 16;
+
+/// Quantize whole rows into `dst`, spread across the rayon pool.
+#[cfg(feature = "rayon")]
+fn quantize_rows(ty: Type, src: &[f32], dst: &mut [u8], n_per_row: usize, imatrix: Option<&[f32]>) {
+    use rayon::prelude::*;
+
+    let rows = (MIN_CHUNK_VALUES / n_per_row).max(1);
+    let row_size = ty.row_size(n_per_row as i64);
+    src.par_chunks(rows * n_per_row)
+        .zip(dst.par_chunks_mut(rows * row_size))
+        .for_each(|(src, dst)| quantize_chunk(ty, src, dst, n_per_row, imatrix));
+}
+
+#[cfg(not(feature = "rayon"))]
+fn quantize_rows(ty: Type, src: &[f32], dst: &mut [u8], n_per_row: usize, imatrix: Option<&[f32]>) {
+    quantize_chunk(ty, src, dst, n_per_row, imatrix)
+}
+
+/// Quantize whole rows into `dst`, which must hold their row size.
+/// Arguments must have passed [`check_quantize`].
+fn quantize_chunk(
+    ty: Type,
+    src: &[f32],
+    dst: &mut [u8],
+    n_per_row: usize,
+    imatrix: Option<&[f32]>,
+) {
+    let n_rows = src.len() / n_per_row;
+    debug_assert_eq!(dst.len(), n_rows * ty.row_size(n_per_row as i64));
+    unsafe {
+        crate::ggml_quantize_chunk(
+            ty.as_raw(),
+            src.as_ptr(),
+            dst.as_mut_ptr() as *mut c_void,
+            0,
+            n_rows as i64,
+            n_per_row as i64,
+            imatrix.map_or(std::ptr::null(), |w| w.as_ptr()),
+        )
+    };
+}
+
+/// Quantize `src`, rows of `n_per_row` values, to `ty`.
+///
+/// `n_per_row` must be a multiple of the block size of `ty`. `imatrix`
+/// weighs the error of each column; it is required for types where
+/// [`Type::requires_imatrix`] holds and optional for the others.
+pub fn quantize(
+    ty: Type,
+    src: &[f32],
+    n_per_row: usize,
+    imatrix: Option<&[f32]>,
+) -> Result<Vec<u8>> {
+    check_quantize(ty, src, n_per_row, imatrix)?;
+    let n_rows = src.len() / n_per_row;
+    let mut dst = vec![0u8; n_rows * ty.row_size(n_per_row as i64)];
+    quantize_rows(ty, src, &mut dst, n_per_row, imatrix);
+    Ok(dst)
+}
+
+/// Number of values held by `len` bytes of `ty` data, which must be whole
+/// blocks of a type [`dequantize`] supports.
+pub(super) fn check_dequantize(ty: Type, len: usize) -> Result<usize> {
+    if !ty.can_dequantize() {
+        return Err(Error::InvalidArgument(format!(
+            "no f32 conversion for {}",
+            ty
+        )));
+    }
+    if !len.is_multiple_of(ty.type_size()) {
+        return Err(Error::ShapeMismatch(format!(
+            "{} bytes are not whole {} blocks of {} bytes",
+            len,
+            ty,
+            ty.type_size()
+        )));
+    }
+    Ok(len / ty.type_size() * ty.block_size())
+}
+
+/// Convert whole blocks of `src` into `dst`, which must hold their values.
+/// Arguments must have passed [`check_dequantize`].
+pub(super) fn dequantize_into(ty: Type, src: &[u8], dst: &mut [f32]) {
+    if ty == Type::F32 {
+        for (d, s) in dst.iter_mut().zip(src.chunks_exact(4)) {
+            *d = f32::from_ne_bytes(s.try_into().unwrap());
+        }
+        return;
+    }
+    let to_float = unsafe { (*crate::ggml_get_type_traits(ty.as_raw())).to_float }
+        .expect("checked by check_dequantize");
+    unsafe {
+        to_float(
+            src.as_ptr() as *const c_void,
+            dst.as_mut_ptr(),
+            dst.len() as i64,
+        )
+    };
+}
+
+/// Convert `src`, whole blocks of `ty`, to `f32` values.
+pub fn dequantize(ty: Type, src: &[u8]) -> Result<Vec<f32>> {
+    let n = check_dequantize(ty, src.len())?;
+    let mut dst = vec![0f32; n];
+    dequantize_into(ty, src, &mut dst);
+    Ok(dst)
+}
+
+/// Values converted at a time by [`dequantize_to`].
+const CHUNK_VALUES: usize = 1 << 14;
+
+/// Convert `src`, whole blocks of `ty`, to `to`: F32, F16 or BF16.
+///
+/// Blocks are dequantized a chunk at a time into a small `f32` buffer and
+/// narrowed from there, so converting to a half-precision type never holds
+/// an `f32` copy of all of `src`.
+pub fn dequantize_to(ty: Type, src: &[u8], to: Type) -> Result<Vec<u8>> {
+    let n = check_dequantize(ty, src.len())?;
+    if !matches!(to, Type::F32 | Type::F16 | Type::BF16) {
+        return Err(Error::InvalidArgument(format!(
+            "cannot dequantize to {}",
+            to
+        )));
+    }
+    if ty == to {
+        return Ok(src.to_vec());
+    }
+    let mut dst = vec![0u8; n * to.type_size()];
+    let blocks = (CHUNK_VALUES / ty.block_size()).max(1);
+    let mut buf = vec![0f32; blocks * ty.block_size()];
+    let src_chunks = src.chunks(blocks * ty.type_size());
+    let dst_chunks = dst.chunks_mut(buf.len() * to.type_size());
+    for (s, d) in src_chunks.zip(dst_chunks) {
+        let values = &mut buf[..s.len() / ty.type_size() * ty.block_size()];
+        dequantize_into(ty, s, values);
+        let (x, k) = (values.as_ptr(), values.len() as i64);
+        match to {
+            Type::F16 => unsafe { crate::ggml_fp32_to_fp16_row(x, d.as_mut_ptr() as *mut _, k) },
+            Type::BF16 => unsafe { crate::ggml_fp32_to_bf16_row(x, d.as_mut_ptr() as *mut _, k) },
+            _ => {
+                for (o, v) in d.chunks_exact_mut(4).zip(values.iter()) {
+                    o.copy_from_slice(&v.to_ne_bytes());
+                }
+            }
+        }
+    }
+    Ok(dst)
+}
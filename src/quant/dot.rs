@@ -0,0 +1,126 @@
+//! Dot products on quantized rows with the CPU backend's kernels.
+
+use std::os::raw::c_void;
+use std::sync::Once;
+
+use crate::error::{Error, Result};
+use crate::types::Type;
+
+/// The CPU kernels read lookup tables filled by `ggml_cpu_init`.
+fn cpu_init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe { crate::ggml_cpu_init() });
+}
+
+fn cpu_traits(ty: Type) -> &'static crate::ggml_type_traits_cpu {
+    cpu_init();
+    unsafe { &*crate::ggml_get_type_traits_cpu(ty.as_raw()) }
+}
+
+/// Convert a query of `f32` values to the [`Type::vec_dot_type`] of `ty`,
+/// the form [`vec_dot`] takes its second operand in.
+pub fn quantize_query(ty: Type, query: &[f32]) -> Result<Vec<u8>> {
+    let dot_ty = ty.vec_dot_type().ok_or_else(|| {
+        Error::InvalidArgument(format!("the CPU backend has no dot product for {}", ty))
+    })?;
+    check_len(dot_ty, query.len())?;
+    let mut out = vec![0u8; dot_ty.row_size(query.len() as i64)];
+    match cpu_traits(dot_ty).from_float {
+        Some(from_float) => unsafe {
+            from_float(
+                query.as_ptr(),
+                out.as_mut_ptr() as *mut c_void,
+                query.len() as i64,
+            )
+        },
+        // F32 is its own vec_dot_type
+        None if dot_ty == Type::F32 => {
+            for (o, v) in out.chunks_exact_mut(4).zip(query) {
+                o.copy_from_slice(&v.to_ne_bytes());
+            }
+        }
+        None => {
+            return Err(Error::InvalidArgument(format!(
+                "the CPU backend cannot convert f32 to {}",
+                dot_ty
+            )))
+        }
+    }
+    Ok(out)
+}
+
+fn check_len(ty: Type, n: usize) -> Result<()> {
+    if n == 0 || !n.is_multiple_of(ty.block_size()) {
+        return Err(Error::ShapeMismatch(format!(
+            "{} values do not split into {} blocks of {}",
+            n,
+            ty,
+            ty.block_size()
+        )));
+    }
+    Ok(())
+}
+
+/// Dot product of `x`, a row of `n` values of type `ty`, and `y`, a row of
+/// `n` values of `ty`'s [`Type::vec_dot_type`] (see [`quantize_query`]).
+pub fn vec_dot(ty: Type, n: usize, x: &[u8], y: &[u8]) -> Result<f32> {
+    let mut out = [0f32];
+    dot_rows(ty, n, x, y, &mut out)?;
+    Ok(out[0])
+}
+
+/// Dot products of every row of `matrix`, rows of `n` values of type `ty`,
+/// with `query`, written to `scores`; one score per row. This is the
+/// inner loop of a similarity search over quantized embeddings:
+///
+/// ```ignore
+/// let query = quant::quantize_query(Type::Q8_0, &embedding)?;
+/// let mut scores = vec![0.0; n_docs];
+/// quant::dot_rows(Type::Q8_0, dim, &doc_embeddings, &query, &mut scores)?;
+/// ```
+pub fn dot_rows(ty: Type, n: usize, matrix: &[u8], query: &[u8], scores: &mut [f32]) -> Result<()> {
+    let traits = cpu_traits(ty);
+    let (Some(vec_dot), Some(dot_ty)) = (traits.vec_dot, ty.vec_dot_type()) else {
+        return Err(Error::InvalidArgument(format!(
+            "the CPU backend has no dot product for {}",
+            ty
+        )));
+    };
+    check_len(ty, n)?;
+    check_len(dot_ty, n)?;
+    let row_size = ty.row_size(n as i64);
+    if query.len() != dot_ty.row_size(n as i64) {
+        return Err(Error::ShapeMismatch(format!(
+            "query of {} bytes is not a {} row of {} values",
+            query.len(),
+            dot_ty,
+            n
+        )));
+    }
+    if matrix.len() != row_size * scores.len() {
+        return Err(Error::ShapeMismatch(format!(
+            "{} bytes are not {} {} rows of {} values",
+            matrix.len(),
+            scores.len(),
+            ty,
+            n
+        )));
+    }
+    let n = i32::try_from(n)
+        .map_err(|_| Error::InvalidArgument(format!("rows of {} values are too long", n)))?;
+    for (row, score) in matrix.chunks_exact(row_size).zip(scores.iter_mut()) {
+        unsafe {
+            vec_dot(
+                n,
+                score,
+                0,
+                row.as_ptr() as *const c_void,
+                0,
+                query.as_ptr() as *const c_void,
+                0,
+                1,
+            )
+        };
+    }
+    Ok(())
+}
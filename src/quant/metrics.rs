@@ -0,0 +1,100 @@
+//! How much precision quantization loses.
+
+use std::fmt;
+
+use super::convert::{dequantize, quantize};
+use crate::error::{Error, Result};
+use crate::gguf::{DiffStats, DiffSums};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+fn round_trip(
+    ty: Type,
+    src: &[f32],
+    n_per_row: usize,
+    imatrix: Option<&[f32]>,
+) -> Result<DiffSums> {
+    let back = dequantize(ty, &quantize(ty, src, n_per_row, imatrix)?)?;
+    let mut sums = DiffSums::default();
+    sums.add(src, &back);
+    Ok(sums)
+}
+
+/// Error of quantizing `src`, rows of `n_per_row` values, to `ty` and
+/// dequantizing it again, against the original values.
+pub fn quantization_error(
+    ty: Type,
+    src: &[f32],
+    n_per_row: usize,
+    imatrix: Option<&[f32]>,
+) -> Result<DiffStats> {
+    round_trip(ty, src, n_per_row, imatrix).map(|sums| sums.stats())
+}
+
+/// Quantization error of one tensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorError {
+    pub name: String,
+    pub stats: DiffStats,
+}
+
+/// Result of [`evaluate`]: the error of every tensor, and over all their
+/// values together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantReport {
+    pub ty: Type,
+    pub tensors: Vec<TensorError>,
+    pub total: DiffStats,
+}
+
+/// Quantize each tensor to `ty` row by row, dequantize it and compare it
+/// with its original values (read with [`Tensor::to_vec_f32`]).
+///
+/// Rows of every tensor must split into blocks of `ty`; filter out tensors
+/// that are not quantized in practice, such as 1-d norms.
+///
+/// ```ignore
+/// let model = GgufModel::open("model-f16.gguf")?;
+/// let report = quant::evaluate(Type::Q4_K, model.tensors().filter(|t| t.n_dims() >= 2))?;
+/// println!("{}", report);
+/// ```
+pub fn evaluate<'a>(
+    ty: Type,
+    tensors: impl IntoIterator<Item = Tensor<'a>>,
+) -> Result<QuantReport> {
+    let mut total = DiffSums::default();
+    let mut errors = Vec::new();
+    for t in tensors {
+        let n_per_row = t.ne()[0] as usize;
+        let sums = round_trip(ty, &t.to_vec_f32()?, n_per_row, None).map_err(|e| match e {
+            Error::ShapeMismatch(msg) => Error::ShapeMismatch(format!("{}: {}", t.name(), msg)),
+            e => e,
+        })?;
+        total.merge(&sums);
+        errors.push(TensorError {
+            name: t.name().to_string(),
+            stats: sums.stats(),
+        });
+    }
+    Ok(QuantReport {
+        ty,
+        tensors: errors,
+        total: total.stats(),
+    })
+}
+
+impl fmt::Display for QuantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut row = |name: &str, s: &DiffStats| {
+            writeln!(
+                f,
+                "{:<48} rmse {:.3e} max_abs {:.3e} rel_l2 {:.3e} cos {:.6}",
+                name, s.rmse, s.max_abs, s.rel_l2, s.cosine
+            )
+        };
+        for t in &self.tensors {
+            row(&t.name, &t.stats)?;
+        }
+        row(&format!("total ({})", self.ty), &self.total)
+    }
+}
@@ -0,0 +1,43 @@
+//! Quantizing and dequantizing raw tensor data.
+//!
+//! [`quantize`] converts rows of `f32` values to any type ggml can quantize
+//! to, including F16 and BF16, and [`dequantize`] converts the blocks back;
+//! [`dequantize_to`] goes straight to F16 or BF16.
+//! [`QuantType`] offers the same per type, for code generic over formats:
+//!
+//! ```ignore
+//! let q = quant::Q4_K::quantize(&weights, 4096)?;
+//! let back = quant::Q4_K::dequantize(&q)?;
+//! ```
+//!
+//! [`DequantRows`] reads a tensor back one row at a time, and [`evaluate`]
+//! measures how much precision a type loses on a set of tensors.
+//! [`vec_dot`] and [`dot_rows`] run the CPU backend's dot product kernels
+//! on quantized rows directly, e.g. to search quantized embeddings.
+//!
+//! [`requantize`] converts the tensors of a GGUF file to the types a
+//! [`QuantPlan`] picks per tensor name.
+//!
+//! With the `rayon` feature, rows are quantized on the rayon thread pool in
+//! chunks of at least [`MIN_CHUNK_VALUES`] values; the result is the same.
+
+mod convert;
+mod dot;
+mod metrics;
+mod plan;
+mod requantize;
+mod rows;
+mod types;
+
+#[cfg(feature = "rayon")]
+pub use convert::MIN_CHUNK_VALUES;
+pub use convert::{dequantize, dequantize_to, quantize};
+pub use dot::{dot_rows, quantize_query, vec_dot};
+pub use metrics::{evaluate, quantization_error, QuantReport, TensorError};
+pub use plan::QuantPlan;
+pub use requantize::{requantize, Requantized};
+pub use rows::DequantRows;
+pub use types::{
+    QuantType, BF16, F16, F32, IQ1_M, IQ1_S, IQ2_S, IQ2_XS, IQ2_XXS, IQ3_S, IQ3_XXS, IQ4_NL,
+    IQ4_XS, MXFP4, Q2_K, Q3_K, Q4_0, Q4_1, Q4_K, Q5_0, Q5_1, Q5_K, Q6_K, Q8_0, TQ1_0, TQ2_0,
+};
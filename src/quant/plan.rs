@@ -0,0 +1,125 @@
+//! Choosing a type per tensor when quantizing a model.
+
+use crate::gguf::GgufTensorInfo;
+use crate::types::Type;
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    pattern: String,
+    /// `None` keeps the tensor's type.
+    ty: Option<Type>,
+}
+
+/// Which type each tensor of a model is quantized to: rules matching
+/// tensor names, where a later rule overrides earlier ones, and a default
+/// for tensors no rule matches. Patterns may use `*` as a wildcard.
+///
+/// Tensors are only converted where it is possible: they must have at
+/// least two dimensions, rows that split into blocks of the target type,
+/// and a source type that can be dequantized. Others keep their type, as
+/// do tensors whose target needs an importance matrix.
+///
+/// ```ignore
+/// let mut plan = QuantPlan::new(Type::Q4_K);
+/// plan.set("output.weight", Type::Q6_K)
+///     .set("token_embd.weight", Type::Q8_0)
+///     .keep("*.ffn_gate_inp.weight");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantPlan {
+    rules: Vec<Rule>,
+    default: Option<Type>,
+}
+
+impl QuantPlan {
+    /// Convert every tensor to `ty` unless a rule says otherwise.
+    pub fn new(ty: Type) -> Self {
+        QuantPlan {
+            rules: Vec::new(),
+            default: Some(ty),
+        }
+    }
+
+    /// Keep every tensor's type unless a rule says otherwise.
+    pub fn keep_all() -> Self {
+        QuantPlan {
+            rules: Vec::new(),
+            default: None,
+        }
+    }
+
+    /// Mostly `ty`, with llama.cpp's main exceptions for quantized
+    /// targets: `output.weight`, which affects every token, at Q6_K unless
+    /// `ty` is at least as precise, and MoE router weights kept as they are.
+    pub fn llama(ty: Type) -> Self {
+        let mut plan = Self::new(ty);
+        if ty.is_quantized() && bits_per_weight(ty) < bits_per_weight(Type::Q6_K) {
+            plan.set("output.weight", Type::Q6_K);
+        }
+        plan.keep("*.ffn_gate_inp.weight");
+        plan
+    }
+
+    /// Convert tensors matching `pattern` to `ty`.
+    pub fn set(&mut self, pattern: &str, ty: Type) -> &mut Self {
+        self.rules.push(Rule {
+            pattern: pattern.to_string(),
+            ty: Some(ty),
+        });
+        self
+    }
+
+    /// Keep the type of tensors matching `pattern`.
+    pub fn keep(&mut self, pattern: &str) -> &mut Self {
+        self.rules.push(Rule {
+            pattern: pattern.to_string(),
+            ty: None,
+        });
+        self
+    }
+
+    /// The type `info` is to be written as: its own type if the plan keeps
+    /// it or cannot convert it. `None` for tensors of unknown type.
+    pub fn target(&self, info: &GgufTensorInfo) -> Option<Type> {
+        let from = info.ty?;
+        let wanted = self
+            .rules
+            .iter()
+            .rev()
+            .find(|r| glob_match(&r.pattern, &info.name))
+            .map_or(self.default, |r| r.ty);
+        let Some(to) = wanted else {
+            return Some(from);
+        };
+        let convertible = info.n_dims >= 2
+            && (info.ne[0] as usize).is_multiple_of(to.block_size())
+            && from.can_dequantize()
+            && to.can_quantize()
+            && !to.requires_imatrix();
+        Some(if convertible { to } else { from })
+    }
+}
+
+fn bits_per_weight(ty: Type) -> f64 {
+    (ty.type_size() * 8) as f64 / ty.block_size() as f64
+}
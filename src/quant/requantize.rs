@@ -0,0 +1,69 @@
+//! Converting the tensors of a GGUF file to other types.
+
+use std::io::{Seek, Write};
+
+use super::convert::{dequantize, dequantize_to, quantize};
+use super::plan::QuantPlan;
+use crate::error::{Error, Result};
+use crate::gguf::{GgufFile, GgufStreamWriter, GgufWriter};
+use crate::types::Type;
+
+/// What [`requantize`] did to one tensor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requantized {
+    pub name: String,
+    pub from: Type,
+    pub to: Type,
+    /// Size of the data in bytes before and after.
+    pub size_in: usize,
+    pub size_out: usize,
+}
+
+/// Write `file` to `out` with its tensors converted as `plan` says.
+///
+/// Metadata is copied, with `general.quantization_version` set. Tensor data
+/// is streamed, so only one tensor is held in memory at a time.
+pub fn requantize<W: Write + Seek>(
+    file: &GgufFile,
+    plan: &QuantPlan,
+    out: W,
+) -> Result<Vec<Requantized>> {
+    let mut meta = GgufWriter::new()?;
+    for (key, value) in file.metadata() {
+        meta.set_value(&key, &value)?;
+    }
+    meta.set("general.quantization_version", crate::GGML_QNT_VERSION)?;
+
+    let mut w = GgufStreamWriter::new(meta, out)?;
+    let mut targets = Vec::new();
+    for info in file.tensor_infos() {
+        let (Some(from), Some(to)) = (info.ty, plan.target(&info)) else {
+            return Err(Error::InvalidFormat(format!(
+                "tensor {:?} has an unknown type",
+                info.name
+            )));
+        };
+        w.declare_tensor(&info.name, to, &info.ne[..info.n_dims.max(1)])?;
+        targets.push((info, from, to));
+    }
+
+    let mut done = Vec::new();
+    for (info, from, to) in targets {
+        let data = file.read_tensor_data(&info.name)?;
+        let data = match to {
+            _ if to == from => data,
+            Type::F32 | Type::F16 | Type::BF16 => dequantize_to(from, &data, to)?,
+            _ => quantize(to, &dequantize(from, &data)?, info.ne[0] as usize, None)?,
+        };
+        done.push(Requantized {
+            size_in: info.size,
+            size_out: data.len(),
+            name: info.name,
+            from,
+            to,
+        });
+        w.write_tensor_chunks(&done[done.len() - 1].name, [data])?;
+    }
+    w.finish()?;
+    Ok(done)
+}
@@ -0,0 +1,137 @@
+//! Dequantizing one row at a time.
+
+use std::marker::PhantomData;
+
+use super::convert::{check_dequantize, dequantize_into};
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Iterator over the rows of a tensor as `f32` values, dequantizing each
+/// row as it is reached so only one row is held in memory.
+///
+/// Rows are visited in `ne[1]`, then `ne[2]`, then `ne[3]` order. With a
+/// memory-mapped [`GgufModel`](crate::GgufModel), the OS pages the data in
+/// as it is read:
+///
+/// ```ignore
+/// let model = GgufModel::open("model.gguf")?;
+/// let mut row = Vec::new();
+/// let mut rows = model.get_tensor("token_embd.weight").unwrap().dequant_rows()?;
+/// while rows.next_into(&mut row) {
+///     writeln!(out, "{:?}", row)?;
+/// }
+/// ```
+pub struct DequantRows<'a> {
+    ty: Type,
+    base: *const u8,
+    ne: [i64; 4],
+    nb: [usize; 4],
+    /// Index of the next row.
+    row: i64,
+    n_rows: i64,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> DequantRows<'a> {
+    /// Rows of `n_per_row` values stored densely in `data`, of type `ty`.
+    pub fn from_bytes(ty: Type, data: &'a [u8], n_per_row: usize) -> Result<Self> {
+        if n_per_row == 0 || !n_per_row.is_multiple_of(ty.block_size()) {
+            return Err(Error::ShapeMismatch(format!(
+                "rows of {} values do not split into {} blocks of {}",
+                n_per_row,
+                ty,
+                ty.block_size()
+            )));
+        }
+        let row_size = ty.row_size(n_per_row as i64);
+        check_dequantize(ty, row_size)?;
+        if !data.len().is_multiple_of(row_size) {
+            return Err(Error::ShapeMismatch(format!(
+                "{} bytes are not whole rows of {} bytes",
+                data.len(),
+                row_size
+            )));
+        }
+        let n_rows = (data.len() / row_size) as i64;
+        Ok(DequantRows {
+            ty,
+            base: data.as_ptr(),
+            ne: [n_per_row as i64, n_rows, 1, 1],
+            nb: [
+                ty.type_size(),
+                row_size,
+                row_size * n_rows as usize,
+                row_size * n_rows as usize,
+            ],
+            row: 0,
+            n_rows,
+            _data: PhantomData,
+        })
+    }
+
+    /// Number of values in each row.
+    pub fn row_len(&self) -> usize {
+        self.ne[0] as usize
+    }
+
+    /// Dequantize the next row into `row`, resized to [`row_len`](Self::row_len)
+    /// so its allocation is reused. Returns `false` after the last row.
+    pub fn next_into(&mut self, row: &mut Vec<f32>) -> bool {
+        if self.row >= self.n_rows {
+            return false;
+        }
+        let i1 = self.row % self.ne[1];
+        let i2 = self.row / self.ne[1] % self.ne[2];
+        let i3 = self.row / (self.ne[1] * self.ne[2]);
+        let offset = i1 as usize * self.nb[1] + i2 as usize * self.nb[2] + i3 as usize * self.nb[3];
+        let size = self.ty.row_size(self.ne[0]);
+        // the constructors checked that every row lies in the borrowed data
+        let src = unsafe { std::slice::from_raw_parts(self.base.add(offset), size) };
+        row.resize(self.row_len(), 0.0);
+        dequantize_into(self.ty, src, row);
+        self.row += 1;
+        true
+    }
+}
+
+impl Iterator for DequantRows<'_> {
+    type Item = Vec<f32>;
+
+    fn next(&mut self) -> Option<Vec<f32>> {
+        let mut row = Vec::new();
+        self.next_into(&mut row).then_some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.n_rows - self.row) as usize;
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for DequantRows<'_> {}
+
+impl<'a> Tensor<'a> {
+    /// Iterate over the rows of the tensor as `f32` values; see
+    /// [`DequantRows`]. Rows must be densely packed, but the tensor may be
+    /// otherwise strided.
+    pub fn dequant_rows(&self) -> Result<DequantRows<'a>> {
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        let ty = self.ty();
+        if self.nb()[0] != ty.type_size() {
+            return Err(Error::NotContiguous);
+        }
+        check_dequantize(ty, ty.row_size(self.ne()[0]))?;
+        Ok(DequantRows {
+            ty,
+            base: self.data() as *const u8,
+            ne: self.ne(),
+            nb: self.nb(),
+            row: 0,
+            n_rows: self.nrows(),
+            _data: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! ggml element types as Rust types.
+
+use super::convert::{dequantize, quantize};
+use crate::error::Result;
+use crate::types::Type;
+
+impl Type {
+    /// Type the CPU backend converts the other operand of a matrix
+    /// multiplication with this type to (e.g. Q8_K for Q4_K); `None` if
+    /// the CPU backend cannot multiply this type.
+    pub fn vec_dot_type(self) -> Option<Type> {
+        let traits = unsafe { &*crate::ggml_get_type_traits_cpu(self.as_raw()) };
+        traits.vec_dot.and(Type::from_raw(traits.vec_dot_type))
+    }
+}
+
+/// A ggml element type as a Rust type, for code generic over formats.
+///
+/// The layout constants are fixed by ggml's block formats, so they can
+/// size buffers at compile time:
+///
+/// ```ignore
+/// fn blocks<Q: QuantType>(n: usize) -> usize {
+///     n.div_ceil(Q::BLOCK_SIZE)
+/// }
+/// let row = [0u8; quant::Q4_K::TYPE_SIZE * 16];
+/// ```
+pub trait QuantType {
+    const TYPE: Type;
+    /// Values per block.
+    const BLOCK_SIZE: usize;
+    /// Bytes per block.
+    const TYPE_SIZE: usize;
+    /// Whether this is a block-quantized type.
+    const IS_QUANTIZED: bool;
+    /// Whether quantizing to this type needs an importance matrix.
+    const REQUIRES_IMATRIX: bool;
+
+    /// Bytes in a row of `n` values; `n` must be a multiple of
+    /// [`BLOCK_SIZE`](Self::BLOCK_SIZE).
+    fn row_size(n: usize) -> usize {
+        n / Self::BLOCK_SIZE * Self::TYPE_SIZE
+    }
+
+    /// Whether the CPU backend can multiply matrices of this type; see
+    /// [`Type::vec_dot_type`].
+    fn supports_cpu_mul_mat() -> bool {
+        Self::TYPE.vec_dot_type().is_some()
+    }
+
+    /// [`quantize`] rows of `n_per_row` values, without an importance matrix.
+    fn quantize(src: &[f32], n_per_row: usize) -> Result<Vec<u8>> {
+        quantize(Self::TYPE, src, n_per_row, None)
+    }
+
+    /// [`quantize`] rows of `n_per_row` values weighted by `imatrix`.
+    fn quantize_with_imatrix(src: &[f32], n_per_row: usize, imatrix: &[f32]) -> Result<Vec<u8>> {
+        quantize(Self::TYPE, src, n_per_row, Some(imatrix))
+    }
+
+    /// [`dequantize`] whole blocks.
+    fn dequantize(src: &[u8]) -> Result<Vec<f32>> {
+        dequantize(Self::TYPE, src)
+    }
+}
+
+macro_rules! quant_types {
+    ($($name:ident: $block:literal values in $size:literal bytes $(, $flag:ident)*;)*) => {
+        $(
+            #[doc = concat!("[`Type::", stringify!($name), "`] as a [`QuantType`].")]
+            #[allow(non_camel_case_types)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct $name;
+
+            impl QuantType for $name {
+                const TYPE: Type = Type::$name;
+                const BLOCK_SIZE: usize = $block;
+                const TYPE_SIZE: usize = $size;
+                const IS_QUANTIZED: bool = $block > 1;
+                const REQUIRES_IMATRIX: bool = quant_types!(@imatrix $($flag)*);
+            }
+        )*
+    };
+    (@imatrix imatrix) => { true };
+    (@imatrix) => { false };
+}
+
+// block sizes from the block_* structs of ggml-common.h
+quant_types! {
+    F32: 1 values in 4 bytes;
+    F16: 1 values in 2 bytes;
+    BF16: 1 values in 2 bytes;
+    Q4_0: 32 values in 18 bytes;
+    Q4_1: 32 values in 20 bytes;
+    Q5_0: 32 values in 22 bytes;
+    Q5_1: 32 values in 24 bytes;
+    Q8_0: 32 values in 34 bytes;
+    Q2_K: 256 values in 84 bytes;
+    Q3_K: 256 values in 110 bytes;
+    Q4_K: 256 values in 144 bytes;
+    Q5_K: 256 values in 176 bytes;
+    Q6_K: 256 values in 210 bytes;
+    IQ2_XXS: 256 values in 66 bytes, imatrix;
+    IQ2_XS: 256 values in 74 bytes, imatrix;
+    IQ2_S: 256 values in 82 bytes;
+    IQ3_XXS: 256 values in 98 bytes;
+    IQ3_S: 256 values in 110 bytes;
+    IQ1_S: 256 values in 50 bytes, imatrix;
+    IQ1_M: 256 values in 56 bytes;
+    IQ4_NL: 32 values in 18 bytes;
+    IQ4_XS: 256 values in 136 bytes;
+    TQ1_0: 256 values in 54 bytes;
+    TQ2_0: 256 values in 66 bytes;
+    MXFP4: 32 values in 17 bytes;
+}
@@ -0,0 +1,118 @@
+//! Seeded random initialization of F32, F16 and BF16 tensors.
+//!
+//! [`Rng`] is a small xoshiro256++ generator, so fixtures are reproducible
+//! across platforms and runs without pulling in a dependency. Values are
+//! generated as `f32` and converted to the tensor type.
+//!
+//! Fan-in and fan-out follow ggml's weight layout for
+//! [`Context::mul_mat`](crate::Context::mul_mat): a weight of shape
+//! `[n_in, n_out, ...]` has fan-in `ne[0]` and fan-out `ne[1]`; higher
+//! dimensions are treated as a stack of independent matrices.
+
+use crate::error::Result;
+use crate::tensor::Tensor;
+
+/// Deterministic pseudo-random number generator (xoshiro256++).
+#[derive(Debug, Clone)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    /// Seed the generator; equal seeds produce equal streams.
+    pub fn new(seed: u64) -> Self {
+        // expand the seed with splitmix64, which never yields an all-zero state
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Rng {
+            s: [next(), next(), next(), next()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+    }
+
+    /// Uniform in `[low, high)`.
+    pub fn uniform(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.next_f32()
+    }
+
+    /// Normally distributed (Box-Muller).
+    pub fn normal(&mut self, mean: f32, std: f32) -> f32 {
+        // 1 - u keeps the log argument in (0, 1]
+        let u1 = 1.0 - self.next_f32();
+        let u2 = self.next_f32();
+        let z = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+        mean + std * z
+    }
+}
+
+fn fans(t: &Tensor<'_>) -> (f32, f32) {
+    let ne = t.ne();
+    (ne[0] as f32, ne[1] as f32)
+}
+
+impl Tensor<'_> {
+    fn fill_with(&self, mut f: impl FnMut() -> f32) -> Result<()> {
+        let values: Vec<f32> = (0..self.nelements()).map(|_| f()).collect();
+        self.write_f32(&values)
+    }
+
+    /// Fill with values uniform in `[low, high)`.
+    pub fn fill_uniform(&self, rng: &mut Rng, low: f32, high: f32) -> Result<()> {
+        self.fill_with(|| rng.uniform(low, high))
+    }
+
+    /// Fill with normally distributed values.
+    pub fn fill_normal(&self, rng: &mut Rng, mean: f32, std: f32) -> Result<()> {
+        self.fill_with(|| rng.normal(mean, std))
+    }
+
+    /// Glorot/Xavier uniform: `U(-a, a)` with `a = sqrt(6 / (fan_in + fan_out))`.
+    pub fn fill_xavier_uniform(&self, rng: &mut Rng) -> Result<()> {
+        let (fan_in, fan_out) = fans(self);
+        let a = (6.0 / (fan_in + fan_out)).sqrt();
+        self.fill_uniform(rng, -a, a)
+    }
+
+    /// Glorot/Xavier normal: `N(0, 2 / (fan_in + fan_out))`.
+    pub fn fill_xavier_normal(&self, rng: &mut Rng) -> Result<()> {
+        let (fan_in, fan_out) = fans(self);
+        self.fill_normal(rng, 0.0, (2.0 / (fan_in + fan_out)).sqrt())
+    }
+
+    /// He/Kaiming uniform for ReLU layers: `U(-a, a)` with
+    /// `a = sqrt(6 / fan_in)`.
+    pub fn fill_kaiming_uniform(&self, rng: &mut Rng) -> Result<()> {
+        let (fan_in, _) = fans(self);
+        let a = (6.0 / fan_in).sqrt();
+        self.fill_uniform(rng, -a, a)
+    }
+
+    /// He/Kaiming normal for ReLU layers: `N(0, 2 / fan_in)`.
+    pub fn fill_kaiming_normal(&self, rng: &mut Rng) -> Result<()> {
+        let (fan_in, _) = fans(self);
+        self.fill_normal(rng, 0.0, (2.0 / fan_in).sqrt())
+    }
+}
@@ -0,0 +1,216 @@
+//! Pure-Rust F32 implementations of a few ops, for checking ggml results
+//! without a second backend and for CI machines that cannot build ggml.
+//!
+//! Data is a flat slice laid out with shape `ne` (`ne[0]` fastest, unused
+//! dimensions 1), as in `testing::compare_slices`. Each function follows
+//! the semantics of the `Context` op of the same name, including
+//! broadcasting, and accumulates in f64 so it can serve as ground truth.
+//! None of this needs the `native` feature.
+
+use crate::error::{Error, Result};
+
+/// Number of elements of shape `ne`.
+pub fn nelements(ne: [i64; 4]) -> usize {
+    ne.iter().map(|&n| n.max(0) as usize).product()
+}
+
+fn check(data: &[f32], ne: [i64; 4], what: &str) -> Result<()> {
+    if ne.iter().any(|&n| n < 1) {
+        return Err(Error::InvalidArgument(format!(
+            "{} shape {:?} has an empty dimension",
+            what, ne
+        )));
+    }
+    if data.len() != nelements(ne) {
+        return Err(Error::ShapeMismatch(format!(
+            "{} has {} values for shape {:?}",
+            what,
+            data.len(),
+            ne
+        )));
+    }
+    Ok(())
+}
+
+fn index(ne: [i64; 4], i0: usize, i1: usize, i2: usize, i3: usize) -> usize {
+    let (n0, n1, n2) = (ne[0] as usize, ne[1] as usize, ne[2] as usize);
+    ((i3 * n2 + i2) * n1 + i1) * n0 + i0
+}
+
+/// `a + b`, with `b` broadcast to the shape of `a`: every `a_ne[i]` must be
+/// a multiple of `b_ne[i]`. The result has shape `a_ne`.
+pub fn add(a: &[f32], a_ne: [i64; 4], b: &[f32], b_ne: [i64; 4]) -> Result<Vec<f32>> {
+    check(a, a_ne, "a")?;
+    check(b, b_ne, "b")?;
+    if a_ne.iter().zip(&b_ne).any(|(&na, &nb)| na % nb != 0) {
+        return Err(Error::ShapeMismatch(format!(
+            "cannot broadcast {:?} to {:?}",
+            b_ne, a_ne
+        )));
+    }
+    let b_at = |i: [usize; 4]| {
+        let [i0, i1, i2, i3] = i;
+        b[index(
+            b_ne,
+            i0 % b_ne[0] as usize,
+            i1 % b_ne[1] as usize,
+            i2 % b_ne[2] as usize,
+            i3 % b_ne[3] as usize,
+        )]
+    };
+    let mut out = Vec::with_capacity(a.len());
+    for i3 in 0..a_ne[3] as usize {
+        for i2 in 0..a_ne[2] as usize {
+            for i1 in 0..a_ne[1] as usize {
+                for i0 in 0..a_ne[0] as usize {
+                    out.push(a[index(a_ne, i0, i1, i2, i3)] + b_at([i0, i1, i2, i3]));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Shape of [`mul_mat`]'s result: `[a_ne[1], b_ne[1], b_ne[2], b_ne[3]]`.
+pub fn mul_mat_ne(a_ne: [i64; 4], b_ne: [i64; 4]) -> [i64; 4] {
+    [a_ne[1], b_ne[1], b_ne[2], b_ne[3]]
+}
+
+/// Matrix product `b * a^T`: `a` is `[k, m]`, `b` is `[k, n]`, the result is
+/// `[m, n]`, batched over dims 2 and 3 with `a` broadcast (`b_ne[2]` and
+/// `b_ne[3]` must be multiples of `a_ne[2]` and `a_ne[3]`).
+pub fn mul_mat(a: &[f32], a_ne: [i64; 4], b: &[f32], b_ne: [i64; 4]) -> Result<Vec<f32>> {
+    check(a, a_ne, "a")?;
+    check(b, b_ne, "b")?;
+    if a_ne[0] != b_ne[0] || b_ne[2] % a_ne[2] != 0 || b_ne[3] % a_ne[3] != 0 {
+        return Err(Error::ShapeMismatch(format!(
+            "mul_mat of {:?} and {:?}",
+            a_ne, b_ne
+        )));
+    }
+    let k = a_ne[0] as usize;
+    let (m, n) = (a_ne[1] as usize, b_ne[1] as usize);
+    let r2 = (b_ne[2] / a_ne[2]) as usize;
+    let r3 = (b_ne[3] / a_ne[3]) as usize;
+    let mut out = Vec::with_capacity(nelements(mul_mat_ne(a_ne, b_ne)));
+    for i3 in 0..b_ne[3] as usize {
+        for i2 in 0..b_ne[2] as usize {
+            for j in 0..n {
+                let row_b = &b[index(b_ne, 0, j, i2, i3)..][..k];
+                for i in 0..m {
+                    let row_a = &a[index(a_ne, 0, i, i2 / r2, i3 / r3)..][..k];
+                    let dot: f64 = row_a
+                        .iter()
+                        .zip(row_b)
+                        .map(|(&x, &y)| x as f64 * y as f64)
+                        .sum();
+                    out.push(dot as f32);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Softmax along rows (dim 0).
+pub fn soft_max(a: &[f32], ne: [i64; 4]) -> Result<Vec<f32>> {
+    check(a, ne, "a")?;
+    let mut out = Vec::with_capacity(a.len());
+    for row in a.chunks_exact(ne[0] as usize) {
+        let max = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let exp: Vec<f64> = row.iter().map(|&x| ((x - max) as f64).exp()).collect();
+        let sum: f64 = exp.iter().sum();
+        out.extend(exp.iter().map(|&e| (e / sum) as f32));
+    }
+    Ok(out)
+}
+
+/// Scale each row by the inverse of its root mean square:
+/// `x / sqrt(mean(x^2) + eps)`.
+pub fn rms_norm(a: &[f32], ne: [i64; 4], eps: f32) -> Result<Vec<f32>> {
+    check(a, ne, "a")?;
+    let mut out = Vec::with_capacity(a.len());
+    for row in a.chunks_exact(ne[0] as usize) {
+        let mean = row.iter().map(|&x| x as f64 * x as f64).sum::<f64>() / row.len() as f64;
+        let scale = 1.0 / (mean + eps as f64).sqrt();
+        out.extend(row.iter().map(|&x| (x as f64 * scale) as f32));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(got: &[f32], expected: &[f32]) {
+        assert_eq!(got.len(), expected.len());
+        for (g, e) in got.iter().zip(expected) {
+            assert!((g - e).abs() < 1e-6, "{:?} != {:?}", got, expected);
+        }
+    }
+
+    #[test]
+    fn add_broadcasts() {
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let out = add(&a, [3, 2, 1, 1], &[10.0, 20.0, 30.0], [3, 1, 1, 1]).unwrap();
+        assert_eq!(out, [11.0, 22.0, 33.0, 14.0, 25.0, 36.0]);
+        let out = add(&a, [3, 2, 1, 1], &[100.0, 200.0], [1, 2, 1, 1]).unwrap();
+        assert_eq!(out, [101.0, 102.0, 103.0, 204.0, 205.0, 206.0]);
+        assert!(add(&a, [3, 2, 1, 1], &[1.0, 2.0], [2, 1, 1, 1]).is_err());
+    }
+
+    #[test]
+    fn mul_mat_known_answer() {
+        let a = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let out = mul_mat(&a, [3, 2, 1, 1], &[1.0, 1.0, 1.0], [3, 1, 1, 1]).unwrap();
+        assert_eq!(mul_mat_ne([3, 2, 1, 1], [3, 1, 1, 1]), [2, 1, 1, 1]);
+        assert_eq!(out, [6.0, 15.0]);
+        // two columns of b, and a broadcast over a batch of 2
+        let b = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+        let out = mul_mat(&a, [3, 2, 1, 1], &b, [3, 2, 2, 1]).unwrap();
+        assert_eq!(out, [1.0, 4.0, 2.0, 5.0, 3.0, 6.0, 6.0, 15.0]);
+        assert!(mul_mat(&a, [3, 2, 1, 1], &[1.0, 1.0], [2, 1, 1, 1]).is_err());
+    }
+
+    #[test]
+    fn soft_max_rows() {
+        let out = soft_max(&[1.0, 2.0, 3.0, 0.0, 0.0, 0.0], [3, 2, 1, 1]).unwrap();
+        let third = 1.0 / 3.0;
+        assert_close(
+            &out,
+            &[
+                0.090_030_57,
+                0.244_728_48,
+                0.665_240_94,
+                third,
+                third,
+                third,
+            ],
+        );
+        // large inputs do not overflow
+        let out = soft_max(&[1000.0, 1000.0], [2, 1, 1, 1]).unwrap();
+        assert_close(&out, &[0.5, 0.5]);
+    }
+
+    #[test]
+    fn rms_norm_rows() {
+        let out = rms_norm(&[3.0, 4.0, 1.0, 1.0], [2, 2, 1, 1], 0.0).unwrap();
+        // rms of (3, 4) is sqrt(12.5)
+        let r = 12.5f32.sqrt();
+        assert_close(&out, &[3.0 / r, 4.0 / r, 1.0, 1.0]);
+        let out = rms_norm(&[0.0, 0.0], [2, 1, 1, 1], 1e-6).unwrap();
+        assert_close(&out, &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_bad_shapes() {
+        assert!(matches!(
+            soft_max(&[1.0; 5], [3, 2, 1, 1]),
+            Err(Error::ShapeMismatch(_))
+        ));
+        assert!(matches!(
+            rms_norm(&[], [0, 1, 1, 1], 0.0),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
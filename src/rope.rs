@@ -0,0 +1,229 @@
+//! Sectioned rotary position embeddings (`ggml_rope_multi`) for
+//! vision-language models such as Qwen2-VL.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Frequency and YaRN settings shared by the rope ops. The defaults are
+/// plain RoPE with base 10000 and no context extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RopeParams {
+    /// Training context length, used by YaRN scaling; 0 when unused.
+    pub n_ctx_orig: usize,
+    pub freq_base: f32,
+    pub freq_scale: f32,
+    /// YaRN extrapolation mix factor; 0 disables YaRN.
+    pub ext_factor: f32,
+    pub attn_factor: f32,
+    pub beta_fast: f32,
+    pub beta_slow: f32,
+}
+
+impl Default for RopeParams {
+    fn default() -> Self {
+        RopeParams {
+            n_ctx_orig: 0,
+            freq_base: 10000.0,
+            freq_scale: 1.0,
+            ext_factor: 0.0,
+            attn_factor: 1.0,
+            beta_fast: 32.0,
+            beta_slow: 1.0,
+        }
+    }
+}
+
+impl RopeParams {
+    pub fn freq_base(mut self, freq_base: f32) -> Self {
+        self.freq_base = freq_base;
+        self
+    }
+
+    pub fn freq_scale(mut self, freq_scale: f32) -> Self {
+        self.freq_scale = freq_scale;
+        self
+    }
+
+    /// Enable YaRN context extension for a model trained on `n_ctx_orig`
+    /// tokens.
+    pub fn yarn(
+        mut self,
+        n_ctx_orig: usize,
+        ext_factor: f32,
+        beta_fast: f32,
+        beta_slow: f32,
+    ) -> Self {
+        self.n_ctx_orig = n_ctx_orig;
+        self.ext_factor = ext_factor;
+        self.beta_fast = beta_fast;
+        self.beta_slow = beta_slow;
+        self
+    }
+
+    pub fn attn_factor(mut self, attn_factor: f32) -> Self {
+        self.attn_factor = attn_factor;
+        self
+    }
+}
+
+/// How many rotary dimension pairs each position component rotates.
+///
+/// Positions for `rope_multi` come in four streams (temporal, height, width
+/// and an extra one); section `i` of the rotated dimensions uses stream `i`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MropeSections {
+    pub temporal: u32,
+    pub height: u32,
+    pub width: u32,
+    pub extra: u32,
+}
+
+impl MropeSections {
+    pub fn new(temporal: u32, height: u32, width: u32, extra: u32) -> Self {
+        MropeSections {
+            temporal,
+            height,
+            width,
+            extra,
+        }
+    }
+
+    /// Total dimension pairs covered by all sections.
+    pub fn total(&self) -> u32 {
+        self.temporal + self.height + self.width + self.extra
+    }
+
+    fn to_raw(self) -> Result<[i32; crate::GGML_MROPE_SECTIONS as usize]> {
+        let raw = [self.temporal, self.height, self.width, self.extra];
+        if raw[..3].iter().all(|&s| s == 0) {
+            return Err(Error::InvalidArgument(
+                "rope_multi: one of the temporal, height and width sections must be non-zero"
+                    .to_string(),
+            ));
+        }
+        let mut out = [0; crate::GGML_MROPE_SECTIONS as usize];
+        for (o, s) in out.iter_mut().zip(raw) {
+            *o = i32::try_from(s).map_err(|_| {
+                Error::InvalidArgument(format!("rope_multi: section {} exceeds i32", s))
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+/// Variant of multi-section rope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MropeMode {
+    /// `GGML_ROPE_TYPE_MROPE`: text and image tokens in a language model.
+    #[default]
+    Mrope,
+    /// `GGML_ROPE_TYPE_VISION`: 2-D rope in a vision encoder, where `n_dims`
+    /// must be half the head size.
+    Vision,
+}
+
+impl MropeMode {
+    fn as_raw(self) -> i32 {
+        match self {
+            MropeMode::Mrope => crate::GGML_ROPE_TYPE_MROPE as i32,
+            MropeMode::Vision => crate::GGML_ROPE_TYPE_VISION as i32,
+        }
+    }
+}
+
+impl Context {
+    /// Multi-section rotary embedding.
+    ///
+    /// - `a`: F32 or F16 `[head_dim, n_head, n_tokens, ...]`
+    /// - `pos`: I32 vector of `4 * n_tokens` positions, laid out as all
+    ///   temporal positions, then all height, width and extra positions
+    /// - `freq_factors`: optional F32 per-dimension frequency divisors with at
+    ///   least `n_dims / 2` entries
+    /// - `n_dims`: number of leading dimensions of each head that are rotated
+    #[allow(clippy::too_many_arguments)]
+    pub fn rope_multi<'a>(
+        &'a self,
+        a: Tensor<'a>,
+        pos: Tensor<'a>,
+        freq_factors: Option<Tensor<'a>>,
+        n_dims: usize,
+        sections: MropeSections,
+        mode: MropeMode,
+        params: &RopeParams,
+    ) -> Result<Tensor<'a>> {
+        if !matches!(a.ty(), Type::F32 | Type::F16) {
+            return Err(Error::TypeMismatch {
+                expected: "rope_multi input F32 or F16".to_string(),
+                found: a.ty().to_string(),
+            });
+        }
+        if pos.ty() != Type::I32 {
+            return Err(Error::TypeMismatch {
+                expected: Type::I32.to_string(),
+                found: pos.ty().to_string(),
+            });
+        }
+        let (an, pn) = (a.ne(), pos.ne());
+        if pn[1..] != [1, 1, 1] || pn[0] != 4 * an[2] {
+            return Err(Error::ShapeMismatch(format!(
+                "rope_multi needs {} positions (4 per token) for {:?}, got {:?}",
+                4 * an[2],
+                an,
+                pn
+            )));
+        }
+        let head_dim = an[0] as usize;
+        let dims_ok = match mode {
+            MropeMode::Mrope => n_dims <= head_dim,
+            MropeMode::Vision => 2 * n_dims == head_dim,
+        };
+        if !dims_ok || !n_dims.is_multiple_of(2) {
+            return Err(Error::InvalidArgument(format!(
+                "rope_multi: n_dims = {} is invalid for head size {} in {:?} mode",
+                n_dims, head_dim, mode
+            )));
+        }
+        if let Some(c) = freq_factors {
+            if c.ty() != Type::F32 {
+                return Err(Error::TypeMismatch {
+                    expected: Type::F32.to_string(),
+                    found: c.ty().to_string(),
+                });
+            }
+            if (c.ne()[0] as usize) < n_dims / 2 {
+                return Err(Error::ShapeMismatch(format!(
+                    "rope_multi needs at least {} frequency factors, got {}",
+                    n_dims / 2,
+                    c.ne()[0]
+                )));
+            }
+        }
+        let mut sections = sections.to_raw()?;
+        let n_ctx_orig = i32::try_from(params.n_ctx_orig).map_err(|_| {
+            Error::InvalidArgument(format!("n_ctx_orig = {} exceeds i32", params.n_ctx_orig))
+        })?;
+
+        self.ensure_capacity(a.nbytes())?;
+        let c = freq_factors.map_or(std::ptr::null_mut(), |c| c.as_ptr());
+        self.op_result("ggml_rope_multi", unsafe {
+            crate::ggml_rope_multi(
+                self.as_ptr(),
+                a.as_ptr(),
+                pos.as_ptr(),
+                c,
+                n_dims as i32,
+                sections.as_mut_ptr(),
+                mode.as_raw(),
+                n_ctx_orig,
+                params.freq_base,
+                params.freq_scale,
+                params.ext_factor,
+                params.attn_factor,
+                params.beta_fast,
+                params.beta_slow,
+            )
+        })
+    }
+}
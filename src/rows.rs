@@ -0,0 +1,191 @@
+//! Parallel iteration over the rows of host tensors on the rayon thread
+//! pool.
+//!
+//! [`Tensor::par_rows`] yields one `ne[0]`-element slice per row, in
+//! `ne[1]`-fastest order, and works on strided views as long as each row
+//! is packed. [`Tensor::par_chunks`] yields several contiguous rows at a
+//! time, for work small enough that one task per row costs more than it
+//! saves. Type, host-memory and stride checks run once, when the iterator
+//! is created:
+//!
+//! ```ignore
+//! let mut emb = out.into_mut();
+//! unsafe { emb.par_rows_mut::<f32>()? }.for_each(|row| {
+//!     let norm = row.iter().map(|v| v * v).sum::<f32>().sqrt();
+//!     row.iter_mut().for_each(|v| *v /= norm);
+//! });
+//! ```
+//!
+//! The iterators are `unsafe`: [`Tensor`] is `Copy` and [`TensorMut`] is not
+//! unique, so other handles to the tensor may write its data while the rows
+//! are borrowed.
+
+use rayon::prelude::*;
+
+use crate::error::{Error, Result};
+use crate::inplace::TensorMut;
+use crate::tensor::Tensor;
+use crate::types::Element;
+
+#[derive(Clone, Copy)]
+struct SendPtr(*mut u8);
+
+// only dereferenced for rows the layout checks proved in bounds, and
+// disjoint when written
+unsafe impl Send for SendPtr {}
+unsafe impl Sync for SendPtr {}
+
+/// Where each row of a checked tensor starts.
+#[derive(Clone, Copy)]
+struct RowLayout {
+    base: SendPtr,
+    row_len: usize,
+    ne: [usize; 3],
+    nb: [usize; 3],
+}
+
+impl RowLayout {
+    fn n_rows(&self) -> usize {
+        self.ne.iter().product()
+    }
+
+    fn row_ptr(&self, i: usize) -> *mut u8 {
+        let (i1, i2, i3) = (
+            i % self.ne[0],
+            i / self.ne[0] % self.ne[1],
+            i / (self.ne[0] * self.ne[1]),
+        );
+        unsafe {
+            self.base
+                .0
+                .add(i1 * self.nb[0] + i2 * self.nb[1] + i3 * self.nb[2])
+        }
+    }
+}
+
+impl<'a> Tensor<'a> {
+    /// Check that the tensor holds `T` in host memory with packed rows, and
+    /// with `disjoint` that no two rows share memory (as broadcast views
+    /// with a zero stride would).
+    fn row_layout<T: Element>(&self, disjoint: bool) -> Result<RowLayout> {
+        if self.ty() != T::TYPE {
+            return Err(Error::TypeMismatch {
+                expected: T::TYPE.to_string(),
+                found: self.ty().to_string(),
+            });
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        let (ne, nb) = (self.ne(), self.nb());
+        let size = std::mem::size_of::<T>();
+        if nb[0] != size || !(self.data() as usize | nb[1] | nb[2] | nb[3]).is_multiple_of(size) {
+            return Err(Error::NotContiguous);
+        }
+        let row_len = ne[0] as usize;
+        let ne = [ne[1] as usize, ne[2] as usize, ne[3] as usize];
+        let nb = [nb[1], nb[2], nb[3]];
+        if disjoint {
+            let mut dims: Vec<(usize, usize)> = (0..3)
+                .filter(|&i| ne[i] > 1)
+                .map(|i| (nb[i], ne[i]))
+                .collect();
+            dims.sort_unstable();
+            let mut extent = row_len * size;
+            for (nb, ne) in dims {
+                if nb < extent {
+                    return Err(Error::InvalidArgument(format!(
+                        "rows of a view with strides {:?} overlap",
+                        self.nb()
+                    )));
+                }
+                extent += nb * (ne - 1);
+            }
+        }
+        Ok(RowLayout {
+            base: SendPtr(self.data() as *mut u8),
+            row_len,
+            ne,
+            nb,
+        })
+    }
+
+    fn chunk_len(&self, rows_per_chunk: usize) -> Result<usize> {
+        if rows_per_chunk == 0 || self.ne()[0] == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "chunks of {} rows of {} elements",
+                rows_per_chunk,
+                self.ne()[0]
+            )));
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        Ok(rows_per_chunk * self.ne()[0] as usize)
+    }
+
+    /// Iterate the rows of a host tensor of `T` in parallel.
+    ///
+    /// # Safety
+    /// While the iterator or any row is alive, the tensor's data must not
+    /// be written, through any handle to it or by computing a graph.
+    pub unsafe fn par_rows<T: Element + Sync>(
+        &self,
+    ) -> Result<impl IndexedParallelIterator<Item = &'a [T]> + 'a> {
+        let layout = self.row_layout::<T>(false)?;
+        Ok((0..layout.n_rows()).into_par_iter().map(move |i| unsafe {
+            std::slice::from_raw_parts(layout.row_ptr(i) as *const T, layout.row_len)
+        }))
+    }
+
+    /// Iterate a contiguous host tensor of `T` in parallel, `rows_per_chunk`
+    /// rows at a time (fewer in the last chunk).
+    ///
+    /// # Safety
+    /// As for [`Tensor::par_rows`].
+    pub unsafe fn par_chunks<T: Element + Sync>(
+        &self,
+        rows_per_chunk: usize,
+    ) -> Result<impl IndexedParallelIterator<Item = &'a [T]> + 'a> {
+        let layout = self.row_layout::<T>(false)?;
+        let chunk = self.chunk_len(rows_per_chunk)?;
+        let data = unsafe {
+            std::slice::from_raw_parts(layout.base.0 as *const T, self.nelements() as usize)
+        };
+        Ok(data.par_chunks(chunk))
+    }
+}
+
+impl TensorMut<'_> {
+    /// Like [`Tensor::par_rows`], writable. Views whose rows overlap are
+    /// rejected.
+    ///
+    /// # Safety
+    /// While the iterator or any row is alive, the tensor's data must not
+    /// be read or written through any other handle to it, nor by computing
+    /// a graph.
+    pub unsafe fn par_rows_mut<T: Element + Send>(
+        &mut self,
+    ) -> Result<impl IndexedParallelIterator<Item = &mut [T]> + '_> {
+        let layout = self.row_layout::<T>(true)?;
+        Ok((0..layout.n_rows()).into_par_iter().map(move |i| unsafe {
+            std::slice::from_raw_parts_mut(layout.row_ptr(i) as *mut T, layout.row_len)
+        }))
+    }
+
+    /// Like [`Tensor::par_chunks`], writable.
+    ///
+    /// # Safety
+    /// As for [`TensorMut::par_rows_mut`].
+    pub unsafe fn par_chunks_mut<T: Element + Send>(
+        &mut self,
+        rows_per_chunk: usize,
+    ) -> Result<impl IndexedParallelIterator<Item = &mut [T]> + '_> {
+        let layout = self.row_layout::<T>(true)?;
+        let chunk = self.chunk_len(rows_per_chunk)?;
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(layout.base.0 as *mut T, self.nelements() as usize)
+        };
+        Ok(data.par_chunks_mut(chunk))
+    }
+}
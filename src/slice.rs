@@ -0,0 +1,193 @@
+//! Views from Rust range syntax.
+//!
+//! Arguments follow ggml's dimension order, `ne[0]` (innermost) first, the
+//! same order as [`Context::new_tensor`]:
+//!
+//! ```ignore
+//! // a: [256, 32, 8] -> first 128 columns of rows 4..8 of plane 3: [128, 4]
+//! let v = ctx.slice(a, &s![..128, 4..8, 3])?;
+//! ```
+//!
+//! A range keeps the dimension, an integer index removes it. Negative values
+//! count from the end. Missing trailing arguments select the whole dimension.
+
+use std::ops::{Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+/// One argument of [`s!`]: a range of a dimension or a single index into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceArg {
+    /// `start..end`, `None` meaning the start or end of the dimension.
+    Range(Option<i64>, Option<i64>),
+    Index(i64),
+}
+
+impl From<i64> for SliceArg {
+    fn from(i: i64) -> Self {
+        SliceArg::Index(i)
+    }
+}
+
+impl From<RangeFull> for SliceArg {
+    fn from(_: RangeFull) -> Self {
+        SliceArg::Range(None, None)
+    }
+}
+
+impl From<Range<i64>> for SliceArg {
+    fn from(r: Range<i64>) -> Self {
+        SliceArg::Range(Some(r.start), Some(r.end))
+    }
+}
+
+impl From<RangeFrom<i64>> for SliceArg {
+    fn from(r: RangeFrom<i64>) -> Self {
+        SliceArg::Range(Some(r.start), None)
+    }
+}
+
+impl From<RangeTo<i64>> for SliceArg {
+    fn from(r: RangeTo<i64>) -> Self {
+        SliceArg::Range(None, Some(r.end))
+    }
+}
+
+impl From<RangeInclusive<i64>> for SliceArg {
+    fn from(r: RangeInclusive<i64>) -> Self {
+        let end = if *r.end() == -1 {
+            None
+        } else {
+            Some(r.end() + 1)
+        };
+        SliceArg::Range(Some(*r.start()), end)
+    }
+}
+
+impl From<RangeToInclusive<i64>> for SliceArg {
+    fn from(r: RangeToInclusive<i64>) -> Self {
+        let end = if r.end == -1 { None } else { Some(r.end + 1) };
+        SliceArg::Range(None, end)
+    }
+}
+
+/// Build slice arguments for [`Context::slice`], e.g. `s![.., 0..128, 3]`.
+#[macro_export]
+macro_rules! s {
+    ($($arg:expr),* $(,)?) => {
+        [$($crate::SliceArg::from($arg)),*]
+    };
+}
+
+fn resolve(i: i64, n: i64, dim: usize) -> Result<i64> {
+    let r = if i < 0 { i + n } else { i };
+    if (0..=n).contains(&r) {
+        Ok(r)
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "slice index {} out of bounds for dimension {} of size {}",
+            i, dim, n
+        )))
+    }
+}
+
+impl Context {
+    /// A view of part of `a`, selected by one [`SliceArg`] per dimension.
+    ///
+    /// Ranges on `ne[0]` of a quantized tensor must be aligned to the block
+    /// size, and cannot be indexed.
+    pub fn slice<'a>(&'a self, a: Tensor<'a>, args: &[SliceArg]) -> Result<Tensor<'a>> {
+        let max_dims = crate::GGML_MAX_DIMS as usize;
+        if args.len() > max_dims {
+            return Err(Error::InvalidArgument(format!(
+                "{} slice arguments for a tensor of at most {} dimensions",
+                args.len(),
+                max_dims
+            )));
+        }
+        let (ne, nb) = (a.ne(), a.nb());
+        let blck = a.ty().block_size() as i64;
+
+        let mut view_ne = ne;
+        let mut offset = 0usize;
+        let mut kept = Vec::with_capacity(max_dims);
+        let mut dropped = Vec::new();
+        for d in 0..max_dims {
+            let (start, len) = match args.get(d).copied().unwrap_or(SliceArg::Range(None, None)) {
+                SliceArg::Index(i) => {
+                    let i = resolve(i, ne[d], d)?;
+                    if i == ne[d] {
+                        return Err(Error::InvalidArgument(format!(
+                            "slice index {} out of bounds for dimension {} of size {}",
+                            i, d, ne[d]
+                        )));
+                    }
+                    dropped.push(d);
+                    (i, 1)
+                }
+                SliceArg::Range(start, end) => {
+                    let start = resolve(start.unwrap_or(0), ne[d], d)?;
+                    let end = resolve(end.unwrap_or(ne[d]), ne[d], d)?;
+                    if end < start {
+                        return Err(Error::InvalidArgument(format!(
+                            "empty slice {}..{} of dimension {}",
+                            start, end, d
+                        )));
+                    }
+                    kept.push(d);
+                    (start, end - start)
+                }
+            };
+            if d == 0 && blck > 1 && (start % blck != 0 || len % blck != 0 || dropped.contains(&0))
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "slices of ne[0] of a {} tensor must be aligned to its block size {}",
+                    a.ty(),
+                    blck
+                )));
+            }
+            view_ne[d] = len;
+            offset += if d == 0 {
+                (start / blck) as usize * nb[0]
+            } else {
+                start as usize * nb[d]
+            };
+        }
+
+        self.ensure_capacity(0)?;
+        let view = self.op_result("ggml_view_4d", unsafe {
+            crate::ggml_view_4d(
+                self.as_ptr(),
+                a.as_ptr(),
+                view_ne[0],
+                view_ne[1],
+                view_ne[2],
+                view_ne[3],
+                nb[1],
+                nb[2],
+                nb[3],
+                offset,
+            )
+        })?;
+
+        // move indexed (size 1) dimensions behind the kept ones
+        let order: Vec<usize> = kept.into_iter().chain(dropped).collect();
+        if order.iter().enumerate().all(|(i, &d)| i == d) {
+            return Ok(view);
+        }
+        let mut axes = [0; 4];
+        for (pos, &d) in order.iter().enumerate() {
+            axes[d] = pos;
+        }
+        self.permute(view, axes)
+    }
+}
+
+impl<'a> Tensor<'a> {
+    /// See [`Context::slice`].
+    pub fn slice(self, ctx: &'a Context, args: &[SliceArg]) -> Result<Tensor<'a>> {
+        ctx.slice(self, args)
+    }
+}
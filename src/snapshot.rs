@@ -0,0 +1,185 @@
+//! Serde-serializable snapshots of tensors and contexts, for checkpointing
+//! state (optimizer moments, KV caches, RNN states) across process
+//! restarts with any serde format, e.g. bincode or CBOR.
+//!
+//! A snapshot keeps each tensor's name, type, shape and data; graph
+//! structure (ops and sources) is not part of it. Tensors are read from and
+//! written to wherever they live, host or device memory.
+//!
+//! ```ignore
+//! let snap = state_ctx.snapshot()?;
+//! std::fs::write("state.bin", bincode::serialize(&snap)?)?;
+//!
+//! // after a restart, into the same (device-allocated) tensors
+//! let snap: ContextSnapshot = bincode::deserialize(&std::fs::read("state.bin")?)?;
+//! snap.restore_into(&state_ctx)?;
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// A type serializes as its name (e.g. `"q4_K"`), stable across ggml
+/// versions where the enum values are not.
+mod type_name {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::types::Type;
+
+    pub fn serialize<S: Serializer>(ty: &Type, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(ty.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Type, D::Error> {
+        let name = String::deserialize(d)?;
+        Type::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown ggml type {}", name)))
+    }
+}
+
+/// One tensor's name, type, shape and data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TensorSnapshot {
+    pub name: String,
+    #[serde(with = "type_name")]
+    pub ty: Type,
+    pub ne: [i64; 4],
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl TensorSnapshot {
+    fn check_fits(&self, t: &Tensor<'_>) -> Result<()> {
+        if t.ty() != self.ty {
+            return Err(Error::TypeMismatch {
+                expected: t.ty().to_string(),
+                found: self.ty.to_string(),
+            });
+        }
+        if t.ne() != self.ne {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot restore a snapshot of {:?} into {:?}",
+                self.ne,
+                t.ne()
+            )));
+        }
+        if self.data.len() != t.nbytes() {
+            return Err(Error::InvalidFormat(format!(
+                "snapshot of {} holds {} bytes, expected {}",
+                self.name,
+                self.data.len(),
+                t.nbytes()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Every tensor of a context that owns its data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextSnapshot {
+    pub tensors: Vec<TensorSnapshot>,
+}
+
+impl ContextSnapshot {
+    /// A new context holding a copy of every tensor, by name.
+    pub fn restore(&self) -> Result<Context> {
+        let overhead = unsafe { crate::ggml_tensor_overhead() };
+        let align = crate::GGML_MEM_ALIGN as usize;
+        let size = self
+            .tensors
+            .iter()
+            .map(|t| overhead + t.data.len().next_multiple_of(align) + align)
+            .sum::<usize>()
+            + overhead;
+        let ctx = Context::new(size)?;
+        for snap in &self.tensors {
+            let t = ctx.new_tensor(snap.ty, &snap.ne)?.set_name(&snap.name);
+            t.restore(snap)?;
+        }
+        Ok(ctx)
+    }
+
+    /// Overwrite the tensors of `ctx` with the snapshot's, matched by name.
+    /// Each must exist in `ctx` with the same type and shape.
+    pub fn restore_into(&self, ctx: &Context) -> Result<()> {
+        for snap in &self.tensors {
+            let t = ctx.get_tensor(&snap.name).ok_or_else(|| {
+                Error::InvalidArgument(format!("no tensor named {:?} to restore", snap.name))
+            })?;
+            t.restore(snap)?;
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Snapshot every tensor of the context that has data of its own
+    /// (skipping views and unallocated tensors). For
+    /// [`ContextSnapshot::restore_into`], give them unique names first.
+    pub fn snapshot(&self) -> Result<ContextSnapshot> {
+        let tensors = self
+            .tensors()
+            .filter(|t| !t.data().is_null() && unsafe { (*t.as_ptr()).view_src.is_null() })
+            .map(|t| t.snapshot())
+            .collect::<Result<_>>()?;
+        Ok(ContextSnapshot { tensors })
+    }
+
+    /// A new tensor holding a copy of `snap`, named after it.
+    pub fn new_tensor_from_snapshot(&self, snap: &TensorSnapshot) -> Result<Tensor<'_>> {
+        let t = self.new_tensor(snap.ty, &snap.ne)?.set_name(&snap.name);
+        t.restore(snap)?;
+        Ok(t)
+    }
+}
+
+impl Tensor<'_> {
+    fn check_snapshot_data(&self) -> Result<()> {
+        if self.data().is_null() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        Ok(())
+    }
+
+    /// Copy the tensor's name, type, shape and data. The data must be
+    /// contiguous.
+    pub fn snapshot(&self) -> Result<TensorSnapshot> {
+        self.check_snapshot_data()?;
+        let mut data = vec![0; self.nbytes()];
+        if self.is_host() {
+            let src = unsafe { std::slice::from_raw_parts(self.data() as *const u8, data.len()) };
+            data.copy_from_slice(src);
+        } else {
+            self.read_bytes(0, &mut data)?;
+        }
+        Ok(TensorSnapshot {
+            name: self.name().to_string(),
+            ty: self.ty(),
+            ne: self.ne(),
+            data,
+        })
+    }
+
+    /// Overwrite the tensor's data with `snap`'s. Type and shape must
+    /// match; the name is not checked.
+    pub fn restore(&self, snap: &TensorSnapshot) -> Result<()> {
+        self.check_snapshot_data()?;
+        snap.check_fits(self)?;
+        if self.is_host() {
+            let dst =
+                unsafe { std::slice::from_raw_parts_mut(self.data() as *mut u8, self.nbytes()) };
+            dst.copy_from_slice(&snap.data);
+        } else {
+            self.write_bytes(0, &snap.data)?;
+        }
+        Ok(())
+    }
+}
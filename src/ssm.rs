@@ -0,0 +1,134 @@
+//! State-space model ops (`ggml_ssm_conv`, `ggml_ssm_scan`) for Mamba-style
+//! recurrent layers.
+//!
+//! Shapes below list `ne[0]` (innermost) first. All inputs except `ids` are
+//! F32.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+fn shape_check(ok: bool, what: &str, detail: impl FnOnce() -> String) -> Result<()> {
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::ShapeMismatch(format!("{}: {}", what, detail())))
+    }
+}
+
+fn packed_rows(t: Tensor<'_>) -> bool {
+    let (ne, nb) = (t.ne(), t.nb());
+    nb[0] == t.element_size() && nb[1] == ne[0] as usize * nb[0]
+}
+
+impl Context {
+    /// Depthwise causal convolution over the token axis.
+    ///
+    /// - `sx`: `[d_conv - 1 + n_t, d_inner, n_s]`, the previous conv state
+    ///   concatenated with the new tokens (transposed, tokens innermost)
+    /// - `c`: `[d_conv, d_inner]`, the conv weights
+    ///
+    /// Returns F32 `[d_inner, n_t, n_s]`.
+    pub fn ssm_conv<'a>(&'a self, sx: Tensor<'a>, c: Tensor<'a>) -> Result<Tensor<'a>> {
+        Self::check_f32("ssm_conv", sx)?;
+        Self::check_f32("ssm_conv", c)?;
+        let (sn, cn) = (sx.ne(), c.ne());
+        shape_check(sn[3] == 1 && cn[2] == 1 && cn[3] == 1, "ssm_conv", || {
+            format!("sx {:?} must be 3-d and c {:?} a matrix", sn, cn)
+        })?;
+        let (d_conv, d_inner) = (cn[0], cn[1]);
+        let n_t = sn[0] - d_conv + 1;
+        shape_check(n_t >= 0 && sn[1] == d_inner, "ssm_conv", || {
+            format!("sx {:?} does not match weights {:?}", sn, cn)
+        })?;
+        self.ensure_capacity(Type::F32.row_size(d_inner) * (n_t * sn[2]) as usize)?;
+        self.op_result("ggml_ssm_conv", unsafe {
+            crate::ggml_ssm_conv(self.as_ptr(), sx.as_ptr(), c.as_ptr())
+        })
+    }
+
+    /// Selective scan.
+    ///
+    /// - `s`: `[d_state, head_dim, n_head, n_rs]`, recurrent states (contiguous)
+    /// - `x`: `[head_dim, n_head, n_seq_tokens, n_seqs]`
+    /// - `dt`: `[n_head, n_seq_tokens, n_seqs]` (contiguous)
+    /// - `a`: `[d_state, n_head]` for Mamba-1 or `[1, n_head]` for Mamba-2
+    ///   (contiguous)
+    /// - `b`, `c`: `[d_state, n_group, n_seq_tokens, n_seqs]`
+    /// - `ids`: I32 `[n_seqs]`, which state in `s` each sequence starts from
+    ///
+    /// Returns a flat F32 tensor holding the outputs `y` (`nelements(x)`
+    /// values) followed by the updated states (`d_state * head_dim * n_head *
+    /// n_seqs` values); split it with views.
+    #[allow(clippy::too_many_arguments)]
+    pub fn ssm_scan<'a>(
+        &'a self,
+        s: Tensor<'a>,
+        x: Tensor<'a>,
+        dt: Tensor<'a>,
+        a: Tensor<'a>,
+        b: Tensor<'a>,
+        c: Tensor<'a>,
+        ids: Tensor<'a>,
+    ) -> Result<Tensor<'a>> {
+        for t in [s, x, dt, a, b, c] {
+            Self::check_f32("ssm_scan", t)?;
+        }
+        if ids.ty() != Type::I32 {
+            return Err(Error::TypeMismatch {
+                expected: Type::I32.to_string(),
+                found: ids.ty().to_string(),
+            });
+        }
+        let contiguous = s.is_contiguous() && dt.is_contiguous() && a.is_contiguous();
+        if !(contiguous && packed_rows(x) && packed_rows(b) && packed_rows(c)) {
+            return Err(Error::NotContiguous);
+        }
+
+        let (sn, xn, dtn, an, bn, idn) = (s.ne(), x.ne(), dt.ne(), a.ne(), b.ne(), ids.ne());
+        let [head_dim, n_head, n_seq_tokens, n_seqs] = xn;
+        let d_state = sn[0];
+        shape_check(
+            dtn == [n_head, n_seq_tokens, n_seqs, 1],
+            "ssm_scan dt",
+            || {
+                format!(
+                    "expected [{}, {}, {}], got {:?}",
+                    n_head, n_seq_tokens, n_seqs, dtn
+                )
+            },
+        )?;
+        shape_check(sn[1] == head_dim && sn[2] == n_head, "ssm_scan s", || {
+            format!("{:?} does not match x {:?}", sn, xn)
+        })?;
+        shape_check(
+            b.same_shape(c) && bn[0] == d_state && bn[2] == n_seq_tokens && bn[3] == n_seqs,
+            "ssm_scan B/C",
+            || format!("B {:?} and C {:?} do not match x {:?}", bn, c.ne(), xn),
+        )?;
+        shape_check(idn == [n_seqs, 1, 1, 1], "ssm_scan ids", || {
+            format!("expected [{}], got {:?}", n_seqs, idn)
+        })?;
+        shape_check(
+            an[1] == n_head && an[2] == 1 && an[3] == 1 && (an[0] == 1 || an[0] == d_state),
+            "ssm_scan A",
+            || format!("expected [{} or 1, {}], got {:?}", d_state, n_head, an),
+        )?;
+
+        let n = x.nelements() + d_state * head_dim * n_head * n_seqs;
+        self.ensure_capacity(Type::F32.row_size(n))?;
+        self.op_result("ggml_ssm_scan", unsafe {
+            crate::ggml_ssm_scan(
+                self.as_ptr(),
+                s.as_ptr(),
+                x.as_ptr(),
+                dt.as_ptr(),
+                a.as_ptr(),
+                b.as_ptr(),
+                c.as_ptr(),
+                ids.as_ptr(),
+            )
+        })
+    }
+}
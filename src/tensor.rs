@@ -0,0 +1,318 @@
+//! Borrowed handle to a `ggml_tensor`.
+
+use std::ffi::{c_void, CStr, CString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::types::{Element, Type};
+
+/// A tensor owned by a [`Context`].
+///
+/// `Tensor` is a cheap `Copy` handle; the lifetime ties it to the context
+/// whose memory pool holds the tensor header.
+#[derive(Clone, Copy)]
+pub struct Tensor<'ctx> {
+    ptr: NonNull<crate::ggml_tensor>,
+    _ctx: PhantomData<&'ctx Context>,
+}
+
+impl<'ctx> Tensor<'ctx> {
+    /// Wrap a raw tensor pointer, returning `None` for null.
+    ///
+    /// # Safety
+    /// `ptr` must point to a tensor that lives at least as long as `'ctx`.
+    pub unsafe fn from_raw(ptr: *mut crate::ggml_tensor) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Tensor {
+            ptr,
+            _ctx: PhantomData,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::ggml_tensor {
+        self.ptr.as_ptr()
+    }
+
+    fn raw(&self) -> &crate::ggml_tensor {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Element type. Panics only if ggml reports a type this crate doesn't know.
+    pub fn ty(&self) -> Type {
+        Type::from_raw(self.raw().type_).expect("unknown ggml_type")
+    }
+
+    /// Number of elements per dimension (`ne`), innermost first.
+    pub fn ne(&self) -> [i64; 4] {
+        self.raw().ne
+    }
+
+    /// Strides in bytes per dimension (`nb`).
+    pub fn nb(&self) -> [usize; 4] {
+        self.raw().nb
+    }
+
+    /// Number of dimensions, ignoring trailing dimensions of size 1 (at least 1).
+    pub fn n_dims(&self) -> usize {
+        unsafe { crate::ggml_n_dims(self.as_ptr()) as usize }
+    }
+
+    /// The shape trimmed to [`Tensor::n_dims`] dimensions.
+    pub fn shape(&self) -> Vec<i64> {
+        self.ne()[..self.n_dims()].to_vec()
+    }
+
+    pub fn nelements(&self) -> i64 {
+        unsafe { crate::ggml_nelements(self.as_ptr()) }
+    }
+
+    pub fn nrows(&self) -> i64 {
+        unsafe { crate::ggml_nrows(self.as_ptr()) }
+    }
+
+    pub fn nbytes(&self) -> usize {
+        unsafe { crate::ggml_nbytes(self.as_ptr()) }
+    }
+
+    pub fn element_size(&self) -> usize {
+        unsafe { crate::ggml_element_size(self.as_ptr()) }
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        unsafe { crate::ggml_is_contiguous(self.as_ptr()) }
+    }
+
+    pub fn same_shape(&self, other: Tensor<'_>) -> bool {
+        unsafe { crate::ggml_are_same_shape(self.as_ptr(), other.as_ptr()) }
+    }
+
+    /// The raw op that produces this tensor (`GGML_OP_NONE` for leaves).
+    pub fn op(&self) -> crate::ggml_op {
+        self.raw().op
+    }
+
+    /// Human readable op name, e.g. `"MUL_MAT"` or the unary op name for `UNARY`.
+    pub fn op_desc(&self) -> &'static str {
+        unsafe {
+            let ptr = crate::ggml_op_desc(self.as_ptr());
+            if ptr.is_null() {
+                return "";
+            }
+            CStr::from_ptr(ptr).to_str().unwrap_or("")
+        }
+    }
+
+    /// Source tensor `i` of this node, if any.
+    pub fn src(&self, i: usize) -> Option<Tensor<'ctx>> {
+        let src = *self.raw().src.get(i)?;
+        unsafe { Tensor::from_raw(src) }
+    }
+
+    pub fn name(&self) -> &str {
+        unsafe {
+            CStr::from_ptr(crate::ggml_get_name(self.as_ptr()))
+                .to_str()
+                .unwrap_or("")
+        }
+    }
+
+    /// Set the tensor name (truncated by ggml to `GGML_MAX_NAME - 1` bytes).
+    pub fn set_name(self, name: &str) -> Self {
+        if let Ok(name) = CString::new(name) {
+            unsafe { crate::ggml_set_name(self.as_ptr(), name.as_ptr()) };
+        }
+        self
+    }
+
+    /// Mark as a graph input.
+    pub fn set_input(self) -> Self {
+        unsafe { crate::ggml_set_input(self.as_ptr()) };
+        self
+    }
+
+    /// Mark as a graph output so allocators don't reuse its memory.
+    pub fn set_output(self) -> Self {
+        unsafe { crate::ggml_set_output(self.as_ptr()) };
+        self
+    }
+
+    /// Raw data pointer; null for `no_alloc` tensors without a buffer.
+    pub fn data(&self) -> *mut c_void {
+        self.raw().data
+    }
+
+    fn check_host_access<T: Element>(&self) -> Result<()> {
+        if self.ty() != T::TYPE {
+            return Err(Error::TypeMismatch {
+                expected: T::TYPE.to_string(),
+                found: self.ty().to_string(),
+            });
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        Ok(())
+    }
+
+    /// Whether the data lives in host memory that can be read directly.
+    pub fn is_host(&self) -> bool {
+        let buffer = self.raw().buffer;
+        buffer.is_null() || unsafe { crate::ggml_backend_buffer_is_host(buffer) }
+    }
+
+    /// Copy `values` into the tensor. Length and element type must match exactly.
+    pub fn write<T: Element>(&self, values: &[T]) -> Result<()> {
+        self.check_host_access::<T>()?;
+        if values.len() as i64 != self.nelements() {
+            return Err(Error::ShapeMismatch(format!(
+                "tensor has {} elements, got {}",
+                self.nelements(),
+                values.len()
+            )));
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), self.data() as *mut T, values.len());
+        }
+        Ok(())
+    }
+
+    /// Copy the tensor contents into a new `Vec`.
+    pub fn to_vec<T: Element>(&self) -> Result<Vec<T>> {
+        self.check_host_access::<T>()?;
+        let n = self.nelements() as usize;
+        let mut out = vec![T::default(); n];
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.data() as *const T, out.as_mut_ptr(), n);
+        }
+        Ok(out)
+    }
+
+    /// Write `f32` values into an F32, F16 or BF16 tensor, converting as
+    /// needed. The tensor must be contiguous and the length must match.
+    pub fn write_f32(&self, values: &[f32]) -> Result<()> {
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        if values.len() as i64 != self.nelements() {
+            return Err(Error::ShapeMismatch(format!(
+                "tensor has {} elements, got {}",
+                self.nelements(),
+                values.len()
+            )));
+        }
+        let n = values.len() as i64;
+        unsafe {
+            match self.ty() {
+                Type::F32 => std::ptr::copy_nonoverlapping(
+                    values.as_ptr(),
+                    self.data() as *mut f32,
+                    values.len(),
+                ),
+                Type::F16 => {
+                    crate::ggml_fp32_to_fp16_row(values.as_ptr(), self.data() as *mut u16, n)
+                }
+                Type::BF16 => crate::ggml_fp32_to_bf16_row(
+                    values.as_ptr(),
+                    self.data() as *mut crate::ggml_bf16_t,
+                    n,
+                ),
+                ty => {
+                    return Err(Error::TypeMismatch {
+                        expected: "F32, F16 or BF16".to_string(),
+                        found: ty.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the tensor as `f32` values in `ne[0]`-fastest order, converting
+    /// from any type with a `to_float` conversion (F16, BF16, the quantized
+    /// types) and casting integer and F64 tensors.
+    ///
+    /// Rows must be densely packed, but the tensor may be otherwise strided
+    /// (e.g. a permuted view that keeps `ne[0]` innermost).
+    pub fn to_vec_f32(&self) -> Result<Vec<f32>> {
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        let ty = self.ty();
+        if self.nb()[0] != ty.type_size() {
+            return Err(Error::NotContiguous);
+        }
+        let to_float = unsafe { (*crate::ggml_get_type_traits(ty.as_raw())).to_float };
+        let (ne, nb) = (self.ne(), self.nb());
+        let row_len = ne[0] as usize;
+        let mut out = vec![0f32; self.nelements() as usize];
+        let rows = out.chunks_exact_mut(row_len.max(1));
+        let offsets = (0..ne[3]).flat_map(|i3| {
+            (0..ne[2]).flat_map(move |i2| {
+                (0..ne[1])
+                    .map(move |i1| i1 as usize * nb[1] + i2 as usize * nb[2] + i3 as usize * nb[3])
+            })
+        });
+        for (row, offset) in rows.zip(offsets) {
+            let src = unsafe { (self.data() as *const u8).add(offset) };
+            unsafe {
+                match ty {
+                    Type::F32 => {
+                        std::ptr::copy_nonoverlapping(src as *const f32, row.as_mut_ptr(), row_len)
+                    }
+                    Type::F64 => cast_row(src as *const f64, row, |v| v as f32),
+                    Type::I8 => cast_row(src as *const i8, row, |v| v as f32),
+                    Type::I16 => cast_row(src as *const i16, row, |v| v as f32),
+                    Type::I32 => cast_row(src as *const i32, row, |v| v as f32),
+                    Type::I64 => cast_row(src as *const i64, row, |v| v as f32),
+                    _ => match to_float {
+                        Some(f) => f(src as *const c_void, row.as_mut_ptr(), row_len as i64),
+                        None => {
+                            return Err(Error::InvalidArgument(format!(
+                                "no f32 conversion for {}",
+                                ty
+                            )))
+                        }
+                    },
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Convert `dst.len()` values starting at `src`.
+///
+/// # Safety
+/// `src` must be valid for `dst.len()` reads of `T` (alignment not required).
+unsafe fn cast_row<T: Copy>(src: *const T, dst: &mut [f32], f: impl Fn(T) -> f32) {
+    for (i, d) in dst.iter_mut().enumerate() {
+        *d = f(src.add(i).read_unaligned());
+    }
+}
+
+impl PartialEq for Tensor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl Eq for Tensor<'_> {}
+
+impl fmt::Debug for Tensor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tensor")
+            .field("name", &self.name())
+            .field("type", &self.ty())
+            .field("ne", &self.ne())
+            .field("op", &self.op_desc())
+            .finish()
+    }
+}
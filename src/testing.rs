@@ -0,0 +1,268 @@
+//! Numeric comparison of tensors, for validating graphs against reference
+//! implementations.
+//!
+//! Both sides are read back with [`Tensor::to_vec_f32`], so F16, BF16 and
+//! quantized tensors can be compared against F32 references directly. Two
+//! values `actual` and `expected` are close when
+//! `|actual - expected| <= atol + rtol * |expected|`; NaNs only match NaNs.
+//! With the `tch` feature, `compare_torch` checks results against PyTorch
+//! tensors directly.
+//! With the `reference` feature, `reference` computes expected values
+//! in Rust for [`compare_slices`].
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+
+/// Absolute and relative tolerance, as in numpy's `allclose`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    pub atol: f32,
+    pub rtol: f32,
+}
+
+impl Default for Tolerance {
+    /// `atol = 1e-5`, `rtol = 1e-5`, suitable for F32 results.
+    fn default() -> Self {
+        Tolerance {
+            atol: 1e-5,
+            rtol: 1e-5,
+        }
+    }
+}
+
+impl Tolerance {
+    pub fn new(atol: f32, rtol: f32) -> Self {
+        Tolerance { atol, rtol }
+    }
+
+    /// Looser defaults for half-precision results.
+    pub fn f16() -> Self {
+        Tolerance::new(1e-3, 1e-3)
+    }
+
+    /// Looser defaults for quantized weights.
+    pub fn quantized() -> Self {
+        Tolerance::new(1e-2, 5e-2)
+    }
+
+    pub fn is_close(&self, actual: f32, expected: f32) -> bool {
+        if actual.is_nan() || expected.is_nan() {
+            return actual.is_nan() && expected.is_nan();
+        }
+        if actual == expected {
+            // also covers matching infinities
+            return true;
+        }
+        (actual - expected).abs() <= self.atol + self.rtol * expected.abs()
+    }
+}
+
+/// One element outside tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    /// Flat index, `ne[0]` fastest.
+    pub index: usize,
+    /// Position as `[i0, i1, i2, i3]`.
+    pub coords: [i64; 4],
+    pub actual: f32,
+    pub expected: f32,
+}
+
+impl Mismatch {
+    pub fn abs_diff(&self) -> f32 {
+        (self.actual - self.expected).abs()
+    }
+}
+
+/// Result of [`compare`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub tolerance: Tolerance,
+    /// Number of compared elements.
+    pub len: usize,
+    /// Number of elements outside tolerance.
+    pub mismatches: usize,
+    /// The first element outside tolerance, in index order.
+    pub first_mismatch: Option<Mismatch>,
+    /// The element with the largest absolute difference (NaNs excluded).
+    pub worst: Option<Mismatch>,
+    pub max_abs_diff: f32,
+    /// Largest `|actual - expected| / |expected|` over non-zero `expected`.
+    pub max_rel_diff: f32,
+}
+
+impl Comparison {
+    pub fn is_close(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} elements differ (atol {}, rtol {}); max abs diff {}, max rel diff {}",
+            self.mismatches,
+            self.len,
+            self.tolerance.atol,
+            self.tolerance.rtol,
+            self.max_abs_diff,
+            self.max_rel_diff
+        )?;
+        if let Some(m) = self.first_mismatch {
+            write!(
+                f,
+                "; first at {:?}: {} vs expected {}",
+                m.coords, m.actual, m.expected
+            )?;
+        }
+        if let Some(m) = self.worst {
+            write!(
+                f,
+                "; worst at {:?}: {} vs expected {}",
+                m.coords, m.actual, m.expected
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn coords(mut index: usize, ne: [i64; 4]) -> [i64; 4] {
+    let mut out = [0; 4];
+    for (c, &n) in out.iter_mut().zip(&ne) {
+        let n = n.max(1) as usize;
+        *c = (index % n) as i64;
+        index /= n;
+    }
+    out
+}
+
+/// Compare flat values laid out with shape `ne` (`ne[0]` fastest).
+pub fn compare_slices(
+    actual: &[f32],
+    expected: &[f32],
+    ne: [i64; 4],
+    tolerance: Tolerance,
+) -> Result<Comparison> {
+    if actual.len() != expected.len() {
+        return Err(Error::ShapeMismatch(format!(
+            "comparing {} values against {}",
+            actual.len(),
+            expected.len()
+        )));
+    }
+    let mut cmp = Comparison {
+        tolerance,
+        len: actual.len(),
+        mismatches: 0,
+        first_mismatch: None,
+        worst: None,
+        max_abs_diff: 0.0,
+        max_rel_diff: 0.0,
+    };
+    for (index, (&a, &e)) in actual.iter().zip(expected).enumerate() {
+        let diff = (a - e).abs();
+        let mismatch = || Mismatch {
+            index,
+            coords: coords(index, ne),
+            actual: a,
+            expected: e,
+        };
+        if !tolerance.is_close(a, e) {
+            cmp.mismatches += 1;
+            cmp.first_mismatch.get_or_insert_with(mismatch);
+        }
+        if diff > cmp.max_abs_diff {
+            cmp.max_abs_diff = diff;
+            cmp.worst = Some(mismatch());
+        }
+        if e != 0.0 && diff / e.abs() > cmp.max_rel_diff {
+            cmp.max_rel_diff = diff / e.abs();
+        }
+    }
+    Ok(cmp)
+}
+
+/// Compare two tensors of the same shape (types may differ).
+pub fn compare(
+    actual: Tensor<'_>,
+    expected: Tensor<'_>,
+    tolerance: Tolerance,
+) -> Result<Comparison> {
+    if actual.ne() != expected.ne() {
+        return Err(Error::ShapeMismatch(format!(
+            "comparing {:?} against {:?}",
+            actual.ne(),
+            expected.ne()
+        )));
+    }
+    compare_slices(
+        &actual.to_vec_f32()?,
+        &expected.to_vec_f32()?,
+        actual.ne(),
+        tolerance,
+    )
+}
+
+/// Whether every element of `actual` is within `tolerance` of `expected`.
+pub fn allclose(actual: Tensor<'_>, expected: Tensor<'_>, tolerance: Tolerance) -> Result<bool> {
+    compare(actual, expected, tolerance).map(|c| c.is_close())
+}
+
+/// Largest absolute elementwise difference (NaNs ignored).
+pub fn max_abs_diff(actual: Tensor<'_>, expected: Tensor<'_>) -> Result<f32> {
+    compare(actual, expected, Tolerance::default()).map(|c| c.max_abs_diff)
+}
+
+/// Panic with a description of the differences unless `actual` is within
+/// `tolerance` of `expected`. Meant for tests.
+#[track_caller]
+pub fn assert_close(actual: Tensor<'_>, expected: Tensor<'_>, tolerance: Tolerance) {
+    match compare(actual, expected, tolerance) {
+        Ok(cmp) if cmp.is_close() => {}
+        Ok(cmp) => panic!(
+            "tensors `{}` and `{}` are not close: {}",
+            actual.name(),
+            expected.name(),
+            cmp
+        ),
+        Err(e) => panic!(
+            "cannot compare `{}` and `{}`: {}",
+            actual.name(),
+            expected.name(),
+            e
+        ),
+    }
+}
+
+/// Compare a ggml result against a torch reference whose size is `ne`
+/// reversed; see [`Tensor::to_torch`](crate::Tensor::to_torch).
+#[cfg(feature = "tch")]
+pub fn compare_torch(
+    actual: Tensor<'_>,
+    expected: &tch::Tensor,
+    tolerance: Tolerance,
+) -> Result<Comparison> {
+    crate::torch::compare_torch(actual, expected, tolerance)
+}
+
+/// Like [`assert_close`], against a torch reference.
+#[cfg(feature = "tch")]
+#[track_caller]
+pub fn assert_close_torch(actual: Tensor<'_>, expected: &tch::Tensor, tolerance: Tolerance) {
+    match compare_torch(actual, expected, tolerance) {
+        Ok(cmp) if cmp.is_close() => {}
+        Ok(cmp) => panic!(
+            "tensor `{}` is not close to the torch reference: {}",
+            actual.name(),
+            cmp
+        ),
+        Err(e) => panic!(
+            "cannot compare `{}` with the torch reference: {}",
+            actual.name(),
+            e
+        ),
+    }
+}
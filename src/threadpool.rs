@@ -0,0 +1,350 @@
+//! CPU threadpool (`ggml_threadpool`) with affinity, priority and polling control.
+//!
+//! A [`Threadpool`] keeps its worker threads alive between graph computations.
+//! When ggml shares a machine with another runtime (e.g. tokio), pin the workers
+//! with [`ThreadpoolParams::cpu`] and set [`ThreadpoolParams::poll`] to `0` so idle
+//! workers sleep instead of spinning.
+//!
+//! For latency-sensitive deployments, reserve cores for ggml (e.g. with
+//! `isolcpus=8-15`), pin the workers to them one each and raise their
+//! priority, then hand the pool to the CPU backend:
+//!
+//! ```ignore
+//! let params = ThreadpoolParams::new(8)
+//!     .cpu_list("8-15")?
+//!     .strict_cpu(true)
+//!     .priority(Priority::High);
+//! let cpu = CpuBackend::with_threadpool_params(&params)?;
+//! ```
+//!
+//! The thread that calls compute works as worker 0, so ggml applies worker
+//! 0's CPU and priority to it too, and they stay after the compute returns.
+//! Compute from a thread dedicated to ggml, not from an async executor's
+//! worker threads.
+//!
+//! On multi-socket machines, choose a [`NumaStrategy`] once at startup. NUMA
+//! placement and the affinity mask both pin the workers, and NUMA wins: each
+//! compute re-pins them to their node, replacing [`ThreadpoolParams::cpumask`].
+//! Use one or the other; to restrict a NUMA-aware process to some CPUs, start
+//! it under `numactl` and use [`NumaStrategy::Numactl`].
+
+use std::ptr::NonNull;
+use std::sync::OnceLock;
+
+use crate::error::{check_status, Error, Result};
+use crate::graph::Graph;
+
+/// The most threads ggml can compute with: one in wasm builds without the
+/// `atomics` target feature, where no worker threads can be started.
+pub(crate) fn max_threads() -> usize {
+    if cfg!(all(target_family = "wasm", not(target_feature = "atomics"))) {
+        1
+    } else {
+        crate::GGML_MAX_N_THREADS as usize
+    }
+}
+
+/// Scheduling priority of the worker threads, mirroring `enum ggml_sched_priority`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    Medium,
+    High,
+    Realtime,
+}
+
+impl Priority {
+    fn as_raw(self) -> crate::ggml_sched_priority {
+        match self {
+            Priority::Low => crate::ggml_sched_priority_GGML_SCHED_PRIO_LOW,
+            Priority::Normal => crate::ggml_sched_priority_GGML_SCHED_PRIO_NORMAL,
+            Priority::Medium => crate::ggml_sched_priority_GGML_SCHED_PRIO_MEDIUM,
+            Priority::High => crate::ggml_sched_priority_GGML_SCHED_PRIO_HIGH,
+            Priority::Realtime => crate::ggml_sched_priority_GGML_SCHED_PRIO_REALTIME,
+        }
+    }
+
+    fn from_raw(raw: crate::ggml_sched_priority) -> Self {
+        match raw {
+            crate::ggml_sched_priority_GGML_SCHED_PRIO_LOW => Priority::Low,
+            crate::ggml_sched_priority_GGML_SCHED_PRIO_MEDIUM => Priority::Medium,
+            crate::ggml_sched_priority_GGML_SCHED_PRIO_HIGH => Priority::High,
+            crate::ggml_sched_priority_GGML_SCHED_PRIO_REALTIME => Priority::Realtime,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+/// How CPU workers are placed on NUMA nodes, mirroring `enum ggml_numa_strategy`.
+/// Only Linux has NUMA support; elsewhere every strategy behaves like
+/// [`Disabled`](Self::Disabled).
+///
+/// ```ignore
+/// // dual-socket server: spread the workers over both sockets
+/// NumaStrategy::Distribute.init()?;
+/// let pool = Threadpool::new(ThreadpoolParams::new(64))?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumaStrategy {
+    /// Leave placement to the OS.
+    #[default]
+    Disabled,
+    /// Worker `i` runs on node `i % nodes`, using every socket's memory
+    /// bandwidth. Suits one large model spanning both sockets.
+    Distribute,
+    /// All workers run on the node the process started on. Suits one
+    /// process per socket.
+    Isolate,
+    /// Workers keep the CPU set the process was started with, e.g. by
+    /// `numactl --cpunodebind=1 --membind=1`.
+    Numactl,
+}
+
+static NUMA: OnceLock<NumaStrategy> = OnceLock::new();
+
+impl NumaStrategy {
+    fn as_raw(self) -> crate::ggml_numa_strategy {
+        match self {
+            NumaStrategy::Disabled => crate::ggml_numa_strategy_GGML_NUMA_STRATEGY_DISABLED,
+            NumaStrategy::Distribute => crate::ggml_numa_strategy_GGML_NUMA_STRATEGY_DISTRIBUTE,
+            NumaStrategy::Isolate => crate::ggml_numa_strategy_GGML_NUMA_STRATEGY_ISOLATE,
+            NumaStrategy::Numactl => crate::ggml_numa_strategy_GGML_NUMA_STRATEGY_NUMACTL,
+        }
+    }
+
+    /// Detect the NUMA topology and apply this strategy to every later
+    /// compute, with or without a [`Threadpool`]. Call it before creating
+    /// threadpools and loading weights, so memory is first touched by
+    /// workers already in place.
+    ///
+    /// ggml applies a strategy once per process: calling this again with
+    /// the same strategy does nothing, with another one it fails.
+    pub fn init(self) -> Result<()> {
+        let current = *NUMA.get_or_init(|| {
+            unsafe { crate::ggml_numa_init(self.as_raw()) };
+            self
+        });
+        if current != self {
+            return Err(Error::InvalidArgument(format!(
+                "NUMA strategy is already {:?}",
+                current
+            )));
+        }
+        Ok(())
+    }
+
+    /// The strategy applied by [`init`](Self::init), if any.
+    pub fn current() -> Option<Self> {
+        NUMA.get().copied()
+    }
+
+    /// Whether a strategy is in effect on a machine with more than one
+    /// node. ggml also splits matrix multiplications differently then, so
+    /// workers mostly read memory local to their node.
+    pub fn is_active() -> bool {
+        unsafe { crate::ggml_is_numa() }
+    }
+}
+
+/// Builder for `ggml_threadpool_params`.
+#[derive(Clone, Copy)]
+pub struct ThreadpoolParams {
+    raw: crate::ggml_threadpool_params,
+}
+
+impl ThreadpoolParams {
+    /// Defaults from `ggml_threadpool_params_default`: no affinity, normal
+    /// priority, polling level 50, not paused.
+    pub fn new(n_threads: usize) -> Self {
+        ThreadpoolParams {
+            raw: unsafe {
+                crate::ggml_threadpool_params_default(n_threads.clamp(1, max_threads()) as i32)
+            },
+        }
+    }
+
+    pub fn n_threads(&self) -> usize {
+        self.raw.n_threads as usize
+    }
+
+    /// Allow workers to run on CPU `cpu`. With no CPUs set, the OS decides.
+    pub fn cpu(mut self, cpu: usize) -> Self {
+        if let Some(slot) = self.raw.cpumask.get_mut(cpu) {
+            *slot = true;
+        }
+        self
+    }
+
+    /// Replace the affinity mask; `mask[i]` enables CPU `i`. Overridden once a
+    /// [`NumaStrategy`] is active.
+    pub fn cpumask(mut self, mask: &[bool]) -> Self {
+        self.raw.cpumask = [false; crate::GGML_MAX_N_THREADS as usize];
+        for (slot, &on) in self.raw.cpumask.iter_mut().zip(mask) {
+            *slot = on;
+        }
+        self
+    }
+
+    /// Allow workers to run on every CPU in `cpus`, e.g. `8..16`.
+    pub fn cpu_range(mut self, cpus: std::ops::Range<usize>) -> Self {
+        for cpu in cpus {
+            self = self.cpu(cpu);
+        }
+        self
+    }
+
+    /// Allow workers to run on the CPUs of a Linux CPU list such as
+    /// `"0-3,8,10-11"`, the format of `taskset -c`, `isolcpus` and
+    /// `/sys/devices/system/cpu/online`.
+    pub fn cpu_list(mut self, list: &str) -> Result<Self> {
+        let parse = |cpu: &str| {
+            cpu.trim()
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidArgument(format!("invalid CPU list {:?}", list)))
+        };
+        for part in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((a, b)) => (parse(a)?, parse(b)?),
+                None => (parse(part)?, parse(part)?),
+            };
+            if first > last || last >= self.raw.cpumask.len() {
+                return Err(Error::InvalidArgument(format!(
+                    "CPU range {:?} must be ascending and below {}",
+                    part,
+                    self.raw.cpumask.len()
+                )));
+            }
+            self = self.cpu_range(first..last + 1);
+        }
+        Ok(self)
+    }
+
+    /// Indices of the CPUs enabled in the affinity mask.
+    pub fn cpus(&self) -> Vec<usize> {
+        (0..self.raw.cpumask.len())
+            .filter(|&i| self.raw.cpumask[i])
+            .collect()
+    }
+
+    /// Priority of the workers. Above [`Priority::Normal`] this needs
+    /// privileges (`CAP_SYS_NICE` on Linux); without them ggml warns and
+    /// the workers keep their priority.
+    pub fn priority(mut self, prio: Priority) -> Self {
+        self.raw.prio = prio.as_raw();
+        self
+    }
+
+    pub fn get_priority(&self) -> Priority {
+        Priority::from_raw(self.raw.prio)
+    }
+
+    /// Polling level from 0 (sleep while idle) to 100 (spin aggressively).
+    pub fn poll(mut self, poll: u32) -> Self {
+        self.raw.poll = poll.min(100);
+        self
+    }
+
+    /// Pin each worker to a single CPU of the mask instead of the whole mask:
+    /// worker `i` gets the `i`-th enabled CPU, wrapping around.
+    pub fn strict_cpu(mut self, strict: bool) -> Self {
+        self.raw.strict_cpu = strict;
+        self
+    }
+
+    /// Start the pool paused; call [`Threadpool::resume`] before computing.
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.raw.paused = paused;
+        self
+    }
+
+    pub fn as_raw(&self) -> &crate::ggml_threadpool_params {
+        &self.raw
+    }
+}
+
+impl PartialEq for ThreadpoolParams {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { crate::ggml_threadpool_params_match(&self.raw, &other.raw) }
+    }
+}
+
+impl std::fmt::Debug for ThreadpoolParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThreadpoolParams")
+            .field("n_threads", &self.raw.n_threads)
+            .field("cpus", &self.cpus())
+            .field("prio", &self.get_priority())
+            .field("poll", &self.raw.poll)
+            .field("strict_cpu", &self.raw.strict_cpu)
+            .field("paused", &self.raw.paused)
+            .finish()
+    }
+}
+
+/// An owned CPU threadpool.
+///
+/// Not `Sync`: `ggml_graph_compute` sets up the pool's shared state without
+/// locking, so only one graph may compute on it at a time, and computing
+/// takes `&mut Threadpool`.
+pub struct Threadpool {
+    ptr: NonNull<crate::ggml_threadpool>,
+}
+
+// pause/resume lock the pool, but compute does not, hence Send only
+unsafe impl Send for Threadpool {}
+
+impl Threadpool {
+    pub fn new(params: &ThreadpoolParams) -> Result<Self> {
+        let mut raw = params.raw;
+        let ptr = unsafe { crate::ggml_threadpool_new(&mut raw) };
+        NonNull::new(ptr)
+            .map(|ptr| Threadpool { ptr })
+            .ok_or(Error::NullPointer("ggml_threadpool_new"))
+    }
+
+    pub fn as_ptr(&self) -> *mut crate::ggml_threadpool {
+        self.ptr.as_ptr()
+    }
+
+    pub fn n_threads(&self) -> usize {
+        unsafe { crate::ggml_threadpool_get_n_threads(self.as_ptr()) as usize }
+    }
+
+    /// Park the workers; they stop polling until [`Threadpool::resume`].
+    pub fn pause(&self) {
+        unsafe { crate::ggml_threadpool_pause(self.as_ptr()) }
+    }
+
+    pub fn resume(&self) {
+        unsafe { crate::ggml_threadpool_resume(self.as_ptr()) }
+    }
+}
+
+impl Drop for Threadpool {
+    fn drop(&mut self) {
+        unsafe { crate::ggml_threadpool_free(self.as_ptr()) }
+    }
+}
+
+impl Graph<'_> {
+    /// Compute the graph on the CPU using the workers of `threadpool`.
+    ///
+    /// The work buffer is allocated on the Rust heap, so the graph's context
+    /// does not need spare room for it.
+    pub fn compute_with_threadpool(&mut self, threadpool: &mut Threadpool) -> Result<()> {
+        let mut plan = unsafe {
+            crate::ggml_graph_plan(
+                self.as_ptr(),
+                threadpool.n_threads() as i32,
+                threadpool.as_ptr(),
+            )
+        };
+        let mut work = vec![0u8; plan.work_size];
+        if plan.work_size > 0 {
+            plan.work_data = work.as_mut_ptr();
+        }
+        check_status(unsafe { crate::ggml_graph_compute(self.as_ptr(), &mut plan) })
+    }
+}
@@ -0,0 +1,167 @@
+//! Conversions between `tch` (libtorch) tensors and ggml tensors, for
+//! checking graphs against PyTorch reference implementations.
+//!
+//! Shapes are reversed on the way: a torch tensor of size `[a, b, c]` is a
+//! ggml tensor with `ne = [c, b, a, 1]`, so the innermost dimension stays
+//! innermost and no data is transposed. Torch tensors of up to four
+//! dimensions convert; they are copied to the CPU and made contiguous
+//! first.
+//!
+//! ```ignore
+//! let x = tch::Tensor::randn([4, 64], (tch::Kind::Float, tch::Device::Cpu));
+//! let input = ctx.new_tensor_from_torch(&x)?; // ne = [64, 4]
+//! let out = build(&ctx, input)?;
+//! // ... compute ...
+//! let expected = reference_module.forward(&x);
+//! testing::assert_close_torch(out, &expected, Tolerance::default());
+//! ```
+
+use tch::{Device, Kind};
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::testing::{compare_slices, Comparison, Tolerance};
+use crate::types::Type;
+
+/// The ggml type holding elements of `kind` without conversion.
+fn kind_to_type(kind: Kind) -> Result<Type> {
+    Ok(match kind {
+        Kind::Float => Type::F32,
+        Kind::Double => Type::F64,
+        Kind::Half => Type::F16,
+        Kind::BFloat16 => Type::BF16,
+        Kind::Int8 => Type::I8,
+        Kind::Int16 => Type::I16,
+        Kind::Int => Type::I32,
+        Kind::Int64 => Type::I64,
+        kind => {
+            return Err(Error::TypeMismatch {
+                expected: "a torch kind with a ggml type".to_string(),
+                found: format!("{:?}", kind),
+            })
+        }
+    })
+}
+
+/// The torch kind holding elements of `ty`, if it is not quantized.
+fn type_to_kind(ty: Type) -> Option<Kind> {
+    Some(match ty {
+        Type::F32 => Kind::Float,
+        Type::F64 => Kind::Double,
+        Type::F16 => Kind::Half,
+        Type::BF16 => Kind::BFloat16,
+        Type::I8 => Kind::Int8,
+        Type::I16 => Kind::Int16,
+        Type::I32 => Kind::Int,
+        Type::I64 => Kind::Int64,
+        _ => return None,
+    })
+}
+
+/// `ne` of the ggml tensor matching `t`.
+fn torch_ne(t: &tch::Tensor) -> Result<[i64; 4]> {
+    let size = t.size();
+    if size.len() > 4 {
+        return Err(Error::ShapeMismatch(format!(
+            "torch tensor of size {:?} has more than 4 dimensions",
+            size
+        )));
+    }
+    let mut ne = [1; 4];
+    for (n, &s) in ne.iter_mut().zip(size.iter().rev()) {
+        *n = s;
+    }
+    Ok(ne)
+}
+
+/// `t` on the CPU with its elements packed in order.
+fn cpu_contiguous(t: &tch::Tensor) -> tch::Tensor {
+    t.to_device(Device::Cpu).contiguous()
+}
+
+impl Context {
+    /// A new tensor holding a copy of `t`, of the matching type: F32, F64,
+    /// F16, BF16 or one of the integer types.
+    pub fn new_tensor_from_torch(&self, t: &tch::Tensor) -> Result<Tensor<'_>> {
+        let ty = kind_to_type(t.kind())?;
+        let size = t.size();
+        let ne = torch_ne(t)?;
+        let out = self.new_tensor(ty, &ne[..size.len().max(1)])?;
+        out.write_torch(t)?;
+        Ok(out)
+    }
+}
+
+impl Tensor<'_> {
+    /// Overwrite the tensor with the contents of `t`, e.g. to feed a graph
+    /// input. Sizes must match (reversed), and so must the element types.
+    pub fn write_torch(&self, t: &tch::Tensor) -> Result<()> {
+        if kind_to_type(t.kind())? != self.ty() {
+            return Err(Error::TypeMismatch {
+                expected: self.ty().to_string(),
+                found: format!("{:?}", t.kind()),
+            });
+        }
+        if torch_ne(t)? != self.ne() {
+            return Err(Error::ShapeMismatch(format!(
+                "cannot write a torch tensor of size {:?} to ne {:?}",
+                t.size(),
+                self.ne()
+            )));
+        }
+        if self.data().is_null() || !self.is_host() {
+            return Err(Error::NoData);
+        }
+        if !self.is_contiguous() {
+            return Err(Error::NotContiguous);
+        }
+        let src = cpu_contiguous(t);
+        let dst = unsafe { std::slice::from_raw_parts_mut(self.data() as *mut u8, self.nbytes()) };
+        src.copy_data_u8(dst, self.nelements() as usize);
+        Ok(())
+    }
+
+    /// Copy the tensor into a new CPU torch tensor of size `ne` reversed,
+    /// with trailing dimensions of 1 dropped (keeping at least one).
+    ///
+    /// Contiguous tensors of a type torch has keep their type. Quantized
+    /// tensors, and tensors whose rows are not packed one after the other
+    /// (e.g. permuted views), are read with [`Tensor::to_vec_f32`] and
+    /// become `Kind::Float`.
+    pub fn to_torch(&self) -> Result<tch::Tensor> {
+        let size: Vec<i64> = self.ne()[..self.n_dims()].iter().rev().copied().collect();
+        match type_to_kind(self.ty()) {
+            Some(kind) if self.is_contiguous() => {
+                if self.data().is_null() || !self.is_host() {
+                    return Err(Error::NoData);
+                }
+                let bytes =
+                    unsafe { std::slice::from_raw_parts(self.data() as *const u8, self.nbytes()) };
+                Ok(tch::Tensor::from_data_size(bytes, &size, kind))
+            }
+            _ => Ok(tch::Tensor::from_slice(&self.to_vec_f32()?).reshape(&size)),
+        }
+    }
+}
+
+/// Compare a ggml result against a torch reference of the same size
+/// (reversed), after converting both to `f32`.
+pub(crate) fn compare_torch(
+    actual: Tensor<'_>,
+    expected: &tch::Tensor,
+    tolerance: Tolerance,
+) -> Result<Comparison> {
+    if torch_ne(expected)? != actual.ne() {
+        return Err(Error::ShapeMismatch(format!(
+            "comparing ne {:?} against a torch tensor of size {:?}",
+            actual.ne(),
+            expected.size()
+        )));
+    }
+    let expected = cpu_contiguous(&expected.to_kind(Kind::Float));
+    let numel = expected.numel();
+    let mut values = vec![0f32; numel];
+    expected.copy_data(&mut values, numel);
+    compare_slices(&actual.to_vec_f32()?, &values, actual.ne(), tolerance)
+}
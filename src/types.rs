@@ -0,0 +1,175 @@
+//! Tensor element types.
+
+use std::ffi::CStr;
+use std::fmt;
+
+/// Safe mirror of `enum ggml_type`.
+///
+/// Discriminants match the C enum so values can be cast with [`Type::as_raw`].
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Type {
+    F32 = 0,
+    F16 = 1,
+    Q4_0 = 2,
+    Q4_1 = 3,
+    Q5_0 = 6,
+    Q5_1 = 7,
+    Q8_0 = 8,
+    Q8_1 = 9,
+    Q2_K = 10,
+    Q3_K = 11,
+    Q4_K = 12,
+    Q5_K = 13,
+    Q6_K = 14,
+    Q8_K = 15,
+    IQ2_XXS = 16,
+    IQ2_XS = 17,
+    IQ3_XXS = 18,
+    IQ1_S = 19,
+    IQ4_NL = 20,
+    IQ3_S = 21,
+    IQ2_S = 22,
+    IQ4_XS = 23,
+    I8 = 24,
+    I16 = 25,
+    I32 = 26,
+    I64 = 27,
+    F64 = 28,
+    IQ1_M = 29,
+    BF16 = 30,
+    TQ1_0 = 34,
+    TQ2_0 = 35,
+    MXFP4 = 39,
+}
+
+impl Type {
+    /// Every type known to this version of ggml, in enum order.
+    pub const ALL: [Type; 32] = [
+        Type::F32,
+        Type::F16,
+        Type::Q4_0,
+        Type::Q4_1,
+        Type::Q5_0,
+        Type::Q5_1,
+        Type::Q8_0,
+        Type::Q8_1,
+        Type::Q2_K,
+        Type::Q3_K,
+        Type::Q4_K,
+        Type::Q5_K,
+        Type::Q6_K,
+        Type::Q8_K,
+        Type::IQ2_XXS,
+        Type::IQ2_XS,
+        Type::IQ3_XXS,
+        Type::IQ1_S,
+        Type::IQ4_NL,
+        Type::IQ3_S,
+        Type::IQ2_S,
+        Type::IQ4_XS,
+        Type::I8,
+        Type::I16,
+        Type::I32,
+        Type::I64,
+        Type::F64,
+        Type::IQ1_M,
+        Type::BF16,
+        Type::TQ1_0,
+        Type::TQ2_0,
+        Type::MXFP4,
+    ];
+
+    /// Convert a raw `ggml_type`, returning `None` for removed or unknown values.
+    pub fn from_raw(raw: crate::ggml_type) -> Option<Type> {
+        Type::ALL.iter().copied().find(|t| t.as_raw() == raw)
+    }
+
+    /// Parse a name as printed by [`Type::name`], ignoring case.
+    pub fn from_name(name: &str) -> Option<Type> {
+        Type::ALL
+            .iter()
+            .copied()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The raw `ggml_type` value.
+    pub fn as_raw(self) -> crate::ggml_type {
+        self as crate::ggml_type
+    }
+
+    /// Name as reported by `ggml_type_name` (e.g. `"q4_K"`).
+    pub fn name(self) -> &'static str {
+        unsafe {
+            let ptr = crate::ggml_type_name(self.as_raw());
+            if ptr.is_null() {
+                return "unknown";
+            }
+            CStr::from_ptr(ptr).to_str().unwrap_or("unknown")
+        }
+    }
+
+    /// Number of elements stored in one block.
+    pub fn block_size(self) -> usize {
+        unsafe { crate::ggml_blck_size(self.as_raw()) as usize }
+    }
+
+    /// Size in bytes of one block.
+    pub fn type_size(self) -> usize {
+        unsafe { crate::ggml_type_size(self.as_raw()) }
+    }
+
+    /// Size in bytes of a row of `ne` elements.
+    pub fn row_size(self, ne: i64) -> usize {
+        unsafe { crate::ggml_row_size(self.as_raw(), ne) }
+    }
+
+    /// Size in bytes of a tensor of shape `ne`, or `None` if a dimension is
+    /// negative or its element or byte count overflows.
+    pub(crate) fn checked_nbytes(self, ne: &[i64]) -> Option<usize> {
+        let (&ne0, rows) = ne.split_first()?;
+        ne.iter().try_fold(1i64, |n, &d| n.checked_mul(d))?;
+        let blocks = usize::try_from(ne0).ok()? / self.block_size();
+        let mut nbytes = blocks.checked_mul(self.type_size())?;
+        for &d in rows {
+            nbytes = nbytes.checked_mul(usize::try_from(d).ok()?)?;
+        }
+        Some(nbytes)
+    }
+
+    /// Whether this is a block-quantized type.
+    pub fn is_quantized(self) -> bool {
+        unsafe { crate::ggml_is_quantized(self.as_raw()) }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Rust scalar types that map one-to-one onto a ggml element type.
+pub trait Element: Copy + Default + 'static {
+    const TYPE: Type;
+}
+
+impl Element for f32 {
+    const TYPE: Type = Type::F32;
+}
+impl Element for f64 {
+    const TYPE: Type = Type::F64;
+}
+impl Element for i8 {
+    const TYPE: Type = Type::I8;
+}
+impl Element for i16 {
+    const TYPE: Type = Type::I16;
+}
+impl Element for i32 {
+    const TYPE: Type = Type::I32;
+}
+impl Element for i64 {
+    const TYPE: Type = Type::I64;
+}
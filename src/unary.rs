@@ -0,0 +1,132 @@
+//! Elementwise unary ops through `ggml_unary`.
+
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::inplace::TensorMut;
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Elementwise activation or math function, mirroring `enum ggml_unary_op`.
+///
+/// `GGML_UNARY_OP_XIELU` is left out: it takes parameters that `ggml_unary`
+/// cannot pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum UnaryOp {
+    Abs = 0,
+    Sgn = 1,
+    Neg = 2,
+    Step = 3,
+    Tanh = 4,
+    Elu = 5,
+    Relu = 6,
+    Sigmoid = 7,
+    Gelu = 8,
+    GeluQuick = 9,
+    Silu = 10,
+    Hardswish = 11,
+    Hardsigmoid = 12,
+    Exp = 13,
+    GeluErf = 14,
+    Floor = 16,
+    Ceil = 17,
+    Round = 18,
+    Trunc = 19,
+}
+
+impl UnaryOp {
+    pub const ALL: [UnaryOp; 19] = [
+        UnaryOp::Abs,
+        UnaryOp::Sgn,
+        UnaryOp::Neg,
+        UnaryOp::Step,
+        UnaryOp::Tanh,
+        UnaryOp::Elu,
+        UnaryOp::Relu,
+        UnaryOp::Sigmoid,
+        UnaryOp::Gelu,
+        UnaryOp::GeluQuick,
+        UnaryOp::Silu,
+        UnaryOp::Hardswish,
+        UnaryOp::Hardsigmoid,
+        UnaryOp::Exp,
+        UnaryOp::GeluErf,
+        UnaryOp::Floor,
+        UnaryOp::Ceil,
+        UnaryOp::Round,
+        UnaryOp::Trunc,
+    ];
+
+    pub fn from_raw(raw: crate::ggml_unary_op) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.as_raw() == raw)
+    }
+
+    pub fn as_raw(self) -> crate::ggml_unary_op {
+        self as crate::ggml_unary_op
+    }
+
+    /// Name as reported by `ggml_unary_op_name`, e.g. `"GELU_QUICK"`.
+    pub fn name(self) -> &'static str {
+        unsafe { CStr::from_ptr(crate::ggml_unary_op_name(self.as_raw())) }
+            .to_str()
+            .unwrap_or("?")
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+fn check_unary_input(op: UnaryOp, a: Tensor<'_>) -> Result<()> {
+    if !matches!(a.ty(), Type::F32 | Type::F16 | Type::BF16) {
+        return Err(Error::TypeMismatch {
+            expected: format!("{} input F32, F16 or BF16", op),
+            found: a.ty().to_string(),
+        });
+    }
+    Ok(())
+}
+
+impl Context {
+    /// Apply `op` to every element of `a`. Rows of `a` must be contiguous.
+    pub fn unary<'a>(&'a self, a: Tensor<'a>, op: UnaryOp) -> Result<Tensor<'a>> {
+        check_unary_input(op, a)?;
+        let a = if unsafe { crate::ggml_is_contiguous_1(a.as_ptr()) } {
+            a
+        } else {
+            self.require_contiguous(a)?
+        };
+        self.ensure_capacity(a.nbytes())?;
+        self.op_result("ggml_unary", unsafe {
+            crate::ggml_unary(self.as_ptr(), a.as_ptr(), op.as_raw())
+        })
+    }
+
+    /// Apply `op` to every element of `a`, writing over it.
+    pub fn unary_inplace<'a>(&'a self, a: TensorMut<'a>, op: UnaryOp) -> Result<TensorMut<'a>> {
+        check_unary_input(op, *a)?;
+        if !unsafe { crate::ggml_is_contiguous_1(a.as_ptr()) } {
+            return Err(Error::NotContiguous);
+        }
+        self.ensure_capacity(0)?;
+        self.op_result("ggml_unary_inplace", unsafe {
+            crate::ggml_unary_inplace(self.as_ptr(), a.as_ptr(), op.as_raw())
+        })
+        .map(Tensor::into_mut)
+    }
+}
+
+impl Tensor<'_> {
+    /// The unary op computing this tensor, if it is a `GGML_OP_UNARY` node.
+    pub fn unary_op(&self) -> Option<UnaryOp> {
+        if self.op() != crate::ggml_op_GGML_OP_UNARY {
+            return None;
+        }
+        UnaryOp::from_raw(unsafe { crate::ggml_get_unary_op(self.as_ptr()) })
+    }
+}
@@ -0,0 +1,144 @@
+//! Ops used by diffusion and vision-encoder graphs: timestep embeddings,
+//! padding and resampling. All of them take and return F32 tensors.
+
+use crate::context::Context;
+use crate::error::{Error, Result};
+use crate::tensor::Tensor;
+use crate::types::Type;
+
+/// Resampling filter for [`Context::upscale`] and [`Context::interpolate`],
+/// mirroring `enum ggml_scale_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    #[default]
+    Nearest,
+    /// Applied to the first two dimensions; the others use nearest.
+    Bilinear,
+}
+
+impl ScaleMode {
+    fn as_raw(self) -> crate::ggml_scale_mode {
+        match self {
+            ScaleMode::Nearest => crate::ggml_scale_mode_GGML_SCALE_MODE_NEAREST,
+            ScaleMode::Bilinear => crate::ggml_scale_mode_GGML_SCALE_MODE_BILINEAR,
+        }
+    }
+}
+
+fn to_i32(what: &str, v: usize) -> Result<i32> {
+    i32::try_from(v).map_err(|_| Error::InvalidArgument(format!("{} = {} exceeds i32", what, v)))
+}
+
+impl Context {
+    /// Sinusoidal embeddings of the F32 vector `timesteps` (`[n]`), as used
+    /// by diffusion UNets. Returns F32 `[dim, n]`.
+    pub fn timestep_embedding<'a>(
+        &'a self,
+        timesteps: Tensor<'a>,
+        dim: usize,
+        max_period: usize,
+    ) -> Result<Tensor<'a>> {
+        Self::check_f32("timestep_embedding", timesteps)?;
+        let ne = timesteps.ne();
+        if ne[1..] != [1, 1, 1] {
+            return Err(Error::ShapeMismatch(format!(
+                "timestep_embedding needs a vector, got {:?}",
+                ne
+            )));
+        }
+        let (dim_raw, period) = (to_i32("dim", dim)?, to_i32("max_period", max_period)?);
+        self.ensure_capacity(Type::F32.row_size(dim as i64) * ne[0] as usize)?;
+        self.op_result("ggml_timestep_embedding", unsafe {
+            crate::ggml_timestep_embedding(self.as_ptr(), timesteps.as_ptr(), dim_raw, period)
+        })
+    }
+
+    /// Zero-pad the end of each dimension: dimension `i` grows by `p[i]`.
+    pub fn pad<'a>(&'a self, a: Tensor<'a>, p: [usize; 4]) -> Result<Tensor<'a>> {
+        self.pad_ext(a, p.map(|p| (0, p)))
+    }
+
+    /// Zero-pad both sides of each dimension: `p[i]` is `(before, after)`.
+    pub fn pad_ext<'a>(&'a self, a: Tensor<'a>, p: [(usize, usize); 4]) -> Result<Tensor<'a>> {
+        Self::check_f32("pad", a)?;
+        let mut raw = [0i32; 8];
+        let mut ne = a.ne();
+        for (i, &(l, r)) in p.iter().enumerate() {
+            raw[2 * i] = to_i32("padding", l)?;
+            raw[2 * i + 1] = to_i32("padding", r)?;
+            ne[i] += (l + r) as i64;
+        }
+        self.ensure_capacity(Type::F32.row_size(ne.iter().product()))?;
+        self.op_result("ggml_pad_ext", unsafe {
+            crate::ggml_pad_ext(
+                self.as_ptr(),
+                a.as_ptr(),
+                raw[0],
+                raw[1],
+                raw[2],
+                raw[3],
+                raw[4],
+                raw[5],
+                raw[6],
+                raw[7],
+            )
+        })
+    }
+
+    /// Multiply the first two dimensions by `factor` (at least 2).
+    pub fn upscale<'a>(
+        &'a self,
+        a: Tensor<'a>,
+        factor: usize,
+        mode: ScaleMode,
+    ) -> Result<Tensor<'a>> {
+        Self::check_f32("upscale", a)?;
+        if factor < 2 {
+            return Err(Error::InvalidArgument(format!(
+                "upscale factor must be at least 2, got {}",
+                factor
+            )));
+        }
+        let factor = to_i32("upscale factor", factor)?;
+        let ne = a.ne();
+        let f = factor as i64;
+        self.ensure_capacity(Type::F32.row_size(ne[0] * f * ne[1] * f * ne[2] * ne[3]))?;
+        self.op_result("ggml_upscale", unsafe {
+            crate::ggml_upscale(self.as_ptr(), a.as_ptr(), factor, mode.as_raw())
+        })
+    }
+
+    /// Resample `a` to shape `ne`, up or down. With `align_corners`, bilinear
+    /// sampling maps the corner pixels of input and output onto each other.
+    pub fn interpolate<'a>(
+        &'a self,
+        a: Tensor<'a>,
+        ne: [i64; 4],
+        mode: ScaleMode,
+        align_corners: bool,
+    ) -> Result<Tensor<'a>> {
+        Self::check_f32("interpolate", a)?;
+        if ne.iter().any(|&n| n <= 0) {
+            return Err(Error::InvalidArgument(format!(
+                "interpolate target shape {:?} must be positive",
+                ne
+            )));
+        }
+        let mut raw_mode = mode.as_raw();
+        if align_corners {
+            raw_mode |= crate::ggml_scale_flag_GGML_SCALE_FLAG_ALIGN_CORNERS;
+        }
+        self.ensure_capacity(Type::F32.row_size(ne.iter().product()))?;
+        self.op_result("ggml_interpolate", unsafe {
+            crate::ggml_interpolate(
+                self.as_ptr(),
+                a.as_ptr(),
+                ne[0],
+                ne[1],
+                ne[2],
+                ne[3],
+                raw_mode,
+            )
+        })
+    }
+}
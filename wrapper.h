@@ -1,3 +1,24 @@
 #include "ggml/include/ggml.h"
+#include "ggml/include/ggml-cpu.h"
 #include "ggml/include/gguf.h"
+#include "ggml/include/ggml-opt.h"
 
+#ifdef GGML_RS_CUDA
+#include "ggml/include/ggml-cuda.h"
+#endif
+
+#ifdef GGML_RS_METAL
+#include "ggml/include/ggml-metal.h"
+#endif
+
+#ifdef GGML_RS_VULKAN
+#include "ggml/include/ggml-vulkan.h"
+#endif
+
+#ifdef GGML_RS_CANN
+#include "ggml/include/ggml-cann.h"
+#endif
+
+#ifdef GGML_RS_RPC
+#include "ggml/include/ggml-rpc.h"
+#endif